@@ -0,0 +1,199 @@
+// References
+// https://wiki.hydrogenaud.io/index.php?title=APEv2_specification
+// https://wiki.hydrogenaud.io/index.php?title=APE_Tags_Header
+
+/// Length of the tag header and footer
+const HEADER_LENGTH: usize = 32;
+
+const PREAMBLE: &[u8; 8] = b"APETAGEX";
+
+/// Tag flags
+const FLAG_HAS_HEADER: u32 = 1 << 31;
+const FLAG_IS_HEADER: u32 = 1 << 29;
+
+/// An item of an APE tag
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApeItem {
+    pub key: String,
+    /// Item flags, bits 1 and 2 select UTF-8 text (0), binary (1) or a locator (2)
+    pub flags: u32,
+    pub value: Vec<u8>,
+}
+
+/// An APEv1 or APEv2 tag, written as APEv2
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApeTag {
+    pub items: Vec<ApeItem>,
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_le_bytes(data[0..4].try_into().unwrap())
+}
+
+impl ApeTag {
+    /// Length of the APE tag at the end of the data including its header, 0 if there is none
+    pub fn length_at_end(data: &[u8]) -> usize {
+        if data.len() < HEADER_LENGTH {
+            return 0;
+        }
+        let footer = &data[data.len() - HEADER_LENGTH..];
+        if !footer.starts_with(PREAMBLE) {
+            return 0;
+        }
+        let size = read_u32(&footer[12..]) as usize;
+        let flags = read_u32(&footer[20..]);
+        let length = match flags & FLAG_HAS_HEADER {
+            0 => size,
+            _ => size + HEADER_LENGTH,
+        };
+        match length <= data.len() && size >= HEADER_LENGTH {
+            true => length,
+            false => 0,
+        }
+    }
+
+    /// Length of the APE tag starting with a header at the data, None if there is none.
+    /// The tag may be incomplete.
+    pub fn length_at_start(data: &[u8]) -> Option<usize> {
+        if data.len() < HEADER_LENGTH || !data.starts_with(PREAMBLE) {
+            return None;
+        }
+        let size = read_u32(&data[12..]) as usize;
+        let flags = read_u32(&data[20..]);
+        match flags & FLAG_IS_HEADER != 0 && size >= HEADER_LENGTH {
+            true => Some(size + HEADER_LENGTH),
+            false => None,
+        }
+    }
+
+    /// Parses the APE tag at the end of the data
+    pub fn parse(data: &[u8]) -> Option<ApeTag> {
+        let length = ApeTag::length_at_end(data);
+        if length == 0 {
+            return None;
+        }
+        let footer = &data[data.len() - HEADER_LENGTH..];
+        let count = read_u32(&footer[16..]) as usize;
+        let size = read_u32(&footer[12..]) as usize;
+        let items_data = &data[data.len() - size..data.len() - HEADER_LENGTH];
+
+        let mut items = Vec::new();
+        let mut offset = 0;
+        while items.len() < count && offset + 8 < items_data.len() {
+            let value_length = read_u32(&items_data[offset..]) as usize;
+            let flags = read_u32(&items_data[offset + 4..]);
+            let key_start = offset + 8;
+            let key_end = key_start + items_data[key_start..].iter().position(|&b| b == 0)?;
+            let value_end = key_end + 1 + value_length;
+            if value_end > items_data.len() {
+                break;
+            }
+            items.push(ApeItem {
+                key: String::from_utf8_lossy(&items_data[key_start..key_end]).into_owned(),
+                flags,
+                value: items_data[key_end + 1..value_end].to_vec(),
+            });
+            offset = value_end;
+        }
+        Some(ApeTag { items })
+    }
+
+    /// Text value of the item with the given key, keys are case insensitive
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.items
+            .iter()
+            .find(|item| item.key.eq_ignore_ascii_case(key))
+            .map(|item| String::from_utf8_lossy(&item.value).into_owned())
+    }
+
+    /// Replaces the item with the given key by a text item
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.remove(key);
+        self.items.push(ApeItem {
+            key: key.to_string(),
+            flags: 0,
+            value: value.as_bytes().to_vec(),
+        });
+    }
+
+    /// Removes the item with the given key
+    pub fn remove(&mut self, key: &str) {
+        self.items
+            .retain(|item| !item.key.eq_ignore_ascii_case(key));
+    }
+
+    /// Serializes the tag as APEv2 with header and footer, empty if there are no items
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.items.is_empty() {
+            return Vec::new();
+        }
+        let mut items = Vec::new();
+        for item in &self.items {
+            items.extend((item.value.len() as u32).to_le_bytes());
+            items.extend(item.flags.to_le_bytes());
+            items.extend(item.key.as_bytes());
+            items.push(0);
+            items.extend(&item.value);
+        }
+        let header = |flags: u32| {
+            let mut header = PREAMBLE.to_vec();
+            header.extend(2000u32.to_le_bytes());
+            header.extend(((items.len() + HEADER_LENGTH) as u32).to_le_bytes());
+            header.extend((self.items.len() as u32).to_le_bytes());
+            header.extend(flags.to_le_bytes());
+            header.extend([0; 8]);
+            header
+        };
+        let mut data = header(FLAG_HAS_HEADER | FLAG_IS_HEADER);
+        data.extend(&items);
+        data.extend(header(FLAG_HAS_HEADER));
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut tag = ApeTag::default();
+        tag.set("MP3GAIN_UNDO", "+002,+002,N");
+        tag.set("Title", "Song");
+        let mut data = b"audio".to_vec();
+        data.extend(tag.to_bytes());
+        assert_eq!(ApeTag::length_at_end(&data), data.len() - 5);
+        assert_eq!(ApeTag::length_at_start(&data[5..]), Some(data.len() - 5));
+        let parsed = ApeTag::parse(&data).unwrap();
+        assert_eq!(parsed, tag);
+        assert_eq!(parsed.get("mp3gain_undo"), Some("+002,+002,N".to_string()));
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        // APEv1 without header
+        let mut data = vec![4, 0, 0, 0, 0, 0, 0, 0];
+        data.extend(b"Artist\0Band");
+        let size = data.len() + HEADER_LENGTH;
+        data.extend(PREAMBLE);
+        data.extend(1000u32.to_le_bytes());
+        data.extend((size as u32).to_le_bytes());
+        data.extend(1u32.to_le_bytes());
+        data.extend([0; 12]);
+        assert_eq!(ApeTag::length_at_end(&data), data.len());
+        assert_eq!(ApeTag::length_at_start(&data[data.len() - 32..]), None);
+        assert_eq!(
+            ApeTag::parse(&data).unwrap().get("ARTIST"),
+            Some("Band".to_string())
+        );
+    }
+
+    #[test]
+    fn test_no_tag() {
+        assert_eq!(ApeTag::length_at_end(&[0; 40]), 0);
+        assert_eq!(ApeTag::parse(&[0; 40]), None);
+        assert!(ApeTag::default().to_bytes().is_empty());
+    }
+}
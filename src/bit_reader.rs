@@ -0,0 +1,83 @@
+/// Reads big endian bit fields from a byte slice.
+/// Reading past the end of the data yields zero bits.
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader { data, position: 0 }
+    }
+
+    /// Current position in bits
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// Total length of the data in bits
+    pub fn len(&self) -> usize {
+        self.data.len() * 8
+    }
+
+    pub fn read_bit(&mut self) -> bool {
+        let byte = self.position / 8;
+        let bit = if byte < self.data.len() {
+            self.data[byte] >> (7 - self.position % 8) & 1 == 1
+        } else {
+            false
+        };
+        self.position += 1;
+        bit
+    }
+
+    /// Reads up to 32 bits
+    pub fn read_bits(&mut self, count: usize) -> u32 {
+        let mut value = 0;
+        let mut remaining = count;
+        while remaining > 0 {
+            let byte = self.position / 8;
+            let available = 8 - self.position % 8;
+            let take = usize::min(available, remaining);
+            let bits = if byte < self.data.len() {
+                (self.data[byte] >> (available - take)) as u32 & ((1 << take) - 1)
+            } else {
+                0
+            };
+            value = value << take | bits;
+            remaining -= take;
+            self.position += take;
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_bits() {
+        let data = [0b_1010_0000, 0b_1111_0001];
+        let mut reader = BitReader::new(&data);
+        assert!(reader.read_bit());
+        assert!(!reader.read_bit());
+        assert_eq!(reader.read_bits(4), 0b_1000);
+        assert_eq!(reader.read_bits(6), 0b_00_1111);
+        assert_eq!(reader.position(), 12);
+        assert_eq!(reader.read_bits(4), 0b_0001);
+    }
+
+    #[test]
+    fn test_read_past_end() {
+        let data = [0b_1111_1111];
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.read_bits(12), 0b_1111_1111_0000);
+        assert_eq!(reader.position(), 12);
+    }
+}
@@ -0,0 +1,70 @@
+/// Writes big endian bit fields to a byte vector, the counterpart of `BitReader`
+pub struct BitWriter {
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl BitWriter {
+    pub fn new() -> BitWriter {
+        BitWriter {
+            data: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn write_bit(&mut self, bit: bool) {
+        self.write_bits(bit as u32, 1);
+    }
+
+    /// Writes the lowest `count` bits of `value`, up to 32 bits
+    pub fn write_bits(&mut self, value: u32, count: usize) {
+        for i in (0..count).rev() {
+            if self.position.is_multiple_of(8) {
+                self.data.push(0);
+            }
+            if value >> i & 1 == 1 {
+                self.data[self.position / 8] |= 0x80 >> (self.position % 8);
+            }
+            self.position += 1;
+        }
+    }
+
+    /// Written bytes, the last byte is padded with zero bits
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl Default for BitWriter {
+    fn default() -> Self {
+        BitWriter::new()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
+mod tests {
+    use super::super::bit_reader::BitReader;
+    use super::*;
+
+    #[test]
+    fn test_write_bits() {
+        let mut writer = BitWriter::new();
+        writer.write_bit(true);
+        writer.write_bit(false);
+        writer.write_bits(0b_1000, 4);
+        writer.write_bits(0b_00_1111, 6);
+        assert_eq!(writer.into_bytes(), vec![0b_1010_0000, 0b_1111_0000]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(0x1ABCD, 17);
+        writer.write_bits(5, 3);
+        let data = writer.into_bytes();
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.read_bits(17), 0x1ABCD);
+        assert_eq!(reader.read_bits(3), 5);
+    }
+}
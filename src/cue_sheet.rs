@@ -0,0 +1,142 @@
+// References
+// https://wiki.hydrogenaud.io/index.php?title=Cue_sheet
+// https://www.gnu.org/software/ccd2cue/manual/html_node/CUE-sheet-format.html
+
+/// CD frames (sectors) per second used by CUE sheet times
+const CUE_FRAMES_PER_SECOND: f64 = 75.0;
+
+/// A track of a CUE sheet
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// Start (INDEX 01) in seconds
+    pub start: f64,
+}
+
+/// A CUE sheet describing the tracks of a single file
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CueSheet {
+    /// Album title
+    pub title: Option<String>,
+    /// Album performer
+    pub performer: Option<String>,
+    pub tracks: Vec<CueTrack>,
+}
+
+/// Parses a time of the form mm:ss:ff into seconds
+fn parse_time(time: &str) -> Option<f64> {
+    let parts = time
+        .split(':')
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Option<Vec<u32>>>()?;
+    match parts[..] {
+        [minutes, seconds, frames] => {
+            Some(minutes as f64 * 60.0 + seconds as f64 + frames as f64 / CUE_FRAMES_PER_SECOND)
+        }
+        _ => None,
+    }
+}
+
+/// Removes the quotes around a value
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+        .to_string()
+}
+
+impl CueSheet {
+    /// Parses the commands of a CUE sheet relevant for splitting.
+    /// Unknown commands are ignored, tracks without INDEX 01 are dropped.
+    pub fn parse(text: &str) -> CueSheet {
+        let mut sheet = CueSheet::default();
+        let mut track: Option<CueTrack> = None;
+        let mut indexed = false;
+        let text = text.trim_start_matches('\u{FEFF}');
+        for line in text.lines() {
+            let line = line.trim();
+            let (command, value) = line.split_once(' ').unwrap_or((line, ""));
+            match command.to_ascii_uppercase().as_str() {
+                "TRACK" => {
+                    if let Some(track) = track.take().filter(|_| indexed) {
+                        sheet.tracks.push(track);
+                    }
+                    let number = value.split_whitespace().next();
+                    track = Some(CueTrack {
+                        number: number.and_then(|n| n.parse().ok()).unwrap_or(0),
+                        ..Default::default()
+                    });
+                    indexed = false;
+                }
+                "TITLE" => match &mut track {
+                    Some(track) => track.title = Some(unquote(value)),
+                    None => sheet.title = Some(unquote(value)),
+                },
+                "PERFORMER" => match &mut track {
+                    Some(track) => track.performer = Some(unquote(value)),
+                    None => sheet.performer = Some(unquote(value)),
+                },
+                "INDEX" => {
+                    let mut values = value.split_whitespace();
+                    let start = match (values.next(), values.next()) {
+                        (Some("01"), Some(time)) => parse_time(time),
+                        _ => None,
+                    };
+                    if let (Some(track), Some(start)) = (&mut track, start) {
+                        track.start = start;
+                        indexed = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(track) = track.filter(|_| indexed) {
+            sheet.tracks.push(track);
+        }
+        sheet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("01:02:15"), Some(62.2));
+        assert_eq!(parse_time("01:02"), None);
+        assert_eq!(parse_time("aa:02:00"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let sheet = CueSheet::parse(
+            "REM GENRE Electronic\r\n\
+             PERFORMER \"DJ\"\r\n\
+             TITLE \"Mix\"\r\n\
+             FILE \"mix.mp3\" MP3\r\n\
+             \x20 TRACK 01 AUDIO\r\n\
+             \x20   TITLE \"Intro\"\r\n\
+             \x20   INDEX 01 00:00:00\r\n\
+             \x20 TRACK 02 AUDIO\r\n\
+             \x20   TITLE \"Second\"\r\n\
+             \x20   PERFORMER \"Artist\"\r\n\
+             \x20   INDEX 00 03:59:00\r\n\
+             \x20   INDEX 01 04:00:37\r\n",
+        );
+        assert_eq!(sheet.title, Some("Mix".to_string()));
+        assert_eq!(sheet.performer, Some("DJ".to_string()));
+        assert_eq!(sheet.tracks.len(), 2);
+        assert_eq!(sheet.tracks[0].title, Some("Intro".to_string()));
+        assert_eq!(sheet.tracks[0].performer, None);
+        assert_eq!(sheet.tracks[1].number, 2);
+        assert_eq!(sheet.tracks[1].performer, Some("Artist".to_string()));
+        assert!((sheet.tracks[1].start - (240.0 + 37.0 / 75.0)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,132 @@
+// References
+// https://id3.org/ID3v1
+// https://en.wikipedia.org/wiki/ID3#ID3v1 (ID3v1.1 track number)
+
+/// Length of an ID3v1 tag at the end of a file
+pub const ID3V1_LENGTH: usize = 128;
+
+/// An ID3v1 or ID3v1.1 tag
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Id3v1Tag {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub year: String,
+    pub comment: String,
+    /// Track number of ID3v1.1, stored in the last byte of the comment
+    pub track: Option<u8>,
+    /// Index in the genre list, 255 for none
+    pub genre: u8,
+}
+
+impl Default for Id3v1Tag {
+    fn default() -> Self {
+        Id3v1Tag {
+            title: String::new(),
+            artist: String::new(),
+            album: String::new(),
+            year: String::new(),
+            comment: String::new(),
+            track: None,
+            genre: 255,
+        }
+    }
+}
+
+/// Latin-1 text of a zero padded field
+fn decode_field(data: &[u8]) -> String {
+    data.iter()
+        .take_while(|&&byte| byte != 0)
+        .map(|&byte| byte as char)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// Latin-1 bytes of the text, zero padded or truncated to `length`
+fn encode_field(text: &str, length: usize) -> Vec<u8> {
+    let mut data: Vec<u8> = text
+        .chars()
+        .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+        .take(length)
+        .collect();
+    data.resize(length, 0);
+    data
+}
+
+impl Id3v1Tag {
+    /// Parses a 128 byte ID3v1 tag
+    pub fn parse(data: &[u8]) -> Option<Id3v1Tag> {
+        if data.len() != ID3V1_LENGTH || !data.starts_with(b"TAG") {
+            return None;
+        }
+        // ID3v1.1: a zero byte before the last comment byte
+        let track = (data[125] == 0 && data[126] != 0).then_some(data[126]);
+        let comment_end = match track {
+            Some(_) => 125,
+            None => 127,
+        };
+        Some(Id3v1Tag {
+            title: decode_field(&data[3..33]),
+            artist: decode_field(&data[33..63]),
+            album: decode_field(&data[63..93]),
+            year: decode_field(&data[93..97]),
+            comment: decode_field(&data[97..comment_end]),
+            track,
+            genre: data[127],
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = b"TAG".to_vec();
+        data.extend(encode_field(&self.title, 30));
+        data.extend(encode_field(&self.artist, 30));
+        data.extend(encode_field(&self.album, 30));
+        data.extend(encode_field(&self.year, 4));
+        match self.track {
+            Some(track) => {
+                data.extend(encode_field(&self.comment, 28));
+                data.extend([0, track]);
+            }
+            None => data.extend(encode_field(&self.comment, 30)),
+        }
+        data.push(self.genre);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let tag = Id3v1Tag {
+            title: "Song".to_string(),
+            artist: "Band".to_string(),
+            album: "Album".to_string(),
+            year: "1999".to_string(),
+            comment: "Comment".to_string(),
+            track: Some(7),
+            genre: 17,
+        };
+        let data = tag.to_bytes();
+        assert_eq!(data.len(), ID3V1_LENGTH);
+        assert_eq!(Id3v1Tag::parse(&data), Some(tag));
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        let mut data = b"TAGTitle".to_vec();
+        data.resize(97, b' ');
+        data.extend(b"a comment of exactly thirty ch");
+        data.push(255);
+        let tag = Id3v1Tag::parse(&data).unwrap();
+        assert_eq!(tag.title, "Title");
+        assert_eq!(tag.artist, "");
+        assert_eq!(tag.comment, "a comment of exactly thirty ch");
+        assert_eq!(tag.track, None);
+        assert_eq!(Id3v1Tag::parse(&data[1..]), None);
+    }
+}
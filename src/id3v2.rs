@@ -0,0 +1,1122 @@
+// References
+// https://id3.org/id3v2.3.0
+// https://id3.org/id3v2.4.0-structure
+// https://id3.org/id3v2.4.0-frames
+// https://id3.org/id3v2-00 (ID3v2.2)
+// https://id3.org/id3v2.4.0-changes
+
+use super::id3v1::Id3v1Tag;
+use super::id3v2_chapter::convert_embedded_frames;
+use super::id3v2_lyrics::Id3v2SyncedLyrics;
+
+/// Length of the tag header
+const HEADER_LENGTH: usize = 10;
+
+/// Tag header flags
+const FLAG_UNSYNCHRONISATION: u8 = 0x80;
+const FLAG_EXTENDED_HEADER: u8 = 0x40;
+const FLAG_FOOTER: u8 = 0x10;
+
+/// ID3v2.4 frame format flags
+const FRAME_FLAG_UNSYNCHRONISATION: u16 = 0x0002;
+const FRAME_FLAG_DATA_LENGTH: u16 = 0x0001;
+
+/// Frame format flags of compression, encryption and grouping, whose additional
+/// header data is laid out differently in ID3v2.3 and ID3v2.4
+const FRAME_FORMAT_FLAGS_V23: u16 = 0x00E0;
+const FRAME_FORMAT_FLAGS_V24: u16 = 0x004D;
+
+/// ID3v2.2 frame identifiers and their ID3v2.3 equivalents
+const FRAME_IDS_V22: [(&str, &str); 67] = [
+    ("BUF", "RBUF"),
+    ("CNT", "PCNT"),
+    ("COM", "COMM"),
+    ("CRA", "AENC"),
+    ("ETC", "ETCO"),
+    ("EQU", "EQUA"),
+    ("GEO", "GEOB"),
+    ("IPL", "IPLS"),
+    ("LNK", "LINK"),
+    ("MCI", "MCDI"),
+    ("MLL", "MLLT"),
+    ("PIC", "APIC"),
+    ("POP", "POPM"),
+    ("REV", "RVRB"),
+    ("RVA", "RVAD"),
+    ("SLT", "SYLT"),
+    ("STC", "SYTC"),
+    ("TAL", "TALB"),
+    ("TBP", "TBPM"),
+    ("TCM", "TCOM"),
+    ("TCO", "TCON"),
+    ("TCP", "TCMP"),
+    ("TCR", "TCOP"),
+    ("TDA", "TDAT"),
+    ("TDY", "TDLY"),
+    ("TEN", "TENC"),
+    ("TFT", "TFLT"),
+    ("TIM", "TIME"),
+    ("TKE", "TKEY"),
+    ("TLA", "TLAN"),
+    ("TLE", "TLEN"),
+    ("TMT", "TMED"),
+    ("TOA", "TOPE"),
+    ("TOF", "TOFN"),
+    ("TOL", "TOLY"),
+    ("TOR", "TORY"),
+    ("TOT", "TOAL"),
+    ("TP1", "TPE1"),
+    ("TP2", "TPE2"),
+    ("TP3", "TPE3"),
+    ("TP4", "TPE4"),
+    ("TPA", "TPOS"),
+    ("TPB", "TPUB"),
+    ("TRC", "TSRC"),
+    ("TRD", "TRDA"),
+    ("TRK", "TRCK"),
+    ("TS2", "TSO2"),
+    ("TSA", "TSOA"),
+    ("TSC", "TSOC"),
+    ("TSI", "TSIZ"),
+    ("TSP", "TSOP"),
+    ("TSS", "TSSE"),
+    ("TST", "TSOT"),
+    ("TT1", "TIT1"),
+    ("TT2", "TIT2"),
+    ("TT3", "TIT3"),
+    ("TXT", "TEXT"),
+    ("TXX", "TXXX"),
+    ("TYE", "TYER"),
+    ("UFI", "UFID"),
+    ("ULT", "USLT"),
+    ("WAF", "WOAF"),
+    ("WAR", "WOAR"),
+    ("WAS", "WOAS"),
+    ("WCM", "WCOM"),
+    ("WCP", "WCOP"),
+    ("WPB", "WPUB"),
+];
+
+/// ID3v2.3 frames without an ID3v2.4 equivalent
+const FRAMES_V23_ONLY: [&str; 4] = ["EQUA", "RVAD", "TRDA", "TSIZ"];
+
+/// ID3v2.4 frames without an ID3v2.3 equivalent
+const FRAMES_V24_ONLY: [&str; 11] = [
+    "ASPI", "EQU2", "RVA2", "SEEK", "SIGN", "TDEN", "TDRL", "TDTG", "TMCL", "TMOO", "TPRO",
+];
+
+/// A frame of an ID3v2 tag
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Id3v2Frame {
+    /// Frame identifier, three characters in ID3v2.2 and four in later versions
+    pub id: String,
+    /// Status and format flags, zero in ID3v2.2
+    pub flags: u16,
+    /// Frame content, after removing the unsynchronisation
+    pub data: Vec<u8>,
+}
+
+/// An ID3v2 tag
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Id3v2Tag {
+    /// Major version: 2, 3 or 4
+    pub version: u8,
+    pub frames: Vec<Id3v2Frame>,
+}
+
+impl Default for Id3v2Tag {
+    fn default() -> Self {
+        Id3v2Tag {
+            version: 4,
+            frames: Vec::new(),
+        }
+    }
+}
+
+fn synchsafe(data: &[u8]) -> usize {
+    data.iter()
+        .fold(0, |acc, &byte| acc << 7 | (byte & 0x7F) as usize)
+}
+
+fn be_integer(data: &[u8]) -> usize {
+    data.iter().fold(0, |acc, &byte| acc << 8 | byte as usize)
+}
+
+fn to_synchsafe(value: usize) -> [u8; 4] {
+    [21, 14, 7, 0].map(|shift| (value >> shift & 0x7F) as u8)
+}
+
+/// Removes the zero bytes inserted after 0xFF by the unsynchronisation scheme
+fn resynchronise(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    for (i, &byte) in data.iter().enumerate() {
+        if byte == 0 && i > 0 && data[i - 1] == 0xFF {
+            continue;
+        }
+        result.push(byte);
+    }
+    result
+}
+
+/// Decodes a string with the given text encoding byte
+pub fn decode_string(encoding: u8, data: &[u8]) -> String {
+    let utf16 = |data: &[u8], big_endian: bool| {
+        let units: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|unit| match big_endian {
+                true => u16::from_be_bytes([unit[0], unit[1]]),
+                false => u16::from_le_bytes([unit[0], unit[1]]),
+            })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    match encoding {
+        0 => data.iter().map(|&byte| byte as char).collect(),
+        1 => match data {
+            [0xFF, 0xFE, rest @ ..] => utf16(rest, false),
+            [0xFE, 0xFF, rest @ ..] => utf16(rest, true),
+            _ => utf16(data, true),
+        },
+        2 => utf16(data, true),
+        _ => String::from_utf8_lossy(data).into_owned(),
+    }
+}
+
+/// Encodes a string without terminator, UTF-16 with a byte order mark
+pub fn encode_string(encoding: u8, text: &str) -> Vec<u8> {
+    match encoding {
+        0 => text
+            .chars()
+            .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+            .collect(),
+        1 => [0xFF, 0xFE]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect(),
+        2 => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        _ => text.as_bytes().to_vec(),
+    }
+}
+
+/// Null character of the text encoding
+pub fn terminator(encoding: u8) -> &'static [u8] {
+    match encoding {
+        1 | 2 => &[0, 0],
+        _ => &[0],
+    }
+}
+
+/// Splits the data after the first terminated string of the text encoding
+pub fn split_terminated(encoding: u8, data: &[u8]) -> (&[u8], &[u8]) {
+    let end = match encoding {
+        1 | 2 => (0..data.len() / 2)
+            .map(|i| 2 * i)
+            .find(|&i| data[i] == 0 && data[i + 1] == 0),
+        _ => data.iter().position(|&byte| byte == 0),
+    };
+    match end {
+        Some(end) => (&data[..end], &data[end + terminator(encoding).len()..]),
+        None => (data, &[]),
+    }
+}
+
+/// Encoding of new text frames: UTF-8 for ID3v2.4 and UTF-16 before
+pub fn default_encoding(version: u8) -> u8 {
+    match version {
+        4 => 3,
+        _ => 1,
+    }
+}
+
+/// Encoding able to store the strings in a tag of the given version.
+/// UTF-16BE and UTF-8 exist since ID3v2.4, Latin-1 is replaced by UTF-16
+/// for strings with other characters.
+pub fn usable_encoding(encoding: u8, version: u8, strings: &[String]) -> u8 {
+    match encoding {
+        0 if strings
+            .iter()
+            .flat_map(|s| s.chars())
+            .any(|c| c as u32 > 0xFF) =>
+        {
+            1
+        }
+        0 | 1 => encoding,
+        _ if version < 4 => 1,
+        _ => encoding,
+    }
+}
+
+/// Strings separated by terminators, preceded by the encoding byte
+fn encode_strings(encoding: u8, version: u8, strings: &[String]) -> Vec<u8> {
+    let encoding = usable_encoding(encoding, version, strings);
+    let mut data = vec![encoding];
+    for (i, string) in strings.iter().enumerate() {
+        if i > 0 {
+            data.extend(terminator(encoding));
+        }
+        data.extend(encode_string(encoding, string));
+    }
+    data
+}
+
+/// Identifier of the frame in ID3v2.3, None if there is none
+fn id_from_v22(id: &str) -> Option<&'static str> {
+    FRAME_IDS_V22
+        .iter()
+        .find(|(v22, _)| *v22 == id)
+        .map(|(_, v23)| *v23)
+}
+
+/// Identifier of the frame in ID3v2.2, None if there is none
+fn id_to_v22(id: &str) -> Option<&'static str> {
+    FRAME_IDS_V22
+        .iter()
+        .find(|(_, v23)| *v23 == id)
+        .map(|(v22, _)| *v22)
+}
+
+/// Mime type of an ID3v2.2 image format
+pub fn image_mime_type(format: &[u8]) -> String {
+    match format {
+        b"JPG" => "image/jpeg".to_string(),
+        b"PNG" => "image/png".to_string(),
+        _ => format!("image/{}", String::from_utf8_lossy(format).to_lowercase()),
+    }
+}
+
+/// ID3v2.2 image format of a mime type
+pub fn image_format(mime_type: &[u8]) -> [u8; 3] {
+    match mime_type {
+        b"image/jpeg" | b"image/jpg" => *b"JPG",
+        b"image/png" => *b"PNG",
+        _ => {
+            let subtype = mime_type.strip_prefix(b"image/").unwrap_or(mime_type);
+            let mut format = [b' '; 3];
+            for (byte, c) in format.iter_mut().zip(subtype) {
+                *byte = c.to_ascii_uppercase();
+            }
+            format
+        }
+    }
+}
+
+impl Id3v2Frame {
+    /// Text frame with a single string, UTF-8 encoded for ID3v2.4 and UTF-16 before
+    pub fn text(id: &str, text: &str, version: u8) -> Id3v2Frame {
+        let encoding = default_encoding(version);
+        Id3v2Frame {
+            id: id.to_string(),
+            flags: 0,
+            data: [&[encoding], &encode_string(encoding, text)[..]].concat(),
+        }
+    }
+
+    /// Comment (COMM) or unsynchronised lyrics (USLT) frame with a three letter
+    /// language code, e.g. "eng"
+    pub fn comment(
+        id: &str,
+        language: &str,
+        description: &str,
+        text: &str,
+        version: u8,
+    ) -> Id3v2Frame {
+        let encoding = default_encoding(version);
+        let mut data = vec![encoding];
+        data.extend(language.bytes().chain([b' '; 3]).take(3));
+        data.extend(encode_string(encoding, description));
+        data.extend(terminator(encoding));
+        data.extend(encode_string(encoding, text));
+        Id3v2Frame {
+            id: id.to_string(),
+            flags: 0,
+            data,
+        }
+    }
+
+    /// Whether the frame holds strings only (identifier starting with T)
+    fn is_text(&self) -> bool {
+        self.id.starts_with('T')
+    }
+
+    /// Whether the frame is a comment or unsynchronised lyrics frame
+    fn is_comment(&self) -> bool {
+        ["COMM", "USLT", "COM", "ULT"].contains(&self.id.as_str())
+    }
+
+    /// Language, description and text of a comment or unsynchronised lyrics frame
+    pub fn comment_parts(&self) -> Option<(String, String, String)> {
+        if !self.is_comment() || self.data.len() < 4 {
+            return None;
+        }
+        let encoding = self.data[0];
+        let (description, text) = split_terminated(encoding, &self.data[4..]);
+        Some((
+            String::from_utf8_lossy(&self.data[1..4]).into_owned(),
+            decode_string(encoding, description),
+            decode_string(encoding, text),
+        ))
+    }
+
+    /// Re-encodes the strings of text, comment and synchronised or unsynchronised lyrics frames
+    fn set_encoding(&mut self, encoding: u8, version: u8) {
+        if self.is_text() && !self.data.is_empty() {
+            let strings: Vec<String> = self
+                .strings()
+                .iter()
+                .map(|string| string.trim_start_matches('\u{FEFF}').to_string())
+                .collect();
+            self.data = encode_strings(encoding, version, &strings);
+        } else if let Some((language, description, text)) = self.comment_parts() {
+            let strings = [description, text];
+            let encoded = encode_strings(encoding, version, &strings);
+            self.data = [&encoded[..1], language.as_bytes(), &encoded[1..]].concat();
+        } else if let Some(lyrics) = Id3v2SyncedLyrics::parse(self) {
+            self.data = lyrics.to_frame_with_encoding(encoding, version).data;
+        }
+    }
+
+    /// Whether the frame has no content, or is a text, comment or lyrics frame with empty text
+    fn is_empty(&self) -> bool {
+        if self.data.is_empty() {
+            return true;
+        }
+        if self.is_text() {
+            let strings = self.strings();
+            let values = match self.id.as_str() {
+                // the first string of a user defined text frame is its description
+                "TXXX" | "TXX" => &strings[1.min(strings.len())..],
+                _ => &strings[..],
+            };
+            return values
+                .iter()
+                .all(|value| value.trim_start_matches('\u{FEFF}').trim().is_empty());
+        }
+        match self.comment_parts() {
+            Some((_, _, text)) => text.trim().is_empty(),
+            None => false,
+        }
+    }
+
+    /// Frames with equal keys must not occur more than once in a tag
+    fn unique_key(&self) -> (String, Vec<u8>) {
+        let discriminator = match self.id.as_str() {
+            "TXXX" | "TXX" => self.strings().first().map_or(Vec::new(), |description| {
+                description.to_lowercase().into_bytes()
+            }),
+            _ if self.is_text() => Vec::new(),
+            _ => match self.comment_parts() {
+                Some((language, description, _)) => [language, description].concat().into_bytes(),
+                None => self.data.clone(),
+            },
+        };
+        (self.id.clone(), discriminator)
+    }
+
+    /// Strings of a text frame (identifier starting with T, except TXXX)
+    pub fn strings(&self) -> Vec<String> {
+        let (encoding, data) = match self.data.split_first() {
+            Some((&encoding, data)) => (encoding, data),
+            None => return Vec::new(),
+        };
+        let text = decode_string(encoding, data);
+        // ID3v2.4 separates multiple strings by null characters
+        text.trim_end_matches('\0')
+            .split('\0')
+            .map(|s| s.to_string())
+            .collect()
+    }
+}
+
+impl Id3v2Tag {
+    /// Length of the tag starting at the data including its header and footer,
+    /// None if the data does not start with a tag header. The tag may be incomplete.
+    pub fn length_at_start(data: &[u8]) -> Option<usize> {
+        if data.len() < HEADER_LENGTH
+            || !data.starts_with(b"ID3")
+            || !(2..=4).contains(&data[3])
+            || data[4] == 0xFF
+            || data[6..10].iter().any(|&byte| byte >= 0x80)
+        {
+            return None;
+        }
+        let footer = match data[3] == 4 && data[5] & FLAG_FOOTER != 0 {
+            true => HEADER_LENGTH,
+            false => 0,
+        };
+        Some(HEADER_LENGTH + synchsafe(&data[6..10]) + footer)
+    }
+
+    /// Length of an appended ID3v2.4 tag with footer ending at the end of the data,
+    /// 0 if there is none
+    pub fn length_at_end(data: &[u8]) -> usize {
+        if data.len() < 2 * HEADER_LENGTH {
+            return 0;
+        }
+        let footer = &data[data.len() - HEADER_LENGTH..];
+        if !footer.starts_with(b"3DI")
+            || footer[3] != 4
+            || footer[5] & FLAG_FOOTER == 0
+            || footer[6..10].iter().any(|&byte| byte >= 0x80)
+        {
+            return 0;
+        }
+        let length = 2 * HEADER_LENGTH + synchsafe(&footer[6..10]);
+        match length <= data.len()
+            && Id3v2Tag::length_at_start(&data[data.len() - length..]) == Some(length)
+        {
+            true => length,
+            false => 0,
+        }
+    }
+
+    /// Parses a tag at the start of the data. Returns None if there is no complete tag.
+    pub fn parse(data: &[u8]) -> Option<Id3v2Tag> {
+        if data.len() < HEADER_LENGTH || !data.starts_with(b"ID3") {
+            return None;
+        }
+        let version = data[3];
+        let flags = data[5];
+        let size = synchsafe(&data[6..10]);
+        if !(2..=4).contains(&version) || data.len() < HEADER_LENGTH + size {
+            return None;
+        }
+        let mut body = data[HEADER_LENGTH..HEADER_LENGTH + size].to_vec();
+        // ID3v2.4 unsynchronises frame by frame
+        if flags & FLAG_UNSYNCHRONISATION != 0 && version < 4 {
+            body = resynchronise(&body);
+        }
+        let mut offset = 0;
+        if flags & FLAG_EXTENDED_HEADER != 0 && version > 2 && body.len() >= 4 {
+            offset = match version {
+                3 => 4 + u32::from_be_bytes(body[0..4].try_into().unwrap()) as usize,
+                _ => synchsafe(&body[0..4]),
+            };
+        }
+
+        let frames = parse_frames(version, &body[offset.min(body.len())..]);
+        Some(Id3v2Tag { version, frames })
+    }
+
+    /// Minimum offset from the end of the tag to the next tag, from the SEEK frame
+    pub fn seek_offset(&self) -> Option<usize> {
+        let data = &self.frame("SEEK")?.data;
+        Some(u32::from_be_bytes(data.get(0..4)?.try_into().ok()?) as usize)
+    }
+
+    /// First frame with the given identifier
+    pub fn frame(&self, id: &str) -> Option<&Id3v2Frame> {
+        self.frames.iter().find(|frame| frame.id == id)
+    }
+
+    /// First string of the text frame with the given identifier
+    pub fn text(&self, id: &str) -> Option<String> {
+        self.frame(id)?.strings().into_iter().next()
+    }
+
+    /// Replaces all frames with the given identifier by a text frame
+    pub fn set_text(&mut self, id: &str, text: &str) {
+        self.remove(id);
+        self.frames.push(Id3v2Frame::text(id, text, self.version));
+    }
+
+    /// Identifier of user defined text frames in the version of the tag
+    fn user_text_id(&self) -> &'static str {
+        match self.version {
+            2 => "TXX",
+            _ => "TXXX",
+        }
+    }
+
+    /// Value of the user defined text frame (TXXX) with the given description,
+    /// descriptions are case insensitive
+    pub fn user_text(&self, description: &str) -> Option<String> {
+        self.frames
+            .iter()
+            .filter(|frame| frame.id == self.user_text_id())
+            .map(|frame| frame.strings())
+            .find(|strings| {
+                strings
+                    .first()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(description))
+            })
+            .map(|strings| {
+                let value = strings.get(1).map_or("", |value| value.as_str());
+                value.trim_start_matches('\u{FEFF}').to_string()
+            })
+    }
+
+    /// Replaces the user defined text frame with the given description
+    pub fn set_user_text(&mut self, description: &str, value: &str) {
+        let id = self.user_text_id();
+        self.frames.retain(|frame| {
+            frame.id != id
+                || !frame
+                    .strings()
+                    .first()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(description))
+        });
+        let text = format!("{}\0{}", description, value);
+        let mut frame = Id3v2Frame::text(id, &text, self.version);
+        if self.version < 4 {
+            // each string of a UTF-16 frame starts with a byte order mark
+            let separator = 1 + 2 + 2 * description.encode_utf16().count() + 2;
+            frame.data.splice(separator..separator, [0xFF, 0xFE]);
+        }
+        self.frames.push(frame);
+    }
+
+    /// Removes all frames with the given identifier
+    pub fn remove(&mut self, id: &str) {
+        self.frames.retain(|frame| frame.id != id);
+    }
+
+    /// Identifier in the version of the tag for an ID3v2.3 or ID3v2.4 identifier
+    fn frame_id(&self, id: &str) -> String {
+        match self.version {
+            2 => id_to_v22(id).unwrap_or(id).to_string(),
+            _ => id.to_string(),
+        }
+    }
+
+    /// Converts the tag to another major version: 2, 3 or 4.
+    /// Frame identifiers, dates and images are converted and strings re-encoded if their
+    /// encoding does not exist in the version. Frames without equivalent in the version
+    /// and compressed, encrypted or grouped frames are dropped.
+    pub fn convert(&mut self, version: u8) {
+        if version == self.version || !(2..=4).contains(&version) {
+            return;
+        }
+        let mut frames = std::mem::take(&mut self.frames);
+        if self.version == 2 {
+            frames = frames.into_iter().filter_map(frame_from_v22).collect();
+        }
+        let (from, to) = (self.version.max(3), version.max(3));
+        if from != to {
+            frames.retain(|frame| {
+                let format_flags = match from {
+                    3 => FRAME_FORMAT_FLAGS_V23,
+                    _ => FRAME_FORMAT_FLAGS_V24,
+                };
+                frame.flags & format_flags == 0
+            });
+            for frame in frames.iter_mut() {
+                // tag alter, file alter and read only status flags
+                frame.flags = match to {
+                    4 => frame.flags >> 1 & 0x7000,
+                    _ => frame.flags << 1 & 0xE000,
+                };
+            }
+            frames = match to {
+                4 => dates_to_v24(frames),
+                _ => dates_to_v23(frames),
+            };
+            for frame in frames.iter_mut() {
+                if let Some(converted) = convert_embedded_frames(frame, from, to) {
+                    *frame = converted;
+                }
+            }
+        }
+        if version == 2 {
+            frames = frames.into_iter().filter_map(frame_to_v22).collect();
+        }
+        self.version = version;
+        self.frames = frames;
+        if version < 4 {
+            for frame in self.frames.iter_mut() {
+                if frame.data.first().is_some_and(|&encoding| encoding > 1) {
+                    frame.set_encoding(1, version);
+                }
+            }
+        }
+    }
+
+    /// Re-encodes the strings of all text, comment and lyrics frames: 0 Latin-1, 1 UTF-16,
+    /// 2 UTF-16BE and 3 UTF-8. UTF-16 is used instead of UTF-16BE and UTF-8 before ID3v2.4
+    /// and instead of Latin-1 for strings with other characters.
+    pub fn set_encoding(&mut self, encoding: u8) {
+        for frame in self.frames.iter_mut() {
+            frame.set_encoding(encoding, self.version);
+        }
+    }
+
+    /// Removes frames without content and repeated frames which may only occur once,
+    /// keeping the first
+    pub fn clean(&mut self) {
+        let mut keys = Vec::new();
+        self.frames.retain(|frame| {
+            let key = frame.unique_key();
+            let keep = !frame.is_empty() && !keys.contains(&key);
+            keys.push(key);
+            keep
+        });
+    }
+
+    /// Adds the frames of another tag, converted to the version of this tag.
+    /// Repeated frames are removed by `clean`.
+    pub fn merge(&mut self, other: &Id3v2Tag) {
+        let mut other = other.clone();
+        other.convert(self.version);
+        self.frames.extend(other.frames);
+    }
+
+    /// Adds the fields of an ID3v1 tag missing in this tag
+    pub fn merge_id3v1(&mut self, tag: &Id3v1Tag) {
+        let year = match self.version {
+            4 => "TDRC",
+            _ => "TYER",
+        };
+        let genre = match (tag.genre, self.version) {
+            (255, _) => String::new(),
+            (genre, 4) => genre.to_string(),
+            (genre, _) => format!("({})", genre),
+        };
+        let track = tag.track.map_or(String::new(), |track| track.to_string());
+        for (id, value) in [
+            ("TIT2", &tag.title),
+            ("TPE1", &tag.artist),
+            ("TALB", &tag.album),
+            (year, &tag.year),
+            ("TRCK", &track),
+            ("TCON", &genre),
+        ] {
+            let id = self.frame_id(id);
+            if !value.is_empty() && self.text(&id).is_none_or(|text| text.is_empty()) {
+                self.set_text(&id, value);
+            }
+        }
+        let id = self.frame_id("COMM");
+        if !tag.comment.is_empty() && self.frame(&id).is_none() {
+            self.frames.push(Id3v2Frame::comment(
+                &id,
+                "eng",
+                "",
+                &tag.comment,
+                self.version,
+            ));
+        }
+    }
+
+    /// Serializes the tag without unsynchronisation, extended header or padding
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let body = frames_to_bytes(self.version, &self.frames);
+        let mut data = b"ID3".to_vec();
+        data.extend([self.version, 0, 0]);
+        data.extend(to_synchsafe(body.len()));
+        data.extend(body);
+        data
+    }
+
+    /// Serializes the tag with a footer, which allows locating it from the end when it is
+    /// appended after the audio. Only ID3v2.4 has footers, older tags are serialized without.
+    pub fn to_bytes_with_footer(&self) -> Vec<u8> {
+        let mut data = self.to_bytes();
+        if self.version == 4 {
+            data[5] |= FLAG_FOOTER;
+            let mut footer = data[..HEADER_LENGTH].to_vec();
+            footer[..3].copy_from_slice(b"3DI");
+            data.extend(footer);
+        }
+        data
+    }
+}
+
+/// Parses the frames of a tag body or of a CHAP or CTOC frame, up to the padding
+pub fn parse_frames(version: u8, body: &[u8]) -> Vec<Id3v2Frame> {
+    let (id_length, header_length) = match version {
+        2 => (3, 6),
+        _ => (4, 10),
+    };
+    let mut frames = Vec::new();
+    let mut offset = 0;
+    while offset + header_length <= body.len() && body[offset] != 0 {
+        let header = &body[offset..offset + header_length];
+        let id = String::from_utf8_lossy(&header[..id_length]).into_owned();
+        let size = match version {
+            2 => be_integer(&header[3..6]),
+            3 => be_integer(&header[4..8]),
+            _ => synchsafe(&header[4..8]),
+        };
+        let mut flags = match version {
+            2 => 0,
+            _ => u16::from_be_bytes([header[8], header[9]]),
+        };
+        offset += header_length;
+        if offset + size > body.len() {
+            break;
+        }
+        let mut frame_data = body[offset..offset + size].to_vec();
+        offset += size;
+        if version == 4 && flags & FRAME_FLAG_UNSYNCHRONISATION != 0 {
+            if flags & FRAME_FLAG_DATA_LENGTH != 0 && frame_data.len() >= 4 {
+                frame_data.drain(..4);
+            }
+            frame_data = resynchronise(&frame_data);
+            flags &= !(FRAME_FLAG_UNSYNCHRONISATION | FRAME_FLAG_DATA_LENGTH);
+        }
+        frames.push(Id3v2Frame {
+            id,
+            flags,
+            data: frame_data,
+        });
+    }
+    frames
+}
+
+/// Serializes frames for a tag body or a CHAP or CTOC frame
+pub fn frames_to_bytes(version: u8, frames: &[Id3v2Frame]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for frame in frames {
+        let size = frame.data.len();
+        match version {
+            2 => {
+                body.extend(frame.id.as_bytes().iter().take(3));
+                body.extend(&(size as u32).to_be_bytes()[1..]);
+            }
+            3 => {
+                body.extend(frame.id.as_bytes().iter().take(4));
+                body.extend((size as u32).to_be_bytes());
+                body.extend(frame.flags.to_be_bytes());
+            }
+            _ => {
+                body.extend(frame.id.as_bytes().iter().take(4));
+                body.extend(to_synchsafe(size));
+                body.extend(frame.flags.to_be_bytes());
+            }
+        }
+        body.extend(&frame.data);
+    }
+    body
+}
+
+/// Converts an ID3v2.2 frame to ID3v2.3, None if there is no equivalent
+fn frame_from_v22(mut frame: Id3v2Frame) -> Option<Id3v2Frame> {
+    frame.id = id_from_v22(&frame.id)?.to_string();
+    if frame.id == "APIC" && frame.data.len() >= 4 {
+        let mime_type = image_mime_type(&frame.data[1..4]);
+        frame
+            .data
+            .splice(1..4, mime_type.bytes().chain(std::iter::once(0)));
+    }
+    Some(frame)
+}
+
+/// Converts an ID3v2.3 frame to ID3v2.2, None if there is no equivalent
+fn frame_to_v22(mut frame: Id3v2Frame) -> Option<Id3v2Frame> {
+    frame.id = id_to_v22(&frame.id)?.to_string();
+    frame.flags = 0;
+    if frame.id == "PIC" && !frame.data.is_empty() {
+        let end = frame.data[1..].iter().position(|&byte| byte == 0)? + 1;
+        let format = image_format(&frame.data[1..end]);
+        frame.data.splice(1..end + 1, format);
+    }
+    Some(frame)
+}
+
+/// First string of the text frame with the given identifier
+pub fn frame_text(frames: &[Id3v2Frame], id: &str) -> Option<String> {
+    frames
+        .iter()
+        .find(|frame| frame.id == id)
+        .and_then(|frame| frame.strings().into_iter().next())
+}
+
+/// Replaces the ID3v2.3 date frames TYER, TDAT and TIME by TDRC, TORY by TDOR
+/// and IPLS by TIPL
+fn dates_to_v24(mut frames: Vec<Id3v2Frame>) -> Vec<Id3v2Frame> {
+    let year = frame_text(&frames, "TYER");
+    let date = frame_text(&frames, "TDAT").filter(|date| date.len() == 4 && date.is_ascii());
+    let time = frame_text(&frames, "TIME").filter(|time| time.len() == 4 && time.is_ascii());
+    let original_year = frame_text(&frames, "TORY");
+    frames.retain(|frame| {
+        !["TYER", "TDAT", "TIME", "TORY"].contains(&frame.id.as_str())
+            && !FRAMES_V23_ONLY.contains(&frame.id.as_str())
+    });
+    for frame in frames.iter_mut().filter(|frame| frame.id == "IPLS") {
+        frame.id = "TIPL".to_string();
+    }
+    if let Some(year) = year {
+        let mut timestamp = year;
+        if let Some(date) = date {
+            // TDAT is DDMM
+            timestamp += &format!("-{}-{}", &date[2..4], &date[0..2]);
+            if let Some(time) = time {
+                timestamp += &format!("T{}:{}", &time[0..2], &time[2..4]);
+            }
+        }
+        frames.push(Id3v2Frame::text("TDRC", &timestamp, 4));
+    }
+    if let Some(year) = original_year {
+        frames.push(Id3v2Frame::text("TDOR", &year, 4));
+    }
+    frames
+}
+
+/// Replaces the ID3v2.4 timestamp TDRC by TYER, TDAT and TIME, TDOR by TORY
+/// and TIPL by IPLS
+fn dates_to_v23(mut frames: Vec<Id3v2Frame>) -> Vec<Id3v2Frame> {
+    // timestamps are yyyy-MM-ddTHH:mm:ss with optional parts
+    let timestamp = frame_text(&frames, "TDRC").filter(|timestamp| timestamp.is_ascii());
+    let original = frame_text(&frames, "TDOR").filter(|timestamp| timestamp.is_ascii());
+    frames.retain(|frame| {
+        !["TDRC", "TDOR"].contains(&frame.id.as_str())
+            && !FRAMES_V24_ONLY.contains(&frame.id.as_str())
+    });
+    for frame in frames.iter_mut().filter(|frame| frame.id == "TIPL") {
+        frame.id = "IPLS".to_string();
+    }
+    if let Some(timestamp) = timestamp {
+        frames.push(Id3v2Frame::text(
+            "TYER",
+            &timestamp[..4.min(timestamp.len())],
+            3,
+        ));
+        if timestamp.len() >= 10 {
+            let date = format!("{}{}", &timestamp[8..10], &timestamp[5..7]);
+            frames.push(Id3v2Frame::text("TDAT", &date, 3));
+        }
+        if timestamp.len() >= 16 {
+            let time = format!("{}{}", &timestamp[11..13], &timestamp[14..16]);
+            frames.push(Id3v2Frame::text("TIME", &time, 3));
+        }
+    }
+    if let Some(original) = original {
+        frames.push(Id3v2Frame::text(
+            "TORY",
+            &original[..4.min(original.len())],
+            3,
+        ));
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synchsafe() {
+        assert_eq!(synchsafe(&[0x00, 0x00, 0x02, 0x01]), 257);
+        assert_eq!(to_synchsafe(257), [0x00, 0x00, 0x02, 0x01]);
+    }
+
+    #[test]
+    fn test_length_at_start() {
+        let tag = Id3v2Tag::default().to_bytes();
+        assert_eq!(Id3v2Tag::length_at_start(&tag), Some(tag.len()));
+        assert_eq!(
+            Id3v2Tag::length_at_start(b"ID3\x04\x00\x10\x00\x00\x01\x00"),
+            Some(10 + 128 + 10)
+        );
+        assert_eq!(
+            Id3v2Tag::length_at_start(b"ID3\x04\x00\x00\x00\x00\x80\x00"),
+            None
+        );
+        assert_eq!(Id3v2Tag::length_at_start(b"ID3"), None);
+    }
+
+    #[test]
+    fn test_footer_and_seek() {
+        let mut tag = Id3v2Tag::default();
+        tag.set_text("TIT2", "Appended");
+        let appended = tag.to_bytes_with_footer();
+        assert_eq!(appended.len(), tag.to_bytes().len() + 10);
+        assert_eq!(Id3v2Tag::length_at_start(&appended), Some(appended.len()));
+        let data = [&[0xFF; 100][..], &appended].concat();
+        assert_eq!(Id3v2Tag::length_at_end(&data), appended.len());
+        assert_eq!(Id3v2Tag::length_at_end(&tag.to_bytes()), 0);
+        assert_eq!(Id3v2Tag::length_at_end(&data[..data.len() - 1]), 0);
+        assert_eq!(Id3v2Tag::parse(&appended).unwrap(), tag);
+
+        assert_eq!(tag.seek_offset(), None);
+        tag.frames.push(Id3v2Frame {
+            id: "SEEK".to_string(),
+            flags: 0,
+            data: 1000u32.to_be_bytes().to_vec(),
+        });
+        assert_eq!(tag.seek_offset(), Some(1000));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut tag = Id3v2Tag::default();
+        tag.set_text("TIT2", "Titel ä");
+        tag.set_text("TRCK", "1/2");
+        tag.set_text("TIT2", "Title");
+        let data = tag.to_bytes();
+        assert_eq!(crate::get_id3_offset(&data) as usize, data.len());
+        let parsed = Id3v2Tag::parse(&data).unwrap();
+        assert_eq!(parsed, tag);
+        assert_eq!(parsed.text("TIT2"), Some("Title".to_string()));
+        assert_eq!(parsed.text("TRCK"), Some("1/2".to_string()));
+    }
+
+    #[test]
+    fn test_parse_v23() {
+        let mut tag = Id3v2Tag {
+            version: 3,
+            frames: Vec::new(),
+        };
+        tag.set_text("TPE1", "Ärtist");
+        let mut data = tag.to_bytes();
+        // padding
+        data[9] += 4;
+        data.extend([0; 4]);
+        let parsed = Id3v2Tag::parse(&data).unwrap();
+        assert_eq!(parsed.frames.len(), 1);
+        assert_eq!(parsed.text("TPE1"), Some("Ärtist".to_string()));
+    }
+
+    #[test]
+    fn test_user_text() {
+        for version in [3, 4] {
+            let mut tag = Id3v2Tag {
+                version,
+                frames: Vec::new(),
+            };
+            tag.set_user_text("REPLAYGAIN_TRACK_GAIN", "-1.00 dB");
+            tag.set_user_text("replaygain_track_gain", "-2.50 dB");
+            tag.set_user_text("Other", "");
+            let tag = Id3v2Tag::parse(&tag.to_bytes()).unwrap();
+            assert_eq!(tag.frames.len(), 2);
+            assert_eq!(
+                tag.user_text("REPLAYGAIN_TRACK_GAIN"),
+                Some("-2.50 dB".to_string())
+            );
+            assert_eq!(tag.user_text("other"), Some(String::new()));
+            assert_eq!(tag.user_text("missing"), None);
+        }
+    }
+
+    #[test]
+    fn test_clean_empty_user_text() {
+        // ID3v2.3 tag with a TXXX frame without data
+        let data = b"ID3\x03\x00\x00\x00\x00\x00\x0ATXXX\x00\x00\x00\x00\x00\x00";
+        let mut tag = Id3v2Tag::parse(data).unwrap();
+        assert_eq!(tag.frames.len(), 1);
+        tag.clean();
+        assert!(tag.frames.is_empty());
+
+        let mut tag = Id3v2Tag::parse(data).unwrap();
+        assert_eq!(tag.user_text("REPLAYGAIN_TRACK_GAIN"), None);
+        tag.set_user_text("REPLAYGAIN_TRACK_GAIN", "-1.00 dB");
+        assert_eq!(tag.frames.len(), 2);
+        assert_eq!(
+            tag.user_text("REPLAYGAIN_TRACK_GAIN"),
+            Some("-1.00 dB".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_v22() {
+        let data = b"ID3\x02\x00\x00\x00\x00\x00\x0BTT2\x00\x00\x05\x00Song";
+        let tag = Id3v2Tag::parse(data).unwrap();
+        assert_eq!(tag.text("TT2"), Some("Song".to_string()));
+        assert_eq!(tag.to_bytes(), data.to_vec());
+    }
+
+    #[test]
+    fn test_parse_unsynchronised() {
+        // ID3v2.3 with tag level unsynchronisation of the byte sequence FF 00
+        let data = b"ID3\x03\x00\x80\x00\x00\x00\x0DPRIV\x00\x00\x00\x02\x00\x00\xFF\x00\x00";
+        let tag = Id3v2Tag::parse(data).unwrap();
+        assert_eq!(tag.frames[0].data, vec![0xFF, 0x00]);
+    }
+
+    #[test]
+    fn test_convert() {
+        let mut tag = Id3v2Tag {
+            version: 2,
+            frames: vec![
+                Id3v2Frame::text("TT2", "Song", 2),
+                Id3v2Frame::text("TYE", "1999", 2),
+                Id3v2Frame::text("TDA", "3112", 2),
+                Id3v2Frame {
+                    id: "PIC".to_string(),
+                    flags: 0,
+                    data: b"\x00PNG\x03\x00image".to_vec(),
+                },
+                Id3v2Frame::text("XYZ", "unknown", 2),
+            ],
+        };
+        tag.convert(4);
+        let ids: Vec<&str> = tag.frames.iter().map(|frame| frame.id.as_str()).collect();
+        assert_eq!(ids, ["TIT2", "APIC", "TDRC"]);
+        assert_eq!(tag.frames[1].data, b"\x00image/png\x00\x03\x00image");
+        assert_eq!(tag.text("TDRC"), Some("1999-12-31".to_string()));
+
+        tag.frames.push(Id3v2Frame::text("TMOO", "Calm", 4));
+        tag.frames[0].flags = 0x5000;
+        tag.convert(3);
+        let ids: Vec<&str> = tag.frames.iter().map(|frame| frame.id.as_str()).collect();
+        assert_eq!(ids, ["TIT2", "APIC", "TYER", "TDAT"]);
+        assert_eq!(tag.frames[0].flags, 0xA000);
+        assert_eq!(tag.frames[0].data[0], 1);
+        assert_eq!(tag.text("TIT2"), Some("Song".to_string()));
+
+        tag.convert(2);
+        assert_eq!(tag.frames[1].data, b"\x00PNG\x03\x00image");
+        let tag = Id3v2Tag::parse(&tag.to_bytes()).unwrap();
+        assert_eq!(tag.text("TDA"), Some("3112".to_string()));
+    }
+
+    #[test]
+    fn test_set_encoding() {
+        let mut tag = Id3v2Tag::default();
+        tag.set_text("TIT2", "Ärger");
+        tag.set_text("TPE1", "Дом");
+        tag.set_user_text("Key", "Value");
+        tag.frames
+            .push(Id3v2Frame::comment("COMM", "deu", "", "Gut", 4));
+        tag.set_encoding(0);
+        let encodings: Vec<u8> = tag.frames.iter().map(|frame| frame.data[0]).collect();
+        assert_eq!(encodings, [0, 1, 0, 0]);
+        assert_eq!(tag.frames[0].data, b"\x00\xC4rger");
+        tag.set_encoding(2);
+        let tag = Id3v2Tag::parse(&tag.to_bytes()).unwrap();
+        assert_eq!(tag.text("TPE1"), Some("Дом".to_string()));
+        assert_eq!(tag.user_text("key"), Some("Value".to_string()));
+        assert_eq!(
+            tag.frames[3].comment_parts(),
+            Some(("deu".to_string(), String::new(), "Gut".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_clean_and_merge_id3v1() {
+        let mut tag = Id3v2Tag {
+            version: 3,
+            frames: vec![
+                Id3v2Frame::text("TIT2", "First", 3),
+                Id3v2Frame::text("TIT2", "Second", 3),
+                Id3v2Frame::text("TPE1", "", 3),
+                Id3v2Frame::comment("COMM", "eng", "", "", 3),
+            ],
+        };
+        tag.set_user_text("A", "1");
+        tag.frames.push(Id3v2Frame::text("TXXX", "a\0 2", 3));
+        tag.clean();
+        assert_eq!(tag.frames.len(), 2);
+        assert_eq!(tag.text("TIT2"), Some("First".to_string()));
+
+        tag.merge_id3v1(&Id3v1Tag {
+            title: "Ignored".to_string(),
+            artist: "Artist".to_string(),
+            year: "2000".to_string(),
+            comment: "Comment".to_string(),
+            track: Some(3),
+            genre: 17,
+            ..Default::default()
+        });
+        assert_eq!(tag.text("TIT2"), Some("First".to_string()));
+        assert_eq!(tag.text("TPE1"), Some("Artist".to_string()));
+        assert_eq!(tag.text("TYER"), Some("2000".to_string()));
+        assert_eq!(tag.text("TRCK"), Some("3".to_string()));
+        assert_eq!(tag.text("TCON"), Some("(17)".to_string()));
+        assert_eq!(
+            tag.frame("COMM").unwrap().comment_parts().unwrap().2,
+            "Comment"
+        );
+    }
+}
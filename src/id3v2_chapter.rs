@@ -0,0 +1,357 @@
+// References
+// https://id3.org/id3v2-chapters-1.0
+
+use super::id3v2::{
+    decode_string, encode_string, frame_text, frames_to_bytes, parse_frames, split_terminated,
+    Id3v2Frame, Id3v2Tag,
+};
+use super::id3v2_picture::Id3v2Picture;
+
+/// Byte offset marking an unused offset of a chapter
+const UNUSED_OFFSET: u32 = 0xFFFFFFFF;
+
+const CTOC_FLAG_TOP_LEVEL: u8 = 0x02;
+const CTOC_FLAG_ORDERED: u8 = 0x01;
+
+/// Element identifier of the table of contents written by `set_chapters`
+const TOC_ELEMENT_ID: &str = "toc";
+
+/// A chapter of a CHAP frame
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Id3v2Chapter {
+    /// Identifier referenced by tables of contents, unique within the tag
+    pub element_id: String,
+    /// Start in milliseconds
+    pub start_time: u32,
+    /// End in milliseconds
+    pub end_time: u32,
+    /// Byte offset of the first audio frame of the chapter from the start of the file
+    pub start_offset: Option<u32>,
+    /// Byte offset of the first audio frame after the chapter
+    pub end_offset: Option<u32>,
+    /// Embedded frames such as TIT2, WXXX and APIC
+    pub frames: Vec<Id3v2Frame>,
+}
+
+/// A table of contents of a CTOC frame
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Id3v2TableOfContents {
+    pub element_id: String,
+    /// Root of the tables of contents, at most one per tag
+    pub top_level: bool,
+    /// Whether the children are in playback order
+    pub ordered: bool,
+    /// Element identifiers of the chapters and tables of contents
+    pub children: Vec<String>,
+    /// Embedded frames such as TIT2
+    pub frames: Vec<Id3v2Frame>,
+}
+
+/// Element identifier at the start of the data and the remaining data
+fn element_id(data: &[u8]) -> (String, &[u8]) {
+    let (id, data) = split_terminated(0, data);
+    (decode_string(0, id), data)
+}
+
+fn be_u32(data: &[u8]) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(0..4)?.try_into().ok()?))
+}
+
+/// Byte offset, None if unused
+fn offset(data: &[u8]) -> Option<Option<u32>> {
+    Some(be_u32(data).filter(|&offset| offset != UNUSED_OFFSET))
+}
+
+/// URL of the first WXXX frame
+fn user_url(frames: &[Id3v2Frame]) -> Option<String> {
+    let frame = frames.iter().find(|frame| frame.id == "WXXX")?;
+    let (&encoding, data) = frame.data.split_first()?;
+    let (_, url) = split_terminated(encoding, data);
+    Some(decode_string(0, url).trim_end_matches('\0').to_string())
+}
+
+impl Id3v2Chapter {
+    /// Chapter without offsets and embedded frames
+    pub fn new(element_id: &str, start_time: u32, end_time: u32) -> Id3v2Chapter {
+        Id3v2Chapter {
+            element_id: element_id.to_string(),
+            start_time,
+            end_time,
+            start_offset: None,
+            end_offset: None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Parses a CHAP frame of a tag with the given major version
+    pub fn parse(frame: &Id3v2Frame, version: u8) -> Option<Id3v2Chapter> {
+        if frame.id != "CHAP" {
+            return None;
+        }
+        let (element_id, data) = element_id(&frame.data);
+        Some(Id3v2Chapter {
+            element_id,
+            start_time: be_u32(data)?,
+            end_time: be_u32(data.get(4..)?)?,
+            start_offset: offset(data.get(8..)?)?,
+            end_offset: offset(data.get(12..)?)?,
+            frames: parse_frames(version, data.get(16..)?),
+        })
+    }
+
+    /// CHAP frame for a tag with the given major version
+    pub fn to_frame(&self, version: u8) -> Id3v2Frame {
+        let mut data = encode_string(0, &self.element_id);
+        data.push(0);
+        data.extend(self.start_time.to_be_bytes());
+        data.extend(self.end_time.to_be_bytes());
+        data.extend(self.start_offset.unwrap_or(UNUSED_OFFSET).to_be_bytes());
+        data.extend(self.end_offset.unwrap_or(UNUSED_OFFSET).to_be_bytes());
+        data.extend(frames_to_bytes(version, &self.frames));
+        Id3v2Frame {
+            id: "CHAP".to_string(),
+            flags: 0,
+            data,
+        }
+    }
+
+    /// Text of the embedded TIT2 frame
+    pub fn title(&self) -> Option<String> {
+        frame_text(&self.frames, "TIT2")
+    }
+
+    /// Replaces the embedded TIT2 frame
+    pub fn set_title(&mut self, title: &str, version: u8) {
+        self.frames.retain(|frame| frame.id != "TIT2");
+        self.frames
+            .insert(0, Id3v2Frame::text("TIT2", title, version));
+    }
+
+    /// URL of the embedded WXXX frame
+    pub fn url(&self) -> Option<String> {
+        user_url(&self.frames)
+    }
+
+    /// Replaces the embedded WXXX frames by one without description
+    pub fn set_url(&mut self, url: &str) {
+        self.frames.retain(|frame| frame.id != "WXXX");
+        let mut data = vec![0, 0];
+        data.extend(encode_string(0, url));
+        self.frames.push(Id3v2Frame {
+            id: "WXXX".to_string(),
+            flags: 0,
+            data,
+        });
+    }
+
+    /// Picture of the first embedded APIC frame
+    pub fn picture(&self) -> Option<Id3v2Picture> {
+        self.frames.iter().find_map(Id3v2Picture::parse)
+    }
+}
+
+impl Id3v2TableOfContents {
+    /// Parses a CTOC frame of a tag with the given major version
+    pub fn parse(frame: &Id3v2Frame, version: u8) -> Option<Id3v2TableOfContents> {
+        if frame.id != "CTOC" {
+            return None;
+        }
+        let (element_id, data) = element_id(&frame.data);
+        let (&flags, data) = data.split_first()?;
+        let (&count, mut data) = data.split_first()?;
+        let mut children = Vec::new();
+        for _ in 0..count {
+            let (child, rest) = self::element_id(data);
+            children.push(child);
+            data = rest;
+        }
+        Some(Id3v2TableOfContents {
+            element_id,
+            top_level: flags & CTOC_FLAG_TOP_LEVEL != 0,
+            ordered: flags & CTOC_FLAG_ORDERED != 0,
+            children,
+            frames: parse_frames(version, data),
+        })
+    }
+
+    /// CTOC frame for a tag with the given major version, holding at most 255 children
+    pub fn to_frame(&self, version: u8) -> Id3v2Frame {
+        let mut data = encode_string(0, &self.element_id);
+        data.push(0);
+        data.push(
+            (self.top_level as u8 * CTOC_FLAG_TOP_LEVEL) | (self.ordered as u8 * CTOC_FLAG_ORDERED),
+        );
+        let children = &self.children[..self.children.len().min(255)];
+        data.push(children.len() as u8);
+        for child in children {
+            data.extend(encode_string(0, child));
+            data.push(0);
+        }
+        data.extend(frames_to_bytes(version, &self.frames));
+        Id3v2Frame {
+            id: "CTOC".to_string(),
+            flags: 0,
+            data,
+        }
+    }
+
+    /// Text of the embedded TIT2 frame
+    pub fn title(&self) -> Option<String> {
+        frame_text(&self.frames, "TIT2")
+    }
+}
+
+/// Converts the embedded frames of a CHAP or CTOC frame between ID3v2.3 and ID3v2.4,
+/// None for other frames
+pub fn convert_embedded_frames(frame: &Id3v2Frame, from: u8, to: u8) -> Option<Id3v2Frame> {
+    let convert = |frames: &[Id3v2Frame]| {
+        let mut tag = Id3v2Tag {
+            version: from,
+            frames: frames.to_vec(),
+        };
+        tag.convert(to);
+        tag.frames
+    };
+    let mut converted = match frame.id.as_str() {
+        "CHAP" => {
+            let mut chapter = Id3v2Chapter::parse(frame, from)?;
+            chapter.frames = convert(&chapter.frames);
+            chapter.to_frame(to)
+        }
+        "CTOC" => {
+            let mut toc = Id3v2TableOfContents::parse(frame, from)?;
+            toc.frames = convert(&toc.frames);
+            toc.to_frame(to)
+        }
+        _ => return None,
+    };
+    converted.flags = frame.flags;
+    Some(converted)
+}
+
+impl Id3v2Tag {
+    /// Chapters of all CHAP frames, in tag order
+    pub fn chapters(&self) -> Vec<Id3v2Chapter> {
+        self.frames
+            .iter()
+            .filter_map(|frame| Id3v2Chapter::parse(frame, self.version))
+            .collect()
+    }
+
+    /// Tables of contents of all CTOC frames
+    pub fn tables_of_contents(&self) -> Vec<Id3v2TableOfContents> {
+        self.frames
+            .iter()
+            .filter_map(|frame| Id3v2TableOfContents::parse(frame, self.version))
+            .collect()
+    }
+
+    /// Replaces all chapters and tables of contents by the chapters, sorted by start time,
+    /// and an ordered top level table of contents listing them.
+    /// ID3v2.2 tags cannot hold chapters and are left unchanged.
+    pub fn set_chapters(&mut self, chapters: &[Id3v2Chapter], title: Option<&str>) {
+        if self.version < 3 {
+            return;
+        }
+        self.remove_chapters();
+        let mut chapters = chapters.to_vec();
+        chapters.sort_by_key(|chapter| chapter.start_time);
+        let toc = Id3v2TableOfContents {
+            element_id: TOC_ELEMENT_ID.to_string(),
+            top_level: true,
+            ordered: true,
+            children: chapters
+                .iter()
+                .map(|chapter| chapter.element_id.clone())
+                .collect(),
+            frames: title
+                .map(|title| Id3v2Frame::text("TIT2", title, self.version))
+                .into_iter()
+                .collect(),
+        };
+        self.frames.push(toc.to_frame(self.version));
+        self.frames.extend(
+            chapters
+                .iter()
+                .map(|chapter| chapter.to_frame(self.version)),
+        );
+    }
+
+    /// Removes all CHAP and CTOC frames
+    pub fn remove_chapters(&mut self) {
+        self.frames
+            .retain(|frame| frame.id != "CHAP" && frame.id != "CTOC");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::id3v2_picture::PICTURE_FRONT_COVER;
+    use super::*;
+
+    fn chapter(index: usize, start_time: u32, end_time: u32, version: u8) -> Id3v2Chapter {
+        let mut chapter = Id3v2Chapter::new(&format!("chp{}", index), start_time, end_time);
+        chapter.set_title(&format!("Chapter {}", index), version);
+        chapter
+    }
+
+    #[test]
+    fn test_chapters() {
+        for version in [3, 4] {
+            let mut tag = Id3v2Tag {
+                version,
+                frames: Vec::new(),
+            };
+            tag.set_text("TIT2", "Episode");
+            let mut second = chapter(2, 60000, 125500, version);
+            second.start_offset = Some(960000);
+            second.set_url("https://example.com/");
+            second.frames.push(
+                Id3v2Picture::new(PICTURE_FRONT_COVER, "", vec![0xFF, 0xD8, 0xFF])
+                    .to_frame(version),
+            );
+            tag.set_chapters(&[second, chapter(1, 0, 60000, version)], Some("Contents"));
+
+            let tag = Id3v2Tag::parse(&tag.to_bytes()).unwrap();
+            assert_eq!(tag.text("TIT2").unwrap(), "Episode");
+            let tocs = tag.tables_of_contents();
+            assert_eq!(tocs.len(), 1);
+            assert!(tocs[0].top_level && tocs[0].ordered);
+            assert_eq!(tocs[0].children, ["chp1", "chp2"]);
+            assert_eq!(tocs[0].title().unwrap(), "Contents");
+            let chapters = tag.chapters();
+            assert_eq!(chapters.len(), 2);
+            assert_eq!(chapters[0].title().unwrap(), "Chapter 1");
+            assert_eq!(chapters[0].start_offset, None);
+            assert_eq!(chapters[1].start_time, 60000);
+            assert_eq!(chapters[1].end_time, 125500);
+            assert_eq!(chapters[1].start_offset, Some(960000));
+            assert_eq!(chapters[1].end_offset, None);
+            assert_eq!(chapters[1].url().unwrap(), "https://example.com/");
+            assert_eq!(chapters[1].picture().unwrap().mime_type, "image/jpeg");
+
+            let mut tag = tag;
+            tag.remove_chapters();
+            assert_eq!(tag.frames.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_convert_chapters() {
+        let mut tag = Id3v2Tag {
+            version: 3,
+            frames: Vec::new(),
+        };
+        let mut first = chapter(1, 0, 1000, 3);
+        // embedded frame sizes are synchsafe in ID3v2.4 only
+        first.set_title(&"x".repeat(200), 3);
+        tag.set_chapters(&[first], None);
+        tag.convert(4);
+        let chapters = Id3v2Tag::parse(&tag.to_bytes()).unwrap().chapters();
+        assert_eq!(chapters[0].title().unwrap(), "x".repeat(200));
+        tag.convert(2);
+        assert!(tag.chapters().is_empty());
+    }
+}
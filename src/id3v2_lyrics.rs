@@ -0,0 +1,368 @@
+// References
+// https://id3.org/id3v2.4.0-frames (4.8 Unsynchronised lyrics, 4.9 Synchronised lyrics)
+// https://id3.org/id3v2-00 (4.10 Synchronised lyrics)
+// https://en.wikipedia.org/wiki/LRC_(file_format)
+
+use super::id3v2::{
+    decode_string, default_encoding, encode_string, split_terminated, terminator, usable_encoding,
+    Id3v2Frame, Id3v2Tag,
+};
+use super::mp3_frame::Mp3Frame;
+
+/// Content type of synchronised lyrics
+pub const SYLT_CONTENT_LYRICS: u8 = 1;
+
+/// Language of lyrics imported from LRC, unknown
+const UNKNOWN_LANGUAGE: &str = "XXX";
+
+/// Unit of the timestamps of synchronised lyrics
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Id3v2TimestampFormat {
+    /// Absolute time in MPEG frames, counted from the first audio frame
+    MpegFrames,
+    Milliseconds,
+}
+
+/// Synchronised lyrics or text of a SYLT frame, or an SLT frame of ID3v2.2
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Id3v2SyncedLyrics {
+    /// Three letter language code, e.g. "eng"
+    pub language: String,
+    pub timestamp_format: Id3v2TimestampFormat,
+    /// 0 other, 1 lyrics, 2 transcription, 3 movement, 4 events, 5 chords, 6 trivia, ...
+    pub content_type: u8,
+    pub description: String,
+    /// Timestamp and text, a text starting with a line feed starts a new line
+    pub lines: Vec<(u32, String)>,
+}
+
+/// Start time in milliseconds of the frame with the given index.
+/// Indexes after the last frame continue with its duration.
+fn frame_time(frames: &[Mp3Frame], index: usize) -> u32 {
+    let duration = |frame: &Mp3Frame| {
+        frame.header.samples_per_frame() as f64 * 1000.0 / frame.header.sample_rate().max(1) as f64
+    };
+    let time: f64 = frames.iter().take(index).map(duration).sum();
+    let remaining = index.saturating_sub(frames.len()) as f64;
+    let last = frames.last().map_or(0.0, duration);
+    (time + remaining * last).round() as u32
+}
+
+/// Parses an LRC time of the form mm:ss, mm:ss.xx or mm:ss:xx into milliseconds
+fn parse_lrc_time(time: &str) -> Option<u32> {
+    let (minutes, seconds) = time.split_once(':')?;
+    let minutes: u32 = minutes.trim().parse().ok()?;
+    let seconds: f64 = seconds.trim().replacen(':', ".", 1).parse().ok()?;
+    if !(0.0..60.0).contains(&seconds) {
+        return None;
+    }
+    minutes
+        .checked_mul(60000)?
+        .checked_add((seconds * 1000.0).round() as u32)
+}
+
+impl Id3v2SyncedLyrics {
+    /// Parses a SYLT or SLT frame
+    pub fn parse(frame: &Id3v2Frame) -> Option<Id3v2SyncedLyrics> {
+        if frame.id != "SYLT" && frame.id != "SLT" {
+            return None;
+        }
+        let (&encoding, data) = frame.data.split_first()?;
+        let language = String::from_utf8_lossy(data.get(0..3)?).into_owned();
+        let timestamp_format = match data.get(3)? {
+            1 => Id3v2TimestampFormat::MpegFrames,
+            _ => Id3v2TimestampFormat::Milliseconds,
+        };
+        let content_type = *data.get(4)?;
+        let (description, mut data) = split_terminated(encoding, data.get(5..)?);
+        let mut lines = Vec::new();
+        while !data.is_empty() {
+            let (text, rest) = split_terminated(encoding, data);
+            let timestamp = u32::from_be_bytes(rest.get(0..4)?.try_into().ok()?);
+            lines.push((timestamp, decode_string(encoding, text)));
+            data = &rest[4..];
+        }
+        Some(Id3v2SyncedLyrics {
+            language,
+            timestamp_format,
+            content_type,
+            description: decode_string(encoding, description),
+            lines,
+        })
+    }
+
+    /// SYLT frame, or SLT frame for ID3v2.2, UTF-8 encoded for ID3v2.4 and UTF-16 before
+    pub fn to_frame(&self, version: u8) -> Id3v2Frame {
+        self.to_frame_with_encoding(default_encoding(version), version)
+    }
+
+    /// SYLT or SLT frame with the given text encoding, replaced by UTF-16 if the version
+    /// or the text does not allow it
+    pub fn to_frame_with_encoding(&self, encoding: u8, version: u8) -> Id3v2Frame {
+        let strings: Vec<String> = std::iter::once(&self.description)
+            .chain(self.lines.iter().map(|(_, text)| text))
+            .cloned()
+            .collect();
+        let encoding = usable_encoding(encoding, version, &strings);
+        let mut data = vec![encoding];
+        data.extend(self.language.bytes().chain([b' '; 3]).take(3));
+        data.push(match self.timestamp_format {
+            Id3v2TimestampFormat::MpegFrames => 1,
+            Id3v2TimestampFormat::Milliseconds => 2,
+        });
+        data.push(self.content_type);
+        data.extend(encode_string(encoding, &self.description));
+        data.extend(terminator(encoding));
+        for (timestamp, text) in &self.lines {
+            data.extend(encode_string(encoding, text));
+            data.extend(terminator(encoding));
+            data.extend(timestamp.to_be_bytes());
+        }
+        Id3v2Frame {
+            id: match version {
+                2 => "SLT",
+                _ => "SYLT",
+            }
+            .to_string(),
+            flags: 0,
+            data,
+        }
+    }
+
+    /// Converts timestamps in MPEG frames to milliseconds using the durations of the frames
+    pub fn to_milliseconds(&mut self, frames: &[Mp3Frame]) {
+        if self.timestamp_format == Id3v2TimestampFormat::MpegFrames {
+            for (timestamp, _) in self.lines.iter_mut() {
+                *timestamp = frame_time(frames, *timestamp as usize);
+            }
+            self.timestamp_format = Id3v2TimestampFormat::Milliseconds;
+        }
+    }
+
+    /// Text lines with their start time in milliseconds. Entries not starting with a line
+    /// feed continue the previous line if any entry starts with one.
+    pub fn text_lines(&self, frames: &[Mp3Frame]) -> Vec<(u32, String)> {
+        let mut lyrics = self.clone();
+        lyrics.to_milliseconds(frames);
+        let separated = lyrics
+            .lines
+            .iter()
+            .any(|(_, text)| text.starts_with(['\n', '\r']));
+        let mut lines: Vec<(u32, String)> = Vec::new();
+        for (timestamp, text) in lyrics.lines {
+            let starts_line = !separated || text.starts_with(['\n', '\r']);
+            let text = text.trim_start_matches(['\n', '\r']);
+            match lines.last_mut() {
+                Some((_, line)) if !starts_line => line.push_str(text),
+                _ => lines.push((timestamp, text.to_string())),
+            }
+        }
+        lines
+    }
+
+    /// Lyrics in the LRC format, one [mm:ss.xx] line per text line
+    pub fn to_lrc(&self, frames: &[Mp3Frame]) -> String {
+        self.text_lines(frames)
+            .iter()
+            .map(|(time, text)| {
+                let hundredths = (time + 5) / 10;
+                format!(
+                    "[{:02}:{:02}.{:02}]{}\n",
+                    hundredths / 6000,
+                    hundredths / 100 % 60,
+                    hundredths % 100,
+                    text
+                )
+            })
+            .collect()
+    }
+
+    /// Lyrics with millisecond timestamps from the LRC format, sorted by time.
+    /// A line may have several timestamps, the offset tag is applied and other tags
+    /// and lines without timestamp are ignored.
+    pub fn from_lrc(text: &str) -> Id3v2SyncedLyrics {
+        let mut offset = 0i64;
+        let mut lines = Vec::new();
+        for line in text.lines() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+            while let Some((tag, after)) =
+                rest.strip_prefix('[').and_then(|tag| tag.split_once(']'))
+            {
+                match tag.split_once(':') {
+                    Some(("offset", value)) => offset = value.trim().parse().unwrap_or(0),
+                    _ => times.extend(parse_lrc_time(tag)),
+                }
+                rest = after;
+            }
+            lines.extend(
+                times
+                    .into_iter()
+                    .map(|time| (time, rest.trim().to_string())),
+            );
+        }
+        // a positive offset shows the lyrics earlier
+        for (time, _) in lines.iter_mut() {
+            *time = (*time as i64 - offset).max(0) as u32;
+        }
+        lines.sort_by_key(|(time, _)| *time);
+        Id3v2SyncedLyrics {
+            language: UNKNOWN_LANGUAGE.to_string(),
+            timestamp_format: Id3v2TimestampFormat::Milliseconds,
+            content_type: SYLT_CONTENT_LYRICS,
+            description: String::new(),
+            lines,
+        }
+    }
+}
+
+impl Id3v2Tag {
+    /// Text of the first unsynchronised lyrics (USLT or ULT) frame
+    pub fn lyrics(&self) -> Option<String> {
+        self.frames
+            .iter()
+            .filter(|frame| frame.id == "USLT" || frame.id == "ULT")
+            .find_map(Id3v2Frame::comment_parts)
+            .map(|(_, _, text)| text)
+    }
+
+    /// Replaces all unsynchronised lyrics by a frame with the language and text
+    pub fn set_lyrics(&mut self, language: &str, text: &str) {
+        let id = match self.version {
+            2 => "ULT",
+            _ => "USLT",
+        };
+        self.remove(id);
+        self.frames
+            .push(Id3v2Frame::comment(id, language, "", text, self.version));
+    }
+
+    /// Synchronised lyrics of all SYLT or SLT frames
+    pub fn synced_lyrics(&self) -> Vec<Id3v2SyncedLyrics> {
+        self.frames
+            .iter()
+            .filter_map(Id3v2SyncedLyrics::parse)
+            .collect()
+    }
+
+    /// Adds synchronised lyrics, replacing those with the same language and description
+    pub fn set_synced_lyrics(&mut self, lyrics: &Id3v2SyncedLyrics) {
+        let frame = lyrics.to_frame(self.version);
+        let same = |other: &Id3v2Frame| {
+            Id3v2SyncedLyrics::parse(other).is_some_and(|other| {
+                other.language == lyrics.language && other.description == lyrics.description
+            })
+        };
+        let index = self.frames.iter().position(same);
+        self.frames.retain(|other| !same(other));
+        self.frames
+            .insert(index.unwrap_or(self.frames.len()), frame);
+    }
+
+    /// Removes all synchronised and unsynchronised lyrics
+    pub fn remove_lyrics(&mut self) {
+        self.frames
+            .retain(|frame| !["USLT", "ULT", "SYLT", "SLT"].contains(&frame.id.as_str()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mp3_header::{Mp3ChannelMode, Mp3FrameHeader, Mp3Layer, Mp3Version};
+    use super::super::mp3_synth::Mp3Synth;
+    use super::*;
+
+    const LRC: &str =
+        "[ti:Song]\n[offset:+100]\n[00:12.00]First line\n[00:05.50][01:02.25]Chorus ä\nno time\n";
+
+    #[test]
+    fn test_lrc() {
+        let lyrics = Id3v2SyncedLyrics::from_lrc(LRC);
+        assert_eq!(
+            lyrics.lines,
+            [
+                (5400, "Chorus ä".to_string()),
+                (11900, "First line".to_string()),
+                (62150, "Chorus ä".to_string()),
+            ]
+        );
+        assert_eq!(
+            lyrics.to_lrc(&[]),
+            "[00:05.40]Chorus ä\n[00:11.90]First line\n[01:02.15]Chorus ä\n"
+        );
+        assert_eq!(Id3v2SyncedLyrics::from_lrc(&lyrics.to_lrc(&[])), lyrics);
+        // times beyond the 32 bit millisecond range
+        assert_eq!(parse_lrc_time("71582:47.29"), Some(u32::MAX - 5));
+        assert_eq!(parse_lrc_time("71582:47.30"), None);
+        assert_eq!(parse_lrc_time("99999:00.00"), None);
+    }
+
+    #[test]
+    fn test_synced_lyrics() {
+        for version in [2, 3, 4] {
+            let mut tag = Id3v2Tag {
+                version,
+                frames: Vec::new(),
+            };
+            tag.set_lyrics("eng", "First line\nSecond line");
+            let mut lyrics = Id3v2SyncedLyrics::from_lrc(LRC);
+            lyrics.language = "eng".to_string();
+            tag.set_synced_lyrics(&lyrics);
+            tag.set_synced_lyrics(&lyrics);
+            let tag = Id3v2Tag::parse(&tag.to_bytes()).unwrap();
+            assert_eq!(tag.lyrics().unwrap(), "First line\nSecond line");
+            assert_eq!(tag.synced_lyrics(), [lyrics]);
+            let mut tag = tag;
+            tag.remove_lyrics();
+            assert!(tag.frames.is_empty());
+        }
+
+        // UTF-8 only exists in ID3v2.4
+        let lyrics = Id3v2SyncedLyrics::from_lrc(LRC);
+        let mut tag = Id3v2Tag::default();
+        tag.set_synced_lyrics(&lyrics);
+        assert_eq!(tag.frames[0].data[0], 3);
+        tag.convert(3);
+        assert_eq!(tag.frames[0].data[0], 1);
+        tag.convert(2);
+        assert_eq!(tag.frames[0].id, "SLT");
+        assert_eq!(tag.synced_lyrics(), [lyrics]);
+    }
+
+    #[test]
+    fn test_mpeg_frame_timestamps() {
+        // 1152 samples at 48 kHz are 24 ms
+        let header = Mp3FrameHeader::from_parameters(
+            Mp3Version::V1,
+            Mp3Layer::Layer3,
+            128,
+            48000,
+            Mp3ChannelMode::JointStereo,
+        )
+        .unwrap();
+        let frames = Mp3Synth::new(header, 10).audio_frames();
+        let lyrics = Id3v2SyncedLyrics {
+            language: "eng".to_string(),
+            timestamp_format: Id3v2TimestampFormat::MpegFrames,
+            content_type: SYLT_CONTENT_LYRICS,
+            description: String::new(),
+            lines: vec![
+                (0, "\nKa".to_string()),
+                (5, "ra".to_string()),
+                (10, "\noke".to_string()),
+                (12, "!".to_string()),
+            ],
+        };
+        assert_eq!(
+            lyrics.text_lines(&frames),
+            [(0, "Kara".to_string()), (240, "oke!".to_string())]
+        );
+        assert_eq!(lyrics.to_lrc(&frames), "[00:00.00]Kara\n[00:00.24]oke!\n");
+        let mut converted = lyrics.clone();
+        converted.to_milliseconds(&frames);
+        assert_eq!(converted.lines[1].0, 120);
+        assert_eq!(converted.lines[3].0, 288);
+    }
+}
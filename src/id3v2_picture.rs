@@ -0,0 +1,255 @@
+// References
+// https://id3.org/id3v2.4.0-frames (4.14 Attached picture)
+// https://id3.org/id3v2-00 (4.15 Attached picture)
+// https://www.w3.org/TR/png/#11IHDR
+// https://www.w3.org/Graphics/JPEG/itu-t81.pdf (B.2.2 Frame header)
+
+use super::id3v2::{
+    decode_string, encode_string, image_format, image_mime_type, split_terminated, terminator,
+    usable_encoding, Id3v2Frame, Id3v2Tag,
+};
+
+/// Picture type of the front cover
+pub const PICTURE_FRONT_COVER: u8 = 3;
+
+/// Image format detected from the magic bytes of the image data
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Unknown,
+}
+
+impl ImageFormat {
+    pub fn detect(data: &[u8]) -> ImageFormat {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            ImageFormat::Jpeg
+        } else if data.starts_with(b"\x89PNG\r\n\x1A\n") {
+            ImageFormat::Png
+        } else {
+            ImageFormat::Unknown
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::Unknown => "application/octet-stream",
+        }
+    }
+
+    /// File extension without dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Unknown => "bin",
+        }
+    }
+}
+
+/// Width and height of a PNG image from its IHDR chunk
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+    Some((width, height))
+}
+
+/// Width and height of a JPEG image from its first start of frame segment
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut offset = 2;
+    loop {
+        // segments start with 0xFF, possibly repeated as fill bytes
+        while *data.get(offset)? == 0xFF && *data.get(offset + 1)? == 0xFF {
+            offset += 1;
+        }
+        if *data.get(offset)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(offset + 1)?;
+        let length = u16::from_be_bytes(data.get(offset + 2..offset + 4)?.try_into().ok()?);
+        // SOF0 to SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            let height = u16::from_be_bytes(data.get(offset + 5..offset + 7)?.try_into().ok()?);
+            let width = u16::from_be_bytes(data.get(offset + 7..offset + 9)?.try_into().ok()?);
+            return Some((width as u32, height as u32));
+        }
+        // start of scan: the entropy coded data follows
+        if marker == 0xDA {
+            return None;
+        }
+        offset += 2 + length as usize;
+    }
+}
+
+/// A picture of an APIC frame, or a PIC frame of ID3v2.2
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Id3v2Picture {
+    pub mime_type: String,
+    /// 0 other, 1 file icon, 2 other file icon, 3 front cover, 4 back cover, ...
+    pub picture_type: u8,
+    pub description: String,
+    pub data: Vec<u8>,
+}
+
+impl Id3v2Picture {
+    /// Picture with the mime type detected from the data
+    pub fn new(picture_type: u8, description: &str, data: Vec<u8>) -> Id3v2Picture {
+        Id3v2Picture {
+            mime_type: ImageFormat::detect(&data).mime_type().to_string(),
+            picture_type,
+            description: description.to_string(),
+            data,
+        }
+    }
+
+    /// Parses an APIC or PIC frame
+    pub fn parse(frame: &Id3v2Frame) -> Option<Id3v2Picture> {
+        let (&encoding, data) = frame.data.split_first()?;
+        let (mime_type, data) = match frame.id.as_str() {
+            "APIC" => {
+                let (mime_type, data) = split_terminated(0, data);
+                (decode_string(0, mime_type), data)
+            }
+            "PIC" => (image_mime_type(data.get(0..3)?), data.get(3..)?),
+            _ => return None,
+        };
+        let (&picture_type, data) = data.split_first()?;
+        let (description, data) = split_terminated(encoding, data);
+        Some(Id3v2Picture {
+            mime_type,
+            picture_type,
+            description: decode_string(encoding, description),
+            data: data.to_vec(),
+        })
+    }
+
+    /// APIC frame, or PIC frame for ID3v2.2
+    pub fn to_frame(&self, version: u8) -> Id3v2Frame {
+        let encoding = usable_encoding(0, version, std::slice::from_ref(&self.description));
+        let mut data = vec![encoding];
+        let id = match version {
+            2 => {
+                data.extend(image_format(self.mime_type.as_bytes()));
+                "PIC"
+            }
+            _ => {
+                data.extend(encode_string(0, &self.mime_type));
+                data.push(0);
+                "APIC"
+            }
+        };
+        data.push(self.picture_type);
+        data.extend(encode_string(encoding, &self.description));
+        data.extend(terminator(encoding));
+        data.extend(&self.data);
+        Id3v2Frame {
+            id: id.to_string(),
+            flags: 0,
+            data,
+        }
+    }
+
+    pub fn format(&self) -> ImageFormat {
+        ImageFormat::detect(&self.data)
+    }
+
+    /// Width and height in pixels read from the image header, None for unknown formats
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        match self.format() {
+            ImageFormat::Jpeg => jpeg_dimensions(&self.data),
+            ImageFormat::Png => png_dimensions(&self.data),
+            ImageFormat::Unknown => None,
+        }
+    }
+}
+
+impl Id3v2Tag {
+    /// Pictures of all APIC or PIC frames
+    pub fn pictures(&self) -> Vec<Id3v2Picture> {
+        self.frames.iter().filter_map(Id3v2Picture::parse).collect()
+    }
+
+    /// Adds a picture, replacing the pictures with the same type and description
+    pub fn set_picture(&mut self, picture: &Id3v2Picture) {
+        let frame = picture.to_frame(self.version);
+        let same = |other: &Id3v2Frame| {
+            Id3v2Picture::parse(other).is_some_and(|other| {
+                other.picture_type == picture.picture_type
+                    && other.description == picture.description
+            })
+        };
+        let index = self.frames.iter().position(same);
+        self.frames.retain(|other| !same(other));
+        self.frames
+            .insert(index.unwrap_or(self.frames.len()), frame);
+    }
+
+    /// Removes all pictures, or the pictures of the given type
+    pub fn remove_pictures(&mut self, picture_type: Option<u8>) {
+        self.frames
+            .retain(|frame| match Id3v2Picture::parse(frame) {
+                Some(picture) => picture_type.is_some_and(|t| t != picture.picture_type),
+                None => true,
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smallest parts of a JPEG and a PNG image holding their dimensions
+    const JPEG: &[u8] = b"\xFF\xD8\xFF\xE0\x00\x04\x00\x00\xFF\xC0\x00\x0B\x08\x01\x2C\x01\x90\x01";
+    const PNG: &[u8] = b"\x89PNG\r\n\x1A\n\x00\x00\x00\x0DIHDR\x00\x00\x02\x00\x00\x00\x01\x00";
+
+    #[test]
+    fn test_dimensions() {
+        let jpeg = Id3v2Picture::new(PICTURE_FRONT_COVER, "", JPEG.to_vec());
+        assert_eq!(jpeg.mime_type, "image/jpeg");
+        assert_eq!(jpeg.dimensions(), Some((400, 300)));
+        let png = Id3v2Picture::new(PICTURE_FRONT_COVER, "", PNG.to_vec());
+        assert_eq!(png.format(), ImageFormat::Png);
+        assert_eq!(png.dimensions(), Some((512, 256)));
+        let unknown = Id3v2Picture::new(0, "", b"GIF89a".to_vec());
+        assert_eq!(unknown.format(), ImageFormat::Unknown);
+        assert_eq!(unknown.dimensions(), None);
+    }
+
+    #[test]
+    fn test_pictures() {
+        for version in [2, 3, 4] {
+            let mut tag = Id3v2Tag {
+                version,
+                frames: Vec::new(),
+            };
+            tag.set_text("TIT2", "Song");
+            let cover = Id3v2Picture::new(PICTURE_FRONT_COVER, "Cover ä", JPEG.to_vec());
+            tag.set_picture(&cover);
+            tag.set_picture(&Id3v2Picture::new(4, "", PNG.to_vec()));
+            tag.set_picture(&Id3v2Picture::new(
+                PICTURE_FRONT_COVER,
+                "Cover ä",
+                PNG.to_vec(),
+            ));
+            let tag = Id3v2Tag::parse(&tag.to_bytes()).unwrap();
+            let pictures = tag.pictures();
+            assert_eq!(pictures.len(), 2);
+            assert_eq!(pictures[0].description, "Cover ä");
+            assert_eq!(pictures[0].mime_type, "image/png");
+            assert_eq!(pictures[0].data, PNG);
+
+            let mut tag = tag;
+            tag.remove_pictures(Some(4));
+            assert_eq!(tag.pictures().len(), 1);
+            tag.remove_pictures(None);
+            assert_eq!(tag.frames.len(), 1);
+        }
+    }
+}
@@ -4,23 +4,113 @@
  * http://www.multiweb.cz/twoinches/mp3inside.htm
  * https://wiki.hydrogenaud.io/index.php?title=MP3
  */
+mod apev2;
+mod bit_reader;
+mod bit_writer;
+mod cue_sheet;
+mod id3v1;
+mod id3v2;
+mod id3v2_chapter;
+mod id3v2_lyrics;
+mod id3v2_picture;
+mod loudness;
+mod lyrics3;
+mod mp3_bands;
 mod mp3_body;
+mod mp3_crc;
+mod mp3_decoder;
+mod mp3_edit;
+mod mp3_envelope;
 mod mp3_frame;
+mod mp3_gain;
 mod mp3_header;
+mod mp3_huffman;
+mod mp3_huffman_tables;
+mod mp3_imdct;
+mod mp3_layer1;
+mod mp3_layer2;
+mod mp3_main_data;
+mod mp3_mix;
+mod mp3_repair;
+mod mp3_requantize;
+mod mp3_reservoir;
+mod mp3_scalefactors;
+mod mp3_scan;
+mod mp3_side_info;
+mod mp3_split;
+mod mp3_stereo;
+mod mp3_stream;
+#[cfg(any(test, feature = "synth"))]
+mod mp3_synth;
+mod mp3_synthesis;
+mod mp3_tags;
+mod mp3_validate;
+mod mp3_xing;
+#[cfg(feature = "serde")]
+mod serde_array;
+mod wav_writer;
+mod waveform;
 
-pub use mp3_frame::Mp3Frame;
-use mp3_header::*;
+pub use apev2::{ApeItem, ApeTag};
+pub use cue_sheet::{CueSheet, CueTrack};
+pub use id3v1::Id3v1Tag;
+pub use id3v2::{Id3v2Frame, Id3v2Tag};
+pub use id3v2_chapter::{Id3v2Chapter, Id3v2TableOfContents};
+pub use id3v2_lyrics::{Id3v2SyncedLyrics, Id3v2TimestampFormat, SYLT_CONTENT_LYRICS};
+pub use id3v2_picture::{Id3v2Picture, ImageFormat, PICTURE_FRONT_COVER};
+pub use loudness::{LoudnessAnalysis, ReplayGain, REPLAYGAIN_REFERENCE};
+pub use mp3_decoder::{Mp3Audio, Mp3Decoder};
+use mp3_edit::DECODER_DELAY;
+pub use mp3_edit::{cut, join, Mp3Cut, Mp3ReservoirHandling};
+pub use mp3_frame::{Mp3Frame, Mp3FrameInfo};
+pub use mp3_gain::{
+    apply_gain, global_gain_range, max_gain_without_clipping, undo_gain, GAIN_STEP_DB,
+};
+pub use mp3_header::*;
+pub use mp3_huffman::Mp3Spectrum;
+pub use mp3_main_data::{Mp3GranuleData, Mp3MainData};
+pub use mp3_mix::{mix, Mp3Mix, Mp3MixSchedule};
+pub use mp3_repair::{repair, Mp3Repair};
+use mp3_reservoir::Mp3Reservoir;
+pub use mp3_scalefactors::{Mp3FrameScalefactors, Mp3Scalefactors};
+use mp3_scan::junk_length;
+pub use mp3_scan::{
+    find_id3v2_tags, scan, Id3v2Location, Id3v2Placement, Mp3Region, Mp3RegionKind,
+};
+pub use mp3_side_info::{Mp3GranuleInfo, Mp3SideInfo};
+pub use mp3_split::{silence_points, split, split_cue, Mp3TrackInfo};
+pub use mp3_stream::Mp3Stream;
+#[cfg(feature = "synth")]
+pub use mp3_synth::{Mp3Synth, Mp3SynthPayload};
+pub use mp3_tags::{find_ape, normalise_tags, replace_ape, replace_id3v2, strip_tags};
+pub use mp3_validate::{validate, Mp3Issue, Mp3IssueKind, Mp3Severity};
+pub use mp3_xing::{Mp3LameTag, Mp3XingTag};
 use std::fs::File;
 use std::io::prelude::*;
+pub use wav_writer::{to_wav, WavFormat};
+pub use waveform::{Peak, Waveform, WaveformBits};
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MPeakError {
     CannotOpenFile,
     CannotReadFile,
+    CannotWriteFile,
     InvalidMp3Header,
+    InvalidId3v2Tag,
+    InvalidSideInfo,
+    IncompatibleStreams,
+    GainOutOfRange,
+    MissingGainUndo,
 }
 
-pub fn load_file(file_path: &String) -> Result<Vec<u8>, MPeakError> {
+impl std::fmt::Display for MPeakError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+pub fn load_file(file_path: &str) -> Result<Vec<u8>, MPeakError> {
     match File::open(file_path) {
         Ok(mut f) => {
             let mut buffer = Vec::new();
@@ -33,8 +123,18 @@ pub fn load_file(file_path: &String) -> Result<Vec<u8>, MPeakError> {
     }
 }
 
+pub fn write_file(file_path: &str, data: &[u8]) -> Result<(), MPeakError> {
+    match File::create(file_path) {
+        Ok(mut f) => match f.write_all(data) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(MPeakError::CannotWriteFile),
+        },
+        Err(_) => Err(MPeakError::CannotWriteFile),
+    }
+}
+
 /// Checks wether the data represents a mp3 file.
-pub fn is_mp3_file(data: &Vec<u8>) -> bool {
+pub fn is_mp3_file(data: &[u8]) -> bool {
     // check magic bytes for mp3
     if data.len() < 2 {
         // must have two bytes
@@ -51,59 +151,79 @@ pub fn is_mp3_file(data: &Vec<u8>) -> bool {
 }
 
 /// Check wether the data includes an id3 header
-pub fn has_id3(data: &Vec<u8>) -> bool {
+pub fn has_id3(data: &[u8]) -> bool {
     data.len() > 2 && data[0] == 0x49 && data[1] == 0x44 && data[2] == 0x33
 }
 
-/// Retrieve the offset of the id3 data block
-pub fn get_id3_offset(data: &Vec<u8>) -> u32 {
+/// Retrieve the offset of the id3 data block.
+/// Consecutive ID3v2 tags at the start and footers of ID3v2.4 tags are included.
+pub fn get_id3_offset(data: &[u8]) -> u32 {
     if has_id3(data) {
         if data.len() < 10 {
             data.len() as u32
         } else {
-            10 + ((data[6] as u32) << 21
-                | (data[7] as u32) << 14
-                | (data[8] as u32) << 7
-                | (data[9] as u32) >> 0)
+            let mut offset = Id3v2Tag::length_at_start(data).unwrap_or(
+                10 + ((data[6] as usize) << 21
+                    | (data[7] as usize) << 14
+                    | (data[8] as usize) << 7
+                    | (data[9] as usize)),
+            );
+            while let Some(length) = data.get(offset..).and_then(Id3v2Tag::length_at_start) {
+                offset += length;
+            }
+            offset as u32
         }
     } else {
         0
     }
 }
 
-/// Retrieve the id3 data block
-pub fn get_id3_data(data: &Vec<u8>) -> Vec<u8> {
-    let offset = get_id3_offset(data);
-    data[0..offset as usize].to_vec()
+/// Retrieve the id3 data block, cut off at the end of the data
+pub fn get_id3_data(data: &[u8]) -> Vec<u8> {
+    let offset = get_id3_offset(data) as usize;
+    data[0..offset.min(data.len())].to_vec()
 }
 
-pub fn get_first_mp3_frame_header(data: &Vec<u8>) -> Mp3FrameHeader {
+pub fn get_first_mp3_frame_header(data: &[u8]) -> Mp3FrameHeader {
     let offset = get_id3_offset(data) as usize;
     let header_data = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap());
     Mp3FrameHeader::new(header_data)
 }
 
-pub fn get_frames(data: &Vec<u8>) -> Result<Vec<Mp3Frame>, MPeakError> {
+/// Parses the frames following the ID3v2 tags at the start, skipping further ID3v2 tags
+/// and an appended ID3v2 tag at the end.
+/// Data which is no frame is returned as a frame reaching to the next tag or to the next
+/// frame followed by a frame, a tag or the end.
+pub fn get_frames(data: &[u8]) -> Result<Vec<Mp3Frame>, MPeakError> {
     let mut offset = get_id3_offset(data) as usize;
+    // an appended tag may follow a truncated frame
+    let data = &data[..data.len() - Id3v2Tag::length_at_end(data)];
     let mut frames = Vec::<Mp3Frame>::new();
     let mut curr_pos = 0;
     while offset < data.len() {
-        let header_data = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap());
-        let header = Mp3FrameHeader::new(header_data);
+        // ID3v2 tags may follow the audio or be reached by a SEEK frame
+        if let Some(length) = Id3v2Tag::length_at_start(&data[offset..]) {
+            offset += length;
+            continue;
+        }
+        let mut header_bytes = [0; 4];
+        let available = usize::min(4, data.len() - offset);
+        header_bytes[..available].copy_from_slice(&data[offset..offset + available]);
+        let header = Mp3FrameHeader::new(u32::from_be_bytes(header_bytes));
         match header.frame_length() {
-            Ok(frame_length) => {
-                let frame_data = data[offset..offset + frame_length]
-                    .iter()
-                    .cloned()
-                    .collect();
+            Ok(frame_length) if header.has_sync() => {
+                // the last frame may be truncated
+                let end = usize::min(offset + frame_length, data.len());
+                let frame_data = data[offset..end].to_vec();
                 frames.push(Mp3Frame::new(header, frame_data, curr_pos));
                 offset += frame_length;
                 curr_pos += 1;
             }
-            Err(_) => {
-                let frame_data = data[offset..data.len()].iter().cloned().collect();
+            _ => {
+                let end = offset + 1 + junk_length(&data[offset + 1..]);
+                let frame_data = data[offset..end].to_vec();
                 frames.push(Mp3Frame::new(header, frame_data, curr_pos));
-                offset = data.len();
+                offset = end;
                 curr_pos += 1;
             }
         }
@@ -111,7 +231,105 @@ pub fn get_frames(data: &Vec<u8>) -> Result<Vec<Mp3Frame>, MPeakError> {
     Ok(frames)
 }
 
+/// Decodes the Layer III scalefactors of all granules and channels.
+/// Frames which are not Layer III or whose main data is not available are skipped.
+pub fn get_scalefactors(frames: &[Mp3Frame]) -> Vec<Mp3FrameScalefactors> {
+    let mut reservoir = Mp3Reservoir::new();
+    frames
+        .iter()
+        .filter_map(|frame| {
+            let (side_info, main_data) = reservoir.next_frame(frame)?;
+            Some(Mp3FrameScalefactors::decode(frame, &side_info, &main_data))
+        })
+        .collect()
+}
+
+/// Decodes the Layer III main data (scalefactors and quantized spectrum) of all frames.
+/// Frames which are not Layer III or whose main data is not available are skipped.
+pub fn get_main_data(frames: &[Mp3Frame]) -> Vec<Mp3MainData> {
+    let mut reservoir = Mp3Reservoir::new();
+    frames
+        .iter()
+        .filter_map(|frame| {
+            let (side_info, main_data) = reservoir.next_frame(frame)?;
+            Some(Mp3MainData::decode(frame, side_info, &main_data))
+        })
+        .collect()
+}
+
+/// Xing/Info tag of the stream, stored in the first frame
+pub fn get_xing_tag(frames: &[Mp3Frame]) -> Option<Mp3XingTag> {
+    Mp3XingTag::parse(frames.first()?)
+}
+
+/// Decodes all frames to PCM audio.
+/// Sample rate and channel count are taken from the first frame, frames with
+/// a different channel count are up- or downmixed.
+/// A Xing/Info tag frame carries no audio and is skipped, like junk.
+pub fn decode(frames: &[Mp3Frame]) -> Mp3Audio {
+    let frames = match get_xing_tag(frames) {
+        Some(_) => &frames[1..],
+        None => frames,
+    };
+    let (sample_rate, channels) = frames
+        .iter()
+        .find(|frame| !frame.is_junk())
+        .map_or((0, 0), |frame| {
+            (frame.header.sample_rate() as u32, frame.header.channels())
+        });
+    let mut decoder = Mp3Decoder::new();
+    let mut samples = Vec::new();
+    for frame in frames.iter().filter(|frame| !frame.is_junk()) {
+        let pcm = decoder.decode_frame(frame);
+        match (frame.header.channels(), channels) {
+            (1, 2) => samples.extend(pcm.iter().flat_map(|&x| [x, x])),
+            (2, 1) => samples.extend(pcm.chunks(2).map(|x| (x[0] + x[1]) / 2.0)),
+            _ => samples.extend(pcm),
+        }
+    }
+    Mp3Audio {
+        sample_rate,
+        channels,
+        samples,
+    }
+}
+
+/// Decodes all frames to PCM audio like `decode`, removing the encoder delay and padding
+/// given by the LAME tag. Streams without a LAME tag are not trimmed.
+pub fn decode_gapless(frames: &[Mp3Frame]) -> Mp3Audio {
+    let mut audio = decode(frames);
+    if let Some(lame) = get_xing_tag(frames).and_then(|xing| xing.lame) {
+        audio.trim(
+            lame.encoder_delay as usize + DECODER_DELAY,
+            (lame.encoder_padding as usize).saturating_sub(DECODER_DELAY),
+        );
+    }
+    audio
+}
+
+/// Decodes all frames like `decode_gapless` and computes the waveform peaks
+/// at a resolution of `samples_per_pixel` samples per channel.
+pub fn get_waveform(frames: &[Mp3Frame], samples_per_pixel: usize) -> Waveform {
+    Waveform::from_audio(&decode_gapless(frames), samples_per_pixel)
+}
+
+/// Approximates the waveform peaks from the Layer III side information and scalefactors,
+/// without Huffman decoding or synthesis. Much faster than `get_waveform`, but coarse:
+/// the resolution is rounded down to a multiple of a granule (576 samples).
+pub fn get_approximate_waveform(frames: &[Mp3Frame], samples_per_pixel: usize) -> Waveform {
+    let frames = match get_xing_tag(frames) {
+        Some(_) => &frames[1..],
+        None => frames,
+    };
+    mp3_envelope::approximate_waveform(frames).downsample(samples_per_pixel / 576)
+}
+
 #[cfg(test)]
+#[allow(
+    clippy::useless_vec,
+    clippy::bool_assert_comparison,
+    clippy::identity_op
+)]
 mod tests {
     use super::*;
 
@@ -183,4 +401,28 @@ mod tests {
             10 + 0b1111111_1111111_1111111_1111111
         );
     }
+
+    #[test]
+    fn test_get_frames_junk() {
+        let mut frame = vec![0; 417];
+        frame[0..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        let mut data = frame.repeat(2);
+        data.extend([0x12; 20]);
+        data.extend(frame.repeat(2));
+        let lengths: Vec<usize> = get_frames(&data)
+            .unwrap()
+            .iter()
+            .map(|frame| frame.body.data.len())
+            .collect();
+        assert_eq!(lengths, [417, 417, 20, 417, 417]);
+        assert_eq!(
+            get_id3_data(b"ID3\x03\x00\x00\x00\x00\x10\x00abc").len(),
+            13
+        );
+        // the junk is not decoded
+        assert_eq!(
+            decode(&get_frames(&data).unwrap()).samples.len(),
+            4 * 2 * 1152
+        );
+    }
 }
@@ -0,0 +1,439 @@
+// References
+// ITU-R BS.1770-4 (K-weighting, gating, true peak)
+// EBU Tech 3341 (loudness metering) and EBU Tech 3342 (loudness range)
+// https://wiki.hydrogenaud.io/index.php?title=ReplayGain_2.0_specification
+// https://github.com/jiixyj/libebur128 (filter coefficients for any sample rate)
+
+use std::f64::consts::PI;
+
+use super::apev2::ApeTag;
+use super::id3v2::Id3v2Tag;
+use super::mp3_decoder::Mp3Audio;
+
+/// Offset of the loudness formula in LUFS
+const LOUDNESS_OFFSET: f64 = -0.691;
+
+/// Blocks of the gated integrated loudness
+const BLOCK_SECONDS: f64 = 0.4;
+/// Short-term blocks of the loudness range
+const SHORT_TERM_SECONDS: f64 = 3.0;
+/// Step between consecutive blocks
+const STEP_SECONDS: f64 = 0.1;
+
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+const RANGE_RELATIVE_GATE: f64 = -20.0;
+
+/// Oversampling of the true peak measurement and length of its interpolation filter
+const OVERSAMPLING: usize = 4;
+const INTERPOLATION_TAPS: usize = 12 * OVERSAMPLING + 1;
+
+/// Loudness which ReplayGain 2.0 normalizes to, in LUFS
+pub const REPLAYGAIN_REFERENCE: f64 = -18.0;
+
+/// Second order IIR filter in direct form I
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Biquad {
+        Biquad {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[1] * self.y[0]
+            - self.a[2] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// Pre-filter (high shelf) and RLB filter (high pass) of the K-weighting
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / sample_rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+    [shelf, high_pass]
+}
+
+fn to_loudness(energy: f64) -> f64 {
+    LOUDNESS_OFFSET + 10.0 * energy.log10()
+}
+
+fn to_energy(loudness: f64) -> f64 {
+    10f64.powf((loudness - LOUDNESS_OFFSET) / 10.0)
+}
+
+/// Energies of the blocks of `length` steps, advancing by one step
+fn block_energies(step_energies: &[f64], length: usize) -> Vec<f64> {
+    step_energies
+        .windows(length)
+        .map(|steps| steps.iter().sum::<f64>() / length as f64)
+        .collect()
+}
+
+/// Interpolation filter of the true peak measurement, a Hann windowed sinc
+fn interpolation_filter() -> [f64; INTERPOLATION_TAPS] {
+    let center = (INTERPOLATION_TAPS / 2) as f64;
+    let mut taps = [0.0; INTERPOLATION_TAPS];
+    for (n, tap) in taps.iter_mut().enumerate() {
+        let t = (n as f64 - center) / OVERSAMPLING as f64;
+        let sinc = match t == 0.0 {
+            true => 1.0,
+            false => (PI * t).sin() / (PI * t),
+        };
+        let window = 0.5 + 0.5 * (PI * (n as f64 - center) / (center + 1.0)).cos();
+        *tap = sinc * window;
+    }
+    taps
+}
+
+/// Largest absolute value of the signal oversampled by `OVERSAMPLING`
+fn true_peak(samples: &[f32], channels: usize, taps: &[f64; INTERPOLATION_TAPS]) -> f32 {
+    let mut peak = 0.0f64;
+    for ch in 0..channels {
+        let channel: Vec<f64> = samples
+            .iter()
+            .skip(ch)
+            .step_by(channels)
+            .map(|&x| x as f64)
+            .collect();
+        for i in 0..channel.len() * OVERSAMPLING {
+            let mut sum = 0.0;
+            // taps at the positions of the original samples
+            let mut n = i % OVERSAMPLING;
+            while n < INTERPOLATION_TAPS && n <= i {
+                if let Some(x) = channel.get((i - n) / OVERSAMPLING) {
+                    sum += taps[n] * x;
+                }
+                n += OVERSAMPLING;
+            }
+            peak = peak.max(sum.abs());
+        }
+    }
+    peak as f32
+}
+
+/// Loudness measurement of decoded audio
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoudnessAnalysis {
+    /// Mean square of the K-weighted channels, summed over the channels, of each 400 ms block
+    block_energies: Vec<f64>,
+    /// Like `block_energies` for the 3 s short-term blocks
+    short_term_energies: Vec<f64>,
+    /// Largest absolute sample value
+    pub sample_peak: f32,
+    /// Largest absolute value of the 4 times oversampled signal
+    pub true_peak: f32,
+}
+
+impl LoudnessAnalysis {
+    pub fn new(audio: &Mp3Audio) -> LoudnessAnalysis {
+        let channels = audio.channels.max(1);
+        let sample_rate = audio.sample_rate.max(1) as f64;
+        let step = ((sample_rate * STEP_SECONDS).round() as usize).max(1);
+
+        // mean square of the weighted signal in each step, summed over the channels
+        let mut filters = vec![k_weighting(sample_rate); channels];
+        let mut step_energies = Vec::new();
+        for frame in audio.samples.chunks(step * channels) {
+            let mut energy = 0.0;
+            for (i, &sample) in frame.iter().enumerate() {
+                let [shelf, high_pass] = &mut filters[i % channels];
+                let y = high_pass.process(shelf.process(sample as f64));
+                energy += y * y;
+            }
+            // an incomplete last step is not part of any block
+            if frame.len() == step * channels {
+                step_energies.push(energy / step as f64);
+            }
+        }
+
+        let steps = |seconds: f64| (seconds / STEP_SECONDS).round() as usize;
+        let taps = interpolation_filter();
+        LoudnessAnalysis {
+            block_energies: block_energies(&step_energies, steps(BLOCK_SECONDS)),
+            short_term_energies: block_energies(&step_energies, steps(SHORT_TERM_SECONDS)),
+            sample_peak: audio.samples.iter().fold(0.0, |peak, x| peak.max(x.abs())),
+            true_peak: true_peak(&audio.samples, channels, &taps),
+        }
+    }
+
+    /// Analysis of several tracks played after each other, e.g. an album
+    pub fn combine(tracks: &[LoudnessAnalysis]) -> LoudnessAnalysis {
+        LoudnessAnalysis {
+            block_energies: tracks
+                .iter()
+                .flat_map(|t| t.block_energies.clone())
+                .collect(),
+            short_term_energies: tracks
+                .iter()
+                .flat_map(|t| t.short_term_energies.clone())
+                .collect(),
+            sample_peak: tracks.iter().fold(0.0, |peak, t| peak.max(t.sample_peak)),
+            true_peak: tracks.iter().fold(0.0, |peak, t| peak.max(t.true_peak)),
+        }
+    }
+
+    /// Gated integrated loudness in LUFS, negative infinity for silence
+    pub fn integrated(&self) -> f64 {
+        let gated = |energies: Vec<f64>, gate: f64| -> Vec<f64> {
+            energies
+                .into_iter()
+                .filter(|&energy| to_loudness(energy) > gate)
+                .collect()
+        };
+        let mean = |energies: &[f64]| energies.iter().sum::<f64>() / energies.len() as f64;
+
+        let blocks = gated(self.block_energies.clone(), ABSOLUTE_GATE);
+        if blocks.is_empty() {
+            return f64::NEG_INFINITY;
+        }
+        let gate = to_loudness(mean(&blocks)) + RELATIVE_GATE;
+        to_loudness(mean(&gated(blocks, gate)))
+    }
+
+    /// Loudness range in LU, the spread of the gated short-term loudness
+    /// between its 10th and 95th percentile
+    pub fn range(&self) -> f64 {
+        let mut loudness: Vec<f64> = self
+            .short_term_energies
+            .iter()
+            .map(|&energy| to_loudness(energy))
+            .filter(|&loudness| loudness > ABSOLUTE_GATE)
+            .collect();
+        if loudness.is_empty() {
+            return 0.0;
+        }
+        let mean = loudness.iter().map(|&l| to_energy(l)).sum::<f64>() / loudness.len() as f64;
+        let gate = to_loudness(mean) + RANGE_RELATIVE_GATE;
+        loudness.retain(|&l| l > gate);
+        loudness.sort_by(f64::total_cmp);
+        let percentile = |p: f64| loudness[((loudness.len() - 1) as f64 * p).round() as usize];
+        percentile(0.95) - percentile(0.10)
+    }
+
+    /// Gain in dB to reach the target loudness in LUFS, 0 for silence
+    pub fn gain_to(&self, target: f64) -> f64 {
+        match self.integrated() {
+            loudness if loudness.is_finite() => target - loudness,
+            _ => 0.0,
+        }
+    }
+}
+
+/// ReplayGain 2.0 values of a track and optionally its album
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayGain {
+    /// Gain in dB
+    pub track_gain: f64,
+    /// True peak, linear
+    pub track_peak: f32,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    pub fn new(track: &LoudnessAnalysis, album: Option<&LoudnessAnalysis>) -> ReplayGain {
+        ReplayGain {
+            track_gain: track.gain_to(REPLAYGAIN_REFERENCE),
+            track_peak: track.true_peak,
+            album_gain: album.map(|album| album.gain_to(REPLAYGAIN_REFERENCE)),
+            album_peak: album.map(|album| album.true_peak),
+        }
+    }
+
+    /// Item names and values as written by ReplayGain 2.0 taggers
+    pub fn items(&self) -> Vec<(&'static str, String)> {
+        let mut items = vec![
+            (
+                "REPLAYGAIN_TRACK_GAIN",
+                format!("{:.2} dB", self.track_gain),
+            ),
+            ("REPLAYGAIN_TRACK_PEAK", format!("{:.6}", self.track_peak)),
+        ];
+        if let Some(gain) = self.album_gain {
+            items.push(("REPLAYGAIN_ALBUM_GAIN", format!("{:.2} dB", gain)));
+        }
+        if let Some(peak) = self.album_peak {
+            items.push(("REPLAYGAIN_ALBUM_PEAK", format!("{:.6}", peak)));
+        }
+        items.push((
+            "REPLAYGAIN_REFERENCE_LOUDNESS",
+            format!("{:.2} LUFS", REPLAYGAIN_REFERENCE),
+        ));
+        items
+    }
+
+    /// Writes the values into TXXX frames
+    pub fn write_id3v2(&self, tag: &mut Id3v2Tag) {
+        for (name, value) in self.items() {
+            tag.set_user_text(name, &value);
+        }
+    }
+
+    /// Writes the values into APEv2 items
+    pub fn write_ape(&self, tag: &mut ApeTag) {
+        for (name, value) in self.items() {
+            tag.set(name, &value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sine of the given frequency, amplitude and duration on all channels
+    fn sine(
+        sample_rate: u32,
+        channels: usize,
+        frequency: f64,
+        amplitude: f32,
+        seconds: f64,
+    ) -> Mp3Audio {
+        let length = (sample_rate as f64 * seconds) as usize;
+        let samples = (0..length)
+            .flat_map(|i| {
+                let x = (2.0 * PI * frequency * i as f64 / sample_rate as f64).sin();
+                vec![amplitude * x as f32; channels]
+            })
+            .collect();
+        Mp3Audio {
+            sample_rate,
+            channels,
+            samples,
+        }
+    }
+
+    #[test]
+    fn test_integrated() {
+        // a 0 dBFS 997 Hz sine reads -3.01 LUFS per channel
+        let stereo = LoudnessAnalysis::new(&sine(48000, 2, 997.0, 1.0, 2.0));
+        assert!(
+            (stereo.integrated() - 0.0).abs() < 0.05,
+            "{}",
+            stereo.integrated()
+        );
+        let mono = LoudnessAnalysis::new(&sine(44100, 1, 997.0, 0.1, 2.0));
+        assert!(
+            (mono.integrated() + 23.01).abs() < 0.05,
+            "{}",
+            mono.integrated()
+        );
+        assert!((mono.gain_to(-14.0) - 9.01).abs() < 0.05);
+        let silence = LoudnessAnalysis::new(&sine(44100, 1, 997.0, 0.0, 2.0));
+        assert_eq!(silence.integrated(), f64::NEG_INFINITY);
+        assert_eq!(silence.gain_to(-14.0), 0.0);
+    }
+
+    #[test]
+    fn test_gating() {
+        // the quiet half is below the relative gate and does not lower the loudness
+        let mut audio = sine(16000, 1, 1000.0, 1.0, 10.0);
+        audio
+            .samples
+            .extend(sine(16000, 1, 1000.0, 0.01, 10.0).samples);
+        let analysis = LoudnessAnalysis::new(&audio);
+        assert!(
+            (analysis.integrated() + 3.01).abs() < 0.1,
+            "{}",
+            analysis.integrated()
+        );
+    }
+
+    #[test]
+    fn test_range() {
+        let mut audio = sine(16000, 1, 1000.0, 0.1, 15.0);
+        audio
+            .samples
+            .extend(sine(16000, 1, 1000.0, 0.1f32 * 10f32.powf(-0.5), 15.0).samples);
+        let analysis = LoudnessAnalysis::new(&audio);
+        assert!(
+            (analysis.range() - 10.0).abs() < 0.2,
+            "{}",
+            analysis.range()
+        );
+        assert!(LoudnessAnalysis::new(&sine(16000, 1, 1000.0, 0.1, 5.0)).range() < 0.1);
+    }
+
+    #[test]
+    fn test_true_peak() {
+        // samples of a quarter sample rate sine at 45 degrees miss its peaks by 3 dB
+        let audio = Mp3Audio {
+            sample_rate: 48000,
+            channels: 1,
+            samples: (0..4800)
+                .map(|i| (PI / 2.0 * i as f64 + PI / 4.0).sin() as f32)
+                .collect(),
+        };
+        let analysis = LoudnessAnalysis::new(&audio);
+        assert!((analysis.sample_peak - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+        assert!(
+            (analysis.true_peak - 1.0).abs() < 0.02,
+            "{}",
+            analysis.true_peak
+        );
+    }
+
+    #[test]
+    fn test_replaygain() {
+        let track = LoudnessAnalysis::new(&sine(44100, 2, 997.0, 0.5, 2.0));
+        let quiet = LoudnessAnalysis::new(&sine(44100, 2, 997.0, 0.25, 2.0));
+        let album = LoudnessAnalysis::combine(&[track.clone(), quiet]);
+        let gain = ReplayGain::new(&track, Some(&album));
+        assert!(
+            (gain.track_gain + 18.0 - 6.02).abs() < 0.05,
+            "{}",
+            gain.track_gain
+        );
+        assert!(gain.album_gain.unwrap() > gain.track_gain);
+        assert_eq!(gain.album_peak, Some(track.true_peak));
+
+        let mut tag = Id3v2Tag::default();
+        gain.write_id3v2(&mut tag);
+        let tag = Id3v2Tag::parse(&tag.to_bytes()).unwrap();
+        assert_eq!(
+            tag.user_text("REPLAYGAIN_TRACK_GAIN"),
+            Some(format!("{:.2} dB", gain.track_gain))
+        );
+        let mut ape = ApeTag::default();
+        gain.write_ape(&mut ape);
+        assert_eq!(
+            ape.get("replaygain_reference_loudness"),
+            Some("-18.00 LUFS".to_string())
+        );
+    }
+}
@@ -0,0 +1,58 @@
+// References
+// https://id3.org/Lyrics3
+// https://id3.org/Lyrics3v2
+
+const BEGIN: &[u8; 11] = b"LYRICSBEGIN";
+const END_V1: &[u8; 9] = b"LYRICSEND";
+const END_V2: &[u8; 9] = b"LYRICS200";
+
+/// Largest Lyrics3v1 tag, including begin and end marker
+const MAX_LENGTH_V1: usize = 5100 + BEGIN.len() + END_V1.len();
+
+/// Length of the Lyrics3 tag at the end of the data, 0 if there is none
+pub fn lyrics3_length_at_end(data: &[u8]) -> usize {
+    if data.ends_with(END_V2) && data.len() >= 15 {
+        // Lyrics3v2: 6 digit size of the tag without size and end marker
+        let size = std::str::from_utf8(&data[data.len() - 15..data.len() - 9])
+            .ok()
+            .and_then(|size| size.parse::<usize>().ok());
+        return match size {
+            Some(size) if data.len() >= size + 15 => {
+                let start = data.len() - size - 15;
+                match data[start..].starts_with(BEGIN) {
+                    true => size + 15,
+                    false => 0,
+                }
+            }
+            _ => 0,
+        };
+    }
+    if data.ends_with(END_V1) {
+        let window = &data[data.len().saturating_sub(MAX_LENGTH_V1)..];
+        if let Some(start) = window
+            .windows(BEGIN.len())
+            .rposition(|bytes| bytes == BEGIN)
+        {
+            return window.len() - start;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lyrics3_length() {
+        let v1 = b"audioLYRICSBEGIN[00:01]TextLYRICSEND";
+        assert_eq!(lyrics3_length_at_end(v1), v1.len() - 5);
+        let mut v2 = b"audioLYRICSBEGININD00002".to_vec();
+        v2.extend(b"10");
+        v2.extend(b"000021LYRICS200");
+        assert_eq!(lyrics3_length_at_end(&v2), v2.len() - 5);
+        v2[10] = b'X';
+        assert_eq!(lyrics3_length_at_end(&v2), 0);
+        assert_eq!(lyrics3_length_at_end(b"LYRICS200"), 0);
+    }
+}
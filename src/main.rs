@@ -1,57 +1,1516 @@
 use std::env;
-// use rand::Rng;
-// use std::fs::File;
-// use std::io::Write;
-
-// fn mix() {
-//     let mut rng = rand::thread_rng();
-//     let args: Vec<String> = env::args().collect();
-//     let file_path1 = &args[1];
-//     let file_path2 = &args[2];
-//     let file1 = mpeak::load_file(file_path1).unwrap();
-//     let file2 = mpeak::load_file(file_path2).unwrap();
-//     let frames1 = mpeak::get_frames(&file1).unwrap();
-//     let frames2 = mpeak::get_frames(&file2).unwrap();
-
-//     let mut n_frames = Vec::<u8>::new();
-//     for i in 0..usize::min(frames1.len(), frames2.len()) {
-//         let p = rng.gen::<f64>();
-//         if p < 0.5 {
-//             n_frames.extend(&frames1[i].body.data);
-//         } else {
-//             n_frames.extend(&frames2[i].body.data);
-//         }
-//     }
-
-//     let mut file = File::create("output.mp3").unwrap();
-//     file.write_all(n_frames.as_slice()).unwrap();
-//     file.flush().unwrap();
-// }
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let file_path = &args[1];
-    let file_result = mpeak::load_file(file_path);
-    match file_result {
-        Ok(file_data) => {
-            println!("is mp3:             {:?}", mpeak::is_mp3_file(&file_data));
-            println!("has id3:            {:?}", mpeak::has_id3(&file_data));
-            println!(
-                "id3 offset:         {:?}",
-                mpeak::get_id3_offset(&file_data)
+const USAGE: &str = "usage: mpeak <command> [<args>]
+
+commands:
+  info      summary of each file
+  frames    header of every frame
+  tags      ID3v2, APE and ID3v1 tags
+  validate  report conformance issues of each file
+  peaks     waveform peak data for rendering
+  strip     remove tags
+  normalise merge and convert ID3v2 tags
+  art       list, extract, add and remove embedded pictures
+  chapters  list, set and remove ID3v2 chapters
+  lyrics    show, export, import and remove lyrics
+  repair    rebuild a clean file from a mangled one
+  cut       cut a time range without re-encoding
+  join      concatenate files without re-encoding
+  split     split a file at times, silences or a CUE sheet
+  mix       interleave blocks of frames of several files
+  decode    decode to WAV
+  gain      change the volume without re-encoding
+  loudness  measure the loudness and write ReplayGain tags
+
+`mpeak <command> --help` shows the arguments of a command.
+File arguments may contain * and ? wildcards.
+
+exit codes: 0 success, 1 error, 2 invalid arguments, 3 validation problems";
+
+/// Exit codes
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_INVALID: i32 = 3;
+
+/// `print!` through a locked stdout, see `check_output`
+macro_rules! out {
+    ($($arg:tt)*) => {
+        check_output(write!(io::stdout().lock(), $($arg)*))
+    };
+}
+
+/// `println!` through a locked stdout, see `check_output`
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        check_output(writeln!(io::stdout().lock(), $($arg)*))
+    };
+}
+
+/// Exits quietly if stdout was closed, like by `mpeak frames a.mp3 | head`,
+/// and with EXIT_ERROR on other write errors
+fn check_output(result: io::Result<()>) {
+    match result {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(EXIT_ERROR);
+        }
+    }
+}
+
+type Command = fn(&[String]) -> Result<(), mpeak::MPeakError>;
+
+const COMMANDS: [(&str, &str, Command); 18] = [
+    ("info", INFO_USAGE, info),
+    ("frames", FRAMES_USAGE, frames),
+    ("tags", TAGS_USAGE, tags),
+    ("validate", VALIDATE_USAGE, validate),
+    ("peaks", PEAKS_USAGE, peaks),
+    ("strip", STRIP_USAGE, strip),
+    ("normalise", NORMALISE_USAGE, normalise),
+    ("art", ART_USAGE, art),
+    ("chapters", CHAPTERS_USAGE, chapters),
+    ("lyrics", LYRICS_USAGE, lyrics),
+    ("repair", REPAIR_USAGE, repair),
+    ("cut", CUT_USAGE, cut),
+    ("join", JOIN_USAGE, join),
+    ("split", SPLIT_USAGE, split),
+    ("mix", MIX_USAGE, mix),
+    ("decode", DECODE_USAGE, decode),
+    ("gain", GAIN_USAGE, gain),
+    ("loudness", LOUDNESS_USAGE, loudness),
+];
+
+/// Runs `command` for every path. A failing path is reported and the remaining paths
+/// are processed. Returns whether all paths succeeded.
+fn for_each_path(
+    paths: &[String],
+    mut command: impl FnMut(&str) -> Result<(), mpeak::MPeakError>,
+) -> bool {
+    let mut success = true;
+    for path in paths {
+        if let Err(e) = command(path) {
+            eprintln!("{}: {:?}", path, e);
+            success = false;
+        }
+    }
+    success
+}
+
+/// Exits with EXIT_ERROR after a failure reported by `for_each_path`
+fn exit_on_failure(success: bool) -> Result<(), mpeak::MPeakError> {
+    if !success {
+        process::exit(EXIT_ERROR);
+    }
+    Ok(())
+}
+
+/// Matches a file name against a pattern with * and ? wildcards
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            wildcard_match(rest, name) || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some((b'?', rest)), Some((_, name))) => wildcard_match(rest, name),
+        (Some((p, rest)), Some((n, name))) if p == n => wildcard_match(rest, name),
+        _ => false,
+    }
+}
+
+/// Expands wildcards in the file name of the arguments, for shells which don't.
+/// Hidden files only match patterns starting with a dot, patterns without matches are kept.
+fn expand_globs(args: &[String]) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        let path = Path::new(arg);
+        let pattern = path.file_name().map(|name| name.to_string_lossy());
+        let pattern = match pattern {
+            Some(pattern) if !arg.starts_with('-') && pattern.contains(['*', '?']) => pattern,
+            _ => {
+                expanded.push(arg.clone());
+                continue;
+            }
+        };
+        let parent = path.parent().unwrap_or(Path::new(""));
+        let dir = match parent.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => parent,
+        };
+        let mut matches: Vec<PathBuf> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| !name.starts_with('.') || pattern.starts_with('.'))
+            .filter(|name| wildcard_match(pattern.as_bytes(), name.as_bytes()))
+            .map(|name| parent.join(name))
+            .collect();
+        matches.sort();
+        match matches.is_empty() {
+            true => expanded.push(arg.clone()),
+            false => expanded.extend(matches.iter().map(|path| path.display().to_string())),
+        }
+    }
+    expanded
+}
+
+/// Duration of the frames in seconds
+fn duration(frames: &[mpeak::Mp3Frame]) -> f64 {
+    frames
+        .iter()
+        .map(|frame| {
+            frame.header.samples_per_frame() as f64 / frame.header.sample_rate().max(1) as f64
+        })
+        .sum()
+}
+
+/// Output format of `info`, `frames`, `tags` and `validate`
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+/// Removes `--format json|csv|text` from the arguments
+fn take_format(args: &[String]) -> Option<(Format, Vec<String>)> {
+    let mut format = Format::Text;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = match args.next()?.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    "csv" => Format::Csv,
+                    _ => return None,
+                }
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+    Some((format, rest))
+}
+
+/// Field value of an output record
+enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Null,
+}
+
+impl Value {
+    fn text(&self) -> String {
+        match self {
+            Value::Str(value) => value.clone(),
+            Value::Int(value) => value.to_string(),
+            Value::Float(value) => format!("{:.3}", value),
+            Value::Bool(value) => value.to_string(),
+            Value::Null => "-".to_string(),
+        }
+    }
+
+    fn csv(&self) -> String {
+        match self {
+            Value::Str(value) if value.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", value.replace('"', "\"\""))
+            }
+            Value::Null => String::new(),
+            Value::Float(value) => value.to_string(),
+            value => value.text(),
+        }
+    }
+
+    fn json(&self) -> String {
+        match self {
+            Value::Str(value) => json_string(value),
+            Value::Float(value) if value.is_finite() => value.to_string(),
+            Value::Float(_) | Value::Null => "null".to_string(),
+            value => value.text(),
+        }
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::Int(value as i64)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Self {
+        Value::Int(value as i64)
+    }
+}
+
+impl From<u16> for Value {
+    fn from(value: u16) -> Self {
+        Value::Int(value as i64)
+    }
+}
+
+impl From<u8> for Value {
+    fn from(value: u8) -> Self {
+        Value::Int(value as i64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+/// Prints records with the same fields as text, a JSON array of objects or CSV with a
+/// header row. Text prints `field: value` lines per record for a list, otherwise
+/// one line of tab separated values per record.
+struct Printer {
+    format: Format,
+    list: bool,
+    records: usize,
+}
+
+impl Printer {
+    fn new(format: Format, list: bool) -> Printer {
+        Printer {
+            format,
+            list,
+            records: 0,
+        }
+    }
+
+    fn print(&mut self, record: &[(&str, Value)]) {
+        match self.format {
+            Format::Json => {
+                let fields: Vec<String> = record
+                    .iter()
+                    .map(|(field, value)| format!("{}:{}", json_string(field), value.json()))
+                    .collect();
+                let separator = if self.records == 0 { "[" } else { "," };
+                out!("{}\n{{{}}}", separator, fields.join(","));
+            }
+            Format::Csv => {
+                if self.records == 0 {
+                    let fields: Vec<&str> = record.iter().map(|(field, _)| *field).collect();
+                    outln!("{}", fields.join(","));
+                }
+                let values: Vec<String> = record.iter().map(|(_, value)| value.csv()).collect();
+                outln!("{}", values.join(","));
+            }
+            Format::Text if self.list => {
+                if self.records > 0 {
+                    outln!();
+                }
+                for (field, value) in record {
+                    outln!("{}: {}", field, value.text());
+                }
+            }
+            Format::Text => {
+                let values: Vec<String> = record.iter().map(|(_, value)| value.text()).collect();
+                outln!("{}", values.join("\t"));
+            }
+        }
+        self.records += 1;
+    }
+
+    fn finish(&self) {
+        if self.format == Format::Json {
+            if self.records == 0 {
+                out!("[");
+            }
+            outln!("\n]");
+        }
+    }
+}
+
+/// Parses the format and the paths of `info`, `frames`, `tags` and `validate`
+fn format_and_paths(args: &[String], usage: &str) -> (Format, Vec<String>) {
+    match take_format(args) {
+        Some((format, paths))
+            if !paths.is_empty() && !paths.iter().any(|arg| arg.starts_with("--")) =>
+        {
+            (format, paths)
+        }
+        _ => {
+            eprintln!("{}", usage);
+            process::exit(EXIT_USAGE);
+        }
+    }
+}
+
+const INFO_USAGE: &str = "usage: mpeak info [--format text|json|csv] <in.mp3>...";
+
+/// mpeak info [--format text|json|csv] in.mp3 ...
+fn info(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let (format, paths) = format_and_paths(args, INFO_USAGE);
+    let mut printer = Printer::new(format, true);
+    let success = for_each_path(&paths, |path| {
+        let data = mpeak::load_file(path)?;
+        let stream = mpeak::Mp3Stream::parse(&data)?;
+        let header = stream
+            .frames
+            .first()
+            .ok_or(mpeak::MPeakError::InvalidMp3Header)?
+            .header;
+        let seconds = duration(&stream.frames);
+        let bytes: usize = stream.frames.iter().map(|f| f.body.data.len()).sum();
+        let is_vbr = stream
+            .frames
+            .iter()
+            .any(|frame| frame.header.bitrate_index() != header.bitrate_index());
+        let lame = stream.xing.as_ref().and_then(|xing| xing.lame.as_ref());
+        printer.print(&[
+            ("file", path.into()),
+            ("size", data.len().into()),
+            ("version", format!("{:?}", header.version()).into()),
+            ("layer", format!("{:?}", header.layer()).into()),
+            ("sample_rate", header.sample_rate().into()),
+            (
+                "channel_mode",
+                format!("{:?}", header.channel_mode()).into(),
+            ),
+            (
+                "bitrate",
+                (bytes as f64 * 8.0 / seconds.max(f64::MIN_POSITIVE) / 1000.0).into(),
+            ),
+            ("vbr", is_vbr.into()),
+            ("duration", seconds.into()),
+            ("frames", stream.frames.len().into()),
+            ("id3v2_bytes", stream.id3v2.len().into()),
+            ("ape_bytes", stream.ape.len().into()),
+            ("id3v1_bytes", stream.id3v1.len().into()),
+            (
+                "xing",
+                (stream.xing.as_ref())
+                    .map(|xing| if xing.is_vbr { "Xing" } else { "Info" })
+                    .into(),
+            ),
+            ("encoder", lame.map(|lame| lame.encoder.clone()).into()),
+            ("encoder_delay", lame.map(|lame| lame.encoder_delay).into()),
+            (
+                "encoder_padding",
+                lame.map(|lame| lame.encoder_padding).into(),
+            ),
+        ]);
+        Ok(())
+    });
+    printer.finish();
+    exit_on_failure(success)
+}
+
+const FRAMES_USAGE: &str = "usage: mpeak frames [--format text|json|csv] <in.mp3>...";
+
+/// mpeak frames [--format text|json|csv] in.mp3 ...
+/// data which is no frame is listed with valid false
+fn frames(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let (format, paths) = format_and_paths(args, FRAMES_USAGE);
+    let mut printer = Printer::new(format, false);
+    let success = for_each_path(&paths, |path| {
+        let data = mpeak::load_file(path)?;
+        let frames = mpeak::get_frames(&data)?;
+        let mut offset = mpeak::get_id3_offset(&data) as usize;
+        for (i, frame) in frames.iter().enumerate() {
+            // get_frames skips ID3v2 tags between the frames
+            while let Some(length) = data
+                .get(offset..)
+                .and_then(mpeak::Id3v2Tag::length_at_start)
+            {
+                offset += length;
+            }
+            let header = frame.header;
+            let valid = header.frame_length() == Ok(frame.body.data.len());
+            let field = |value: Value| if valid { value } else { Value::Null };
+            printer.print(&[
+                ("file", path.into()),
+                ("index", i.into()),
+                ("offset", offset.into()),
+                ("length", frame.body.data.len().into()),
+                ("valid", valid.into()),
+                ("version", field(format!("{:?}", header.version()).into())),
+                ("layer", field(format!("{:?}", header.layer()).into())),
+                ("bitrate", field(header.bitrate().into())),
+                ("sample_rate", field(header.sample_rate().into())),
+                ("padding", field(header.padding_bit().into())),
+                (
+                    "channel_mode",
+                    field(format!("{:?}", header.channel_mode()).into()),
+                ),
+                (
+                    "crc",
+                    frame
+                        .crc_matches()
+                        .map(|ok| if ok { "ok" } else { "mismatch" })
+                        .into(),
+                ),
+            ]);
+            offset += frame.body.data.len();
+        }
+        Ok(())
+    });
+    printer.finish();
+    exit_on_failure(success)
+}
+
+const TAGS_USAGE: &str = "usage: mpeak tags [--format text|json|csv] <in.mp3>...";
+
+/// mpeak tags [--format text|json|csv] in.mp3 ...
+/// prints file, tag, key and value of every ID3v2 location and frame, APE item and ID3v1 field
+fn tags(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let (format, paths) = format_and_paths(args, TAGS_USAGE);
+    let mut printer = Printer::new(format, false);
+    let success = for_each_path(&paths, |path| {
+        let data = mpeak::load_file(path)?;
+        let stream = mpeak::Mp3Stream::parse(&data)?;
+        let mut print = |tag: &str, key: &str, value: String| {
+            printer.print(&[
+                ("file", path.into()),
+                ("tag", tag.into()),
+                ("key", key.into()),
+                ("value", value.into()),
+            ])
+        };
+        for location in mpeak::find_id3v2_tags(&data) {
+            let name = format!("ID3v2.{}", location.version);
+            print(
+                &name,
+                "location",
+                format!(
+                    "{:?} at {}, {} bytes",
+                    location.placement, location.offset, location.length
+                ),
             );
-            println!(
-                "id3 data:           {:?}",
-                String::from_utf8_lossy(&mpeak::get_id3_data(&file_data))
+            let bytes = &data[location.offset..location.offset + location.length];
+            let Some(tag) = mpeak::Id3v2Tag::parse(bytes) else {
+                continue;
+            };
+            for frame in &tag.frames {
+                match frame.id.starts_with('T') {
+                    true => print(&name, &frame.id, frame.strings().join(" / ")),
+                    false => print(&name, &frame.id, format!("<{} bytes>", frame.data.len())),
+                }
+            }
+        }
+        if let Some(tag) = mpeak::ApeTag::parse(&stream.ape) {
+            for item in &tag.items {
+                // bits 1 and 2 select text (0), binary (1) or a locator (2)
+                match item.flags >> 1 & 3 {
+                    1 => print("APEv2", &item.key, format!("<{} bytes>", item.value.len())),
+                    _ => print(
+                        "APEv2",
+                        &item.key,
+                        String::from_utf8_lossy(&item.value).into_owned(),
+                    ),
+                }
+            }
+        }
+        if let Some(tag) = mpeak::Id3v1Tag::parse(&stream.id3v1) {
+            for (field, value) in [
+                ("title", tag.title),
+                ("artist", tag.artist),
+                ("album", tag.album),
+                ("year", tag.year),
+                ("comment", tag.comment),
+            ] {
+                print("ID3v1", field, value);
+            }
+            if let Some(track) = tag.track {
+                print("ID3v1", "track", track.to_string());
+            }
+            print("ID3v1", "genre", tag.genre.to_string());
+        }
+        Ok(())
+    });
+    printer.finish();
+    exit_on_failure(success)
+}
+
+const VALIDATE_USAGE: &str = "usage: mpeak validate [--strict] [--format text|json|csv] <in.mp3>...
+exits with 3 if a file has errors, or warnings with --strict";
+
+/// mpeak validate [--strict] [--format text|json|csv] in.mp3 ...
+/// prints file, severity, kind, offset, frame and message of every issue, or severity ok
+fn validate(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let strict = args.iter().any(|arg| arg == "--strict");
+    let args: Vec<String> = args
+        .iter()
+        .filter(|arg| *arg != "--strict")
+        .cloned()
+        .collect();
+    let (format, paths) = format_and_paths(&args, VALIDATE_USAGE);
+    let limit = match strict {
+        true => mpeak::Mp3Severity::Warning,
+        false => mpeak::Mp3Severity::Error,
+    };
+    let mut printer = Printer::new(format, false);
+    let mut invalid = false;
+    let success = for_each_path(&paths, |path| {
+        let issues = mpeak::validate(&mpeak::load_file(path)?);
+        if issues.is_empty() {
+            printer.print(&[
+                ("file", path.into()),
+                ("severity", "ok".into()),
+                ("kind", Value::Null),
+                ("offset", Value::Null),
+                ("frame", Value::Null),
+                ("message", Value::Null),
+            ]);
+        }
+        for issue in issues {
+            invalid |= issue.severity >= limit;
+            printer.print(&[
+                ("file", path.into()),
+                (
+                    "severity",
+                    format!("{:?}", issue.severity).to_lowercase().into(),
+                ),
+                ("kind", format!("{:?}", issue.kind).into()),
+                ("offset", issue.offset.into()),
+                ("frame", issue.frame.into()),
+                ("message", issue.message.into()),
+            ]);
+        }
+        Ok(())
+    });
+    printer.finish();
+    exit_on_failure(success)?;
+    if invalid {
+        process::exit(EXIT_INVALID);
+    }
+    Ok(())
+}
+
+const PEAKS_USAGE: &str = "usage: mpeak peaks [--pixel <samples>] [--bits 8|16] [--dat] \
+[--approximate] <in.mp3>...
+writes audiowaveform peak data to <in>.json or <in>.dat";
+
+/// mpeak peaks [--pixel samples] [--bits 8|16] [--dat] [--approximate] in.mp3 ...
+fn peaks(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let usage = || -> ! {
+        eprintln!("{}", PEAKS_USAGE);
+        process::exit(EXIT_USAGE);
+    };
+    let mut samples_per_pixel = 256;
+    let mut bits = mpeak::WaveformBits::Bits16;
+    let mut dat = false;
+    let mut approximate = false;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pixel" => match args.next().and_then(|pixel| pixel.parse().ok()) {
+                Some(pixel) if pixel > 0 => samples_per_pixel = pixel,
+                _ => usage(),
+            },
+            "--bits" => match args.next().map(|bits| bits.as_str()) {
+                Some("8") => bits = mpeak::WaveformBits::Bits8,
+                Some("16") => bits = mpeak::WaveformBits::Bits16,
+                _ => usage(),
+            },
+            "--dat" => dat = true,
+            "--approximate" => approximate = true,
+            arg if arg.starts_with("--") => usage(),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if paths.is_empty() {
+        usage();
+    }
+    exit_on_failure(for_each_path(&paths, |path| {
+        let frames = mpeak::get_frames(&mpeak::load_file(path)?)?;
+        let waveform = match approximate {
+            true => mpeak::get_approximate_waveform(&frames, samples_per_pixel),
+            false => mpeak::get_waveform(&frames, samples_per_pixel),
+        };
+        let (output, data) = match dat {
+            true => (Path::new(path).with_extension("dat"), waveform.to_dat(bits)),
+            false => (
+                Path::new(path).with_extension("json"),
+                waveform.to_json(bits).into_bytes(),
+            ),
+        };
+        let output = output.display().to_string();
+        mpeak::write_file(&output, &data)?;
+        outln!("{}", output);
+        Ok(())
+    }))
+}
+
+const STRIP_USAGE: &str = "usage: mpeak strip [--id3v2] [--ape] [--lyrics3] [--id3v1] <in.mp3>...
+removes the selected tags in place, all tags if none is selected";
+
+/// mpeak strip [--id3v2] [--ape] [--lyrics3] [--id3v1] in.mp3 ...
+fn strip(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let flags = [
+        ("--id3v2", mpeak::Mp3RegionKind::Id3v2),
+        ("--ape", mpeak::Mp3RegionKind::Ape),
+        ("--lyrics3", mpeak::Mp3RegionKind::Lyrics3),
+        ("--id3v1", mpeak::Mp3RegionKind::Id3v1),
+    ];
+    let mut kinds: Vec<mpeak::Mp3RegionKind> = flags
+        .iter()
+        .filter(|(flag, _)| args.iter().any(|arg| arg == flag))
+        .map(|(_, kind)| *kind)
+        .collect();
+    if kinds.is_empty() {
+        kinds = flags.iter().map(|(_, kind)| *kind).collect();
+    }
+    let paths: Vec<String> = args
+        .iter()
+        .filter(|arg| !flags.iter().any(|(flag, _)| flag == arg))
+        .cloned()
+        .collect();
+    if paths.is_empty() || paths.iter().any(|arg| arg.starts_with("--")) {
+        eprintln!("{}", STRIP_USAGE);
+        process::exit(EXIT_USAGE);
+    }
+    exit_on_failure(for_each_path(&paths, |path| {
+        // the audio is copied unchanged, including junk and incomplete frames
+        let data = mpeak::load_file(path)?;
+        let stripped = mpeak::strip_tags(&data, &kinds);
+        outln!("{}: {} bytes removed", path, data.len() - stripped.len());
+        match stripped.len() == data.len() {
+            true => Ok(()),
+            false => mpeak::write_file(path, &stripped),
+        }
+    }))
+}
+
+const NORMALISE_USAGE: &str = "usage: mpeak normalise [--version 2|3|4] \
+[--encoding latin1|utf16|utf16be|utf8] <in.mp3>...
+merges all ID3v2 tags and the ID3v1 fields into one ID3v2 tag (default version 4) in place";
+
+/// mpeak normalise [--version 2|3|4] [--encoding latin1|utf16|utf16be|utf8] in.mp3 ...
+fn normalise(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let usage = || -> ! {
+        eprintln!("{}", NORMALISE_USAGE);
+        process::exit(EXIT_USAGE);
+    };
+    let mut version = 4;
+    let mut encoding = None;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--version" => match args.next().map(|value| value.as_str()) {
+                Some(value @ ("2" | "3" | "4")) => version = value.parse().unwrap(),
+                _ => usage(),
+            },
+            "--encoding" => {
+                let names = ["latin1", "utf16", "utf16be", "utf8"];
+                match args
+                    .next()
+                    .and_then(|value| names.iter().position(|name| name == value))
+                {
+                    Some(index) => encoding = Some(index as u8),
+                    None => usage(),
+                }
+            }
+            _ if arg.starts_with("--") => usage(),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if paths.is_empty() {
+        usage();
+    }
+    exit_on_failure(for_each_path(&paths, |path| {
+        let data = mpeak::load_file(path)?;
+        let normalised = mpeak::normalise_tags(&data, version, encoding);
+        outln!("{}", path);
+        match normalised == data {
+            true => Ok(()),
+            false => mpeak::write_file(path, &normalised),
+        }
+    }))
+}
+
+const ART_USAGE: &str = "usage: mpeak art list [--format text|json|csv] <in.mp3>...
+       mpeak art extract [--type <n>] <in.mp3>...
+       mpeak art add [--type <n>] [--description <text>] <in.mp3> <image>
+       mpeak art remove [--type <n>] <in.mp3>...
+extract writes <in>-<index>.jpg|png|bin next to the file, add replaces a picture \
+with the same type and description, the type defaults to 3 (front cover)";
+
+/// mpeak art list|extract|add|remove ...
+fn art(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let usage = || -> ! {
+        eprintln!("{}", ART_USAGE);
+        process::exit(EXIT_USAGE);
+    };
+    let (command, args) = args.split_first().unwrap_or_else(|| usage());
+    if command == "list" {
+        let (format, paths) = format_and_paths(args, ART_USAGE);
+        let mut printer = Printer::new(format, false);
+        let success = for_each_path(&paths, |path| {
+            let stream = mpeak::Mp3Stream::parse(&mpeak::load_file(path)?)?;
+            let tag = mpeak::Id3v2Tag::parse(&stream.id3v2).unwrap_or_default();
+            for (i, picture) in tag.pictures().iter().enumerate() {
+                let dimensions = picture.dimensions();
+                printer.print(&[
+                    ("file", path.into()),
+                    ("index", i.into()),
+                    ("type", picture.picture_type.into()),
+                    ("mime_type", picture.mime_type.as_str().into()),
+                    ("format", format!("{:?}", picture.format()).into()),
+                    ("width", dimensions.map(|(width, _)| width).into()),
+                    ("height", dimensions.map(|(_, height)| height).into()),
+                    ("bytes", picture.data.len().into()),
+                    ("description", picture.description.as_str().into()),
+                ]);
+            }
+            Ok(())
+        });
+        printer.finish();
+        return exit_on_failure(success);
+    }
+
+    let mut picture_type = None;
+    let mut description = String::new();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--type" => match args.next().and_then(|value| value.parse().ok()) {
+                Some(value) => picture_type = Some(value),
+                None => usage(),
+            },
+            "--description" => match args.next() {
+                Some(value) => description = value.clone(),
+                None => usage(),
+            },
+            _ if arg.starts_with("--") => usage(),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    match (command.as_str(), paths.as_slice()) {
+        ("extract", [_, ..]) => exit_on_failure(for_each_path(&paths, |path| {
+            let stream = mpeak::Mp3Stream::parse(&mpeak::load_file(path)?)?;
+            let tag = mpeak::Id3v2Tag::parse(&stream.id3v2).unwrap_or_default();
+            let stem = Path::new(path).with_extension("");
+            for (i, picture) in tag.pictures().iter().enumerate() {
+                if picture_type.is_some_and(|t| t != picture.picture_type) {
+                    continue;
+                }
+                let output = format!("{}-{}.{}", stem.display(), i, picture.format().extension());
+                mpeak::write_file(&output, &picture.data)?;
+                outln!("{}", output);
+            }
+            Ok(())
+        })),
+        ("add", [path, image]) => {
+            let data = mpeak::load_file(image)?;
+            let picture = mpeak::Id3v2Picture::new(
+                picture_type.unwrap_or(mpeak::PICTURE_FRONT_COVER),
+                &description,
+                data,
             );
-            let frames = mpeak::get_frames(&file_data).unwrap();
-            for frame in frames {
-                println!("{:?}", frame.header.frame_length());
+            if picture.format() == mpeak::ImageFormat::Unknown {
+                eprintln!("warning: {} is neither JPEG nor PNG", image);
+            }
+            let data = mpeak::load_file(path)?;
+            let mut tag = mpeak::Id3v2Tag::parse(&data).unwrap_or_default();
+            tag.set_picture(&picture);
+            mpeak::write_file(path, &mpeak::replace_id3v2(&data, &tag.to_bytes()))
+        }
+        ("remove", [_, ..]) => exit_on_failure(for_each_path(&paths, |path| {
+            let data = mpeak::load_file(path)?;
+            let mut tag = match mpeak::Id3v2Tag::parse(&data) {
+                Some(tag) => tag,
+                None => return Ok(()),
+            };
+            tag.remove_pictures(picture_type);
+            mpeak::write_file(path, &mpeak::replace_id3v2(&data, &tag.to_bytes()))
+        })),
+        _ => usage(),
+    }
+}
+
+const CHAPTERS_USAGE: &str = "usage: mpeak chapters list [--format text|json|csv] <in.mp3>...
+       mpeak chapters set [--title <text>] <in.mp3> <sheet.cue>
+       mpeak chapters remove <in.mp3>...
+set writes a chapter per CUE track, ending at the next track or the end of the audio, \
+and a table of contents titled like the sheet";
+
+/// mpeak chapters list|set|remove ...
+fn chapters(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let usage = || -> ! {
+        eprintln!("{}", CHAPTERS_USAGE);
+        process::exit(EXIT_USAGE);
+    };
+    let (command, args) = args.split_first().unwrap_or_else(|| usage());
+    match (command.as_str(), args) {
+        ("list", args) => {
+            let (format, paths) = format_and_paths(args, CHAPTERS_USAGE);
+            let mut printer = Printer::new(format, false);
+            let success = for_each_path(&paths, |path| {
+                let stream = mpeak::Mp3Stream::parse(&mpeak::load_file(path)?)?;
+                let tag = mpeak::Id3v2Tag::parse(&stream.id3v2).unwrap_or_default();
+                for chapter in tag.chapters() {
+                    printer.print(&[
+                        ("file", path.into()),
+                        ("element_id", chapter.element_id.as_str().into()),
+                        ("start_ms", chapter.start_time.into()),
+                        ("end_ms", chapter.end_time.into()),
+                        ("start_offset", chapter.start_offset.into()),
+                        ("end_offset", chapter.end_offset.into()),
+                        ("title", chapter.title().into()),
+                        ("url", chapter.url().into()),
+                    ]);
+                }
+                Ok(())
+            });
+            printer.finish();
+            exit_on_failure(success)
+        }
+        ("set", args) => {
+            let (title, args) = match args {
+                [flag, title, rest @ ..] if flag == "--title" => (Some(title.as_str()), rest),
+                _ => (None, args),
+            };
+            let [path, sheet] = args else { usage() };
+            let sheet = mpeak::load_file(sheet)?;
+            let sheet = mpeak::CueSheet::parse(&String::from_utf8_lossy(&sheet));
+            let data = mpeak::load_file(path)?;
+            let stream = mpeak::Mp3Stream::parse(&data)?;
+            let mut tag = mpeak::Id3v2Tag::parse(&data).unwrap_or_default();
+            if tag.version < 3 {
+                tag.convert(4);
+            }
+            let end = (duration(&stream.frames) * 1000.0).round() as u32;
+            let mut chapters: Vec<mpeak::Id3v2Chapter> = sheet
+                .tracks
+                .iter()
+                .enumerate()
+                .map(|(i, track)| {
+                    let start = (track.start * 1000.0).round() as u32;
+                    let next = sheet.tracks.get(i + 1);
+                    let mut chapter = mpeak::Id3v2Chapter::new(
+                        &format!("chp{}", i + 1),
+                        start,
+                        next.map_or(end, |next| (next.start * 1000.0).round() as u32),
+                    );
+                    if let Some(title) = &track.title {
+                        chapter.set_title(title, tag.version);
+                    }
+                    chapter
+                })
+                .collect();
+            let title = title.or(sheet.title.as_deref());
+            // the byte offsets do not change the length of the tag
+            tag.set_chapters(&chapters, title);
+            let data = mpeak::replace_id3v2(&data, &tag.to_bytes());
+            // audio frames of the written file, the Xing/Info frame holds no audio
+            let frames: Vec<mpeak::Mp3Region> = mpeak::scan(&data)
+                .into_iter()
+                .filter(|region| region.kind == mpeak::Mp3RegionKind::Frame)
+                .skip(stream.xing.is_some() as usize)
+                .collect();
+            // offset of the frame or of the end of the audio
+            let frame_offset = |frame: usize| {
+                let offset = match frames.get(frame) {
+                    Some(region) => region.offset,
+                    None => frames.last().map_or(0, |last| last.offset + last.length),
+                };
+                offset as u32
+            };
+            for chapter in chapters.iter_mut() {
+                chapter.start_offset = Some(frame_offset(
+                    stream.frame_at(chapter.start_time as f64 / 1000.0),
+                ));
+                chapter.end_offset = Some(frame_offset(
+                    stream.frame_at(chapter.end_time as f64 / 1000.0),
+                ));
+            }
+            tag.set_chapters(&chapters, title);
+            mpeak::write_file(path, &mpeak::replace_id3v2(&data, &tag.to_bytes()))
+        }
+        ("remove", [_, ..]) => exit_on_failure(for_each_path(args, |path| {
+            let data = mpeak::load_file(path)?;
+            let mut tag = match mpeak::Id3v2Tag::parse(&data) {
+                Some(tag) => tag,
+                None => return Ok(()),
+            };
+            tag.remove_chapters();
+            mpeak::write_file(path, &mpeak::replace_id3v2(&data, &tag.to_bytes()))
+        })),
+        _ => usage(),
+    }
+}
+
+const LYRICS_USAGE: &str = "usage: mpeak lyrics show [--format text|json|csv] <in.mp3>...
+       mpeak lyrics export <in.mp3> [<out.lrc>]
+       mpeak lyrics import [--language <code>] <in.mp3> <in.lrc>
+       mpeak lyrics remove <in.mp3>...
+export writes the synchronised lyrics as LRC to the file or stdout, import replaces the \
+synchronised lyrics of the language and the unsynchronised lyrics";
+
+/// mpeak lyrics show|export|import|remove ...
+fn lyrics(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let usage = || -> ! {
+        eprintln!("{}", LYRICS_USAGE);
+        process::exit(EXIT_USAGE);
+    };
+    let (command, args) = args.split_first().unwrap_or_else(|| usage());
+    match (command.as_str(), args) {
+        ("show", args) => {
+            let (format, paths) = format_and_paths(args, LYRICS_USAGE);
+            let mut printer = Printer::new(format, false);
+            let success = for_each_path(&paths, |path| {
+                let stream = mpeak::Mp3Stream::parse(&mpeak::load_file(path)?)?;
+                let tag = mpeak::Id3v2Tag::parse(&stream.id3v2).unwrap_or_default();
+                let mut print =
+                    |id: &str, language: &str, description: &str, time: Option<u32>, text: &str| {
+                        printer.print(&[
+                            ("file", path.into()),
+                            ("frame", id.into()),
+                            ("language", language.into()),
+                            ("description", description.into()),
+                            ("time_ms", time.into()),
+                            ("text", text.into()),
+                        ])
+                    };
+                for frame in &tag.frames {
+                    if let Some((language, description, text)) = frame
+                        .comment_parts()
+                        .filter(|_| frame.id == "USLT" || frame.id == "ULT")
+                    {
+                        print(&frame.id, &language, &description, None, &text);
+                    }
+                }
+                for lyrics in tag.synced_lyrics() {
+                    for (time, text) in lyrics.text_lines(&stream.frames) {
+                        print(
+                            "SYLT",
+                            &lyrics.language,
+                            &lyrics.description,
+                            Some(time),
+                            &text,
+                        );
+                    }
+                }
+                Ok(())
+            });
+            printer.finish();
+            exit_on_failure(success)
+        }
+        ("export", [path, output @ ..]) if output.len() <= 1 => {
+            let stream = mpeak::Mp3Stream::parse(&mpeak::load_file(path)?)?;
+            let tag = mpeak::Id3v2Tag::parse(&stream.id3v2).unwrap_or_default();
+            let synced_lyrics = tag.synced_lyrics();
+            let lyrics = synced_lyrics
+                .iter()
+                .find(|lyrics| lyrics.content_type == mpeak::SYLT_CONTENT_LYRICS)
+                .or(synced_lyrics.first());
+            let Some(lyrics) = lyrics else {
+                eprintln!("{}: no synchronised lyrics", path);
+                process::exit(EXIT_ERROR);
+            };
+            let lrc = lyrics.to_lrc(&stream.frames);
+            match output {
+                [output] => mpeak::write_file(output, lrc.as_bytes()),
+                _ => {
+                    out!("{}", lrc);
+                    Ok(())
+                }
+            }
+        }
+        ("import", args) => {
+            let (language, args) = match args {
+                [flag, language, rest @ ..] if flag == "--language" && language.len() == 3 => {
+                    (Some(language.as_str()), rest)
+                }
+                _ => (None, args),
+            };
+            let [path, lrc] = args else { usage() };
+            let lrc = mpeak::load_file(lrc)?;
+            let mut lyrics = mpeak::Id3v2SyncedLyrics::from_lrc(&String::from_utf8_lossy(&lrc));
+            if let Some(language) = language {
+                lyrics.language = language.to_string();
+            }
+            let text: Vec<String> = lyrics
+                .text_lines(&[])
+                .into_iter()
+                .map(|(_, text)| text)
+                .collect();
+            let data = mpeak::load_file(path)?;
+            let mut tag = mpeak::Id3v2Tag::parse(&data).unwrap_or_default();
+            tag.set_synced_lyrics(&lyrics);
+            tag.set_lyrics(&lyrics.language, &text.join("\n"));
+            mpeak::write_file(path, &mpeak::replace_id3v2(&data, &tag.to_bytes()))
+        }
+        ("remove", [_, ..]) => exit_on_failure(for_each_path(args, |path| {
+            let data = mpeak::load_file(path)?;
+            let mut tag = match mpeak::Id3v2Tag::parse(&data) {
+                Some(tag) => tag,
+                None => return Ok(()),
+            };
+            tag.remove_lyrics();
+            mpeak::write_file(path, &mpeak::replace_id3v2(&data, &tag.to_bytes()))
+        })),
+        _ => usage(),
+    }
+}
+
+const REPAIR_USAGE: &str = "usage: mpeak repair <in.mp3> <out.mp3>
+removes junk and broken frames, moves tags into place and rebuilds the Xing/Info tag";
+
+/// mpeak repair in.mp3 out.mp3
+fn repair(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    if args.len() != 2 || args.iter().any(|arg| arg.starts_with("--")) {
+        eprintln!("{}", REPAIR_USAGE);
+        process::exit(EXIT_USAGE);
+    }
+    let result = mpeak::repair(&mpeak::load_file(&args[0])?)?;
+    outln!(
+        "removed {} junk bytes, dropped {} frames, muted {} frames, moved {} tags, merged {} tags, dropped {} tags",
+        result.junk_bytes,
+        result.dropped_frames,
+        result.muted_frames,
+        result.moved_tags,
+        result.merged_tags,
+        result.dropped_tags
+    );
+    mpeak::write_file(&args[1], &result.to_bytes())
+}
+
+const DECODE_USAGE: &str = "usage: mpeak decode [--float] [--gapless] <in.mp3> <out.wav>";
+
+/// mpeak decode [--float] [--gapless] in.mp3 out.wav
+fn decode(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let format = match args.iter().any(|arg| arg == "--float") {
+        true => mpeak::WavFormat::Float32,
+        false => mpeak::WavFormat::Pcm16,
+    };
+    let gapless = args.iter().any(|arg| arg == "--gapless");
+    let paths: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    let unknown = args
+        .iter()
+        .any(|arg| arg.starts_with("--") && arg != "--float" && arg != "--gapless");
+    if paths.len() != 2 || unknown {
+        eprintln!("{}", DECODE_USAGE);
+        process::exit(EXIT_USAGE);
+    }
+
+    let file_data = mpeak::load_file(paths[0])?;
+    let frames = mpeak::get_frames(&file_data)?;
+    let audio = match gapless {
+        true => mpeak::decode_gapless(&frames),
+        false => mpeak::decode(&frames),
+    };
+    mpeak::write_file(paths[1], &mpeak::to_wav(&audio, format))
+}
+
+const CUT_USAGE: &str = "usage: mpeak cut [--zero] <in.mp3> <out.mp3> <start> <end> (seconds)";
+
+/// mpeak cut [--zero] in.mp3 out.mp3 start end
+fn cut(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let handling = match args.iter().any(|arg| arg == "--zero") {
+        true => mpeak::Mp3ReservoirHandling::Zero,
+        false => mpeak::Mp3ReservoirHandling::Prime,
+    };
+    let values: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    let times = values.get(2..4).map(|times| {
+        times
+            .iter()
+            .map(|time| time.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+    });
+    let unknown = args
+        .iter()
+        .any(|arg| arg.starts_with("--") && arg != "--zero");
+    let (start, end) = match (values.len(), times) {
+        (4, Some(Ok(times))) if !unknown => (times[0], times[1]),
+        _ => {
+            eprintln!("{}", CUT_USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+
+    let stream = mpeak::Mp3Stream::parse(&mpeak::load_file(values[0])?)?;
+    let result = mpeak::cut(
+        &stream,
+        stream.frame_at(start),
+        stream.frame_at(end),
+        handling,
+    );
+    if result.muted_frames > 0 {
+        eprintln!(
+            "warning: {} frames reference main data before the cut, main_data_begin zeroed and muted",
+            result.muted_frames
+        );
+    }
+    mpeak::write_file(values[1], &result.stream.to_bytes())
+}
+
+const JOIN_USAGE: &str = "usage: mpeak join <in.mp3>... <out.mp3>";
+
+/// mpeak join in1.mp3 in2.mp3 ... out.mp3
+fn join(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let (output, inputs) = match args.split_last() {
+        Some((output, inputs))
+            if !inputs.is_empty() && !args.iter().any(|arg| arg.starts_with("--")) =>
+        {
+            (output, inputs)
+        }
+        _ => {
+            eprintln!("{}", JOIN_USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    let streams = inputs
+        .iter()
+        .map(|path| mpeak::Mp3Stream::parse(&mpeak::load_file(path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    mpeak::write_file(output, &mpeak::join(&streams)?.to_bytes())
+}
+
+const SPLIT_USAGE: &str = "usage: mpeak split [--zero] <in.mp3> <out-prefix> \
+(--at <seconds>,... | --silence [<dB> <seconds>] | --cue <sheet.cue>)";
+
+/// Silence detection defaults of `mpeak split --silence`
+const SILENCE_THRESHOLD_DB: f32 = -50.0;
+const SILENCE_DURATION: f64 = 2.0;
+
+/// mpeak split [--zero] in.mp3 prefix (--at t1,t2 | --silence [db seconds] | --cue sheet.cue)
+/// writes prefix01.mp3, prefix02.mp3, ...
+fn split(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let usage = || -> ! {
+        eprintln!("{}", SPLIT_USAGE);
+        process::exit(EXIT_USAGE);
+    };
+    let handling = match args.iter().any(|arg| arg == "--zero") {
+        true => mpeak::Mp3ReservoirHandling::Zero,
+        false => mpeak::Mp3ReservoirHandling::Prime,
+    };
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--zero").collect();
+    if args.len() < 3 || args[..2].iter().any(|arg| arg.starts_with("--")) {
+        usage();
+    }
+    let stream = mpeak::Mp3Stream::parse(&mpeak::load_file(args[0])?)?;
+    let tracks = match (args[2].as_str(), &args[3..]) {
+        ("--at", [times]) => {
+            let points = times
+                .split(',')
+                .map(|time| time.parse::<f64>().map(|time| stream.frame_at(time)))
+                .collect::<Result<Vec<usize>, _>>()
+                .unwrap_or_else(|_| usage());
+            mpeak::split(&stream, &points, &[], handling)
+        }
+        ("--silence", values) => {
+            let (threshold, duration) = match values {
+                [] => (SILENCE_THRESHOLD_DB, SILENCE_DURATION),
+                [threshold, duration] => match (threshold.parse(), duration.parse()) {
+                    (Ok(threshold), Ok(duration)) => (threshold, duration),
+                    _ => usage(),
+                },
+                _ => usage(),
+            };
+            let waveform = mpeak::get_approximate_waveform(&stream.frames, 576);
+            let threshold = 10f32.powf(threshold / 20.0);
+            let points: Vec<usize> = mpeak::silence_points(&waveform, threshold, duration)
+                .iter()
+                .map(|&time| stream.frame_at(time))
+                .collect();
+            mpeak::split(&stream, &points, &[], handling)
+        }
+        ("--cue", [path]) => {
+            let sheet = mpeak::load_file(path)?;
+            let sheet = mpeak::CueSheet::parse(&String::from_utf8_lossy(&sheet));
+            mpeak::split_cue(&stream, &sheet, handling)
+        }
+        _ => usage(),
+    };
+    for (i, track) in tracks.iter().enumerate() {
+        let path = format!("{}{:02}.mp3", args[1], i + 1);
+        mpeak::write_file(&path, &track.stream.to_bytes())?;
+        outln!("{}", path);
+    }
+    Ok(())
+}
+
+const MIX_USAGE: &str = "usage: mpeak mix [--seed <n> | --pattern <i>,...] [--block <frames>] \
+<in.mp3>... <out.mp3>";
+
+/// mpeak mix [--seed n | --pattern 0,1,...] [--block frames] in1.mp3 in2.mp3 ... out.mp3
+/// prints the stream of each block
+fn mix(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let usage = || -> ! {
+        eprintln!("{}", MIX_USAGE);
+        process::exit(EXIT_USAGE);
+    };
+    let mut schedule = None;
+    let mut block_frames = 1;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let seed = args.next().and_then(|seed| seed.parse().ok());
+                schedule = Some(mpeak::Mp3MixSchedule::Random(
+                    seed.unwrap_or_else(|| usage()),
+                ));
             }
-            // println!("frames              {:?}", frames.len());
-            // println!("header              {:?}", frames[0].header);
-            // println!("header frame_length {:?}", frames[0].header.frame_length());
+            "--pattern" => {
+                let pattern = args.next().map(|pattern| {
+                    pattern
+                        .split(',')
+                        .map(|index| index.parse::<usize>())
+                        .collect::<Result<Vec<usize>, _>>()
+                });
+                match pattern {
+                    Some(Ok(pattern)) => schedule = Some(mpeak::Mp3MixSchedule::Pattern(pattern)),
+                    _ => usage(),
+                }
+            }
+            "--block" => match args.next().and_then(|block| block.parse().ok()) {
+                Some(block) => block_frames = block,
+                None => usage(),
+            },
+            _ if arg.starts_with("--") => usage(),
+            _ => paths.push(arg),
+        }
+    }
+    let (output, inputs) = match paths.split_last() {
+        Some((output, inputs)) if !inputs.is_empty() => (output, inputs),
+        _ => usage(),
+    };
+    let schedule = schedule.unwrap_or_else(|| {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        outln!("seed {}", seed);
+        mpeak::Mp3MixSchedule::Random(seed)
+    });
+
+    let streams = inputs
+        .iter()
+        .map(|path| mpeak::Mp3Stream::parse(&mpeak::load_file(path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let result = mpeak::mix(&streams, &schedule, block_frames)?;
+    if result.muted_frames > 0 {
+        eprintln!("warning: {} frames muted", result.muted_frames);
+    }
+    let mut time = 0.0;
+    for (i, frame) in result.stream.frames.iter().enumerate() {
+        if i % block_frames.max(1) == 0 {
+            outln!("{:10.3}s {}", time, inputs[result.sources[i]]);
+        }
+        time += frame.header.samples_per_frame() as f64 / frame.header.sample_rate().max(1) as f64;
+    }
+    mpeak::write_file(output, &result.stream.to_bytes())
+}
+
+const GAIN_USAGE: &str = "usage: mpeak gain [--clip] <steps> <in.mp3> <out.mp3> (1.5 dB steps)
+       mpeak gain --undo <in.mp3> <out.mp3>";
+
+/// mpeak gain [--clip] steps in.mp3 out.mp3 | mpeak gain --undo in.mp3 out.mp3
+fn gain(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let usage = || -> ! {
+        eprintln!("{}", GAIN_USAGE);
+        process::exit(EXIT_USAGE);
+    };
+    let clip = args.iter().any(|arg| arg == "--clip");
+    let undo = args.iter().any(|arg| arg == "--undo");
+    let values: Vec<&String> = args
+        .iter()
+        .filter(|arg| *arg != "--clip" && *arg != "--undo")
+        .collect();
+    let (steps, input, output) = match (undo, values.as_slice()) {
+        (true, [input, output]) => (None, input, output),
+        (false, [steps, input, output]) => match steps.parse::<i32>() {
+            Ok(steps) => (Some(steps), input, output),
+            Err(_) => usage(),
+        },
+        _ => usage(),
+    };
+
+    let data = mpeak::load_file(input)?;
+    let data = match steps {
+        Some(steps) => {
+            let max = mpeak::max_gain_without_clipping(&mpeak::get_frames(&data)?);
+            let steps = match clip || steps <= max {
+                true => steps,
+                false => {
+                    eprintln!("warning: gain limited to {} steps to prevent clipping", max);
+                    max
+                }
+            };
+            let data = mpeak::apply_gain(&data, steps)?;
+            outln!("{:+.1} dB", steps as f64 * mpeak::GAIN_STEP_DB);
+            data
+        }
+        None => mpeak::undo_gain(&data)?,
+    };
+    mpeak::write_file(output, &data)
+}
+
+const LOUDNESS_USAGE: &str =
+    "usage: mpeak loudness [--target <LUFS>] [--write id3|ape] <in.mp3>...";
+
+/// Loudness of `mpeak loudness` reports the gain to, like common streaming services
+const LOUDNESS_TARGET: f64 = -14.0;
+
+/// mpeak loudness [--target LUFS] [--write id3|ape] in.mp3 ...
+/// --write stores the ReplayGain values in the files, with album values for several files
+fn loudness(args: &[String]) -> Result<(), mpeak::MPeakError> {
+    let usage = || -> ! {
+        eprintln!("{}", LOUDNESS_USAGE);
+        process::exit(EXIT_USAGE);
+    };
+    let mut target = LOUDNESS_TARGET;
+    let mut write = None;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--target" => match args.next().and_then(|target| target.parse().ok()) {
+                Some(value) => target = value,
+                None => usage(),
+            },
+            "--write" => match args.next().map(|tag| tag.as_str()) {
+                Some(tag @ ("id3" | "ape")) => write = Some(tag),
+                _ => usage(),
+            },
+            _ if arg.starts_with("--") => usage(),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        usage();
+    }
+
+    let mut analyses = Vec::new();
+    for path in &paths {
+        let frames = mpeak::get_frames(&mpeak::load_file(path)?)?;
+        let analysis = mpeak::LoudnessAnalysis::new(&mpeak::decode_gapless(&frames));
+        outln!(
+            "{}: {:.1} LUFS, range {:.1} LU, true peak {:.1} dBTP, gain {:+.1} dB",
+            path,
+            analysis.integrated(),
+            analysis.range(),
+            20.0 * (analysis.true_peak as f64).log10(),
+            analysis.gain_to(target)
+        );
+        analyses.push(analysis);
+    }
+    let album = mpeak::LoudnessAnalysis::combine(&analyses);
+    if paths.len() > 1 {
+        outln!(
+            "album: {:.1} LUFS, range {:.1} LU, true peak {:.1} dBTP, gain {:+.1} dB",
+            album.integrated(),
+            album.range(),
+            20.0 * (album.true_peak as f64).log10(),
+            album.gain_to(target)
+        );
+    }
+
+    if let Some(tag) = write {
+        let album = (paths.len() > 1).then_some(&album);
+        for (path, analysis) in paths.iter().zip(&analyses) {
+            let gain = mpeak::ReplayGain::new(analysis, album);
+            let data = mpeak::load_file(path)?;
+            let data = match tag {
+                "id3" => {
+                    let mut id3v2 = mpeak::Id3v2Tag::parse(&data).unwrap_or_default();
+                    gain.write_id3v2(&mut id3v2);
+                    mpeak::replace_id3v2(&data, &id3v2.to_bytes())
+                }
+                _ => {
+                    let mut ape = mpeak::ApeTag::parse(mpeak::find_ape(&data)).unwrap_or_default();
+                    gain.write_ape(&mut ape);
+                    mpeak::replace_ape(&data, &ape.to_bytes())
+                }
+            };
+            mpeak::write_file(path, &data)?;
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (name, args) = match args.split_first() {
+        Some((name, args)) => (name.as_str(), args),
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    let command = COMMANDS.iter().find(|(command, _, _)| *command == name);
+    let result = match (name, command) {
+        ("help" | "--help" | "-h", _) => {
+            let command = args
+                .first()
+                .and_then(|name| COMMANDS.iter().find(|(command, _, _)| command == name));
+            outln!("{}", command.map_or(USAGE, |(_, usage, _)| usage));
+            Ok(())
+        }
+        (_, Some((_, usage, _))) if args.iter().any(|arg| arg == "--help" || arg == "-h") => {
+            outln!("{}", usage);
+            Ok(())
+        }
+        (_, Some((_, _, command))) => command(&expand_globs(args)),
+        // files without a command show their info, like earlier versions
+        (_, None) if Path::new(name).is_file() => {
+            info(&expand_globs(&[&[name.to_string()], args].concat()))
+        }
+        (_, None) => {
+            eprintln!("unknown command {}\n{}", name, USAGE);
+            process::exit(EXIT_USAGE);
         }
-        Err(e) => println!("{:?}", e),
     };
+    if let Err(e) = result {
+        eprintln!("{:?}", e);
+        process::exit(EXIT_ERROR);
+    }
 }
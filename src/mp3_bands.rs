@@ -0,0 +1,176 @@
+// References
+// ISO/IEC 11172-3 Table B.8 and ISO/IEC 13818-3 Table B.2
+
+use super::mp3_header::{Mp3FrameHeader, Mp3Version};
+use super::mp3_side_info::Mp3GranuleInfo;
+
+/// Widths of the long block scalefactor bands.
+/// Indexed by 44.1, 48, 32, 22.05, 24, 16, 11.025, 12 and 8 kHz.
+#[rustfmt::skip]
+const LONG_BAND_WIDTHS: [[usize; 22]; 9] = [
+    [4, 4, 4, 4, 4, 4, 6, 6, 8, 8, 10, 12, 16, 20, 24, 28, 34, 42, 50, 54, 76, 158],
+    [4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 10, 12, 16, 18, 22, 28, 34, 40, 46, 54, 54, 192],
+    [4, 4, 4, 4, 4, 4, 6, 6, 8, 10, 12, 16, 20, 24, 30, 38, 46, 56, 68, 84, 102, 26],
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 38, 46, 52, 60, 68, 58, 54],
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 18, 22, 26, 32, 38, 46, 54, 62, 70, 76, 36],
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 38, 46, 52, 60, 68, 58, 54],
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 38, 46, 52, 60, 68, 58, 54],
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 38, 46, 52, 60, 68, 58, 54],
+    [12, 12, 12, 12, 12, 12, 16, 20, 24, 28, 32, 40, 48, 56, 64, 76, 90, 2, 2, 2, 2, 2],
+];
+
+/// Widths of the short block scalefactor bands (per window).
+/// Indexed like `LONG_BAND_WIDTHS`.
+#[rustfmt::skip]
+const SHORT_BAND_WIDTHS: [[usize; 13]; 9] = [
+    [4, 4, 4, 4, 6, 8, 10, 12, 14, 18, 22, 30, 56],
+    [4, 4, 4, 4, 6, 6, 10, 12, 14, 16, 20, 26, 66],
+    [4, 4, 4, 4, 6, 8, 12, 16, 20, 26, 34, 42, 12],
+    [4, 4, 4, 6, 6, 8, 10, 14, 18, 26, 32, 42, 18],
+    [4, 4, 4, 6, 8, 10, 12, 14, 18, 24, 32, 44, 12],
+    [4, 4, 4, 6, 8, 10, 12, 14, 18, 24, 30, 40, 18],
+    [4, 4, 4, 6, 8, 10, 12, 14, 18, 24, 30, 40, 18],
+    [4, 4, 4, 6, 8, 10, 12, 14, 18, 24, 30, 40, 18],
+    [8, 8, 8, 12, 16, 20, 24, 28, 36, 2, 2, 2, 26],
+];
+
+/// Scalefactor band layout for the sample rate of a frame
+pub struct Mp3Bands {
+    pub long_widths: &'static [usize; 22],
+    pub short_widths: &'static [usize; 13],
+    /// Number of long bands in mixed blocks
+    pub mixed_long_bands: usize,
+}
+
+/// Spectral lines of one scalefactor band of a granule.
+/// Short bands are split into their three windows.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Mp3Band {
+    pub start: usize,
+    pub end: usize,
+    pub index: usize,
+    /// Window of a short band, None for long bands
+    pub window: Option<usize>,
+}
+
+impl Mp3Bands {
+    pub fn new(header: &Mp3FrameHeader) -> Mp3Bands {
+        let version_offset = match header.version() {
+            Mp3Version::V1 | Mp3Version::Reserved => 0,
+            Mp3Version::V2 => 3,
+            Mp3Version::V25 => 6,
+        };
+        let index = version_offset + usize::min(header.sampling_rate_index() as usize, 2);
+        Mp3Bands {
+            long_widths: &LONG_BAND_WIDTHS[index],
+            short_widths: &SHORT_BAND_WIDTHS[index],
+            mixed_long_bands: if version_offset == 0 { 8 } else { 6 },
+        }
+    }
+
+    /// Bands of a granule in the order of the decoded spectrum.
+    /// The windows of a short band follow each other before reordering.
+    pub fn granule_bands(&self, info: &Mp3GranuleInfo) -> Vec<Mp3Band> {
+        let mut bands = Vec::new();
+        let mut start = 0;
+        let long_bands = match (info.is_short_block(), info.mixed_block) {
+            (false, _) => 22,
+            (true, false) => 0,
+            (true, true) => self.mixed_long_bands,
+        };
+        for (index, &width) in self.long_widths[..long_bands].iter().enumerate() {
+            bands.push(Mp3Band {
+                start,
+                end: start + width,
+                index,
+                window: None,
+            });
+            start += width;
+        }
+        if info.is_short_block() {
+            let first_short = if info.mixed_block { 3 } else { 0 };
+            for (index, &width) in self.short_widths.iter().enumerate().skip(first_short) {
+                for window in 0..3 {
+                    bands.push(Mp3Band {
+                        start,
+                        end: start + width,
+                        index,
+                        window: Some(window),
+                    });
+                    start += width;
+                }
+            }
+        }
+        bands
+    }
+
+    /// First spectral line of the long band
+    pub fn long_start(&self, band: usize) -> usize {
+        self.long_widths[..usize::min(band, 22)].iter().sum()
+    }
+
+    /// First spectral line of the short band, counting all three windows
+    pub fn short_start(&self, band: usize) -> usize {
+        3 * self.short_widths[..usize::min(band, 13)]
+            .iter()
+            .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_band_widths_cover_granule() {
+        for widths in LONG_BAND_WIDTHS.iter() {
+            assert_eq!(widths.iter().sum::<usize>(), 576);
+        }
+        for widths in SHORT_BAND_WIDTHS.iter() {
+            assert_eq!(widths.iter().sum::<usize>(), 192);
+        }
+    }
+
+    #[test]
+    fn test_band_starts() {
+        // V1 layer 3 44100Hz
+        let bands = Mp3Bands::new(&Mp3FrameHeader::new(0xFFFB9000));
+        assert_eq!(bands.long_start(8), 36);
+        assert_eq!(bands.long_start(21), 418);
+        assert_eq!(bands.long_start(22), 576);
+        assert_eq!(bands.short_start(3), 36);
+        assert_eq!(bands.short_start(13), 576);
+        // V2 layer 3 22050Hz
+        let bands = Mp3Bands::new(&Mp3FrameHeader::new(0xFFF39000));
+        assert_eq!(bands.long_start(8), 54);
+        assert_eq!(bands.long_start(6), 36);
+    }
+
+    #[test]
+    fn test_granule_bands() {
+        let bands = Mp3Bands::new(&Mp3FrameHeader::new(0xFFFB9000));
+        let long = bands.granule_bands(&Mp3GranuleInfo::default());
+        assert_eq!(long.len(), 22);
+        assert_eq!(long[21].start, 418);
+        assert_eq!(long[21].end, 576);
+
+        let mut info = Mp3GranuleInfo {
+            window_switching: true,
+            block_type: 2,
+            ..Default::default()
+        };
+        let short = bands.granule_bands(&info);
+        assert_eq!(short.len(), 39);
+        assert_eq!(short[4].start, 16);
+        assert_eq!(short[4].index, 1);
+        assert_eq!(short[4].window, Some(1));
+        assert_eq!(short[38].end, 576);
+
+        info.mixed_block = true;
+        let mixed = bands.granule_bands(&info);
+        assert_eq!(mixed.len(), 8 + 30);
+        assert_eq!(mixed[8].start, 36);
+        assert_eq!(mixed[8].index, 3);
+        assert_eq!(mixed[37].end, 576);
+    }
+}
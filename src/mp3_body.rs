@@ -2,6 +2,7 @@
 // http://www.mp3-tech.org/programmer/docs/mp3_theory.pdf
 // https://www.diva-portal.org/smash/get/diva2:830195/FULLTEXT01.pdf
 
+#[derive(Clone)]
 pub struct Mp3Body {
     pub data: Vec<u8>,
     pub is_mono: bool,
@@ -9,10 +10,7 @@ pub struct Mp3Body {
 
 impl Mp3Body {
     pub fn new(data: Vec<u8>, is_mono: bool) -> Mp3Body {
-        Mp3Body {
-            data: data,
-            is_mono: is_mono,
-        }
+        Mp3Body { data, is_mono }
     }
 
     pub fn main_data_begin(&self) -> u16 {
@@ -0,0 +1,54 @@
+// References
+// ISO/IEC 11172-3 2.4.3.1 (error check)
+// http://gabriel.mp3-tech.org/mp3infotag.html (LAME tag CRC)
+
+/// CRC-16 of the frame error check (polynomial 0x8005, initial value 0xFFFF) over the first
+/// `bits` bits of the data. Layer I and II cover bit fields which do not end at a byte boundary.
+pub fn crc16(data: &[u8], bits: usize) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for i in 0..bits {
+        let bit = data.get(i / 8).map_or(0, |&byte| byte >> (7 - i % 8) & 1) as u16;
+        crc = if (crc >> 15 ^ bit) != 0 {
+            crc << 1 ^ 0x8005
+        } else {
+            crc << 1
+        };
+    }
+    crc
+}
+
+/// CRC-16 of the LAME tag (polynomial 0x8005 reflected, initial value 0)
+pub fn lame_crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc16() {
+        // CRC-16/CMS check value
+        assert_eq!(crc16(b"123456789", 72), 0xAEE7);
+        // trailing bits beyond the count are ignored
+        assert_eq!(crc16(&[0xA5, 0xFF], 12), crc16(&[0xA5, 0xF0], 12));
+        assert_ne!(crc16(&[0xA5, 0xFF], 12), crc16(&[0xA5, 0xFF], 16));
+    }
+
+    #[test]
+    fn test_lame_crc16() {
+        // CRC-16/ARC check value
+        assert_eq!(lame_crc16(b"123456789"), 0xBB3D);
+    }
+}
@@ -0,0 +1,297 @@
+// References
+// ISO/IEC 11172-3 2.4.3 (decoding process)
+// http://www.mp3-tech.org/programmer/docs/mp3_theory.pdf
+
+use super::mp3_bands::Mp3Bands;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::Mp3Layer;
+use super::mp3_imdct::Mp3Imdct;
+use super::mp3_layer1;
+use super::mp3_layer2;
+use super::mp3_main_data::Mp3MainData;
+use super::mp3_requantize::{reorder, requantize};
+use super::mp3_reservoir::Mp3Reservoir;
+use super::mp3_side_info::Mp3GranuleInfo;
+use super::mp3_stereo;
+use super::mp3_synthesis::{synthesize_channels, Mp3Synthesis};
+
+/// Decoded PCM audio with interleaved channels
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3Audio {
+    pub sample_rate: u32,
+    pub channels: usize,
+    /// Interleaved samples in the range -1.0 to 1.0
+    pub samples: Vec<f32>,
+}
+
+impl Mp3Audio {
+    /// Interleaved samples converted to 16 bit, clipping out of range values
+    pub fn to_i16(&self) -> Vec<i16> {
+        self.samples.iter().map(|&x| to_i16(x)).collect()
+    }
+
+    /// Removes `start` samples per channel from the beginning and `end` from the end
+    pub fn trim(&mut self, start: usize, end: usize) {
+        let length = self.samples.len();
+        let end = length.saturating_sub(end * self.channels);
+        let start = usize::min(start * self.channels, end);
+        self.samples.truncate(end);
+        self.samples.drain(..start);
+    }
+}
+
+pub fn to_i16(sample: f32) -> i16 {
+    (sample * 32768.0).round().clamp(-32768.0, 32767.0) as i16
+}
+
+/// Decoder state carried from frame to frame
+pub struct Mp3Decoder {
+    reservoir: Mp3Reservoir,
+    imdct: [Mp3Imdct; 2],
+    synthesis: [Mp3Synthesis; 2],
+}
+
+impl Mp3Decoder {
+    pub fn new() -> Mp3Decoder {
+        Mp3Decoder {
+            reservoir: Mp3Reservoir::new(),
+            imdct: Default::default(),
+            synthesis: Default::default(),
+        }
+    }
+
+    /// Decodes a frame to interleaved samples.
+    /// Frames which cannot be decoded (e.g. missing bit reservoir data) produce silence,
+    /// keeping the timing of the stream intact.
+    pub fn decode_frame(&mut self, frame: &Mp3Frame) -> Vec<f32> {
+        if frame.header.layer() != Mp3Layer::Layer3 {
+            self.reservoir.clear();
+        }
+        match frame.header.layer() {
+            Mp3Layer::Layer1 => mp3_layer1::decode(frame, &mut self.synthesis),
+            Mp3Layer::Layer2 => mp3_layer2::decode(frame, &mut self.synthesis),
+            Mp3Layer::Layer3 => self.decode_layer3(frame),
+            Mp3Layer::Reserved => Vec::new(),
+        }
+    }
+
+    fn decode_layer3(&mut self, frame: &Mp3Frame) -> Vec<f32> {
+        let header = &frame.header;
+        let channels = header.channels();
+        let bands = Mp3Bands::new(header);
+        let main_data = self
+            .reservoir
+            .next_frame(frame)
+            .map(|(side_info, data)| Mp3MainData::decode(frame, side_info, &data));
+
+        let mut pcm = vec![0.0; header.samples_per_frame() * channels];
+        for gr in 0..header.granules() {
+            let mut samples = [[0.0; 576]; 2];
+            let mut infos = [Mp3GranuleInfo::default(); 2];
+            if let Some(main_data) = &main_data {
+                for ch in 0..channels {
+                    let info = main_data.side_info.granules[gr][ch];
+                    let data = &main_data.granules[gr][ch];
+                    let granule_bands = bands.granule_bands(&info);
+                    samples[ch] =
+                        requantize(&granule_bands, &info, &data.scalefactors, &data.spectrum);
+                    infos[ch] = info;
+                }
+                if channels == 2 {
+                    mp3_stereo::process(
+                        header,
+                        &bands.granule_bands(&infos[1]),
+                        &infos[1],
+                        &main_data.granules[gr][1].scalefactors,
+                        &mut samples,
+                    );
+                }
+            }
+
+            let mut slots = [[[0.0; 32]; 18]; 2];
+            for ch in 0..channels {
+                if infos[ch].is_short_block() {
+                    reorder(&bands.granule_bands(&infos[ch]), &mut samples[ch]);
+                }
+                slots[ch] = self.imdct[ch].process(&infos[ch], &mut samples[ch]);
+            }
+            for (t, (left, right)) in slots[0].iter().zip(slots[1].iter()).enumerate() {
+                let start = (gr * 576 + t * 32) * channels;
+                synthesize_channels(
+                    &mut self.synthesis,
+                    &[*left, *right],
+                    channels,
+                    &mut pcm[start..start + 32 * channels],
+                );
+            }
+        }
+        pcm
+    }
+}
+
+impl Default for Mp3Decoder {
+    fn default() -> Self {
+        Mp3Decoder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::get_frames;
+    use super::super::mp3_header::Mp3FrameHeader;
+    use super::*;
+
+    fn write_bits(bits: &mut Vec<bool>, value: u32, count: usize) {
+        for i in (0..count).rev() {
+            bits.push(value >> i & 1 == 1);
+        }
+    }
+
+    /// V1 layer 3 128kbit/s 44100Hz mono frame with a single spectral line of value 1
+    /// in both granules, coded with count1 table B
+    fn single_line_frame(line: usize, global_gain: u32) -> Mp3Frame {
+        let quads = line / 4;
+        let part2_3_length = quads * 4 + 4 + 1;
+        let mut bits = Vec::new();
+        write_bits(&mut bits, 0xFFFB90C0, 32);
+        write_bits(&mut bits, 0, 9 + 5 + 4);
+        for _ in 0..2 {
+            write_bits(&mut bits, part2_3_length as u32, 12);
+            write_bits(&mut bits, 0, 9);
+            write_bits(&mut bits, global_gain, 8);
+            write_bits(&mut bits, 0, 4 + 1 + 15 + 4 + 3 + 2);
+            write_bits(&mut bits, 1, 1);
+        }
+        for _ in 0..2 {
+            for _ in 0..quads {
+                write_bits(&mut bits, 0b1111, 4);
+            }
+            write_bits(&mut bits, 15 - (8 >> (line % 4)), 4);
+            write_bits(&mut bits, 0, 1);
+        }
+        bits.resize(417 * 8, false);
+        let data: Vec<u8> = bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+            .collect();
+        Mp3Frame::new(Mp3FrameHeader::new(0xFFFB90C0), data, 0)
+    }
+
+    /// Signal power at the given frequency (Goertzel algorithm)
+    fn power(samples: &[f32], frequency: f64, sample_rate: f64) -> f64 {
+        let coefficient = 2.0 * (2.0 * std::f64::consts::PI * frequency / sample_rate).cos();
+        let (mut s1, mut s2) = (0.0, 0.0);
+        for &x in samples {
+            let s = x as f64 + coefficient * s1 - s2;
+            s2 = s1;
+            s1 = s;
+        }
+        s1 * s1 + s2 * s2 - coefficient * s1 * s2
+    }
+
+    #[test]
+    fn test_decode_silence() {
+        let mut decoder = Mp3Decoder::new();
+        let frame = single_line_frame(100, 0);
+        for _ in 0..3 {
+            let pcm = decoder.decode_frame(&frame);
+            assert_eq!(pcm.len(), 1152);
+            assert!(pcm.iter().all(|&x| x.abs() < 1e-6));
+        }
+    }
+
+    #[test]
+    fn test_decode_single_line() {
+        let mut decoder = Mp3Decoder::new();
+        let frame = single_line_frame(100, 180);
+        let mut pcm = Vec::new();
+        for _ in 0..4 {
+            pcm.extend(decoder.decode_frame(&frame));
+        }
+        // skip the filterbank delay
+        let pcm = &pcm[1152..];
+        // the center frequency of spectral line k is (k + 0.5) * 44100 / 1152
+        let expected = power(pcm, 100.5 * 44100.0 / 1152.0, 44100.0);
+        for frequency in [1000.0, 3000.0, 4500.0, 8000.0] {
+            assert!(expected > 100.0 * power(pcm, frequency, 44100.0));
+        }
+        let peak = pcm.iter().fold(0.0f32, |max, x| max.max(x.abs()));
+        assert!(peak > 0.001 && peak < 1.0, "peak {}", peak);
+    }
+
+    /// Decodes the stream and compares it with the reference PCM in testdata/,
+    /// decoded by symphonia. Rounded to 16 bit, no sample may differ by more than one step.
+    fn assert_matches_reference(mp3: &[u8], pcm: &[u8]) {
+        let frames = get_frames(mp3).unwrap();
+        assert!(frames.iter().all(|frame| !frame.is_junk()));
+        let mut decoder = Mp3Decoder::new();
+        let samples: Vec<f32> = frames
+            .iter()
+            .flat_map(|frame| decoder.decode_frame(frame))
+            .collect();
+        let reference: Vec<i16> = pcm
+            .chunks(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        assert_eq!(samples.len(), reference.len());
+        // not silent
+        assert!(reference.iter().any(|&x| x.abs() > 8192));
+        for (i, (&sample, &expected)) in samples.iter().zip(reference.iter()).enumerate() {
+            let difference = (to_i16(sample) as i32 - expected as i32).abs();
+            assert!(difference <= 1, "sample {}: {} {}", i, sample, expected);
+        }
+    }
+
+    #[test]
+    fn test_decode_reference() {
+        // Layer I, 384 kbit/s, 44.1 kHz stereo
+        assert_matches_reference(
+            include_bytes!("../testdata/layer1.mp3"),
+            include_bytes!("../testdata/layer1.pcm"),
+        );
+        // Layer II, 192 kbit/s, 48 kHz stereo with grouped samples
+        assert_matches_reference(
+            include_bytes!("../testdata/layer2.mp3"),
+            include_bytes!("../testdata/layer2.pcm"),
+        );
+        // Layer III, 160 kbit/s, 44.1 kHz joint stereo: all Huffman tables with linbits and
+        // region boundaries, bit reservoir, scfsi, preflag, mid/side and intensity stereo,
+        // start, short and stop blocks. Mixed blocks only carry values in their long bands.
+        assert_matches_reference(
+            include_bytes!("../testdata/layer3.mp3"),
+            include_bytes!("../testdata/layer3.pcm"),
+        );
+        // Layer III MPEG-2, 64 kbit/s, 22.05 kHz mid/side stereo with LSF scalefactors
+        assert_matches_reference(
+            include_bytes!("../testdata/layer3_lsf.mp3"),
+            include_bytes!("../testdata/layer3_lsf.pcm"),
+        );
+        // Layer III MPEG-2.5, 32 kbit/s, 11.025 kHz mono
+        assert_matches_reference(
+            include_bytes!("../testdata/layer3_mpeg25.mp3"),
+            include_bytes!("../testdata/layer3_mpeg25.pcm"),
+        );
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut audio = Mp3Audio {
+            sample_rate: 44100,
+            channels: 2,
+            samples: (0..10).map(|x| x as f32).collect(),
+        };
+        audio.trim(1, 2);
+        assert_eq!(audio.samples, vec![2.0, 3.0, 4.0, 5.0]);
+        audio.trim(3, 0);
+        assert!(audio.samples.is_empty());
+    }
+
+    #[test]
+    fn test_to_i16() {
+        assert_eq!(to_i16(0.0), 0);
+        assert_eq!(to_i16(0.5), 16384);
+        assert_eq!(to_i16(-1.0), -32768);
+        assert_eq!(to_i16(1.5), 32767);
+    }
+}
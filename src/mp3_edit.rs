@@ -0,0 +1,394 @@
+// References
+// ISO/IEC 11172-3 2.4.3.4.2 (bit reservoir)
+// http://gabriel.mp3-tech.org/mp3infotag.html (encoder delay and padding)
+
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::{Mp3FrameHeader, Mp3Layer};
+use super::mp3_side_info::Mp3SideInfo;
+use super::mp3_stream::Mp3Stream;
+use super::mp3_xing::{Mp3LameTag, Mp3XingTag};
+use super::MPeakError;
+
+/// Delay of the decoder filterbank assumed by the LAME encoder delay
+pub const DECODER_DELAY: usize = 529;
+
+/// Largest number of frames included before a cut to prime the bit reservoir.
+/// The 12 bit LAME encoder delay covers up to 4 frames of 1152 samples.
+const MAX_PRIMING_FRAMES: usize = 4;
+
+/// Handling of frames whose main data starts before the first frame of a cut
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mp3ReservoirHandling {
+    /// Include the preceding frames holding the main data. They are removed
+    /// again by the LAME encoder delay when decoding gapless.
+    Prime,
+    /// Set main_data_begin to zero and mute the affected frames.
+    /// The frames covering the decoder delay are still included before the start.
+    Zero,
+}
+
+/// Result of a cut
+#[derive(Clone)]
+pub struct Mp3Cut {
+    pub stream: Mp3Stream,
+    /// Number of frames before the start included to cover the decoder delay
+    /// and to prime the bit reservoir
+    pub priming_frames: usize,
+    /// Number of frames of the cut range muted because their main data was not available
+    pub muted_frames: usize,
+}
+
+/// Indices of the Layer III frames referencing main data before the first frame
+fn missing_main_data(frames: &[Mp3Frame]) -> Vec<usize> {
+    let mut available = 0;
+    let mut missing = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        if frame.header.layer() != Mp3Layer::Layer3 {
+            available = 0;
+            continue;
+        }
+        if let Ok(side_info) = frame.side_info() {
+            if side_info.main_data_begin as usize > available {
+                missing.push(i);
+            }
+        }
+        available += frame.main_data().len();
+    }
+    missing
+}
+
+/// Sets main_data_begin to zero and mutes all granules
+pub fn mute_side_info(side_info: &mut Mp3SideInfo) {
+    side_info.main_data_begin = 0;
+    side_info.scfsi = [[false; 4]; 2];
+    // no scalefactor bits, which would exceed the empty part 2
+    for info in side_info.granules.iter_mut().flatten() {
+        info.part2_3_length = 0;
+        info.big_values = 0;
+        info.scalefac_compress = 0;
+    }
+}
+
+/// Sets main_data_begin of a Layer III frame to zero and mutes all of its granules
+fn mute(frame: &mut Mp3Frame) {
+    if let Ok(mut side_info) = frame.side_info() {
+        mute_side_info(&mut side_info);
+        frame.set_side_info(&side_info);
+    }
+}
+
+/// Extracts the frames `start..end` into a new stream without re-encoding.
+/// Tags are kept, the Xing/Info tag is rebuilt and the LAME encoder delay and padding
+/// are adjusted (in a new LAME tag if the stream has none and frames are included before
+/// the start), so gapless decoding yields exactly the samples of the cut range.
+pub fn cut(stream: &Mp3Stream, start: usize, end: usize, handling: Mp3ReservoirHandling) -> Mp3Cut {
+    let end = usize::min(end, stream.frames.len());
+    let start = usize::min(start, end);
+
+    // the frame before the start has to be decoded as well,
+    // its second half of the IMDCT overlaps with the first frame of the cut
+    let primed = |priming_frames: usize| {
+        let missing = missing_main_data(&stream.frames[start - priming_frames..end]);
+        match start {
+            0 => missing.is_empty(),
+            _ => priming_frames > 0 && missing.iter().all(|&i| i + 1 < priming_frames),
+        }
+    };
+    // the encoder delay cannot be negative,
+    // so the frames decoded during the decoder delay are always included
+    let samples_per_frame = stream
+        .frames
+        .get(start)
+        .map_or(0, |f| f.header.samples_per_frame());
+    let mut priming_frames = match samples_per_frame {
+        0 => 0,
+        samples => usize::min(start, DECODER_DELAY.div_ceil(samples)),
+    };
+    if handling == Mp3ReservoirHandling::Prime {
+        while priming_frames < usize::min(start, MAX_PRIMING_FRAMES) && !primed(priming_frames) {
+            priming_frames += 1;
+        }
+    }
+    let mut frames = stream.frames[start - priming_frames..end].to_vec();
+    let missing = missing_main_data(&frames);
+    for &i in &missing {
+        mute(&mut frames[i]);
+    }
+
+    // without a LAME tag the priming frames would be played
+    let lame = stream
+        .xing
+        .as_ref()
+        .and_then(|xing| xing.lame.clone())
+        .or_else(|| {
+            (priming_frames > 0).then(|| Mp3LameTag {
+                encoder: "mpeak".to_string(),
+                ..Default::default()
+            })
+        });
+    let lame = lame.map(|mut lame| {
+        if start > 0 {
+            let delay = (priming_frames * samples_per_frame).saturating_sub(DECODER_DELAY);
+            lame.encoder_delay = delay.min(0xFFF) as u16;
+        }
+        if end < stream.frames.len() {
+            lame.encoder_padding = DECODER_DELAY as u16;
+        }
+        lame
+    });
+
+    Mp3Cut {
+        stream: Mp3Stream {
+            id3v2: stream.id3v2.clone(),
+            xing: Some(Mp3XingTag {
+                lame,
+                ..Default::default()
+            }),
+            frames,
+            ape: stream.ape.clone(),
+            id3v1: stream.id3v1.clone(),
+        },
+        priming_frames,
+        muted_frames: missing.iter().filter(|&&i| i >= priming_frames).count(),
+    }
+}
+
+/// Whether frames with the given headers can be part of the same stream
+pub fn compatible(a: &Mp3FrameHeader, b: &Mp3FrameHeader) -> bool {
+    a.version() == b.version()
+        && a.layer() == b.layer()
+        && a.sample_rate() == b.sample_rate()
+        && a.channels() == b.channels()
+}
+
+/// Concatenates the frames of the streams without re-encoding.
+/// Tags are taken from the first stream and a new Xing/Info tag is built, with the
+/// LAME encoder delay of the first and the padding of the last stream.
+/// Frames referencing main data of the previous stream are muted.
+pub fn join(streams: &[Mp3Stream]) -> Result<Mp3Stream, MPeakError> {
+    let first = streams
+        .iter()
+        .flat_map(|stream| stream.frames.first())
+        .next();
+    let mut frames: Vec<Mp3Frame> = Vec::new();
+    for (i, stream) in streams.iter().enumerate() {
+        if let Some(first) = first {
+            if !stream
+                .frames
+                .iter()
+                .all(|f| compatible(&first.header, &f.header))
+            {
+                return Err(MPeakError::IncompatibleStreams);
+            }
+        }
+        let mut stream_frames = stream.frames.clone();
+        if i > 0 {
+            for index in missing_main_data(&stream_frames) {
+                mute(&mut stream_frames[index]);
+            }
+        }
+        frames.extend(stream_frames);
+    }
+
+    let lame_tag = |stream: Option<&Mp3Stream>| {
+        stream
+            .and_then(|stream| stream.xing.as_ref())
+            .and_then(|xing| xing.lame.clone())
+    };
+    let lame = lame_tag(streams.first()).map(|mut lame| {
+        lame.encoder_padding = lame_tag(streams.last()).map_or(0, |last| last.encoder_padding);
+        lame
+    });
+    let (id3v2, ape, id3v1) = match streams.first() {
+        Some(first) => (first.id3v2.clone(), first.ape.clone(), first.id3v1.clone()),
+        None => Default::default(),
+    };
+    Ok(Mp3Stream {
+        id3v2,
+        xing: Some(Mp3XingTag {
+            lame,
+            ..Default::default()
+        }),
+        frames,
+        ape,
+        id3v1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mp3_header::Mp3FrameHeader;
+    use super::super::mp3_side_info::Mp3GranuleInfo;
+    use super::*;
+
+    /// V1 layer 3 128kbit/s 44100Hz mono frame with 100 bits of main data per granule
+    fn frame(main_data_begin: u16) -> Mp3Frame {
+        let header = Mp3FrameHeader::new(0xFFFB90C0);
+        let info = Mp3GranuleInfo {
+            part2_3_length: 100,
+            big_values: 10,
+            global_gain: 150,
+            ..Default::default()
+        };
+        let side_info = Mp3SideInfo {
+            main_data_begin,
+            private_bits: 0,
+            scfsi: [[false; 4]; 2],
+            granules: vec![vec![info]; 2],
+        };
+        let mut data = vec![0; 417];
+        data[0..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0xC0]);
+        let mut frame = Mp3Frame::new(header, data, 0);
+        frame.set_side_info(&side_info);
+        frame
+    }
+
+    fn stream() -> Mp3Stream {
+        let lame = Mp3LameTag {
+            encoder_delay: 576,
+            encoder_padding: 1000,
+            ..Default::default()
+        };
+        Mp3Stream {
+            id3v2: b"ID3\x04\x00\x00\x00\x00\x00\x00".to_vec(),
+            xing: Some(Mp3XingTag {
+                lame: Some(lame),
+                ..Default::default()
+            }),
+            frames: vec![frame(0), frame(0), frame(450), frame(0)],
+            ape: Vec::new(),
+            id3v1: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_missing_main_data() {
+        assert!(missing_main_data(&stream().frames).is_empty());
+        assert_eq!(missing_main_data(&stream().frames[1..]), vec![1]);
+        assert_eq!(missing_main_data(&stream().frames[2..]), vec![0]);
+    }
+
+    #[test]
+    fn test_cut_prime() {
+        let cut = cut(&stream(), 2, 3, Mp3ReservoirHandling::Prime);
+        // 450 bytes reach back two frames of 396 bytes main data
+        assert_eq!(cut.priming_frames, 2);
+        assert_eq!(cut.muted_frames, 0);
+        assert_eq!(cut.stream.frames.len(), 3);
+        assert_eq!(cut.stream.id3v2, stream().id3v2);
+        let lame = cut.stream.xing.unwrap().lame.unwrap();
+        assert_eq!(lame.encoder_delay, 2 * 1152 - 529);
+        assert_eq!(lame.encoder_padding, 529);
+    }
+
+    #[test]
+    fn test_cut_prime_overlap() {
+        // frame 3 needs no reservoir data, but frame 2 for the IMDCT overlap
+        let cut = cut(&stream(), 3, 4, Mp3ReservoirHandling::Prime);
+        assert_eq!(cut.priming_frames, 3);
+        assert_eq!(
+            cut.stream.xing.unwrap().lame.unwrap().encoder_delay,
+            3 * 1152 - 529
+        );
+    }
+
+    #[test]
+    fn test_cut_zero() {
+        let cut = cut(&stream(), 2, 4, Mp3ReservoirHandling::Zero);
+        // one frame before the start covers the decoder delay
+        assert_eq!(cut.priming_frames, 1);
+        assert_eq!(cut.muted_frames, 1);
+        assert_eq!(cut.stream.frames.len(), 3);
+        assert_eq!(cut.stream.frames[0].body.data, stream().frames[1].body.data);
+        let side_info = cut.stream.frames[1].side_info().unwrap();
+        assert_eq!(side_info.main_data_begin, 0);
+        assert_eq!(side_info.granules[0][0].part2_3_length, 0);
+        assert_eq!(side_info.granules[1][0].global_gain, 150);
+        assert_eq!(cut.stream.frames[2].side_info().unwrap().main_data_begin, 0);
+        // the end of the stream keeps the original padding
+        let lame = cut.stream.xing.unwrap().lame.unwrap();
+        assert_eq!(lame.encoder_delay, 1152 - 529);
+        assert_eq!(lame.encoder_padding, 1000);
+    }
+
+    #[test]
+    fn test_cut_decoder_delay() {
+        // Layer I frames of 384 samples, two of them cover the decoder delay
+        let header = Mp3FrameHeader::new(0xFFFF9000);
+        let frame = Mp3Frame::new(header, vec![0; header.frame_length().unwrap()], 0);
+        let mut stream = stream();
+        stream.frames = vec![frame; 4];
+        for handling in [Mp3ReservoirHandling::Prime, Mp3ReservoirHandling::Zero] {
+            let cut = cut(&stream, 3, 4, handling);
+            assert_eq!(cut.priming_frames, 2);
+            assert_eq!(
+                cut.stream.xing.unwrap().lame.unwrap().encoder_delay,
+                2 * 384 - 529
+            );
+        }
+    }
+
+    #[test]
+    fn test_join() {
+        let mut second = stream();
+        second.id3v2 = Vec::new();
+        second.frames = vec![frame(450), frame(0)];
+        second
+            .xing
+            .as_mut()
+            .unwrap()
+            .lame
+            .as_mut()
+            .unwrap()
+            .encoder_padding = 300;
+        let joined = join(&[stream(), second]).unwrap();
+        assert_eq!(joined.frames.len(), 6);
+        assert_eq!(joined.id3v2, stream().id3v2);
+        // main data of the second stream's first frame would be read from the first stream
+        assert_eq!(joined.frames[2].side_info().unwrap().main_data_begin, 450);
+        assert_eq!(joined.frames[4].side_info().unwrap().main_data_begin, 0);
+        let lame = joined.xing.unwrap().lame.unwrap();
+        assert_eq!(lame.encoder_delay, 576);
+        assert_eq!(lame.encoder_padding, 300);
+    }
+
+    #[test]
+    fn test_join_incompatible() {
+        let mut second = stream();
+        // 48000Hz
+        let mut data = second.frames[0].body.data.clone();
+        data[2] = 0x94;
+        second.frames = vec![Mp3Frame::new(Mp3FrameHeader::new(0xFFFB94C0), data, 0)];
+        assert!(matches!(
+            join(&[stream(), second]),
+            Err(MPeakError::IncompatibleStreams)
+        ));
+    }
+
+    #[test]
+    fn test_cut_start() {
+        let cut = cut(&stream(), 0, 10, Mp3ReservoirHandling::Prime);
+        assert_eq!(cut.stream.frames.len(), 4);
+        let lame = cut.stream.xing.unwrap().lame.unwrap();
+        assert_eq!(lame.encoder_delay, 576);
+        assert_eq!(lame.encoder_padding, 1000);
+    }
+
+    #[test]
+    fn test_cut_without_lame_tag() {
+        let mut stream = stream();
+        stream.xing = None;
+        let cut = cut(&stream, 2, 3, Mp3ReservoirHandling::Prime);
+        assert_eq!(cut.priming_frames, 2);
+        // the new LAME tag survives writing and parsing the stream
+        let parsed = Mp3Stream::parse(&cut.stream.to_bytes()).unwrap();
+        let lame = parsed.xing.unwrap().lame.unwrap();
+        assert_eq!(lame.encoder, "mpeak");
+        assert_eq!(lame.encoder_delay, 2 * 1152 - 529);
+        assert_eq!(lame.encoder_padding, 529);
+
+        let cut = super::cut(&stream, 0, 4, Mp3ReservoirHandling::Prime);
+        assert_eq!(cut.stream.xing.unwrap().lame, None);
+    }
+}
@@ -0,0 +1,283 @@
+// References
+// ISO/IEC 11172-3 2.4.3.4.7 (requantization)
+// http://www.mp3-tech.org/programmer/docs/mp3_theory.pdf
+
+use super::mp3_bands::Mp3Bands;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::{Mp3FrameHeader, Mp3Layer};
+use super::mp3_huffman::{region_boundaries, table_max_value};
+use super::mp3_requantize::PRETAB;
+use super::mp3_reservoir::Mp3Reservoir;
+use super::mp3_scalefactors::{Mp3FrameScalefactors, Mp3Scalefactors};
+use super::mp3_side_info::Mp3GranuleInfo;
+use super::waveform::{Peak, Waveform};
+
+/// Ratio of the decoded RMS to the RMS of the requantized spectral values of a granule,
+/// the gain of the IMDCT and synthesis filterbank (the square root of 288)
+const RMS_SCALE: f32 = 16.97;
+
+/// Ratio of peak to RMS assumed for a granule, typical of noise-like signals over 576 samples
+const CREST_FACTOR: f32 = 3.2;
+
+/// Estimates the energy of one channel in one granule from the side information and
+/// scalefactors only, without decoding the Huffman coded spectrum.
+/// The magnitude of each line is approximated from the Huffman table of its region.
+/// The count1 region of values up to 1 following the big values is not included, so
+/// granules coded mostly in that region are underestimated.
+pub fn estimate_energy(
+    header: &Mp3FrameHeader,
+    info: &Mp3GranuleInfo,
+    scalefactors: &Mp3Scalefactors,
+) -> f32 {
+    if info.part2_3_length == 0 {
+        return 0.0;
+    }
+    let big_values_end = usize::min(info.big_values as usize * 2, 576);
+    let (region1_start, region2_start) = region_boundaries(header, info);
+    let multiplier = if info.scalefac_scale { 1.0 } else { 0.5 };
+
+    let mut energy = 0.0;
+    for band in Mp3Bands::new(header).granule_bands(info) {
+        if band.start >= big_values_end {
+            break;
+        }
+        let region = if band.start < region1_start {
+            0
+        } else if band.start < region2_start {
+            1
+        } else {
+            2
+        };
+        let magnitude = typical_value(table_max_value(info.table_select[region])).powf(4.0 / 3.0);
+        let exponent = match band.window {
+            None => {
+                let pretab = if info.preflag { PRETAB[band.index] } else { 0 };
+                let scalefactor = (scalefactors.long[band.index] + pretab) as f32;
+                0.25 * (info.global_gain as f32 - 210.0) - multiplier * scalefactor
+            }
+            Some(window) => {
+                let gain =
+                    info.global_gain as f32 - 210.0 - 8.0 * info.subblock_gain[window] as f32;
+                0.25 * gain - multiplier * scalefactors.short[band.index][window] as f32
+            }
+        };
+        let amplitude = exponent.exp2() * magnitude;
+        let width = usize::min(band.end, big_values_end) - band.start;
+        energy += amplitude * amplitude * width as f32;
+    }
+    energy
+}
+
+/// Typical magnitude of the values coded with a table of the given largest value.
+/// Encoders pick the smallest table fitting the largest value of a region, most values are smaller.
+fn typical_value(max_value: u32) -> f32 {
+    (max_value as f32).sqrt()
+}
+
+/// Approximates the peaks of each Layer III granule (576 samples per pixel) from the
+/// side information and scalefactors. Other layers are skipped.
+/// Channel count and sample rate are taken from the first frame.
+pub fn approximate_waveform(frames: &[Mp3Frame]) -> Waveform {
+    let (sample_rate, channels) = frames
+        .iter()
+        .find(|frame| !frame.is_junk())
+        .map_or((0, 0), |frame| {
+            (frame.header.sample_rate() as u32, frame.header.channels())
+        });
+    let mut reservoir = Mp3Reservoir::new();
+    let mut peaks = Vec::new();
+    for frame in frames.iter().filter(|frame| !frame.is_junk()) {
+        if frame.header.layer() != Mp3Layer::Layer3 {
+            reservoir.clear();
+            continue;
+        }
+        let header = &frame.header;
+        let (side_info, scalefactors) = match reservoir.next_frame(frame) {
+            Some((side_info, main_data)) => {
+                let scalefactors = Mp3FrameScalefactors::decode(frame, &side_info, &main_data);
+                (side_info, Some(scalefactors))
+            }
+            // without main data the scalefactors are unknown, the global gain is still valid
+            None => match frame.side_info() {
+                Ok(side_info) => (side_info, None),
+                Err(_) => {
+                    let silence = header.granules() * channels;
+                    peaks.extend(std::iter::repeat_n(Peak::default(), silence));
+                    continue;
+                }
+            },
+        };
+
+        for (gr, granule) in side_info.granules.iter().enumerate() {
+            let mut energies: Vec<f32> = granule
+                .iter()
+                .enumerate()
+                .map(|(ch, info)| {
+                    let scalefactors = scalefactors
+                        .as_ref()
+                        .map_or(Default::default(), |s| s.granules[gr][ch]);
+                    estimate_energy(header, info, &scalefactors)
+                })
+                .collect();
+            // mid and side are spread evenly over left and right
+            if header.is_mid_side_stereo() {
+                let energy = energies.iter().sum::<f32>() / 2.0;
+                energies = vec![energy; 2];
+            }
+            let energies = match (energies.len(), channels) {
+                (1, 2) => vec![energies[0]; 2],
+                (2, 1) => vec![(energies[0] + energies[1]) / 2.0],
+                _ => energies,
+            };
+            for energy in energies {
+                let rms = f32::min(RMS_SCALE * (energy / 576.0).sqrt(), 1.0);
+                let peak = f32::min(rms * CREST_FACTOR, 1.0);
+                peaks.push(Peak {
+                    min: -peak,
+                    max: peak,
+                    rms,
+                });
+            }
+        }
+    }
+    Waveform {
+        sample_rate,
+        channels,
+        samples_per_pixel: 576,
+        peaks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::bit_writer::BitWriter;
+    use super::super::{get_approximate_waveform, get_waveform};
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_estimate_energy() {
+        let header = Mp3FrameHeader::new(0xFFFB9000);
+        let info = Mp3GranuleInfo {
+            part2_3_length: 100,
+            big_values: 2,
+            global_gain: 210,
+            table_select: [1, 0, 0],
+            region0_count: 15,
+            region1_count: 4,
+            ..Default::default()
+        };
+        let mut scalefactors = Mp3Scalefactors::default();
+        // 4 lines of magnitude 1 at a gain of 1
+        assert_eq!(estimate_energy(&header, &info, &scalefactors), 4.0);
+        // scalefactor 2 halves the amplitude
+        scalefactors.long[0] = 2;
+        assert_eq!(estimate_energy(&header, &info, &scalefactors), 1.0);
+
+        // 74 lines up to the end of band 11, whose pretab of 1 halves the energy of its 12 lines
+        let preflag = Mp3GranuleInfo {
+            big_values: 37,
+            table_select: [1, 1, 1],
+            preflag: true,
+            ..info
+        };
+        let scalefactors = Mp3Scalefactors::default();
+        assert_eq!(estimate_energy(&header, &preflag, &scalefactors), 68.0);
+        let preflag = Mp3GranuleInfo {
+            scalefac_scale: true,
+            ..preflag
+        };
+        assert_eq!(estimate_energy(&header, &preflag, &scalefactors), 65.0);
+        let mut scalefactors = Mp3Scalefactors::default();
+        scalefactors.long[0] = 2;
+        let info = Mp3GranuleInfo {
+            part2_3_length: 0,
+            ..info
+        };
+        assert_eq!(estimate_energy(&header, &info, &scalefactors), 0.0);
+    }
+
+    #[test]
+    fn test_estimate_energy_gain() {
+        let header = Mp3FrameHeader::new(0xFFFB9000);
+        let quiet = Mp3GranuleInfo {
+            part2_3_length: 1000,
+            big_values: 200,
+            global_gain: 150,
+            table_select: [24, 13, 7],
+            ..Default::default()
+        };
+        let loud = Mp3GranuleInfo {
+            global_gain: 170,
+            ..quiet
+        };
+        let scalefactors = Mp3Scalefactors::default();
+        let ratio = estimate_energy(&header, &loud, &scalefactors)
+            / estimate_energy(&header, &quiet, &scalefactors);
+        // 20 global gain steps are 5 doublings of the amplitude
+        assert!((ratio - 1024.0).abs() < 0.1, "ratio {}", ratio);
+    }
+
+    /// V1 layer 3 128kbit/s 44100Hz mono frame with all 576 lines of both granules
+    /// set to 1 with random signs, coded with table 1, so `estimate_energy` is exact
+    fn dense_frame(rng: &mut StdRng, global_gain: u32, preflag: bool) -> Mp3Frame {
+        let mut writer = BitWriter::new();
+        writer.write_bits(0xFFFB90C0, 32);
+        writer.write_bits(0, 9 + 5 + 4);
+        for _ in 0..2 {
+            writer.write_bits(288 * 5, 12);
+            writer.write_bits(288, 9);
+            writer.write_bits(global_gain, 8);
+            writer.write_bits(0, 4 + 1);
+            for _ in 0..3 {
+                writer.write_bits(1, 5);
+            }
+            writer.write_bits(15, 4);
+            writer.write_bits(7, 3);
+            writer.write_bit(preflag);
+            writer.write_bits(0, 2);
+        }
+        // code 000 for the pair (1, 1) followed by the two signs
+        for _ in 0..2 * 288 {
+            writer.write_bits(0, 3);
+            writer.write_bits(rng.gen_range(0..4), 2);
+        }
+        let mut data = writer.into_bytes();
+        data.resize(417, 0);
+        Mp3Frame::new(Mp3FrameHeader::new(0xFFFB90C0), data, 0)
+    }
+
+    #[test]
+    fn test_approximate_waveform() {
+        // the gain rises every 8 frames (16 granules), every other 8 frames use preflag
+        let mut rng = StdRng::seed_from_u64(1);
+        let frames: Vec<Mp3Frame> = (0..64)
+            .map(|i| dense_frame(&mut rng, 140 + 6 * (i / 8) as u32, i / 8 % 2 == 1))
+            .collect();
+        let exact = get_waveform(&frames, 576);
+        let approximate = get_approximate_waveform(&frames, 576);
+        assert_eq!(exact.peaks.len(), approximate.peaks.len());
+        // the decoder delay shifts the decoded audio by about a granule,
+        // so the first two granules after a change of the gain are skipped
+        for (i, (exact, approximate)) in exact.peaks.iter().zip(&approximate.peaks).enumerate() {
+            if i % 16 < 2 {
+                continue;
+            }
+            let peak = exact.max.max(-exact.min);
+            // RMS within 5%, peak within 25% of the decoded waveform
+            assert!(
+                (approximate.rms / exact.rms - 1.0).abs() < 0.05,
+                "granule {}",
+                i
+            );
+            assert!(
+                (approximate.max / peak - 1.0).abs() < 0.25,
+                "granule {}: {:?} {:?}",
+                i,
+                exact,
+                approximate
+            );
+        }
+    }
+}
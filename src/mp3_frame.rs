@@ -1,6 +1,26 @@
+// References
+// ISO/IEC 11172-3 2.4.3.1 (error check), 2.4.1.5 and 2.4.1.6 (audio data Layer I and II)
+
+use super::bit_reader::BitReader;
 use super::mp3_body::Mp3Body;
-use super::mp3_header::{Mp3ChannelMode, Mp3FrameHeader};
+use super::mp3_crc::crc16;
+use super::mp3_header::{Mp3ChannelMode, Mp3FrameHeader, Mp3Layer, Mp3Protection};
+use super::mp3_layer2::allocation_levels;
+use super::mp3_side_info::Mp3SideInfo;
+use super::MPeakError;
+
+/// Metadata of a frame without its data
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3FrameInfo {
+    pub position: u32,
+    pub length: usize,
+    pub header: Mp3FrameHeader,
+    /// Whether the stored crc is correct, None for frames without a checkable crc
+    pub crc: Option<bool>,
+}
 
+#[derive(Clone)]
 pub struct Mp3Frame {
     pub header: Mp3FrameHeader,
     pub body: Mp3Body,
@@ -11,9 +31,277 @@ impl Mp3Frame {
     pub fn new(header: Mp3FrameHeader, data: Vec<u8>, position: u32) -> Mp3Frame {
         let is_mono = header.channel_mode() == Mp3ChannelMode::SingleChannel;
         Mp3Frame {
-            header: header,
+            header,
             body: Mp3Body::new(data, is_mono),
-            position: position,
+            position,
+        }
+    }
+
+    /// Replaces the header. The crc is inserted or removed if the protection changes,
+    /// the frame is resized to the new frame length and the crc is recomputed.
+    pub fn set_header(&mut self, header: Mp3FrameHeader) {
+        let had_crc = self.payload_offset() == 6;
+        self.header = header;
+        self.body.is_mono = header.channel_mode() == Mp3ChannelMode::SingleChannel;
+        if self.body.data.len() < 4 {
+            self.body.data.resize(4, 0);
+        }
+        match (had_crc, self.payload_offset() == 6) {
+            (false, true) => {
+                self.body.data.splice(4..4, [0, 0]);
+            }
+            (true, false) => {
+                self.body.data.drain(4..usize::min(6, self.body.data.len()));
+            }
+            _ => {}
+        }
+        if let Ok(length) = header.frame_length() {
+            self.body.data.resize(length, 0);
+        }
+        self.body.data[0..4].copy_from_slice(&header.to_bytes());
+        self.update_crc();
+    }
+
+    /// Whether the header has no sync word or no valid frame length,
+    /// like data which `get_frames` returns for junk between the frames
+    pub fn is_junk(&self) -> bool {
+        !self.header.has_sync() || self.header.frame_length().is_err()
+    }
+
+    pub fn info(&self) -> Mp3FrameInfo {
+        Mp3FrameInfo {
+            position: self.position,
+            length: self.body.data.len(),
+            header: self.header,
+            crc: self.crc_matches(),
+        }
+    }
+
+    /// Offset of the first byte after the header and the optional crc
+    pub fn payload_offset(&self) -> usize {
+        match self.header.protected() {
+            Mp3Protection::ProtectedByCrc => 6,
+            Mp3Protection::NotProtected => 4,
+        }
+    }
+
+    /// Parses the Layer III side information of the frame
+    pub fn side_info(&self) -> Result<Mp3SideInfo, MPeakError> {
+        let offset = usize::min(self.payload_offset(), self.body.data.len());
+        Mp3SideInfo::parse(&self.header, &self.body.data[offset..])
+    }
+
+    /// Replaces the Layer III side information of the frame and updates the crc
+    pub fn set_side_info(&mut self, side_info: &Mp3SideInfo) {
+        let offset = self.payload_offset();
+        let bytes = side_info.to_bytes(&self.header);
+        if self.body.data.len() >= offset + bytes.len() {
+            self.body.data[offset..offset + bytes.len()].copy_from_slice(&bytes);
+            self.update_crc();
+        }
+    }
+
+    /// Number of bits after the crc covered by it: the Layer I bit allocation, the Layer II
+    /// bit allocation and scale factor selection information or the Layer III side information
+    fn crc_coverage(&self) -> Option<usize> {
+        let channels = self.header.channels();
+        match self.header.layer() {
+            Mp3Layer::Layer1 => {
+                let bound = self.header.joint_stereo_bound().min(32);
+                Some(4 * (bound * channels + 32 - bound))
+            }
+            Mp3Layer::Layer2 => {
+                let levels = allocation_levels(&self.header);
+                let bound = self.header.joint_stereo_bound().min(levels.len());
+                let data = &self.body.data[self.payload_offset().min(self.body.data.len())..];
+                let mut reader = BitReader::new(data);
+                let mut allocated = 0;
+                for (subband, levels) in levels.iter().enumerate() {
+                    let bits = levels.len().trailing_zeros() as usize;
+                    let shared = subband >= bound;
+                    for _ in 0..channels {
+                        if reader.read_bits(bits) != 0 {
+                            // shared allocations apply to both channels
+                            allocated += match shared {
+                                true => channels,
+                                false => 1,
+                            };
+                        }
+                        if shared {
+                            break;
+                        }
+                    }
+                }
+                // two bits of scale factor selection information per allocated subband
+                Some(reader.position() + 2 * allocated)
+            }
+            Mp3Layer::Layer3 => Some(8 * self.header.side_info_length()),
+            Mp3Layer::Reserved => None,
+        }
+    }
+
+    /// Crc of a protected frame,
+    /// which covers the last two header bytes and the bits given by `crc_coverage`
+    fn crc(&self) -> Option<u16> {
+        if self.header.protected() != Mp3Protection::ProtectedByCrc {
+            return None;
         }
+        let bits = self.crc_coverage()?;
+        let end = 6 + bits.div_ceil(8);
+        if self.body.data.len() < end {
+            return None;
+        }
+        let mut covered = self.body.data[2..4].to_vec();
+        covered.extend_from_slice(&self.body.data[6..end]);
+        Some(crc16(&covered, 16 + bits))
+    }
+
+    /// Recomputes the crc of a protected frame
+    pub fn update_crc(&mut self) {
+        if let Some(crc) = self.crc() {
+            self.body.data[4..6].copy_from_slice(&crc.to_be_bytes());
+        }
+    }
+
+    /// Whether the stored crc is correct, None for frames without a checkable crc
+    pub fn crc_matches(&self) -> Option<bool> {
+        self.crc()
+            .map(|crc| self.body.data[4..6] == crc.to_be_bytes())
+    }
+
+    /// Layer III main data bytes stored in this frame
+    pub fn main_data(&self) -> &[u8] {
+        let offset = self.payload_offset() + self.header.side_info_length();
+        &self.body.data[usize::min(offset, self.body.data.len())..]
+    }
+}
+
+/// Serializes the frame metadata, not the data
+#[cfg(feature = "serde")]
+impl serde::Serialize for Mp3Frame {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.info().serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mp3_header::Mp3Version;
+    use super::*;
+
+    #[test]
+    fn test_set_header() {
+        let header = Mp3FrameHeader::new(0xFFFB9000);
+        let mut data = vec![0; 417];
+        data[0..4].copy_from_slice(&header.to_bytes());
+        data[4] = 0x12;
+        let mut frame = Mp3Frame::new(header, data, 0);
+
+        let mut protected = header;
+        protected.set_protection(Mp3Protection::ProtectedByCrc);
+        protected.set_padding_bit(true);
+        protected.set_copyright(true);
+        frame.set_header(protected);
+        assert_eq!(frame.body.data.len(), 418);
+        assert_eq!(frame.body.data[0..4], protected.to_bytes());
+        assert_eq!(frame.body.data[6], 0x12);
+        let mut covered = frame.body.data[2..4].to_vec();
+        covered.extend_from_slice(&frame.body.data[6..38]);
+        assert_eq!(
+            frame.body.data[4..6],
+            crc16(&covered, 8 * covered.len()).to_be_bytes()
+        );
+        assert_eq!(frame.crc_matches(), Some(true));
+        frame.body.data[10] ^= 1;
+        assert_eq!(frame.crc_matches(), Some(false));
+
+        frame.set_header(header);
+        assert_eq!(frame.body.data.len(), 417);
+        assert_eq!(frame.body.data[4], 0x12);
+        assert!(!frame.header.copyright());
+        assert_eq!(frame.crc_matches(), None);
+
+        // a valid Layer II crc stays valid
+        let mut frame = protected_frame(Mp3Layer::Layer2, 192, Mp3ChannelMode::Stereo);
+        frame.body.data[6] = 0x10;
+        frame.body.data[6 + 22] = 0b1000_0000;
+        let crc = expected_crc(&frame, 2 * 88 + 2);
+        frame.body.data[4..6].copy_from_slice(&crc);
+        let mut header = frame.header;
+        header.set_copyright(true);
+        frame.set_header(header);
+        assert_eq!(frame.body.data[4..6], expected_crc(&frame, 2 * 88 + 2));
+        assert_eq!(frame.crc_matches(), Some(true));
+    }
+
+    /// Protected silent frame, so the covered bits are those given by the test
+    fn protected_frame(layer: Mp3Layer, bitrate: u16, channel_mode: Mp3ChannelMode) -> Mp3Frame {
+        let mut header =
+            Mp3FrameHeader::from_parameters(Mp3Version::V1, layer, bitrate, 48000, channel_mode)
+                .unwrap();
+        header.set_protection(Mp3Protection::ProtectedByCrc);
+        let mut data = vec![0; header.frame_length().unwrap()];
+        data[0..4].copy_from_slice(&header.to_bytes());
+        Mp3Frame::new(header, data, 0)
+    }
+
+    /// Crc over the last two header bytes and the given number of bits after the crc
+    fn expected_crc(frame: &Mp3Frame, bits: usize) -> [u8; 2] {
+        let covered = [&frame.body.data[2..4], &frame.body.data[6..]].concat();
+        crc16(&covered, 16 + bits).to_be_bytes()
+    }
+
+    #[test]
+    fn test_layer1_and_layer2_crc() {
+        // Layer II, 96 kbit/s per channel at 48 kHz: table B.2a with 27 subbands of
+        // 11 x 4, 12 x 3 and 4 x 2 allocation bits
+        let mut frame = protected_frame(Mp3Layer::Layer2, 192, Mp3ChannelMode::Stereo);
+        // allocation of subband 0 of the first channel, followed by its scfsi
+        frame.body.data[6] = 0x10;
+        frame.body.data[6 + 22] = 0b1000_0000;
+        frame.update_crc();
+        assert_eq!(frame.body.data[4..6], expected_crc(&frame, 2 * 88 + 2));
+        frame.body.data[6 + 22] ^= 0b0010_0000;
+        assert_eq!(frame.crc_matches(), Some(true));
+        frame.body.data[6 + 22] ^= 0b0100_0000;
+        assert_eq!(frame.crc_matches(), Some(false));
+
+        // joint stereo with bound 4: subbands 4 to 26 share one allocation
+        let mut header = frame.header;
+        header.set_channel_mode(Mp3ChannelMode::JointStereo);
+        frame.body.data[6] = 0;
+        frame.body.data[6 + 22] = 0;
+        frame.set_header(header);
+        assert_eq!(frame.body.data[4..6], expected_crc(&frame, 2 * 16 + 72));
+
+        // Layer I with bound 8: 8 subbands per channel and 24 shared, 4 bits each
+        let mut frame = protected_frame(Mp3Layer::Layer1, 384, Mp3ChannelMode::JointStereo);
+        let mut header = frame.header;
+        header.set_mode_extension(1).unwrap();
+        frame.set_header(header);
+        assert_eq!(
+            frame.body.data[4..6],
+            expected_crc(&frame, 4 * (2 * 8 + 24))
+        );
+        let mut mono = protected_frame(Mp3Layer::Layer1, 192, Mp3ChannelMode::SingleChannel);
+        mono.update_crc();
+        assert_eq!(mono.body.data[4..6], expected_crc(&mono, 4 * 32));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let header = Mp3FrameHeader::new(0xFFFA9000);
+        let mut data = vec![0; 417];
+        data[0..4].copy_from_slice(&header.to_bytes());
+        let mut frame = Mp3Frame::new(header, data, 3);
+        frame.update_crc();
+        let value = serde_json::to_value(&frame).unwrap();
+        assert_eq!(value["position"], 3);
+        assert_eq!(value["length"], 417);
+        assert_eq!(value["crc"], true);
+        assert_eq!(value["header"]["protection"], "ProtectedByCrc");
+        let info: Mp3FrameInfo = serde_json::from_value(value).unwrap();
+        assert_eq!(info, frame.info());
     }
 }
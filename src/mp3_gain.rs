@@ -0,0 +1,222 @@
+// References
+// ISO/IEC 11172-3 2.4.3.4.7 (global_gain)
+// http://mp3gain.sourceforge.net (MP3GAIN_UNDO and MP3GAIN_MINMAX APE items)
+
+use super::apev2::ApeTag;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::{Mp3FrameHeader, Mp3Layer};
+use super::mp3_scan::{scan, Mp3RegionKind};
+use super::mp3_tags::{find_ape, replace_ape};
+use super::mp3_xing::Mp3XingTag;
+use super::{decode, MPeakError};
+
+/// Change of the amplitude by one global_gain step in dB, 20 * log10(2^(1/4))
+pub const GAIN_STEP_DB: f64 = 1.505;
+
+const UNDO_KEY: &str = "MP3GAIN_UNDO";
+const MINMAX_KEY: &str = "MP3GAIN_MINMAX";
+
+/// Smallest and largest global_gain of all Layer III granules
+pub fn global_gain_range(frames: &[Mp3Frame]) -> Option<(u8, u8)> {
+    let gains: Vec<u8> = frames
+        .iter()
+        .filter(|frame| frame.header.layer() == Mp3Layer::Layer3)
+        .filter_map(|frame| frame.side_info().ok())
+        .flat_map(|side_info| side_info.granules.into_iter().flatten())
+        .map(|info| info.global_gain)
+        .collect();
+    Some((*gains.iter().min()?, *gains.iter().max()?))
+}
+
+/// Adds `steps[ch]` to the global_gain of every granule of channel `ch`.
+/// Fails without changing any frame if a global_gain would leave the range 0 to 255,
+/// or if the channels of a mid/side stereo frame would be changed differently.
+fn change_gain(frames: &mut [Mp3Frame], steps: [i32; 2]) -> Result<(), MPeakError> {
+    let mut changed = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        if frame.header.layer() != Mp3Layer::Layer3 {
+            continue;
+        }
+        let Ok(mut side_info) = frame.side_info() else {
+            continue;
+        };
+        if frame.header.is_mid_side_stereo() && steps[0] != steps[1] {
+            return Err(MPeakError::GainOutOfRange);
+        }
+        for info in side_info.granules.iter_mut() {
+            for (ch, info) in info.iter_mut().enumerate() {
+                let gain = info.global_gain as i32 + steps[ch];
+                info.global_gain = u8::try_from(gain).map_err(|_| MPeakError::GainOutOfRange)?;
+            }
+        }
+        changed.push((i, side_info));
+    }
+    for (i, side_info) in changed {
+        frames[i].set_side_info(&side_info);
+    }
+    Ok(())
+}
+
+/// Largest number of global_gain steps the decoded audio can be raised without clipping
+pub fn max_gain_without_clipping(frames: &[Mp3Frame]) -> i32 {
+    let audio = decode(frames);
+    let peak = audio
+        .samples
+        .iter()
+        .fold(0.0f32, |peak, x| peak.max(x.abs()));
+    match peak > 0.0 {
+        true => (-4.0 * peak.log2()).floor() as i32,
+        false => u8::MAX as i32,
+    }
+}
+
+/// Changes of the left and right channel recorded in the APE tag
+fn undo_steps(ape: &ApeTag) -> Option<[i32; 2]> {
+    let undo = ape.get(UNDO_KEY)?;
+    let mut values = undo
+        .split(',')
+        .map(|value| value.trim().parse::<i32>().ok());
+    Some([values.next()??, values.next()??])
+}
+
+/// Frames of the file data with their offsets, without the Xing/Info tag frame
+fn file_frames(data: &[u8]) -> (Vec<usize>, Vec<Mp3Frame>) {
+    let mut frames: Vec<(usize, Mp3Frame)> = scan(data)
+        .iter()
+        .filter(|region| {
+            matches!(
+                region.kind,
+                Mp3RegionKind::Frame | Mp3RegionKind::TruncatedFrame
+            )
+        })
+        .enumerate()
+        .map(|(i, region)| {
+            let bytes = data[region.offset..region.offset + region.length].to_vec();
+            let mut header = [0; 4];
+            let available = usize::min(4, bytes.len());
+            header[..available].copy_from_slice(&bytes[..available]);
+            let header = Mp3FrameHeader::new(u32::from_be_bytes(header));
+            (region.offset, Mp3Frame::new(header, bytes, i as u32))
+        })
+        .collect();
+    if frames
+        .first()
+        .is_some_and(|(_, frame)| Mp3XingTag::parse(frame).is_some())
+    {
+        frames.remove(0);
+    }
+    frames.into_iter().unzip()
+}
+
+/// Changes the gain of the frames of the file data and records the change `undo`
+/// and the resulting global_gain range in the APE tag.
+/// Only the side information of the frames and the APE tag are replaced,
+/// all other data is copied unchanged.
+fn change_file_gain(data: &[u8], steps: [i32; 2], undo: [i32; 2]) -> Result<Vec<u8>, MPeakError> {
+    let (offsets, mut frames) = file_frames(data);
+    change_gain(&mut frames, steps)?;
+    let mut data = data.to_vec();
+    for (&offset, frame) in offsets.iter().zip(&frames) {
+        data[offset..offset + frame.body.data.len()].copy_from_slice(&frame.body.data);
+    }
+
+    let mut ape = ApeTag::parse(find_ape(&data)).unwrap_or_default();
+    ape.set(UNDO_KEY, &format!("{:+04},{:+04},N", undo[0], undo[1]));
+    if let Some((min, max)) = global_gain_range(&frames) {
+        ape.set(MINMAX_KEY, &format!("{:03},{:03}", min, max));
+    }
+    Ok(replace_ape(&data, &ape.to_bytes()))
+}
+
+/// Changes the gain of the file data by `steps` global_gain steps of 1.5 dB without
+/// re-encoding. The change is added to the undo record in the APE tag, like mp3gain does.
+pub fn apply_gain(data: &[u8], steps: i32) -> Result<Vec<u8>, MPeakError> {
+    let ape = ApeTag::parse(find_ape(data)).unwrap_or_default();
+    let [left, right] = undo_steps(&ape).unwrap_or([0, 0]);
+    change_file_gain(data, [steps; 2], [left + steps, right + steps])
+}
+
+/// Reverts all gain changes recorded in the APE tag of the file data
+pub fn undo_gain(data: &[u8]) -> Result<Vec<u8>, MPeakError> {
+    let ape = ApeTag::parse(find_ape(data)).unwrap_or_default();
+    let [left, right] = undo_steps(&ape).ok_or(MPeakError::MissingGainUndo)?;
+    change_file_gain(data, [-left, -right], [0, 0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::id3v1::Id3v1Tag;
+    use super::super::mp3_side_info::{Mp3GranuleInfo, Mp3SideInfo};
+    use super::*;
+
+    /// V1 layer 3 128kbit/s 44100Hz frame, stereo or mono
+    fn frame(raw: u32, global_gain: u8) -> Mp3Frame {
+        let header = Mp3FrameHeader::new(raw);
+        let info = Mp3GranuleInfo {
+            global_gain,
+            ..Default::default()
+        };
+        let side_info = Mp3SideInfo {
+            main_data_begin: 0,
+            private_bits: 0,
+            scfsi: [[false; 4]; 2],
+            granules: vec![vec![info; header.channels()]; 2],
+        };
+        let mut data = vec![0; 417];
+        data[0..4].copy_from_slice(&raw.to_be_bytes());
+        let mut frame = Mp3Frame::new(header, data, 0);
+        frame.set_side_info(&side_info);
+        frame
+    }
+
+    /// Stereo and mono frames, junk and an ID3v1 tag
+    fn file() -> Vec<u8> {
+        [
+            frame(0xFFFB9000, 150).body.data,
+            frame(0xFFFB90C0, 200).body.data,
+            b"JUNKJUNK".to_vec(),
+            frame(0xFFFB90C0, 160).body.data,
+            Id3v1Tag::default().to_bytes(),
+        ]
+        .concat()
+    }
+
+    fn gain_range(data: &[u8]) -> Option<(u8, u8)> {
+        global_gain_range(&file_frames(data).1)
+    }
+
+    #[test]
+    fn test_apply_gain() {
+        let data = apply_gain(&file(), 3).unwrap();
+        let data = apply_gain(&data, -1).unwrap();
+        assert_eq!(gain_range(&data), Some((152, 202)));
+        let ape = ApeTag::parse(find_ape(&data)).unwrap();
+        assert_eq!(ape.get(UNDO_KEY), Some("+002,+002,N".to_string()));
+        assert_eq!(ape.get(MINMAX_KEY), Some("152,202".to_string()));
+        // the APE tag is inserted before the ID3v1 tag, the junk is kept
+        assert_eq!(data.len(), file().len() + find_ape(&data).len());
+        assert!(data.ends_with(&Id3v1Tag::default().to_bytes()));
+        assert_eq!(data[834..842], *b"JUNKJUNK");
+    }
+
+    #[test]
+    fn test_undo_gain() {
+        assert!(matches!(
+            undo_gain(&file()),
+            Err(MPeakError::MissingGainUndo)
+        ));
+        let changed = apply_gain(&file(), -5).unwrap();
+        let undone = undo_gain(&changed).unwrap();
+        let ape = ApeTag::parse(find_ape(&undone)).unwrap();
+        assert_eq!(ape.get(UNDO_KEY), Some("+000,+000,N".to_string()));
+        assert_eq!(replace_ape(&undone, &[]), file());
+    }
+
+    #[test]
+    fn test_gain_out_of_range() {
+        assert!(matches!(
+            apply_gain(&file(), 60),
+            Err(MPeakError::GainOutOfRange)
+        ));
+    }
+}
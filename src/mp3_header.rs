@@ -23,7 +23,8 @@ const MPEG2: [u16; 4] = [22050, 24000, 16000, 0];
 const MPEG2_5: [u16; 4] = [11025, 12000, 8000, 0];
 const MPEGR: [u16; 4] = [0, 0, 0, 0];
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Mp3Version {
     V25 = 0,
@@ -32,7 +33,8 @@ pub enum Mp3Version {
     V1 = 3,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Mp3Layer {
     Reserved = 0,
@@ -41,13 +43,15 @@ pub enum Mp3Layer {
     Layer1 = 3,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mp3Protection {
     ProtectedByCrc,
     NotProtected,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Mp3ChannelMode {
     Stereo = 0,
@@ -56,7 +60,8 @@ pub enum Mp3ChannelMode {
     SingleChannel = 3,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Mp3Emphasis {
     None = 1,
@@ -65,6 +70,12 @@ pub enum Mp3Emphasis {
     CcitJ17 = 4,
 }
 
+#[derive(PartialEq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "Mp3FrameHeaderFields", try_from = "Mp3FrameHeaderFields")
+)]
 pub struct Mp3FrameHeader {
     //            AAAAAAAA AAABBCCD EEEEFFGH IIJJKLMM
     // sync:      11111111 11100000 00000000 00000000
@@ -85,7 +96,129 @@ pub struct Mp3FrameHeader {
 
 impl Mp3FrameHeader {
     pub fn new(data: u32) -> Mp3FrameHeader {
-        Mp3FrameHeader { data: data }
+        Mp3FrameHeader { data }
+    }
+
+    /// Whether the 11 frame sync bits are set
+    pub fn has_sync(&self) -> bool {
+        self.data >> 21 == 0x7FF
+    }
+
+    /// Header of a frame without crc, padding and flags.
+    /// Fails if the bitrate (kbit/s) or sample rate (Hz) is not defined for the version and layer.
+    pub fn from_parameters(
+        version: Mp3Version,
+        layer: Mp3Layer,
+        bitrate: u16,
+        sample_rate: u16,
+        channel_mode: Mp3ChannelMode,
+    ) -> Result<Mp3FrameHeader, MPeakError> {
+        // sync word, no crc
+        let mut header = Mp3FrameHeader::new(0xFFE0_0000 | 1 << 16);
+        header.set_version(version)?;
+        header.set_layer(layer)?;
+        header.set_channel_mode(channel_mode);
+        let bitrate_index = header.bitrates()[1..15]
+            .iter()
+            .position(|&b| b == bitrate)
+            .ok_or(MPeakError::InvalidMp3Header)?;
+        header.set_bitrate_index(bitrate_index as u8 + 1)?;
+        let sampling_rate_index = header.sample_rates()[..3]
+            .iter()
+            .position(|&r| r == sample_rate)
+            .ok_or(MPeakError::InvalidMp3Header)?;
+        header.set_sampling_rate_index(sampling_rate_index as u8)?;
+        Ok(header)
+    }
+
+    /// The header as stored in a file
+    pub fn to_bytes(&self) -> [u8; 4] {
+        self.data.to_be_bytes()
+    }
+
+    /// Replaces the `mask` bits at `shift` by `value`
+    fn set_bits(&mut self, shift: u32, mask: u32, value: u32) {
+        self.data = self.data & !(mask << shift) | (value & mask) << shift;
+    }
+
+    /// Sets the version, the reserved version is rejected
+    pub fn set_version(&mut self, version: Mp3Version) -> Result<(), MPeakError> {
+        if version == Mp3Version::Reserved {
+            return Err(MPeakError::InvalidMp3Header);
+        }
+        self.set_bits(19, 0x3, version as u32);
+        Ok(())
+    }
+
+    /// Sets the layer, the reserved layer is rejected
+    pub fn set_layer(&mut self, layer: Mp3Layer) -> Result<(), MPeakError> {
+        if layer == Mp3Layer::Reserved {
+            return Err(MPeakError::InvalidMp3Header);
+        }
+        self.set_bits(17, 0x3, layer as u32);
+        Ok(())
+    }
+
+    pub fn set_protection(&mut self, protection: Mp3Protection) {
+        let bit = protection == Mp3Protection::NotProtected;
+        self.set_bits(16, 1, bit as u32);
+    }
+
+    /// Sets the bitrate index, 0 selects free format and 15 is rejected
+    pub fn set_bitrate_index(&mut self, index: u8) -> Result<(), MPeakError> {
+        if index >= 15 {
+            return Err(MPeakError::InvalidMp3Header);
+        }
+        self.set_bits(12, 0xf, index as u32);
+        Ok(())
+    }
+
+    /// Sets the sampling rate index, the reserved index 3 is rejected
+    pub fn set_sampling_rate_index(&mut self, index: u8) -> Result<(), MPeakError> {
+        if index >= 3 {
+            return Err(MPeakError::InvalidMp3Header);
+        }
+        self.set_bits(10, 0x3, index as u32);
+        Ok(())
+    }
+
+    pub fn set_padding_bit(&mut self, padding: bool) {
+        self.set_bits(9, 1, padding as u32);
+    }
+
+    pub fn set_private_bit(&mut self, private: bool) {
+        self.set_bits(8, 1, private as u32);
+    }
+
+    pub fn set_channel_mode(&mut self, channel_mode: Mp3ChannelMode) {
+        self.set_bits(6, 0x3, channel_mode as u32);
+    }
+
+    /// Sets the mode extension, values above 3 are rejected
+    pub fn set_mode_extension(&mut self, mode_extension: u8) -> Result<(), MPeakError> {
+        if mode_extension > 3 {
+            return Err(MPeakError::InvalidMp3Header);
+        }
+        self.set_bits(4, 0x3, mode_extension as u32);
+        Ok(())
+    }
+
+    pub fn set_copyright(&mut self, copyright: bool) {
+        self.set_bits(3, 1, copyright as u32);
+    }
+
+    pub fn set_original(&mut self, original: bool) {
+        self.set_bits(2, 1, original as u32);
+    }
+
+    /// Sets the emphasis, the reserved emphasis is rejected
+    pub fn set_emphasis(&mut self, emphasis: Mp3Emphasis) -> Result<(), MPeakError> {
+        if emphasis == Mp3Emphasis::Reserved {
+            return Err(MPeakError::InvalidMp3Header);
+        }
+        // the enum values start at 1
+        self.set_bits(0, 0x3, emphasis as u32 - 1);
+        Ok(())
     }
 
     pub fn version(&self) -> Mp3Version {
@@ -153,7 +286,7 @@ impl Mp3FrameHeader {
     }
 
     pub fn emphasis(&self) -> Mp3Emphasis {
-        let bits: (bool, bool) = (self.data >> 1 & 1 == 1, self.data >> 0 & 1 == 1);
+        let bits: (bool, bool) = (self.data >> 1 & 1 == 1, self.data & 1 == 1);
         match bits {
             (false, false) => Mp3Emphasis::None,
             (false, true) => Mp3Emphasis::_50_15Ms,
@@ -162,8 +295,9 @@ impl Mp3FrameHeader {
         }
     }
 
-    pub fn frame_length(&self) -> Result<usize, MPeakError> {
-        let bitrate_list = match self.version() {
+    /// Bitrates in kbit/s of the bitrate indices for the version and layer
+    fn bitrates(&self) -> [u16; 16] {
+        match self.version() {
             Mp3Version::V1 => match self.layer() {
                 Mp3Layer::Layer1 => V1_L1,
                 Mp3Layer::Layer2 => V1_L2,
@@ -176,22 +310,105 @@ impl Mp3FrameHeader {
                 Mp3Layer::Reserved => VR_LR,
             },
             Mp3Version::Reserved => VR_LR,
-        };
-        let bitrate = bitrate_list[self.bitrate_index() as usize];
+        }
+    }
 
-        let sample_rate_list = match self.version() {
+    /// Sample rates in Hz of the sampling rate indices for the version
+    fn sample_rates(&self) -> [u16; 4] {
+        match self.version() {
             Mp3Version::V1 => MPEG1,
             Mp3Version::V2 => MPEG2,
             Mp3Version::V25 => MPEG2_5,
             Mp3Version::Reserved => MPEGR,
-        };
-        let sample_rate = sample_rate_list[self.sampling_rate_index() as usize];
+        }
+    }
+
+    /// Bitrate in kbit/s, 0 for free format and invalid headers
+    pub fn bitrate(&self) -> u16 {
+        self.bitrates()[self.bitrate_index() as usize]
+    }
+
+    /// Sample rate in Hz, 0 for invalid headers
+    pub fn sample_rate(&self) -> u16 {
+        self.sample_rates()[self.sampling_rate_index() as usize]
+    }
+
+    pub fn channels(&self) -> usize {
+        if self.channel_mode() == Mp3ChannelMode::SingleChannel {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Joint stereo frame using intensity stereo (Layer III)
+    pub fn is_intensity_stereo(&self) -> bool {
+        self.channel_mode() == Mp3ChannelMode::JointStereo && self.mode_extension() & 0b01 != 0
+    }
+
+    /// Joint stereo frame using mid/side stereo (Layer III)
+    pub fn is_mid_side_stereo(&self) -> bool {
+        self.channel_mode() == Mp3ChannelMode::JointStereo && self.mode_extension() & 0b10 != 0
+    }
+
+    /// First subband sharing the samples of both channels in joint stereo frames (Layer I and II)
+    pub fn joint_stereo_bound(&self) -> usize {
+        if self.channel_mode() == Mp3ChannelMode::JointStereo {
+            4 * (self.mode_extension() as usize + 1)
+        } else {
+            32
+        }
+    }
+
+    /// Number of granules in a Layer III frame
+    pub fn granules(&self) -> usize {
+        if self.version() == Mp3Version::V1 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Number of samples per channel encoded in one frame
+    pub fn samples_per_frame(&self) -> usize {
+        match self.layer() {
+            Mp3Layer::Layer1 => 384,
+            Mp3Layer::Layer2 => 1152,
+            Mp3Layer::Layer3 => 576 * self.granules(),
+            Mp3Layer::Reserved => 0,
+        }
+    }
+
+    /// Length of the Layer III side information in bytes
+    pub fn side_info_length(&self) -> usize {
+        match (self.version() == Mp3Version::V1, self.channels()) {
+            (true, 1) => 17,
+            (true, _) => 32,
+            (false, 1) => 9,
+            (false, _) => 17,
+        }
+    }
+
+    pub fn frame_length(&self) -> Result<usize, MPeakError> {
+        let bitrate = self.bitrate() as u32;
+        let sample_rate = self.sample_rate() as u32;
 
         if sample_rate == 0 || bitrate == 0 {
             return Err(MPeakError::InvalidMp3Header);
         }
 
-        let frame_len: u32 = 144 * (bitrate as u32) * 1000 / (sample_rate as u32);
+        // Layer I frames consist of 4 byte slots
+        if self.layer() == Mp3Layer::Layer1 {
+            let slots = 12 * bitrate * 1000 / sample_rate + self.padding_bit() as u32;
+            return Ok(slots as usize * 4);
+        }
+
+        // Layer III frames of MPEG 2 and 2.5 only carry a single granule
+        let factor = match (self.layer(), self.version()) {
+            (Mp3Layer::Layer3, Mp3Version::V2 | Mp3Version::V25) => 72,
+            _ => 144,
+        };
+        let frame_len: u32 = factor * bitrate * 1000 / sample_rate;
         if self.padding_bit() {
             Ok((frame_len + 1) as usize)
         } else {
@@ -200,6 +417,86 @@ impl Mp3FrameHeader {
     }
 }
 
+/// Serialized form of a header, the decoded fields instead of the raw bits.
+/// Bitrate and sample rate are informative, deserializing uses the indices.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Mp3FrameHeaderFields {
+    /// 0x7FF for a frame
+    sync: u16,
+    version: Mp3Version,
+    layer: Mp3Layer,
+    protection: Mp3Protection,
+    bitrate_index: u8,
+    /// kbit/s
+    #[serde(skip_deserializing)]
+    bitrate: u16,
+    sampling_rate_index: u8,
+    /// Hz
+    #[serde(skip_deserializing)]
+    sample_rate: u16,
+    padding: bool,
+    private: bool,
+    channel_mode: Mp3ChannelMode,
+    mode_extension: u8,
+    copyright: bool,
+    original: bool,
+    emphasis: Mp3Emphasis,
+}
+
+#[cfg(feature = "serde")]
+impl From<Mp3FrameHeader> for Mp3FrameHeaderFields {
+    fn from(header: Mp3FrameHeader) -> Self {
+        Mp3FrameHeaderFields {
+            sync: (header.data >> 21) as u16,
+            version: header.version(),
+            layer: header.layer(),
+            protection: header.protected(),
+            bitrate_index: header.bitrate_index(),
+            bitrate: header.bitrate(),
+            sampling_rate_index: header.sampling_rate_index(),
+            sample_rate: header.sample_rate(),
+            padding: header.padding_bit(),
+            private: header.private_bit(),
+            channel_mode: header.channel_mode(),
+            mode_extension: header.mode_extension(),
+            copyright: header.copyright(),
+            original: header.original(),
+            emphasis: header.emphasis(),
+        }
+    }
+}
+
+/// Restores the exact header bits, including reserved values
+#[cfg(feature = "serde")]
+impl TryFrom<Mp3FrameHeaderFields> for Mp3FrameHeader {
+    type Error = MPeakError;
+
+    fn try_from(fields: Mp3FrameHeaderFields) -> Result<Self, Self::Error> {
+        if fields.sync > 0x7FF
+            || fields.bitrate_index > 15
+            || fields.sampling_rate_index > 3
+            || fields.mode_extension > 3
+        {
+            return Err(MPeakError::InvalidMp3Header);
+        }
+        let mut header = Mp3FrameHeader::new((fields.sync as u32) << 21);
+        header.set_bits(19, 0x3, fields.version as u32);
+        header.set_bits(17, 0x3, fields.layer as u32);
+        header.set_protection(fields.protection);
+        header.set_bits(12, 0xf, fields.bitrate_index as u32);
+        header.set_bits(10, 0x3, fields.sampling_rate_index as u32);
+        header.set_padding_bit(fields.padding);
+        header.set_private_bit(fields.private);
+        header.set_channel_mode(fields.channel_mode);
+        header.set_bits(4, 0x3, fields.mode_extension as u32);
+        header.set_copyright(fields.copyright);
+        header.set_original(fields.original);
+        header.set_bits(0, 0x3, fields.emphasis as u32 - 1);
+        Ok(header)
+    }
+}
+
 impl fmt::Debug for Mp3FrameHeader {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Mp3FrameHeader")
@@ -210,6 +507,7 @@ impl fmt::Debug for Mp3FrameHeader {
 }
 
 #[cfg(test)]
+#[allow(clippy::unusual_byte_groupings, clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -328,24 +626,189 @@ mod tests {
         assert_eq!(header.emphasis(), Mp3Emphasis::CcitJ17);
     }
 
+    #[test]
+    fn test_bitrate_and_sample_rate() {
+        // V1 layer 3 birate 9 samplerate 0
+        let header = Mp3FrameHeader::new(0b_00000000_00011010_10010000_00000000);
+        assert_eq!(header.bitrate(), 128);
+        assert_eq!(header.sample_rate(), 44100);
+        // V25 layer 3 birate 15 samplerate 2
+        let header = Mp3FrameHeader::new(0b_00000000_00000010_11111000_00000000);
+        assert_eq!(header.bitrate(), 0);
+        assert_eq!(header.sample_rate(), 8000);
+    }
+
+    #[test]
+    fn test_side_info_length() {
+        let header = Mp3FrameHeader::new(0b_00000000_00011010_10010000_00000000);
+        assert_eq!(header.side_info_length(), 32);
+        assert_eq!(header.samples_per_frame(), 1152);
+        let header = Mp3FrameHeader::new(0b_00000000_00011010_10010000_11000000);
+        assert_eq!(header.side_info_length(), 17);
+        let header = Mp3FrameHeader::new(0b_00000000_00010010_10010000_11000000);
+        assert_eq!(header.side_info_length(), 9);
+        assert_eq!(header.samples_per_frame(), 576);
+    }
+
+    #[test]
+    fn test_joint_stereo() {
+        // joint stereo with intensity and mid/side stereo
+        let header = Mp3FrameHeader::new(0b_00000000_00011010_10010000_01110000);
+        assert!(header.is_intensity_stereo());
+        assert!(header.is_mid_side_stereo());
+        let header = Mp3FrameHeader::new(0b_00000000_00011010_10010000_01100000);
+        assert!(!header.is_intensity_stereo());
+        assert!(header.is_mid_side_stereo());
+        assert_eq!(header.joint_stereo_bound(), 12);
+        // mode extension is ignored outside of joint stereo
+        let header = Mp3FrameHeader::new(0b_00000000_00011010_10010000_00110000);
+        assert!(!header.is_intensity_stereo());
+        assert!(!header.is_mid_side_stereo());
+        assert_eq!(header.joint_stereo_bound(), 32);
+    }
+
     #[test]
     fn test_frame_length() {
         // V1 Layer 1 birate 4 samplerate 0
         let header = Mp3FrameHeader::new(0b_00000000_00011110_01000000_00000000);
-        assert_eq!(header.frame_length().unwrap(), 417);
+        assert_eq!(header.frame_length().unwrap(), 136);
+        // V1 Layer 1 birate 4 samplerate 0 + padding
+        let header = Mp3FrameHeader::new(0b_00000000_00011110_01000010_00000000);
+        assert_eq!(header.frame_length().unwrap(), 140);
         // V1 Layer 2 birate 13 samplerate 1
         let header = Mp3FrameHeader::new(0b_00000000_00011100_11010100_00000000);
         assert_eq!(header.frame_length().unwrap(), 960);
 
         // V2 Layer 1 birate 2 samplerate 2
         let header = Mp3FrameHeader::new(0b_00000000_00010110_00101000_00000000);
-        assert_eq!(header.frame_length().unwrap(), 432);
+        assert_eq!(header.frame_length().unwrap(), 144);
         // V2 layer 3 birate 8 samplerate 2
         let header = Mp3FrameHeader::new(0b_00000000_00010010_10001000_00000000);
-        assert_eq!(header.frame_length().unwrap(), 576);
+        assert_eq!(header.frame_length().unwrap(), 288);
 
         // V2 layer 3 birate 8 samplerate 2 + padding
         let header = Mp3FrameHeader::new(0b_00000000_00010010_10001010_00000000);
-        assert_eq!(header.frame_length().unwrap(), 577);
+        assert_eq!(header.frame_length().unwrap(), 289);
+
+        // V1 layer 3 birate 9 samplerate 0
+        let header = Mp3FrameHeader::new(0b_00000000_00011010_10010000_00000000);
+        assert_eq!(header.frame_length().unwrap(), 417);
+    }
+
+    #[test]
+    fn test_frame_header_setters() {
+        let mut header = Mp3FrameHeader::new(0xFFFB9000);
+        header.set_version(Mp3Version::V2).unwrap();
+        header.set_layer(Mp3Layer::Layer2).unwrap();
+        header.set_protection(Mp3Protection::ProtectedByCrc);
+        header.set_bitrate_index(3).unwrap();
+        header.set_sampling_rate_index(2).unwrap();
+        header.set_padding_bit(true);
+        header.set_private_bit(true);
+        header.set_channel_mode(Mp3ChannelMode::DualChannel);
+        header.set_mode_extension(2).unwrap();
+        header.set_copyright(true);
+        header.set_original(true);
+        header.set_emphasis(Mp3Emphasis::CcitJ17).unwrap();
+        assert_eq!(header.version(), Mp3Version::V2);
+        assert_eq!(header.layer(), Mp3Layer::Layer2);
+        assert_eq!(header.protected(), Mp3Protection::ProtectedByCrc);
+        assert_eq!(header.bitrate_index(), 3);
+        assert_eq!(header.sampling_rate_index(), 2);
+        assert_eq!(header.padding_bit(), true);
+        assert_eq!(header.private_bit(), true);
+        assert_eq!(header.channel_mode(), Mp3ChannelMode::DualChannel);
+        assert_eq!(header.mode_extension(), 2);
+        assert_eq!(header.copyright(), true);
+        assert_eq!(header.original(), true);
+        assert_eq!(header.emphasis(), Mp3Emphasis::CcitJ17);
+        assert_eq!(header.to_bytes(), [0xFF, 0xF4, 0x3B, 0xAF]);
+    }
+
+    #[test]
+    fn test_frame_header_setters_validation() {
+        let mut header = Mp3FrameHeader::new(0xFFFB9000);
+        assert!(header.set_version(Mp3Version::Reserved).is_err());
+        assert!(header.set_layer(Mp3Layer::Reserved).is_err());
+        assert!(header.set_bitrate_index(15).is_err());
+        assert!(header.set_sampling_rate_index(3).is_err());
+        assert!(header.set_mode_extension(4).is_err());
+        assert!(header.set_emphasis(Mp3Emphasis::Reserved).is_err());
+        assert_eq!(header.to_bytes(), [0xFF, 0xFB, 0x90, 0x00]);
+    }
+
+    #[test]
+    fn test_frame_header_from_parameters() {
+        let header = Mp3FrameHeader::from_parameters(
+            Mp3Version::V1,
+            Mp3Layer::Layer3,
+            128,
+            44100,
+            Mp3ChannelMode::JointStereo,
+        )
+        .unwrap();
+        assert_eq!(header.to_bytes(), [0xFF, 0xFB, 0x90, 0x40]);
+        assert_eq!(header.frame_length(), Ok(417));
+        let header = Mp3FrameHeader::from_parameters(
+            Mp3Version::V2,
+            Mp3Layer::Layer3,
+            64,
+            22050,
+            Mp3ChannelMode::SingleChannel,
+        )
+        .unwrap();
+        assert_eq!(header.bitrate(), 64);
+        assert_eq!(header.sample_rate(), 22050);
+        // 320 kbit/s does not exist in MPEG 2
+        assert!(Mp3FrameHeader::from_parameters(
+            Mp3Version::V2,
+            Mp3Layer::Layer3,
+            320,
+            22050,
+            Mp3ChannelMode::Stereo
+        )
+        .is_err());
+        // neither does 44.1 kHz
+        assert!(Mp3FrameHeader::from_parameters(
+            Mp3Version::V2,
+            Mp3Layer::Layer3,
+            64,
+            44100,
+            Mp3ChannelMode::Stereo
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let header = Mp3FrameHeader::new(0xFFFB9264);
+        let value = serde_json::to_value(header).unwrap();
+        assert_eq!(value["sync"], 0x7FF);
+        assert_eq!(value["version"], "V1");
+        assert_eq!(value["layer"], "Layer3");
+        assert_eq!(value["protection"], "NotProtected");
+        assert_eq!(value["bitrate_index"], 9);
+        assert_eq!(value["bitrate"], 128);
+        assert_eq!(value["sample_rate"], 44100);
+        assert_eq!(value["padding"], true);
+        assert_eq!(value["channel_mode"], "JointStereo");
+        assert_eq!(value["mode_extension"], 2);
+        assert_eq!(value["original"], true);
+        assert_eq!(value["emphasis"], "None");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_round_trip() {
+        // valid, reserved and junk headers
+        for data in [0xFFFB9264, 0xFFE9FFFF, 0x12345678, 0xFFF2_0C03] {
+            let json = serde_json::to_string(&Mp3FrameHeader::new(data)).unwrap();
+            let header: Mp3FrameHeader = serde_json::from_str(&json).unwrap();
+            assert_eq!(header.data, data);
+        }
+        let mut value = serde_json::to_value(Mp3FrameHeader::new(0xFFFB9264)).unwrap();
+        value["mode_extension"] = 4.into();
+        assert!(serde_json::from_value::<Mp3FrameHeader>(value).is_err());
     }
 }
@@ -0,0 +1,306 @@
+// References
+// ISO/IEC 11172-3 2.4.2.7 and Annex B, Table B.7
+// http://www.mp3-tech.org/programmer/docs/mp3_theory.pdf
+
+use super::bit_reader::BitReader;
+use super::mp3_bands::Mp3Bands;
+use super::mp3_header::{Mp3FrameHeader, Mp3Version};
+use super::mp3_huffman_tables::*;
+use super::mp3_side_info::Mp3GranuleInfo;
+use std::sync::OnceLock;
+
+/// Code tables shared by the 32 big_values tables, followed by the count1 tables A and B
+const CODE_TABLES: [(&[u16], &[u8]); 17] = [
+    (&CODES_1, &LENGTHS_1),
+    (&CODES_2, &LENGTHS_2),
+    (&CODES_3, &LENGTHS_3),
+    (&CODES_5, &LENGTHS_5),
+    (&CODES_6, &LENGTHS_6),
+    (&CODES_7, &LENGTHS_7),
+    (&CODES_8, &LENGTHS_8),
+    (&CODES_9, &LENGTHS_9),
+    (&CODES_10, &LENGTHS_10),
+    (&CODES_11, &LENGTHS_11),
+    (&CODES_12, &LENGTHS_12),
+    (&CODES_13, &LENGTHS_13),
+    (&CODES_15, &LENGTHS_15),
+    (&CODES_16, &LENGTHS_16),
+    (&CODES_24, &LENGTHS_24),
+    (&CODES_A, &LENGTHS_A),
+    (&CODES_B, &LENGTHS_B),
+];
+
+const COUNT1_TABLE_A: usize = 15;
+
+/// Big values tables as (code table, values per dimension, linbits).
+/// Tables 0, 4 and 14 carry no codes.
+#[rustfmt::skip]
+const BIG_VALUE_TABLES: [Option<(usize, usize, usize)>; 32] = [
+    None, Some((0, 2, 0)), Some((1, 3, 0)), Some((2, 3, 0)),
+    None, Some((3, 4, 0)), Some((4, 4, 0)), Some((5, 6, 0)),
+    Some((6, 6, 0)), Some((7, 6, 0)), Some((8, 8, 0)), Some((9, 8, 0)),
+    Some((10, 8, 0)), Some((11, 16, 0)), None, Some((12, 16, 0)),
+    Some((13, 16, 1)), Some((13, 16, 2)), Some((13, 16, 3)), Some((13, 16, 4)),
+    Some((13, 16, 6)), Some((13, 16, 8)), Some((13, 16, 10)), Some((13, 16, 13)),
+    Some((14, 16, 4)), Some((14, 16, 5)), Some((14, 16, 6)), Some((14, 16, 7)),
+    Some((14, 16, 8)), Some((14, 16, 9)), Some((14, 16, 11)), Some((14, 16, 13)),
+];
+
+const LEAF: u16 = 0x8000;
+
+/// Binary decoding tree of a Huffman code table.
+/// Each node stores its two children, either a node index or a leaf value marked with `LEAF`.
+/// A child of 0 marks an unused code.
+struct HuffmanTree {
+    nodes: Vec<[u16; 2]>,
+}
+
+impl HuffmanTree {
+    fn new(codes: &[u16], lengths: &[u8]) -> HuffmanTree {
+        let mut nodes = vec![[0, 0]];
+        for (value, (&code, &length)) in codes.iter().zip(lengths.iter()).enumerate() {
+            let mut node = 0;
+            for bit in (0..length).rev() {
+                let branch = (code as u32 >> bit & 1) as usize;
+                if bit == 0 {
+                    nodes[node][branch] = LEAF | value as u16;
+                } else {
+                    if nodes[node][branch] == 0 {
+                        nodes.push([0, 0]);
+                        nodes[node][branch] = (nodes.len() - 1) as u16;
+                    }
+                    node = nodes[node][branch] as usize;
+                }
+            }
+        }
+        HuffmanTree { nodes }
+    }
+
+    /// Decodes one value, invalid codes decode to 0
+    fn decode(&self, reader: &mut BitReader) -> usize {
+        let mut node = 0;
+        loop {
+            let child = self.nodes[node][reader.read_bit() as usize];
+            if child & LEAF != 0 {
+                return (child & !LEAF) as usize;
+            } else if child == 0 {
+                return 0;
+            }
+            node = child as usize;
+        }
+    }
+}
+
+fn trees() -> &'static Vec<HuffmanTree> {
+    static TREES: OnceLock<Vec<HuffmanTree>> = OnceLock::new();
+    TREES.get_or_init(|| {
+        CODE_TABLES
+            .iter()
+            .map(|(codes, lengths)| HuffmanTree::new(codes, lengths))
+            .collect()
+    })
+}
+
+/// Quantized spectral values of one channel in one granule
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3Spectrum {
+    #[cfg_attr(feature = "serde", serde(with = "super::serde_array"))]
+    pub quantized: [i32; 576],
+    /// Number of decoded values, all values from here on are zero (rzero region)
+    pub count: usize,
+}
+
+impl Default for Mp3Spectrum {
+    fn default() -> Self {
+        Mp3Spectrum {
+            quantized: [0; 576],
+            count: 0,
+        }
+    }
+}
+
+impl Mp3Spectrum {
+    /// Decodes the Huffman coded spectral values (part 3) from the current reader position.
+    /// `end` is the bit position where part 3 of this granule and channel ends.
+    pub fn read(
+        reader: &mut BitReader,
+        header: &Mp3FrameHeader,
+        info: &Mp3GranuleInfo,
+        end: usize,
+    ) -> Mp3Spectrum {
+        let trees = trees();
+        let mut spectrum = Mp3Spectrum::default();
+        let big_values_end = usize::min(info.big_values as usize * 2, 576);
+        let (region1_start, region2_start) = region_boundaries(header, info);
+
+        let mut i = 0;
+        while i < big_values_end {
+            let region = if i < region1_start {
+                0
+            } else if i < region2_start {
+                1
+            } else {
+                2
+            };
+            if let Some((table, size, linbits)) =
+                BIG_VALUE_TABLES[info.table_select[region] as usize]
+            {
+                let value = trees[table].decode(reader);
+                spectrum.quantized[i] = read_big_value(reader, value / size, linbits);
+                spectrum.quantized[i + 1] = read_big_value(reader, value % size, linbits);
+            }
+            i += 2;
+        }
+
+        let table = COUNT1_TABLE_A + info.count1table_select as usize;
+        while i + 4 <= 576 && reader.position() < end {
+            let value = trees[table].decode(reader);
+            let mut quad = [0; 4];
+            for (k, v) in quad.iter_mut().enumerate() {
+                if value >> (3 - k) & 1 == 1 {
+                    *v = if reader.read_bit() { -1 } else { 1 };
+                }
+            }
+            // the last quadruple may overrun part 3 and is discarded then
+            if reader.position() > end {
+                break;
+            }
+            spectrum.quantized[i..i + 4].copy_from_slice(&quad);
+            i += 4;
+        }
+        spectrum.count = i;
+
+        spectrum
+    }
+}
+
+/// Largest magnitude codable with a big values table, including the linbits extension
+pub fn table_max_value(table_select: u8) -> u32 {
+    match BIG_VALUE_TABLES[table_select as usize & 31] {
+        Some((_, size, linbits)) => (size as u32 - 1) + (1 << linbits) - 1,
+        None => 0,
+    }
+}
+
+fn read_big_value(reader: &mut BitReader, value: usize, linbits: usize) -> i32 {
+    let mut value = value as i32;
+    if linbits > 0 && value == 15 {
+        value += reader.read_bits(linbits) as i32;
+    }
+    if value != 0 && reader.read_bit() {
+        -value
+    } else {
+        value
+    }
+}
+
+/// First spectral lines of region 1 and region 2 of the big values
+pub fn region_boundaries(header: &Mp3FrameHeader, info: &Mp3GranuleInfo) -> (usize, usize) {
+    let bands = Mp3Bands::new(header);
+    if !info.window_switching {
+        let region0 = info.region0_count as usize + 1;
+        let region1 = region0 + info.region1_count as usize + 1;
+        (bands.long_start(region0), bands.long_start(region1))
+    } else if !info.is_short_block() {
+        (bands.long_start(8), 576)
+    } else if !info.mixed_block {
+        (bands.short_start(3), 576)
+    } else if header.version() == Mp3Version::V1 {
+        (bands.long_start(8), 576)
+    } else {
+        (bands.long_start(6) + 2 * bands.short_widths[3], 576)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_tables_are_prefix_free() {
+        for (codes, lengths) in CODE_TABLES.iter() {
+            for i in 0..codes.len() {
+                for j in 0..codes.len() {
+                    if i == j || lengths[i] > lengths[j] {
+                        continue;
+                    }
+                    let prefix = codes[j] as u32 >> (lengths[j] - lengths[i]);
+                    assert_ne!(prefix, codes[i] as u32, "code {} is a prefix of {}", i, j);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tree_decodes_all_codes() {
+        for (table, (codes, lengths)) in CODE_TABLES.iter().enumerate() {
+            for (value, (&code, &length)) in codes.iter().zip(lengths.iter()).enumerate() {
+                let data = ((code as u32) << (32 - length)).to_be_bytes();
+                let mut reader = BitReader::new(&data);
+                assert_eq!(trees()[table].decode(&mut reader), value);
+                assert_eq!(reader.position(), length as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_big_values() {
+        // V1 layer 3 44100Hz stereo, region 0 ends at line 4, region 1 at line 8
+        let header = Mp3FrameHeader::new(0xFFFB9000);
+        let info = Mp3GranuleInfo {
+            big_values: 3,
+            table_select: [1, 16, 0],
+            ..Default::default()
+        };
+        let data = [
+            0b_1_000_1_0_00, // (0, 0), (1, 1) -+, table 16 (15, 15)
+            0b_0000_11_1_1,  // table 16 (15, 15), linbit 1 -
+            0b_0_0_00_0000,  // linbit 0 +
+        ];
+        let mut reader = BitReader::new(&data);
+        let spectrum = Mp3Spectrum::read(&mut reader, &header, &info, 18);
+        assert_eq!(spectrum.quantized[0..8], [0, 0, -1, 1, -16, 15, 0, 0]);
+        assert_eq!(spectrum.count, 6);
+        assert_eq!(reader.position(), 18);
+    }
+
+    #[test]
+    fn test_table_max_value() {
+        assert_eq!(table_max_value(0), 0);
+        assert_eq!(table_max_value(1), 1);
+        assert_eq!(table_max_value(13), 15);
+        assert_eq!(table_max_value(16), 16);
+        assert_eq!(table_max_value(31), 15 + 8191);
+    }
+
+    #[test]
+    fn test_read_count1() {
+        // V1 layer 3 44100Hz stereo
+        let header = Mp3FrameHeader::new(0xFFFB9000);
+        let info = Mp3GranuleInfo {
+            count1table_select: true,
+            ..Default::default()
+        };
+        // table B: code 15 - vwxy
+        let data = [0b_1111_1010, 0b_1_0_000000];
+        let mut reader = BitReader::new(&data);
+        let spectrum = Mp3Spectrum::read(&mut reader, &header, &info, 10);
+        assert_eq!(spectrum.quantized[0..8], [0, 0, 0, 0, 0, -1, 0, 1]);
+        assert_eq!(spectrum.count, 8);
+        assert_eq!(reader.position(), 10);
+    }
+
+    #[test]
+    fn test_read_count1_overrun() {
+        let header = Mp3FrameHeader::new(0xFFFB9000);
+        let info = Mp3GranuleInfo::default();
+        // table A: code 1 for 0000, code 0101 for 0001 followed by a sign bit
+        let data = [0b_1_0101_1_00];
+        let mut reader = BitReader::new(&data);
+        let spectrum = Mp3Spectrum::read(&mut reader, &header, &info, 5);
+        assert_eq!(spectrum.count, 4);
+        assert_eq!(spectrum.quantized[0..8], [0; 8]);
+    }
+}
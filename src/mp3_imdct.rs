@@ -0,0 +1,256 @@
+// References
+// ISO/IEC 11172-3 2.4.3.4.10 (alias reduction, IMDCT, windowing and overlap-add)
+// http://www.mp3-tech.org/programmer/docs/mp3_theory.pdf
+
+use super::mp3_side_info::Mp3GranuleInfo;
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+/// Coefficients of the alias reduction butterflies
+const ALIAS_COEFFICIENTS: [f64; 8] = [
+    -0.6, -0.535, -0.33, -0.185, -0.095, -0.041, -0.0142, -0.0037,
+];
+
+/// Precomputed IMDCT cosines and windows
+struct ImdctTables {
+    /// cos(pi / 72 * (2i + 1 + 18) * (2k + 1)) indexed by i * 18 + k
+    long: Vec<f32>,
+    /// cos(pi / 24 * (2i + 1 + 6) * (2k + 1)) indexed by i * 6 + k
+    short: Vec<f32>,
+    /// Windows of the long block types 0 (normal), 1 (start) and 3 (stop)
+    windows: [[f32; 36]; 4],
+    short_window: [f32; 12],
+    /// Butterfly coefficients (cs, ca)
+    alias: [(f32, f32); 8],
+}
+
+fn tables() -> &'static ImdctTables {
+    static TABLES: OnceLock<ImdctTables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut long = Vec::with_capacity(36 * 18);
+        for i in 0..36 {
+            for k in 0..18 {
+                long.push((PI / 72.0 * (2 * i + 1 + 18) as f64 * (2 * k + 1) as f64).cos() as f32);
+            }
+        }
+        let mut short = Vec::with_capacity(12 * 6);
+        for i in 0..12 {
+            for k in 0..6 {
+                short.push((PI / 24.0 * (2 * i + 1 + 6) as f64 * (2 * k + 1) as f64).cos() as f32);
+            }
+        }
+
+        let sine_36 = |i: usize| (PI / 36.0 * (i as f64 + 0.5)).sin() as f32;
+        let sine_12 = |i: usize| (PI / 12.0 * (i as f64 + 0.5)).sin() as f32;
+        let mut windows = [[0.0; 36]; 4];
+        windows[0] = std::array::from_fn(sine_36);
+        windows[1] = std::array::from_fn(|i| match i {
+            0..=17 => sine_36(i),
+            18..=23 => 1.0,
+            24..=29 => sine_12(i - 18),
+            _ => 0.0,
+        });
+        windows[3] = std::array::from_fn(|i| match i {
+            0..=5 => 0.0,
+            6..=11 => sine_12(i - 6),
+            12..=17 => 1.0,
+            _ => sine_36(i),
+        });
+        let short_window = std::array::from_fn(sine_12);
+
+        let mut alias = [(0.0, 0.0); 8];
+        for (a, c) in alias.iter_mut().zip(ALIAS_COEFFICIENTS.iter()) {
+            let norm = (1.0 + c * c).sqrt();
+            *a = ((1.0 / norm) as f32, (c / norm) as f32);
+        }
+
+        ImdctTables {
+            long,
+            short,
+            windows,
+            short_window,
+            alias,
+        }
+    })
+}
+
+/// Hybrid filterbank state of one channel: the second halves of the previous IMDCT outputs
+pub struct Mp3Imdct {
+    overlap: [[f32; 18]; 32],
+}
+
+impl Mp3Imdct {
+    pub fn new() -> Mp3Imdct {
+        Mp3Imdct {
+            overlap: [[0.0; 18]; 32],
+        }
+    }
+
+    /// Converts the reordered spectrum of one granule to 18 time slots of 32 subband samples.
+    /// Applies alias reduction, IMDCT with windowing, overlap-add and frequency inversion.
+    pub fn process(&mut self, info: &Mp3GranuleInfo, samples: &mut [f32; 576]) -> [[f32; 32]; 18] {
+        let tables = tables();
+        antialias(info, samples);
+
+        let mut output = [[0.0; 32]; 18];
+        for sb in 0..32 {
+            let input = &samples[sb * 18..sb * 18 + 18];
+            let long_block = !info.is_short_block() || (info.mixed_block && sb < 2);
+            let mut block = [0.0; 36];
+            if long_block {
+                let window = if info.window_switching && !info.is_short_block() {
+                    &tables.windows[info.block_type as usize]
+                } else {
+                    &tables.windows[0]
+                };
+                for (i, x) in block.iter_mut().enumerate() {
+                    let cosines = &tables.long[i * 18..i * 18 + 18];
+                    let sum: f32 = input.iter().zip(cosines.iter()).map(|(a, b)| a * b).sum();
+                    *x = sum * window[i];
+                }
+            } else {
+                // three overlapping short transforms of the interleaved windows
+                for w in 0..3 {
+                    for i in 0..12 {
+                        let cosines = &tables.short[i * 6..i * 6 + 6];
+                        let sum: f32 = (0..6).map(|k| input[3 * k + w] * cosines[k]).sum();
+                        block[6 + 6 * w + i] += sum * tables.short_window[i];
+                    }
+                }
+            }
+
+            for i in 0..18 {
+                let mut value = block[i] + self.overlap[sb][i];
+                // frequency inversion of odd time samples in odd subbands
+                if sb % 2 == 1 && i % 2 == 1 {
+                    value = -value;
+                }
+                output[i][sb] = value;
+            }
+            self.overlap[sb].copy_from_slice(&block[18..]);
+        }
+        output
+    }
+}
+
+impl Default for Mp3Imdct {
+    fn default() -> Self {
+        Mp3Imdct::new()
+    }
+}
+
+/// Alias reduction butterflies between neighbouring subbands of long blocks
+fn antialias(info: &Mp3GranuleInfo, samples: &mut [f32; 576]) {
+    let subbands = match (info.is_short_block(), info.mixed_block) {
+        (false, _) => 32,
+        (true, false) => return,
+        (true, true) => 2,
+    };
+    let alias = &tables().alias;
+    for sb in 1..subbands {
+        for (i, &(cs, ca)) in alias.iter().enumerate() {
+            let lower = samples[18 * sb - 1 - i];
+            let upper = samples[18 * sb + i];
+            samples[18 * sb - 1 - i] = lower * cs - upper * ca;
+            samples[18 * sb + i] = upper * cs + lower * ca;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silence() {
+        let mut imdct = Mp3Imdct::new();
+        let mut samples = [0.0; 576];
+        let output = imdct.process(&Mp3GranuleInfo::default(), &mut samples);
+        assert_eq!(output, [[0.0; 32]; 18]);
+    }
+
+    #[test]
+    fn test_antialias() {
+        let mut samples = [0.0; 576];
+        samples[17] = 1.0;
+        antialias(&Mp3GranuleInfo::default(), &mut samples);
+        let (cs, ca) = tables().alias[0];
+        assert_eq!(samples[17], cs);
+        assert_eq!(samples[18], ca);
+
+        // short blocks are not alias reduced
+        let info = Mp3GranuleInfo {
+            window_switching: true,
+            block_type: 2,
+            ..Default::default()
+        };
+        let mut samples = [0.0; 576];
+        samples[17] = 1.0;
+        antialias(&info, &mut samples);
+        assert_eq!(samples[17], 1.0);
+        assert_eq!(samples[18], 0.0);
+    }
+
+    #[test]
+    fn test_long_block_overlap() {
+        // a constant spectral line produces a windowed cosine split over two granules,
+        // overlapping with its successor
+        let mut imdct = Mp3Imdct::new();
+        let info = Mp3GranuleInfo::default();
+        let mut outputs = Vec::new();
+        for _ in 0..3 {
+            let mut samples = [0.0; 576];
+            samples[0] = 1.0;
+            outputs.push(imdct.process(&info, &mut samples));
+        }
+        let tables = tables();
+        let first: Vec<f32> = (0..18)
+            .map(|i| tables.long[i * 18] * tables.windows[0][i])
+            .collect();
+        let second: Vec<f32> = (0..18)
+            .map(|i| tables.long[(i + 18) * 18] * tables.windows[0][i + 18] + first[i])
+            .collect();
+        for (output, expected) in outputs.iter().zip([&first, &second, &second]) {
+            for (slot, value) in output.iter().zip(expected.iter()) {
+                assert!((slot[0] - value).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_short_block_windows() {
+        let mut imdct = Mp3Imdct::new();
+        let info = Mp3GranuleInfo {
+            window_switching: true,
+            block_type: 2,
+            ..Default::default()
+        };
+        // first spectral line of the last window
+        let mut samples = [0.0; 576];
+        samples[2] = 1.0;
+        let output = imdct.process(&info, &mut samples);
+        for (i, slot) in output.iter().enumerate() {
+            // the last window starts at sample 18 and lies in the overlap
+            assert_eq!(slot[0], 0.0, "sample {}", i);
+        }
+        assert_eq!(imdct.overlap[0][12..], [0.0; 6]);
+        assert!(imdct.overlap[0][..12].iter().any(|&x| x != 0.0));
+    }
+
+    #[test]
+    fn test_frequency_inversion() {
+        let mut imdct = Mp3Imdct::new();
+        let info = Mp3GranuleInfo::default();
+        // line 8 of a subband is not touched by alias reduction
+        let mut lower = [0.0; 576];
+        lower[8] = 1.0;
+        let mut upper = [0.0; 576];
+        upper[18 + 8] = 1.0;
+        let lower = Mp3Imdct::new().process(&info, &mut lower);
+        let upper = imdct.process(&info, &mut upper);
+        for i in 0..18 {
+            let sign = if i % 2 == 1 { -1.0 } else { 1.0 };
+            assert_eq!(upper[i][1], sign * lower[i][0]);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// References
+// ISO/IEC 11172-3 2.4.1.5, 2.4.2.5 and 2.4.3.2 (Layer I audio data)
+// ISO/IEC 11172-3 Annex B, Table B.1 (scalefactors)
+
+use super::bit_reader::BitReader;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_synthesis::{synthesize_channels, Mp3Synthesis};
+
+/// Scalefactor for a 6 bit scalefactor index (Layer I and II)
+pub fn scalefactor(index: u32) -> f32 {
+    (1.0 - index as f32 / 3.0).exp2()
+}
+
+/// Requantizes a sample coded with one of `levels` equally spaced quantization levels
+/// to the range -1.0 to 1.0 (Layer I and II).
+/// This is the C * (s + D) formula of the standard with the most significant bit inverted.
+pub fn dequantize(raw: u32, levels: u32) -> f32 {
+    (2.0 * raw as f32 - (levels - 1) as f32) / levels as f32
+}
+
+/// Decodes a Layer I frame to 384 interleaved samples per channel
+pub fn decode(frame: &Mp3Frame, synthesis: &mut [Mp3Synthesis; 2]) -> Vec<f32> {
+    let header = &frame.header;
+    let channels = header.channels();
+    let bound = header.joint_stereo_bound();
+    let data = &frame.body.data[usize::min(frame.payload_offset(), frame.body.data.len())..];
+    let mut reader = BitReader::new(data);
+
+    // bit allocation per subband and channel,
+    // subbands above the joint stereo bound share one allocation
+    let mut allocation = [[0; 2]; 32];
+    for (sb, allocation) in allocation.iter_mut().enumerate() {
+        allocation[0] = reader.read_bits(4);
+        if channels == 2 {
+            allocation[1] = if sb < bound {
+                reader.read_bits(4)
+            } else {
+                allocation[0]
+            };
+        }
+    }
+
+    let mut scalefactors = [[0.0; 2]; 32];
+    for (allocation, scalefactors) in allocation.iter().zip(scalefactors.iter_mut()) {
+        for ch in 0..channels {
+            if allocation[ch] != 0 {
+                scalefactors[ch] = scalefactor(reader.read_bits(6));
+            }
+        }
+    }
+
+    let mut pcm = vec![0.0; 384 * channels];
+    for slot in 0..12 {
+        let mut subbands = [[0.0; 32]; 2];
+        for sb in 0..32 {
+            let mut sample = 0.0;
+            for ch in 0..channels {
+                // allocation 15 is forbidden
+                let bits = allocation[sb][ch] as usize + 1;
+                if ch == 0 || sb < bound {
+                    sample = match bits {
+                        2..=15 => dequantize(reader.read_bits(bits), (1 << bits) - 1),
+                        _ => 0.0,
+                    };
+                }
+                subbands[ch][sb] = sample * scalefactors[sb][ch];
+            }
+        }
+        let start = slot * 32 * channels;
+        synthesize_channels(
+            synthesis,
+            &subbands,
+            channels,
+            &mut pcm[start..start + 32 * channels],
+        );
+    }
+    pcm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mp3_header::Mp3FrameHeader;
+    use super::*;
+
+    #[test]
+    fn test_scalefactor() {
+        assert_eq!(scalefactor(0), 2.0);
+        assert_eq!(scalefactor(3), 1.0);
+        assert!((scalefactor(1) - 1.587_401).abs() < 1e-6);
+        assert!((scalefactor(62) - 0.000_001_201_554_3).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_dequantize() {
+        // C * (s + D) with C = 4/3 and D = 0.5 for 3 levels
+        assert!((dequantize(0, 3) + 2.0 / 3.0).abs() < 1e-6);
+        assert_eq!(dequantize(1, 3), 0.0);
+        // C = 8/7 and D = 0.25 for 7 levels
+        assert!((dequantize(6, 7) - 8.0 / 7.0 * 0.75).abs() < 1e-6);
+        // C = 8/5 and D = 0.5 for 5 levels
+        assert!((dequantize(3, 5) - 8.0 / 5.0 * 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_decode_silence() {
+        // V1 layer 1 128kbit/s 44100Hz stereo without any bit allocation
+        let header = Mp3FrameHeader::new(0xFFFF4000);
+        let mut data = vec![0; header.frame_length().unwrap()];
+        data[0..4].copy_from_slice(&[0xFF, 0xFF, 0x40, 0x00]);
+        let frame = Mp3Frame::new(header, data, 0);
+        let mut synthesis = Default::default();
+        let pcm = decode(&frame, &mut synthesis);
+        assert_eq!(pcm, vec![0.0; 768]);
+    }
+
+    #[test]
+    fn test_decode_subband() {
+        // V1 layer 1 128kbit/s 44100Hz mono with a constant sample in subband 4
+        let header = Mp3FrameHeader::new(0xFFFF40C0);
+        let mut bits = vec![false; 32];
+        // allocation 3 (4 bit samples) for subband 4
+        bits.extend([false; 16]);
+        bits.extend([false, false, true, true]);
+        bits.extend([false; 27 * 4]);
+        // scalefactor index 3
+        bits.extend([false, false, false, false, true, true]);
+        // 12 samples of value 14 (largest level)
+        for _ in 0..12 {
+            bits.extend([true, true, true, false]);
+        }
+        bits.resize(header.frame_length().unwrap() * 8, false);
+        let data: Vec<u8> = bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+            .collect();
+        let frame = Mp3Frame::new(header, data, 0);
+        let mut synthesis = Default::default();
+        let pcm = decode(&frame, &mut synthesis);
+        assert_eq!(pcm.len(), 384);
+        let peak = pcm.iter().fold(0.0f32, |max, x| max.max(x.abs()));
+        // a constant subband sample synthesizes a cosine at the subband center frequency
+        // with an amplitude of up to sqrt(2)
+        assert!(peak > 0.5 && peak < 1.5, "peak {}", peak);
+    }
+}
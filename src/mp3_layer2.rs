@@ -0,0 +1,263 @@
+// References
+// ISO/IEC 11172-3 2.4.1.6, 2.4.2.6 and 2.4.3.3 (Layer II audio data)
+// ISO/IEC 11172-3 Annex B, Tables B.2a to B.2d and B.4 (allocation and quantization)
+// ISO/IEC 13818-3 Annex B, Table B.1 (allocation for lower sampling frequencies)
+
+use super::bit_reader::BitReader;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::{Mp3FrameHeader, Mp3Version};
+use super::mp3_layer1::{dequantize, scalefactor};
+use super::mp3_synthesis::{synthesize_channels, Mp3Synthesis};
+
+/// Quantization levels selectable by the bit allocation of a subband.
+/// Allocation 0 means the subband carries no samples.
+/// The length of a row defines the number of allocation bits.
+const LEVELS_2A: [u16; 4] = [0, 3, 5, 65535];
+const LEVELS_2B: [u16; 4] = [0, 3, 5, 9];
+const LEVELS_3A: [u16; 8] = [0, 3, 5, 9, 15, 31, 63, 127];
+const LEVELS_3B: [u16; 8] = [0, 3, 5, 7, 9, 15, 31, 65535];
+#[rustfmt::skip]
+const LEVELS_4A: [u16; 16] = [
+    0, 3, 5, 7, 9, 15, 31, 63, 127, 255, 511, 1023, 2047, 4095, 8191, 16383,
+];
+#[rustfmt::skip]
+const LEVELS_4B: [u16; 16] = [
+    0, 3, 5, 9, 15, 31, 63, 127, 255, 511, 1023, 2047, 4095, 8191, 16383, 32767,
+];
+#[rustfmt::skip]
+const LEVELS_4C: [u16; 16] = [
+    0, 3, 5, 7, 9, 15, 31, 63, 127, 255, 511, 1023, 2047, 4095, 8191, 65535,
+];
+#[rustfmt::skip]
+const LEVELS_4D: [u16; 16] = [
+    0, 3, 7, 15, 31, 63, 127, 255, 511, 1023, 2047, 4095, 8191, 16383, 32767, 65535,
+];
+
+/// Allocation tables as runs of subbands sharing the same quantization levels
+type AllocationTable = &'static [(usize, &'static [u16])];
+
+/// Table B.2a, 27 subbands
+const TABLE_A: AllocationTable = &[
+    (3, &LEVELS_4D),
+    (8, &LEVELS_4C),
+    (12, &LEVELS_3B),
+    (4, &LEVELS_2A),
+];
+/// Table B.2b, 30 subbands
+const TABLE_B: AllocationTable = &[
+    (3, &LEVELS_4D),
+    (8, &LEVELS_4C),
+    (12, &LEVELS_3B),
+    (7, &LEVELS_2A),
+];
+/// Table B.2c, 8 subbands
+const TABLE_C: AllocationTable = &[(2, &LEVELS_4B), (6, &LEVELS_3A)];
+/// Table B.2d, 12 subbands
+const TABLE_D: AllocationTable = &[(2, &LEVELS_4B), (10, &LEVELS_3A)];
+/// ISO/IEC 13818-3 Table B.1, 30 subbands
+const TABLE_LSF: AllocationTable = &[(4, &LEVELS_4A), (7, &LEVELS_3A), (19, &LEVELS_2B)];
+
+/// Quantization levels of each subband, the number of entries is the subband limit
+pub fn allocation_levels(header: &Mp3FrameHeader) -> Vec<&'static [u16]> {
+    let table = if header.version() != Mp3Version::V1 {
+        TABLE_LSF
+    } else {
+        let bitrate = header.bitrate() as usize / header.channels();
+        match (bitrate, header.sample_rate()) {
+            (0..=48, 32000) => TABLE_D,
+            (0..=48, _) => TABLE_C,
+            (49..=80, _) | (_, 48000) => TABLE_A,
+            _ => TABLE_B,
+        }
+    };
+    table
+        .iter()
+        .flat_map(|&(count, levels)| std::iter::repeat_n(levels, count))
+        .collect()
+}
+
+/// Reads three consecutive samples of a subband, either as a grouped codeword or individually
+fn read_samples(reader: &mut BitReader, levels: u32) -> [f32; 3] {
+    let mut raw = [0; 3];
+    match levels {
+        3 | 5 | 9 => {
+            let bits = match levels {
+                3 => 5,
+                5 => 7,
+                _ => 10,
+            };
+            let mut codeword = reader.read_bits(bits);
+            for value in raw.iter_mut() {
+                *value = codeword % levels;
+                codeword /= levels;
+            }
+        }
+        _ => {
+            let bits = (levels + 1).trailing_zeros() as usize;
+            for value in raw.iter_mut() {
+                *value = reader.read_bits(bits);
+            }
+        }
+    }
+    raw.map(|value| dequantize(value, levels))
+}
+
+/// Decodes a Layer II frame to 1152 interleaved samples per channel
+pub fn decode(frame: &Mp3Frame, synthesis: &mut [Mp3Synthesis; 2]) -> Vec<f32> {
+    let header = &frame.header;
+    let channels = header.channels();
+    let levels = allocation_levels(header);
+    let sblimit = levels.len();
+    let bound = usize::min(header.joint_stereo_bound(), sblimit);
+    let data = &frame.body.data[usize::min(frame.payload_offset(), frame.body.data.len())..];
+    let mut reader = BitReader::new(data);
+
+    // bit allocation, subbands above the joint stereo bound share one allocation
+    let mut allocation = [[0; 32]; 2];
+    for (sb, levels) in levels.iter().enumerate() {
+        let bits = levels.len().trailing_zeros() as usize;
+        allocation[0][sb] = reader.read_bits(bits) as usize;
+        if channels == 2 {
+            allocation[1][sb] = if sb < bound {
+                reader.read_bits(bits) as usize
+            } else {
+                allocation[0][sb]
+            };
+        }
+    }
+
+    let mut scfsi = [[0; 32]; 2];
+    for sb in 0..sblimit {
+        for ch in 0..channels {
+            if allocation[ch][sb] != 0 {
+                scfsi[ch][sb] = reader.read_bits(2);
+            }
+        }
+    }
+
+    // scalefactors of the three parts of the frame, each covering 12 time slots
+    let mut scalefactors = [[[0.0; 3]; 32]; 2];
+    for sb in 0..sblimit {
+        for ch in 0..channels {
+            if allocation[ch][sb] == 0 {
+                continue;
+            }
+            let parts = &mut scalefactors[ch][sb];
+            match scfsi[ch][sb] {
+                0 => {
+                    for part in parts.iter_mut() {
+                        *part = scalefactor(reader.read_bits(6));
+                    }
+                }
+                1 => {
+                    parts[0] = scalefactor(reader.read_bits(6));
+                    parts[1] = parts[0];
+                    parts[2] = scalefactor(reader.read_bits(6));
+                }
+                2 => {
+                    *parts = [scalefactor(reader.read_bits(6)); 3];
+                }
+                _ => {
+                    parts[0] = scalefactor(reader.read_bits(6));
+                    parts[1] = scalefactor(reader.read_bits(6));
+                    parts[2] = parts[1];
+                }
+            }
+        }
+    }
+
+    let mut pcm = vec![0.0; 1152 * channels];
+    for granule in 0..12 {
+        let part = granule / 4;
+        let mut subbands = [[[0.0; 32]; 2]; 3];
+        for sb in 0..sblimit {
+            let mut samples = [0.0; 3];
+            for ch in 0..channels {
+                if ch == 0 || sb < bound {
+                    samples = match levels[sb][allocation[ch][sb]] {
+                        0 => [0.0; 3],
+                        l => read_samples(&mut reader, l as u32),
+                    };
+                }
+                for (slot, &sample) in samples.iter().enumerate() {
+                    subbands[slot][ch][sb] = sample * scalefactors[ch][sb][part];
+                }
+            }
+        }
+        for (slot, subbands) in subbands.iter().enumerate() {
+            let start = (granule * 3 + slot) * 32 * channels;
+            synthesize_channels(
+                synthesis,
+                subbands,
+                channels,
+                &mut pcm[start..start + 32 * channels],
+            );
+        }
+    }
+    pcm
+}
+
+#[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocation_tables() {
+        for (table, sblimit) in [
+            (TABLE_A, 27),
+            (TABLE_B, 30),
+            (TABLE_C, 8),
+            (TABLE_D, 12),
+            (TABLE_LSF, 30),
+        ] {
+            assert_eq!(table.iter().map(|(count, _)| count).sum::<usize>(), sblimit);
+        }
+        // V1 layer 2 192kbit/s 44100Hz stereo: 96kbit/s per channel
+        let header = Mp3FrameHeader::new(0xFFFDA000);
+        assert_eq!(allocation_levels(&header).len(), 30);
+        // V1 layer 2 192kbit/s 48000Hz stereo
+        let header = Mp3FrameHeader::new(0xFFFDA400);
+        assert_eq!(allocation_levels(&header).len(), 27);
+        // V1 layer 2 64kbit/s 32000Hz mono
+        let header = Mp3FrameHeader::new(0xFFFD48C0);
+        assert_eq!(allocation_levels(&header).len(), 27);
+        // V1 layer 2 64kbit/s 32000Hz stereo
+        let header = Mp3FrameHeader::new(0xFFFD4800);
+        assert_eq!(allocation_levels(&header).len(), 12);
+        // V2 layer 2
+        let header = Mp3FrameHeader::new(0xFFF5A000);
+        assert_eq!(allocation_levels(&header).len(), 30);
+    }
+
+    #[test]
+    fn test_read_grouped_samples() {
+        // 9 levels: 2 + 9 * 4 + 81 * 8 = 686
+        let data = [0b_1010_1011, 0b_10_000000];
+        let mut reader = BitReader::new(&data);
+        let samples = read_samples(&mut reader, 9);
+        assert_eq!(reader.position(), 10);
+        assert_eq!(samples, [-4.0 / 9.0, 0.0, 8.0 / 9.0]);
+    }
+
+    #[test]
+    fn test_read_samples() {
+        let data = [0b_000_011_11, 0b_0_0000000];
+        let mut reader = BitReader::new(&data);
+        let samples = read_samples(&mut reader, 7);
+        assert_eq!(reader.position(), 9);
+        assert_eq!(samples, [-6.0 / 7.0, 0.0, 6.0 / 7.0]);
+    }
+
+    #[test]
+    fn test_decode_silence() {
+        // V1 layer 2 192kbit/s 44100Hz stereo without any bit allocation
+        let header = Mp3FrameHeader::new(0xFFFDA000);
+        let mut data = vec![0; header.frame_length().unwrap()];
+        data[0..4].copy_from_slice(&[0xFF, 0xFD, 0xA0, 0x00]);
+        let frame = Mp3Frame::new(header, data, 0);
+        let mut synthesis = Default::default();
+        let pcm = decode(&frame, &mut synthesis);
+        assert_eq!(pcm, vec![0.0; 2304]);
+    }
+}
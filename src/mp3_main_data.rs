@@ -0,0 +1,63 @@
+use super::bit_reader::BitReader;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_huffman::Mp3Spectrum;
+use super::mp3_scalefactors::Mp3Scalefactors;
+use super::mp3_side_info::Mp3SideInfo;
+
+/// Scalefactors and quantized spectrum of one channel in one granule
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3GranuleData {
+    pub scalefactors: Mp3Scalefactors,
+    pub spectrum: Mp3Spectrum,
+}
+
+/// Decoded Layer III main data of a frame
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3MainData {
+    /// Position of the frame in the stream
+    pub position: u32,
+    pub side_info: Mp3SideInfo,
+    /// Granule data indexed by granule and channel
+    pub granules: Vec<Vec<Mp3GranuleData>>,
+}
+
+impl Mp3MainData {
+    /// Decodes scalefactors and spectral values of all granules and channels.
+    /// `main_data` starts at the main_data_begin position of the frame.
+    pub fn decode(frame: &Mp3Frame, side_info: Mp3SideInfo, main_data: &[u8]) -> Mp3MainData {
+        let mut reader = BitReader::new(main_data);
+        let mut granules: Vec<Vec<Mp3GranuleData>> = Vec::new();
+        for gr in 0..side_info.granules.len() {
+            let mut channels = Vec::new();
+            for ch in 0..side_info.granules[gr].len() {
+                let info = &side_info.granules[gr][ch];
+                let end = reader.position() + info.part2_3_length as usize;
+                let first_granule = granules
+                    .first()
+                    .map_or(Default::default(), |g| g[ch].scalefactors);
+                let scalefactors = Mp3Scalefactors::read(
+                    &mut reader,
+                    &frame.header,
+                    &side_info,
+                    gr,
+                    ch,
+                    &first_granule,
+                );
+                let spectrum = Mp3Spectrum::read(&mut reader, &frame.header, info, end);
+                reader.set_position(end);
+                channels.push(Mp3GranuleData {
+                    scalefactors,
+                    spectrum,
+                });
+            }
+            granules.push(channels);
+        }
+        Mp3MainData {
+            position: frame.position,
+            side_info,
+            granules,
+        }
+    }
+}
@@ -0,0 +1,355 @@
+// References
+// ISO/IEC 11172-3 2.4.3.4.2 (bit reservoir)
+// ISO/IEC 13818-3 2.4.1.7 (main_data_begin of MPEG 2)
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::mp3_edit::{compatible, mute_side_info};
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::{Mp3FrameHeader, Mp3Layer, Mp3Version};
+use super::mp3_reservoir::Mp3Reservoir;
+use super::mp3_side_info::Mp3SideInfo;
+use super::mp3_stream::Mp3Stream;
+use super::mp3_xing::Mp3XingTag;
+use super::MPeakError;
+
+/// Selection of the stream for each block of frames
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mp3MixSchedule {
+    /// Stream indices of consecutive blocks, repeated.
+    /// Indices are taken modulo the number of streams.
+    Pattern(Vec<usize>),
+    /// Random stream for each block, reproducible by the seed (ChaCha8)
+    Random(u64),
+}
+
+/// Result of a mix
+#[derive(Clone)]
+pub struct Mp3Mix {
+    pub stream: Mp3Stream,
+    /// Index of the stream each frame was taken from
+    pub sources: Vec<usize>,
+    /// Number of frames muted because their main data was not available or did not fit
+    pub muted_frames: usize,
+}
+
+/// Largest main_data_begin back reference (9 bits for MPEG 1, 8 bits for MPEG 2 and 2.5)
+fn max_main_data_begin(header: &Mp3FrameHeader) -> usize {
+    match header.version() {
+        Mp3Version::V1 => 511,
+        _ => 255,
+    }
+}
+
+/// Side information and main data (without ancillary data) of a Layer III frame
+pub type Mp3FrameMainData = (Mp3SideInfo, Vec<u8>);
+
+/// Main data of the next Layer III frame from the reservoir, None if it is not available
+pub fn next_main_data(reservoir: &mut Mp3Reservoir, frame: &Mp3Frame) -> Option<Mp3FrameMainData> {
+    let (side_info, mut main_data) = reservoir.next_frame(frame)?;
+    let bits: usize = side_info
+        .granules
+        .iter()
+        .flatten()
+        .map(|info| info.part2_3_length as usize)
+        .sum();
+    main_data.truncate(bits.div_ceil(8));
+    Some((side_info, main_data))
+}
+
+/// Main data of each Layer III frame, None if it is not available
+fn frame_main_data(frames: &[Mp3Frame]) -> Vec<Option<Mp3FrameMainData>> {
+    let mut reservoir = Mp3Reservoir::new();
+    frames
+        .iter()
+        .map(|frame| next_main_data(&mut reservoir, frame))
+        .collect()
+}
+
+/// Builds Layer III frames from the headers of the given frames and the side information
+/// and main data, storing the main data in a new bit reservoir.
+/// The bitrate of a frame is increased if its main data does not fit otherwise,
+/// frames without main data or whose main data does not fit at any bitrate are muted.
+/// Returns the frames and the number of muted frames.
+pub fn repack(frames: &[(&Mp3Frame, Option<Mp3FrameMainData>)]) -> (Vec<Mp3Frame>, usize) {
+    // main data areas of all frames, following each other
+    let mut data: Vec<u8> = Vec::new();
+    let mut end = 0;
+    let mut muted = 0;
+    let mut layouts = Vec::new();
+    for (frame, main) in frames {
+        let (mut side_info, main_data) = match main {
+            Some((side_info, main_data)) => (side_info.clone(), main_data.as_slice()),
+            None => match frame.side_info() {
+                Ok(side_info) => (side_info, &[][..]),
+                Err(_) => {
+                    layouts.push(Err((*frame).clone()));
+                    continue;
+                }
+            },
+        };
+        let offset = frame.payload_offset() + frame.header.side_info_length();
+        let start = data.len();
+        let begin = usize::max(
+            end,
+            start.saturating_sub(max_main_data_begin(&frame.header)),
+        );
+        let capacity = |header: Mp3FrameHeader| header.frame_length().ok()?.checked_sub(offset);
+        let fit = (frame.header.bitrate_index()..15).find_map(|index| {
+            let mut header = frame.header;
+            header.set_bitrate_index(index).ok()?;
+            let capacity = capacity(header)?;
+            (begin + main_data.len() <= start + capacity).then_some((header, capacity))
+        });
+
+        let (header, capacity) = match fit {
+            Some((header, capacity)) if main.is_some() => {
+                data.resize(start + capacity, 0);
+                data[begin..begin + main_data.len()].copy_from_slice(main_data);
+                end = begin + main_data.len();
+                side_info.main_data_begin = (start - begin) as u16;
+                (header, capacity)
+            }
+            _ => {
+                muted += 1;
+                mute_side_info(&mut side_info);
+                let capacity = capacity(frame.header).unwrap_or(0);
+                data.resize(start + capacity, 0);
+                (frame.header, capacity)
+            }
+        };
+        layouts.push(Ok((header, offset, side_info, start, capacity)));
+    }
+
+    let frames = layouts
+        .into_iter()
+        .enumerate()
+        .map(|(position, layout)| match layout {
+            Ok((header, offset, side_info, start, capacity)) => {
+                let mut body = header.to_bytes().to_vec();
+                body.resize(offset, 0);
+                body.extend(&data[start..start + capacity]);
+                let mut frame = Mp3Frame::new(header, body, position as u32);
+                frame.set_side_info(&side_info);
+                frame
+            }
+            Err(frame) => frame,
+        })
+        .collect();
+    (frames, muted)
+}
+
+/// Switches between streams at frame boundaries, e.g. for blind listening tests.
+/// Frames are taken from the stream selected by the schedule for each block of
+/// `block_frames` frames, the result has the length of the shortest stream.
+/// The main data of Layer III frames is moved into a new bit reservoir, so every frame
+/// decodes as in its stream. The result has no tags, which could reveal the encoders.
+pub fn mix(
+    streams: &[Mp3Stream],
+    schedule: &Mp3MixSchedule,
+    block_frames: usize,
+) -> Result<Mp3Mix, MPeakError> {
+    let first = streams
+        .iter()
+        .flat_map(|stream| stream.frames.first())
+        .next();
+    if let Some(first) = first {
+        for stream in streams {
+            if !stream
+                .frames
+                .iter()
+                .all(|f| compatible(&first.header, &f.header))
+            {
+                return Err(MPeakError::IncompatibleStreams);
+            }
+        }
+    }
+
+    let length = streams
+        .iter()
+        .map(|stream| stream.frames.len())
+        .min()
+        .unwrap_or(0);
+    let block_frames = block_frames.max(1);
+    let mut rng = match schedule {
+        Mp3MixSchedule::Random(seed) => Some(ChaCha8Rng::seed_from_u64(*seed)),
+        Mp3MixSchedule::Pattern(_) => None,
+    };
+    let sources: Vec<usize> = (0..length.div_ceil(block_frames))
+        .flat_map(|block| {
+            let source = match (schedule, &mut rng) {
+                (Mp3MixSchedule::Random(_), Some(rng)) => {
+                    // not gen_range, its sampling may change between rand versions
+                    (rng.next_u64() % streams.len() as u64) as usize
+                }
+                (Mp3MixSchedule::Pattern(pattern), _) if !pattern.is_empty() => {
+                    pattern[block % pattern.len()] % streams.len()
+                }
+                _ => 0,
+            };
+            std::iter::repeat_n(source, block_frames)
+        })
+        .take(length)
+        .collect();
+
+    let (frames, muted_frames) = match first.map(|frame| frame.header.layer()) {
+        Some(Mp3Layer::Layer3) => {
+            let mut main_data: Vec<_> = streams
+                .iter()
+                .map(|stream| frame_main_data(&stream.frames))
+                .collect();
+            let selected: Vec<_> = sources
+                .iter()
+                .enumerate()
+                .map(|(i, &source)| (&streams[source].frames[i], main_data[source][i].take()))
+                .collect();
+            repack(&selected)
+        }
+        _ => {
+            let frames = sources
+                .iter()
+                .enumerate()
+                .map(|(i, &source)| streams[source].frames[i].clone())
+                .collect();
+            (frames, 0)
+        }
+    };
+
+    Ok(Mp3Mix {
+        stream: Mp3Stream {
+            id3v2: Vec::new(),
+            xing: Some(Mp3XingTag::default()),
+            frames,
+            ape: Vec::new(),
+            id3v1: Vec::new(),
+        },
+        sources,
+        muted_frames,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mp3_side_info::Mp3GranuleInfo;
+    use super::*;
+
+    /// V1 layer 3 128kbit/s 44100Hz mono frame header
+    const HEADER: u32 = 0xFFFB90C0;
+
+    /// Side information and main data of `count` frames with `bytes` bytes of main data
+    /// per granule, filled with `value` and the frame index
+    fn main_data(count: usize, bytes: usize, value: u8) -> Vec<Mp3FrameMainData> {
+        let info = Mp3GranuleInfo {
+            part2_3_length: (bytes * 8) as u16,
+            big_values: 10,
+            global_gain: 150,
+            ..Default::default()
+        };
+        let side_info = Mp3SideInfo {
+            main_data_begin: 0,
+            private_bits: 0,
+            scfsi: [[false; 4]; 2],
+            granules: vec![vec![info]; 2],
+        };
+        (0..count)
+            .map(|i| (side_info.clone(), vec![value + i as u8; 2 * bytes]))
+            .collect()
+    }
+
+    fn stream(main_data: Vec<Mp3FrameMainData>) -> Mp3Stream {
+        let mut data = vec![0; 417];
+        data[0..4].copy_from_slice(&HEADER.to_be_bytes());
+        let frame = Mp3Frame::new(Mp3FrameHeader::new(HEADER), data, 0);
+        let frames: Vec<_> = main_data
+            .into_iter()
+            .map(|main_data| (&frame, Some(main_data)))
+            .collect();
+        let (frames, muted) = repack(&frames);
+        assert_eq!(muted, 0);
+        Mp3Stream {
+            id3v2: Vec::new(),
+            xing: None,
+            frames,
+            ape: Vec::new(),
+            id3v1: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_repack() {
+        // 300 bytes per frame, each frame leaves 96 bytes for the next one
+        let stream = stream(main_data(3, 150, 0x10));
+        let main_data = frame_main_data(&stream.frames);
+        for (i, frame) in stream.frames.iter().enumerate() {
+            assert_eq!(frame.header.bitrate_index(), 9);
+            let (side_info, data) = main_data[i].as_ref().unwrap();
+            assert_eq!(data, &vec![0x10 + i as u8; 300]);
+            assert_eq!(side_info.main_data_begin, [0, 96, 192][i]);
+        }
+    }
+
+    #[test]
+    fn test_repack_bitrate() {
+        let stream = stream(main_data(2, 450, 0x20));
+        // 900 bytes need a frame of 320 kbit/s, the second frame fits
+        // 256 kbit/s using the 123 bytes left in the first one
+        assert_eq!(stream.frames[0].header.bitrate_index(), 14);
+        assert_eq!(stream.frames[1].header.bitrate_index(), 13);
+        let main_data = frame_main_data(&stream.frames);
+        assert_eq!(main_data[1].as_ref().unwrap().1, vec![0x21; 900]);
+    }
+
+    #[test]
+    fn test_mix_pattern() {
+        let a = stream(main_data(5, 100, 0x10));
+        let b = stream(main_data(5, 250, 0x20));
+        let mix = mix(&[a, b], &Mp3MixSchedule::Pattern(vec![0, 1, 1]), 1).unwrap();
+        assert_eq!(mix.sources, vec![0, 1, 1, 0, 1]);
+        assert_eq!(mix.muted_frames, 0);
+        let main_data = frame_main_data(&mix.stream.frames);
+        for (i, &source) in mix.sources.iter().enumerate() {
+            let (bytes, value) = [(200, 0x10), (500, 0x20)][source];
+            assert_eq!(
+                main_data[i].as_ref().unwrap().1,
+                vec![value + i as u8; bytes]
+            );
+        }
+    }
+
+    #[test]
+    fn test_mix_random() {
+        let streams = [
+            stream(main_data(40, 100, 0)),
+            stream(main_data(30, 100, 0x80)),
+        ];
+        let first = mix(&streams, &Mp3MixSchedule::Random(1), 4).unwrap();
+        let second = mix(&streams, &Mp3MixSchedule::Random(1), 4).unwrap();
+        assert_eq!(first.sources, second.sources);
+        assert_eq!(first.sources.len(), 30);
+        assert!(first.sources.contains(&0) && first.sources.contains(&1));
+        assert!(first
+            .sources
+            .chunks(4)
+            .all(|block| block.iter().all(|&s| s == block[0])));
+        // the schedule of a seed does not change with the rand version
+        let blocks: Vec<usize> = first.sources.iter().step_by(4).copied().collect();
+        assert_eq!(blocks, vec![1, 1, 1, 0, 1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_mix_incompatible() {
+        let mut b = stream(main_data(1, 100, 0));
+        // 48000Hz
+        b.frames[0].header = Mp3FrameHeader::new(0xFFFB94C0);
+        assert!(matches!(
+            mix(
+                &[stream(main_data(1, 100, 0)), b],
+                &Mp3MixSchedule::Random(0),
+                1
+            ),
+            Err(MPeakError::IncompatibleStreams)
+        ));
+    }
+}
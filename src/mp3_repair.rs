@@ -0,0 +1,423 @@
+// References
+// ISO/IEC 11172-3 2.4.3.4.2 (bit reservoir)
+// http://gabriel.mp3-tech.org/mp3infotag.html
+// https://id3.org/id3v2.4.0-structure
+
+use super::apev2::ApeTag;
+use super::id3v1::Id3v1Tag;
+use super::id3v2::Id3v2Tag;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::{Mp3FrameHeader, Mp3Layer, Mp3Version};
+use super::mp3_mix::{next_main_data, repack, Mp3FrameMainData};
+use super::mp3_reservoir::Mp3Reservoir;
+use super::mp3_scan::{scan, Mp3RegionKind};
+use super::mp3_stream::Mp3Stream;
+use super::mp3_xing::Mp3XingTag;
+use super::MPeakError;
+
+/// Result of a repair
+#[derive(Clone)]
+pub struct Mp3Repair {
+    pub stream: Mp3Stream,
+    /// Lyrics3 tag, which the stream cannot hold, written before the ID3v1 tag
+    pub lyrics3: Vec<u8>,
+    /// Number of removed bytes which were neither frames nor complete tags
+    pub junk_bytes: usize,
+    /// Number of dropped truncated, corrupt, foreign and surplus Xing/Info frames
+    pub dropped_frames: usize,
+    /// Number of frames muted because their main data was lost
+    pub muted_frames: usize,
+    /// Number of tags moved to the start or the end of the file
+    pub moved_tags: usize,
+    /// Number of tags merged into the first tag of the same kind
+    pub merged_tags: usize,
+    /// Number of dropped tags, identical to a kept one or a further Lyrics3 tag
+    pub dropped_tags: usize,
+}
+
+impl Mp3Repair {
+    /// The repaired file data
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.stream.to_bytes();
+        let end = data.len() - self.stream.id3v1.len();
+        data.splice(end..end, self.lyrics3.iter().copied());
+        data
+    }
+}
+
+/// Version, layer, sample rate and channels, which all frames of the result share
+fn format(header: &Mp3FrameHeader) -> (Mp3Version, Mp3Layer, u16, usize) {
+    (
+        header.version(),
+        header.layer(),
+        header.sample_rate(),
+        header.channels(),
+    )
+}
+
+/// Tag of the given kind with the content of another tag added, None if either does not parse.
+/// ID3v2 tags are merged frame by frame, APE items and ID3v1 fields are only added if missing.
+fn merge_tags(kind: Mp3RegionKind, tag: &[u8], other: &[u8]) -> Option<Vec<u8>> {
+    match kind {
+        Mp3RegionKind::Id3v2 => {
+            let mut tag = Id3v2Tag::parse(tag)?;
+            tag.merge(&Id3v2Tag::parse(other)?);
+            tag.clean();
+            Some(tag.to_bytes())
+        }
+        Mp3RegionKind::Ape => {
+            let mut tag = ApeTag::parse(tag)?;
+            for item in ApeTag::parse(other)?.items {
+                if tag.get(&item.key).is_none() {
+                    tag.items.push(item);
+                }
+            }
+            Some(tag.to_bytes())
+        }
+        Mp3RegionKind::Id3v1 => {
+            let mut tag = Id3v1Tag::parse(tag)?;
+            let other = Id3v1Tag::parse(other)?;
+            for (field, value) in [
+                (&mut tag.title, other.title),
+                (&mut tag.artist, other.artist),
+                (&mut tag.album, other.album),
+                (&mut tag.year, other.year),
+                (&mut tag.comment, other.comment),
+            ] {
+                if field.is_empty() {
+                    *field = value;
+                }
+            }
+            tag.track = tag.track.or(other.track);
+            if tag.genre == 255 {
+                tag.genre = other.genre;
+            }
+            Some(tag.to_bytes())
+        }
+        _ => None,
+    }
+}
+
+/// Whether the frame has a defined layer, a matching crc and valid side information
+fn is_intact(frame: &Mp3Frame) -> bool {
+    match frame.header.layer() {
+        Mp3Layer::Reserved => false,
+        Mp3Layer::Layer3 => frame.crc_matches() != Some(false) && frame.side_info().is_ok(),
+        _ => frame.crc_matches() != Some(false),
+    }
+}
+
+/// Rebuilds a clean file from mangled data without re-encoding.
+/// Junk, incomplete tags and truncated, corrupt or surplus Xing/Info frames are removed.
+/// Frames with a format other than the most common one are dropped as well.
+/// Tags are kept with ID3v2 at the start and APE, Lyrics3 and ID3v1 at the end.
+/// Further tags of a kind are merged into the first one unless they are identical copies,
+/// only the first Lyrics3 tag is kept.
+/// A Xing/Info tag is rebuilt to match the remaining frames.
+/// If Layer III frames were dropped, the main data of the remaining frames is moved into
+/// a new bit reservoir, muting frames whose main data was lost.
+pub fn repair(data: &[u8]) -> Result<Mp3Repair, MPeakError> {
+    let regions = scan(data);
+    let mut repair = Mp3Repair {
+        stream: Mp3Stream {
+            id3v2: Vec::new(),
+            xing: None,
+            frames: Vec::new(),
+            ape: Vec::new(),
+            id3v1: Vec::new(),
+        },
+        lyrics3: Vec::new(),
+        junk_bytes: 0,
+        dropped_frames: 0,
+        muted_frames: 0,
+        moved_tags: 0,
+        merged_tags: 0,
+        dropped_tags: 0,
+    };
+    let last_frame = regions
+        .iter()
+        .rposition(|region| region.kind == Mp3RegionKind::Frame);
+    let mut frames = Vec::new();
+    // complete tags as found, to recognize identical copies
+    let mut found: Vec<&[u8]> = Vec::new();
+    for (i, region) in regions.iter().enumerate() {
+        let bytes = &data[region.offset..region.offset + region.length];
+        let at_end = last_frame.is_none_or(|last| i > last);
+        let (tag, standard, complete) = match region.kind {
+            Mp3RegionKind::Junk => {
+                repair.junk_bytes += region.length;
+                continue;
+            }
+            Mp3RegionKind::TruncatedFrame => {
+                repair.dropped_frames += 1;
+                continue;
+            }
+            Mp3RegionKind::Lyrics3 => (
+                &mut repair.lyrics3,
+                at_end
+                    && regions[i + 1..]
+                        .iter()
+                        .all(|other| other.kind == Mp3RegionKind::Id3v1),
+                true,
+            ),
+            Mp3RegionKind::Frame => {
+                let header =
+                    Mp3FrameHeader::new(u32::from_be_bytes(bytes[0..4].try_into().unwrap()));
+                frames.push(Mp3Frame::new(header, bytes.to_vec(), frames.len() as u32));
+                continue;
+            }
+            Mp3RegionKind::Id3v2 => (
+                &mut repair.stream.id3v2,
+                region.offset == 0,
+                Id3v2Tag::length_at_start(bytes) == Some(bytes.len()),
+            ),
+            Mp3RegionKind::Ape => (
+                &mut repair.stream.ape,
+                at_end
+                    && regions[i + 1..].iter().all(|other| {
+                        matches!(other.kind, Mp3RegionKind::Lyrics3 | Mp3RegionKind::Id3v1)
+                    }),
+                // an APE tag without header is only found at the end and always complete
+                ApeTag::length_at_start(bytes).is_none_or(|length| length == bytes.len()),
+            ),
+            Mp3RegionKind::Id3v1 => (&mut repair.stream.id3v1, i + 1 == regions.len(), true),
+        };
+        if !complete {
+            repair.junk_bytes += region.length;
+            continue;
+        }
+        let identical = found.contains(&bytes);
+        found.push(bytes);
+        if tag.is_empty() {
+            repair.moved_tags += !standard as usize;
+            *tag = bytes.to_vec();
+        } else if identical {
+            repair.dropped_tags += 1;
+        } else if let Some(merged) = merge_tags(region.kind, tag, bytes) {
+            repair.merged_tags += 1;
+            *tag = merged;
+        } else {
+            repair.dropped_tags += 1;
+        }
+    }
+
+    let mut formats: Vec<((Mp3Version, Mp3Layer, u16, usize), usize)> = Vec::new();
+    for frame in &frames {
+        match formats
+            .iter_mut()
+            .find(|(f, _)| *f == format(&frame.header))
+        {
+            Some((_, count)) => *count += 1,
+            None => formats.push((format(&frame.header), 1)),
+        }
+    }
+    let reference = formats
+        .iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(format, _)| *format)
+        .ok_or(MPeakError::InvalidMp3Header)?;
+
+    repair.stream.xing = frames
+        .first()
+        .and_then(Mp3XingTag::parse)
+        .map(|xing| Mp3XingTag {
+            lame: xing.lame,
+            ..Default::default()
+        });
+    let mut reservoir = Mp3Reservoir::new();
+    let mut kept: Vec<(&Mp3Frame, Option<Mp3FrameMainData>)> = Vec::new();
+    // main data of a dropped frame may be referenced by the following frames
+    let mut dropped_main_data = false;
+    let mut moved_main_data = false;
+    for (i, frame) in frames.iter().enumerate() {
+        let is_xing = Mp3XingTag::parse(frame).is_some();
+        if !is_xing && is_intact(frame) && format(&frame.header) == reference {
+            let main = next_main_data(&mut reservoir, frame);
+            moved_main_data |= dropped_main_data || main.is_none();
+            kept.push((frame, main));
+            continue;
+        }
+        match frame.header.layer() {
+            Mp3Layer::Layer3 => {
+                reservoir.push(0, frame.main_data());
+                dropped_main_data |= i > 0 || !is_xing;
+            }
+            _ => reservoir.clear(),
+        }
+        repair.dropped_frames += (i > 0 || !is_xing) as usize;
+    }
+
+    repair.stream.frames = match reference.1 == Mp3Layer::Layer3 && moved_main_data {
+        true => {
+            let (frames, muted) = repack(&kept);
+            repair.muted_frames = muted;
+            frames
+        }
+        false => kept
+            .into_iter()
+            .enumerate()
+            .map(|(position, (frame, _))| {
+                let mut frame = frame.clone();
+                frame.position = position as u32;
+                frame
+            })
+            .collect(),
+    };
+    Ok(repair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mp3_header::{Mp3ChannelMode, Mp3Protection};
+    use super::super::mp3_synth::{Mp3Synth, Mp3SynthPayload};
+    use super::super::mp3_validate::validate;
+    use super::super::mp3_xing::Mp3LameTag;
+    use super::*;
+
+    fn header() -> Mp3FrameHeader {
+        let mut header = Mp3FrameHeader::from_parameters(
+            Mp3Version::V1,
+            Mp3Layer::Layer3,
+            128,
+            48000,
+            Mp3ChannelMode::JointStereo,
+        )
+        .unwrap();
+        header.set_protection(Mp3Protection::ProtectedByCrc);
+        header
+    }
+
+    #[test]
+    fn test_repair_layout() {
+        let mut id3v2 = Id3v2Tag::default();
+        id3v2.set_text("TIT2", "Stray");
+        let lame = Mp3LameTag {
+            encoder: "LAME3.100".to_string(),
+            encoder_delay: 576,
+            ..Default::default()
+        };
+        let mut synth = Mp3Synth::new(header(), 8);
+        synth.xing = Some(Some(lame));
+        synth.junk = vec![
+            (2, id3v2.to_bytes()),
+            (3, vec![0x12; 33]),
+            (5, Id3v1Tag::default().to_bytes()),
+        ];
+        synth.id3v1 = Some(Id3v1Tag::default());
+        synth.truncate = 50;
+        let repair = repair(&synth.to_bytes()).unwrap();
+
+        assert_eq!(repair.junk_bytes, 33);
+        assert_eq!(repair.dropped_frames, 1);
+        assert_eq!(repair.muted_frames, 0);
+        assert_eq!(repair.moved_tags, 2);
+        assert_eq!(repair.dropped_tags, 1);
+        assert_eq!(repair.stream.id3v2, id3v2.to_bytes());
+        assert_eq!(repair.stream.frames.len(), 7);
+        assert_eq!(
+            repair.stream.frames[3].body.data,
+            synth.audio_frames()[3].body.data
+        );
+        let data = repair.stream.to_bytes();
+        assert_eq!(validate(&data), []);
+        let stream = Mp3Stream::parse(&data).unwrap();
+        let xing = stream.xing.unwrap();
+        assert_eq!(xing.frames, Some(7));
+        assert_eq!(xing.lame.unwrap().encoder_delay, 576);
+    }
+
+    #[test]
+    fn test_repair_main_data() {
+        let mut frames = Mp3Synth::new(header(), 5).audio_frames();
+        // frame 3 starts its main data 50 bytes before the end of frame 1
+        let main_data_length = frames[1].main_data().len();
+        let mut side_info = frames[3].side_info().unwrap();
+        side_info.main_data_begin = (main_data_length + 50) as u16;
+        side_info.granules[0][0].part2_3_length = 8 * 60;
+        frames[3].set_side_info(&side_info);
+        let length = frames[1].body.data.len();
+        for (i, byte) in frames[1].body.data[length - 50..].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        // the crc marks the side information of frame 1 as corrupt
+        frames[1].body.data[4] ^= 0xFF;
+        let data: Vec<u8> = frames
+            .iter()
+            .flat_map(|frame| frame.body.data.clone())
+            .collect();
+
+        let mut reservoir = Mp3Reservoir::new();
+        let original: Vec<_> = frames
+            .iter()
+            .map(|frame| next_main_data(&mut reservoir, frame))
+            .collect();
+        let repair = repair(&data).unwrap();
+        assert_eq!(repair.dropped_frames, 1);
+        assert_eq!(repair.muted_frames, 0);
+        let mut reservoir = Mp3Reservoir::new();
+        let repaired: Vec<_> = repair
+            .stream
+            .frames
+            .iter()
+            .map(|frame| next_main_data(&mut reservoir, frame))
+            .collect();
+        let main_data = &repaired[2].as_ref().unwrap().1;
+        assert_eq!(main_data, &original[3].as_ref().unwrap().1);
+        assert_eq!(main_data[..50], frames[1].body.data[length - 50..]);
+        assert_eq!(validate(&repair.stream.to_bytes()), []);
+    }
+
+    #[test]
+    fn test_repair_protected_layer2() {
+        let mut header = Mp3FrameHeader::from_parameters(
+            Mp3Version::V1,
+            Mp3Layer::Layer2,
+            192,
+            48000,
+            Mp3ChannelMode::Stereo,
+        )
+        .unwrap();
+        header.set_protection(Mp3Protection::ProtectedByCrc);
+        let mut synth = Mp3Synth::new(header, 20);
+        synth.payload = Mp3SynthPayload::Random(2);
+        let data = synth.to_bytes();
+        let repair = repair(&data).unwrap();
+        assert_eq!(repair.dropped_frames, 0);
+        assert_eq!(repair.stream.frames.len(), 20);
+        assert_eq!(repair.to_bytes(), data);
+    }
+
+    #[test]
+    fn test_repair_tags() {
+        let mut first = Id3v2Tag::default();
+        first.set_text("TIT2", "Title");
+        let mut appended = Id3v2Tag::default();
+        appended.set_text("TIT2", "Other");
+        appended.set_text("TPE1", "Artist");
+        let lyrics3 = b"LYRICSBEGIN[00:01]TextLYRICSEND".to_vec();
+        let mut synth = Mp3Synth::new(header(), 4);
+        synth.id3v2 = Some(first.clone());
+        synth.junk = vec![
+            (2, first.to_bytes()),
+            (4, appended.to_bytes_with_footer()),
+            (4, lyrics3.clone()),
+        ];
+        synth.id3v1 = Some(Id3v1Tag::default());
+        let repair = repair(&synth.to_bytes()).unwrap();
+
+        assert_eq!(repair.junk_bytes, 0);
+        assert_eq!(repair.moved_tags, 0);
+        assert_eq!(repair.merged_tags, 1);
+        assert_eq!(repair.dropped_tags, 1);
+        assert_eq!(repair.lyrics3, lyrics3);
+        let tag = Id3v2Tag::parse(&repair.stream.id3v2).unwrap();
+        assert_eq!(tag.text("TIT2").as_deref(), Some("Title"));
+        assert_eq!(tag.text("TPE1").as_deref(), Some("Artist"));
+        let data = repair.to_bytes();
+        assert_eq!(
+            data[data.len() - 128 - lyrics3.len()..data.len() - 128],
+            lyrics3
+        );
+        assert_eq!(validate(&data), []);
+    }
+}
@@ -0,0 +1,166 @@
+// References
+// ISO/IEC 11172-3 2.4.3.4.7 (requantization) and 2.4.3.4.8 (reordering)
+// http://www.mp3-tech.org/programmer/docs/mp3_theory.pdf
+
+use super::mp3_bands::Mp3Band;
+use super::mp3_huffman::Mp3Spectrum;
+use super::mp3_scalefactors::Mp3Scalefactors;
+use super::mp3_side_info::Mp3GranuleInfo;
+use std::sync::OnceLock;
+
+/// Pre-emphasis added to the long band scalefactors when preflag is set
+pub const PRETAB: [u8; 22] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 3, 3, 3, 2, 0,
+];
+
+/// Largest quantized magnitude: 15 plus 13 linbits
+const MAX_QUANTIZED: usize = 15 + (1 << 13) - 1;
+
+/// Table of |x|^(4/3)
+fn pow43() -> &'static Vec<f32> {
+    static POW43: OnceLock<Vec<f32>> = OnceLock::new();
+    POW43.get_or_init(|| {
+        (0..=MAX_QUANTIZED)
+            .map(|x| (x as f64).powf(4.0 / 3.0) as f32)
+            .collect()
+    })
+}
+
+/// Scales the quantized spectrum of one channel in one granule to spectral values.
+/// `bands` is the band layout of the granule, see `Mp3Bands::granule_bands`.
+pub fn requantize(
+    bands: &[Mp3Band],
+    info: &Mp3GranuleInfo,
+    scalefactors: &Mp3Scalefactors,
+    spectrum: &Mp3Spectrum,
+) -> [f32; 576] {
+    let pow43 = pow43();
+    let mut samples = [0.0; 576];
+    let multiplier = if info.scalefac_scale { 1.0 } else { 0.5 };
+
+    for band in bands.iter().filter(|band| band.start < spectrum.count) {
+        let exponent = match band.window {
+            None => {
+                let pretab = if info.preflag { PRETAB[band.index] } else { 0 };
+                let scalefactor = (scalefactors.long[band.index] + pretab) as f64;
+                0.25 * (info.global_gain as f64 - 210.0) - multiplier * scalefactor
+            }
+            Some(window) => {
+                let gain =
+                    info.global_gain as f64 - 210.0 - 8.0 * info.subblock_gain[window] as f64;
+                let scalefactor = scalefactors.short[band.index][window] as f64;
+                0.25 * gain - multiplier * scalefactor
+            }
+        };
+        let scale = exponent.exp2() as f32;
+        let end = usize::min(band.end, spectrum.count);
+        let lines = samples[band.start..end]
+            .iter_mut()
+            .zip(spectrum.quantized[band.start..end].iter());
+        for (sample, &value) in lines {
+            let magnitude = pow43[usize::min(value.unsigned_abs() as usize, MAX_QUANTIZED)] * scale;
+            *sample = if value < 0 { -magnitude } else { magnitude };
+        }
+    }
+
+    samples
+}
+
+/// Reorders the short bands from window order to frequency order,
+/// interleaving the spectral lines of the three windows.
+pub fn reorder(bands: &[Mp3Band], samples: &mut [f32; 576]) {
+    let mut i = 0;
+    while i < bands.len() {
+        if bands[i].window != Some(0) {
+            i += 1;
+            continue;
+        }
+        let start = bands[i].start;
+        let width = bands[i].end - bands[i].start;
+        let mut reordered = [0.0; 3 * 66];
+        for window in 0..3 {
+            for j in 0..width {
+                reordered[3 * j + window] = samples[start + window * width + j];
+            }
+        }
+        samples[start..start + 3 * width].copy_from_slice(&reordered[..3 * width]);
+        i += 3;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mp3_bands::Mp3Bands;
+    use super::super::mp3_header::Mp3FrameHeader;
+    use super::*;
+
+    #[test]
+    fn test_requantize_long() {
+        let bands = Mp3Bands::new(&Mp3FrameHeader::new(0xFFFB9000));
+        let info = Mp3GranuleInfo {
+            global_gain: 214,
+            preflag: true,
+            ..Default::default()
+        };
+        let mut scalefactors = Mp3Scalefactors::default();
+        scalefactors.long[1] = 2;
+        let mut spectrum = Mp3Spectrum::default();
+        spectrum.quantized[0] = 1;
+        spectrum.quantized[1] = -8;
+        spectrum.quantized[4] = 1;
+        spectrum.quantized[418] = 1;
+        spectrum.count = 420;
+        let granule_bands = bands.granule_bands(&info);
+        let samples = requantize(&granule_bands, &info, &scalefactors, &spectrum);
+        // 2^(0.25 * 4) = 2
+        assert_eq!(samples[0], 2.0);
+        assert_eq!(samples[1], -32.0);
+        // scalefactor 2 with multiplier 0.5
+        assert_eq!(samples[4], 1.0);
+        // pretab of band 21 is 0
+        assert_eq!(samples[418], 2.0);
+    }
+
+    #[test]
+    fn test_requantize_short() {
+        let bands = Mp3Bands::new(&Mp3FrameHeader::new(0xFFFB9000));
+        let info = Mp3GranuleInfo {
+            global_gain: 210,
+            window_switching: true,
+            block_type: 2,
+            subblock_gain: [0, 1, 0],
+            scalefac_scale: true,
+            ..Default::default()
+        };
+        let mut scalefactors = Mp3Scalefactors::default();
+        scalefactors.short[0][2] = 1;
+        let mut spectrum = Mp3Spectrum::default();
+        spectrum.quantized[0..12].copy_from_slice(&[1; 12]);
+        spectrum.count = 12;
+        let granule_bands = bands.granule_bands(&info);
+        let samples = requantize(&granule_bands, &info, &scalefactors, &spectrum);
+        assert_eq!(samples[0..4], [1.0; 4]);
+        assert_eq!(samples[4..8], [0.25; 4]);
+        assert_eq!(samples[8..12], [0.5; 4]);
+    }
+
+    #[test]
+    fn test_reorder() {
+        let bands = Mp3Bands::new(&Mp3FrameHeader::new(0xFFFB9000));
+        let info = Mp3GranuleInfo {
+            window_switching: true,
+            block_type: 2,
+            ..Default::default()
+        };
+        let mut samples = [0.0; 576];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample = i as f32;
+        }
+        reorder(&bands.granule_bands(&info), &mut samples);
+        assert_eq!(samples[0..6], [0.0, 4.0, 8.0, 1.0, 5.0, 9.0]);
+        assert_eq!(samples[12..15], [12.0, 16.0, 20.0]);
+        // last band with a width of 56
+        assert_eq!(samples[408..411], [408.0, 464.0, 520.0]);
+        assert_eq!(samples[575], 575.0);
+    }
+}
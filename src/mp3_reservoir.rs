@@ -0,0 +1,89 @@
+// References
+// ISO/IEC 11172-3 2.4.3.4.2 (bit reservoir)
+
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::Mp3Layer;
+use super::mp3_side_info::Mp3SideInfo;
+
+/// Largest main_data_begin back reference (9 bits for MPEG 1)
+const MAX_RESERVOIR: usize = 511;
+
+/// Collects the main data of consecutive Layer III frames to resolve
+/// the main_data_begin back references of the bit reservoir.
+pub struct Mp3Reservoir {
+    data: Vec<u8>,
+}
+
+impl Mp3Reservoir {
+    pub fn new() -> Mp3Reservoir {
+        Mp3Reservoir { data: Vec::new() }
+    }
+
+    /// Appends the main data bytes of a frame to the reservoir and returns the main data
+    /// starting `main_data_begin` bytes before the frame.
+    /// Returns None if the reservoir does not hold enough data, e.g. at the start of a cut stream.
+    pub fn push(&mut self, main_data_begin: usize, frame_data: &[u8]) -> Option<Vec<u8>> {
+        let main_data = if main_data_begin <= self.data.len() {
+            let mut main_data = self.data[self.data.len() - main_data_begin..].to_vec();
+            main_data.extend_from_slice(frame_data);
+            Some(main_data)
+        } else {
+            None
+        };
+
+        self.data.extend_from_slice(frame_data);
+        if self.data.len() > MAX_RESERVOIR {
+            self.data.drain(0..self.data.len() - MAX_RESERVOIR);
+        }
+        main_data
+    }
+
+    /// Parses the side information of a frame and returns it together with the frame's main data.
+    /// Returns None for frames which are not Layer III or whose main data is not available.
+    pub fn next_frame(&mut self, frame: &Mp3Frame) -> Option<(Mp3SideInfo, Vec<u8>)> {
+        if frame.header.layer() != Mp3Layer::Layer3 {
+            self.clear();
+            return None;
+        }
+        let side_info = frame.side_info();
+        let main_data_begin = side_info.as_ref().map_or(0, |s| s.main_data_begin);
+        let main_data = self.push(main_data_begin as usize, frame.main_data());
+        match (side_info, main_data) {
+            (Ok(side_info), Some(main_data)) => Some((side_info, main_data)),
+            _ => None,
+        }
+    }
+
+    /// Discards all buffered data
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+}
+
+impl Default for Mp3Reservoir {
+    fn default() -> Self {
+        Mp3Reservoir::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push() {
+        let mut reservoir = Mp3Reservoir::new();
+        assert_eq!(reservoir.push(0, &[1, 2, 3]), Some(vec![1, 2, 3]));
+        assert_eq!(reservoir.push(2, &[4, 5]), Some(vec![2, 3, 4, 5]));
+        assert_eq!(reservoir.push(6, &[6]), None);
+        assert_eq!(reservoir.push(6, &[7]), Some(vec![1, 2, 3, 4, 5, 6, 7]));
+    }
+
+    #[test]
+    fn test_push_limit() {
+        let mut reservoir = Mp3Reservoir::new();
+        reservoir.push(0, &[1; 600]);
+        assert_eq!(reservoir.push(512, &[2]), None);
+        assert_eq!(reservoir.push(511, &[]).unwrap().len(), 511);
+    }
+}
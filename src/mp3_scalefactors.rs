@@ -0,0 +1,396 @@
+// References
+// ISO/IEC 11172-3 2.4.2.7 and ISO/IEC 13818-3 2.4.3.2
+// http://www.mp3-tech.org/programmer/docs/mp3_theory.pdf
+
+use super::bit_reader::BitReader;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::{Mp3FrameHeader, Mp3Version};
+use super::mp3_side_info::{Mp3GranuleInfo, Mp3SideInfo};
+
+/// Bit lengths (slen1, slen2) of MPEG 1 scalefactors indexed by scalefac_compress
+const SLEN: [(usize, usize); 16] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (3, 0),
+    (1, 1),
+    (1, 2),
+    (1, 3),
+    (2, 1),
+    (2, 2),
+    (2, 3),
+    (3, 1),
+    (3, 2),
+    (3, 3),
+    (4, 2),
+    (4, 3),
+];
+
+/// MPEG 1 long block scalefactor bands grouped by scfsi band
+const SCFSI_BANDS: [(usize, usize); 4] = [(0, 6), (6, 11), (11, 16), (16, 21)];
+
+/// Number of scalefactors per partition for MPEG 2 and 2.5, indexed by
+/// scalefac_compress range and block kind (long, short, mixed).
+/// The last three rows are used for the right channel of intensity stereo frames.
+const LSF_PARTITIONS: [[[usize; 4]; 3]; 6] = [
+    [[6, 5, 5, 5], [9, 9, 9, 9], [6, 9, 9, 9]],
+    [[6, 5, 7, 3], [9, 9, 12, 6], [6, 9, 12, 6]],
+    [[11, 10, 0, 0], [18, 18, 0, 0], [15, 18, 0, 0]],
+    [[7, 7, 7, 0], [12, 12, 12, 0], [6, 15, 12, 0]],
+    [[6, 6, 6, 3], [12, 9, 9, 6], [6, 12, 9, 6]],
+    [[8, 8, 5, 0], [15, 12, 9, 0], [6, 18, 9, 0]],
+];
+
+/// Scalefactors of one channel in one granule.
+/// Long block bands are stored in `long`, short block bands per window in `short`.
+/// Mixed blocks use long bands below the switch point and short bands above it.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3Scalefactors {
+    pub long: [u8; 22],
+    pub short: [[u8; 3]; 13],
+    /// Bit length of each scalefactor partition (MPEG 2 and 2.5 only).
+    /// Needed to detect illegal intensity stereo positions.
+    pub lsf_slen: [u8; 4],
+    /// Number of scalefactors in each partition (MPEG 2 and 2.5 only)
+    pub lsf_counts: [u8; 4],
+}
+
+/// Scalefactors of all granules and channels of a frame
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3FrameScalefactors {
+    /// Position of the frame in the stream
+    pub position: u32,
+    /// Scalefactors indexed by granule and channel
+    pub granules: Vec<Vec<Mp3Scalefactors>>,
+}
+
+impl Mp3FrameScalefactors {
+    /// Decodes the scalefactors of all granules and channels, skipping the Huffman coded data.
+    /// `main_data` starts at the main_data_begin position of the frame.
+    pub fn decode(
+        frame: &Mp3Frame,
+        side_info: &Mp3SideInfo,
+        main_data: &[u8],
+    ) -> Mp3FrameScalefactors {
+        let mut reader = BitReader::new(main_data);
+        let mut granules: Vec<Vec<Mp3Scalefactors>> = Vec::new();
+        for gr in 0..side_info.granules.len() {
+            let mut channels = Vec::new();
+            for ch in 0..side_info.granules[gr].len() {
+                let first_granule = granules.first().map_or(Default::default(), |g| g[ch]);
+                let start = reader.position();
+                channels.push(Mp3Scalefactors::read(
+                    &mut reader,
+                    &frame.header,
+                    side_info,
+                    gr,
+                    ch,
+                    &first_granule,
+                ));
+                reader.set_position(start + side_info.granules[gr][ch].part2_3_length as usize);
+            }
+            granules.push(channels);
+        }
+        Mp3FrameScalefactors {
+            position: frame.position,
+            granules,
+        }
+    }
+}
+
+impl Mp3Scalefactors {
+    /// Decodes the scalefactors (part 2) of one granule and channel starting at the
+    /// current reader position. `first_granule` holds the scalefactors of the same channel
+    /// in granule 0 for scfsi reuse.
+    pub fn read(
+        reader: &mut BitReader,
+        header: &Mp3FrameHeader,
+        side_info: &Mp3SideInfo,
+        gr: usize,
+        ch: usize,
+        first_granule: &Mp3Scalefactors,
+    ) -> Mp3Scalefactors {
+        let info = &side_info.granules[gr][ch];
+        if header.version() == Mp3Version::V1 {
+            read_mpeg1(reader, info, &side_info.scfsi[ch], gr, first_granule)
+        } else {
+            let intensity_stereo = header.is_intensity_stereo();
+            read_lsf(reader, info, intensity_stereo && ch == 1)
+        }
+    }
+}
+
+fn read_mpeg1(
+    reader: &mut BitReader,
+    info: &Mp3GranuleInfo,
+    scfsi: &[bool; 4],
+    gr: usize,
+    first_granule: &Mp3Scalefactors,
+) -> Mp3Scalefactors {
+    let mut scalefactors = Mp3Scalefactors::default();
+    let (slen1, slen2) = SLEN[info.scalefac_compress as usize];
+
+    if info.is_short_block() {
+        let mut sfb = 0;
+        if info.mixed_block {
+            for sf in scalefactors.long[0..8].iter_mut() {
+                *sf = reader.read_bits(slen1) as u8;
+            }
+            sfb = 3;
+        }
+        for band in scalefactors.short[sfb..6].iter_mut() {
+            for sf in band.iter_mut() {
+                *sf = reader.read_bits(slen1) as u8;
+            }
+        }
+        for band in scalefactors.short[6..12].iter_mut() {
+            for sf in band.iter_mut() {
+                *sf = reader.read_bits(slen2) as u8;
+            }
+        }
+    } else {
+        for (i, &(start, end)) in SCFSI_BANDS.iter().enumerate() {
+            if gr == 1 && scfsi[i] {
+                scalefactors.long[start..end].copy_from_slice(&first_granule.long[start..end]);
+            } else {
+                let slen = if i < 2 { slen1 } else { slen2 };
+                for sf in scalefactors.long[start..end].iter_mut() {
+                    *sf = reader.read_bits(slen) as u8;
+                }
+            }
+        }
+    }
+
+    scalefactors
+}
+
+fn read_lsf(
+    reader: &mut BitReader,
+    info: &Mp3GranuleInfo,
+    intensity_channel: bool,
+) -> Mp3Scalefactors {
+    let mut scalefactors = Mp3Scalefactors::default();
+
+    let (slen, row) = if intensity_channel {
+        let sfc = info.scalefac_compress >> 1;
+        match sfc {
+            0..=179 => ([sfc / 36, sfc % 36 / 6, sfc % 6, 0], 3),
+            180..=243 => {
+                let sfc = sfc - 180;
+                ([sfc / 16, sfc % 16 / 4, sfc % 4, 0], 4)
+            }
+            _ => {
+                let sfc = sfc - 244;
+                ([sfc / 3, sfc % 3, 0, 0], 5)
+            }
+        }
+    } else {
+        let sfc = info.scalefac_compress;
+        match sfc {
+            0..=399 => ([sfc / 80, sfc / 16 % 5, sfc % 16 / 4, sfc % 4], 0),
+            400..=499 => {
+                let sfc = sfc - 400;
+                ([sfc / 20, sfc / 4 % 5, sfc % 4, 0], 1)
+            }
+            _ => {
+                let sfc = sfc - 500;
+                ([sfc / 3, sfc % 3, 0, 0], 2)
+            }
+        }
+    };
+
+    let kind = match (info.is_short_block(), info.mixed_block) {
+        (false, _) => 0,
+        (true, false) => 1,
+        (true, true) => 2,
+    };
+
+    // scalefactors are stored sequentially: long bands first (mixed blocks use 6),
+    // followed by short bands with their three windows
+    let long_bands = match kind {
+        0 => 21,
+        1 => 0,
+        _ => 6,
+    };
+    let short_start = if kind == 2 { 3 } else { 0 };
+    let mut index = 0;
+    for (partition, &count) in LSF_PARTITIONS[row][kind].iter().enumerate() {
+        let bits = slen[partition] as usize;
+        scalefactors.lsf_slen[partition] = bits as u8;
+        scalefactors.lsf_counts[partition] = count as u8;
+        for _ in 0..count {
+            let value = reader.read_bits(bits) as u8;
+            if index < long_bands {
+                scalefactors.long[index] = value;
+            } else {
+                let short_index = index - long_bands;
+                scalefactors.short[short_start + short_index / 3][short_index % 3] = value;
+            }
+            index += 1;
+        }
+    }
+
+    scalefactors
+}
+
+#[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
+mod tests {
+    use super::*;
+
+    fn side_info(info: Mp3GranuleInfo, scfsi: [bool; 4]) -> Mp3SideInfo {
+        Mp3SideInfo {
+            main_data_begin: 0,
+            private_bits: 0,
+            scfsi: [scfsi, [false; 4]],
+            granules: vec![vec![info], vec![info]],
+        }
+    }
+
+    #[test]
+    fn test_read_mpeg1_long() {
+        // V1 layer 3 mono
+        let header = Mp3FrameHeader::new(0xFFFB90C0);
+        let info = Mp3GranuleInfo {
+            // slen1 = 1, slen2 = 2
+            scalefac_compress: 6,
+            ..Default::default()
+        };
+        // 11 scalefactors of 1 bit followed by 10 scalefactors of 2 bits
+        let data = [
+            0b_1010_1010,
+            0b_101_01_10_1,
+            0b_1_00_01_10_1,
+            0b_1_00_01_10_1,
+            0b_1_0000000,
+        ];
+        let mut reader = BitReader::new(&data);
+        let side_info = side_info(info, [false; 4]);
+        let default = Mp3Scalefactors::default();
+        let scalefactors = Mp3Scalefactors::read(&mut reader, &header, &side_info, 0, 0, &default);
+        assert_eq!(reader.position(), 31);
+        assert_eq!(
+            scalefactors.long,
+            [1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 0]
+        );
+    }
+
+    #[test]
+    fn test_read_mpeg1_scfsi() {
+        let header = Mp3FrameHeader::new(0xFFFB90C0);
+        let info = Mp3GranuleInfo {
+            scalefac_compress: 6,
+            ..Default::default()
+        };
+        let first_granule = Mp3Scalefactors {
+            long: [7; 22],
+            ..Default::default()
+        };
+        // reuse band groups 0 and 2, read 5 scalefactors of 1 bit and 5 of 2 bits
+        let data = [0b_1111_1111, 0b_1111_1110];
+        let mut reader = BitReader::new(&data);
+        let side_info = side_info(info, [true, false, true, false]);
+        let scalefactors =
+            Mp3Scalefactors::read(&mut reader, &header, &side_info, 1, 0, &first_granule);
+        assert_eq!(reader.position(), 15);
+        assert_eq!(
+            scalefactors.long,
+            [7, 7, 7, 7, 7, 7, 1, 1, 1, 1, 1, 7, 7, 7, 7, 7, 3, 3, 3, 3, 3, 0]
+        );
+    }
+
+    #[test]
+    fn test_read_mpeg1_mixed() {
+        let header = Mp3FrameHeader::new(0xFFFB90C0);
+        let info = Mp3GranuleInfo {
+            // slen1 = 4, slen2 = 2
+            scalefac_compress: 14,
+            window_switching: true,
+            block_type: 2,
+            mixed_block: true,
+            ..Default::default()
+        };
+        // 17 scalefactors of 4 bits followed by 18 scalefactors of 2 bits
+        let mut data = vec![0b_0001_0001; 8];
+        data.extend([0b_0001_10_01, 0b_00_11_10_01, 0, 0, 0]);
+        let mut reader = BitReader::new(&data);
+        let side_info = side_info(info, [false; 4]);
+        let default = Mp3Scalefactors::default();
+        let scalefactors = Mp3Scalefactors::read(&mut reader, &header, &side_info, 0, 0, &default);
+        assert_eq!(reader.position(), 8 * 4 + 9 * 4 + 18 * 2);
+        assert_eq!(scalefactors.long[0..8], [1; 8]);
+        assert_eq!(scalefactors.long[8], 0);
+        assert_eq!(scalefactors.short[0], [0; 3]);
+        assert_eq!(scalefactors.short[3..6], [[1; 3]; 3]);
+        assert_eq!(scalefactors.short[6], [2, 1, 0]);
+        assert_eq!(scalefactors.short[7], [3, 2, 1]);
+        assert_eq!(scalefactors.short[8], [0; 3]);
+        assert_eq!(scalefactors.short[12], [0; 3]);
+    }
+
+    #[test]
+    fn test_read_lsf_long() {
+        // V2 layer 3 mono
+        let header = Mp3FrameHeader::new(0xFFF388C0);
+        let info = Mp3GranuleInfo {
+            // slen = [3, 2, 1, 0]
+            scalefac_compress: 3 * 80 + 2 * 16 + 4,
+            ..Default::default()
+        };
+        let data = [
+            0b_001_010_01,
+            0b_1_100_101_1,
+            0b_10_10_11_10,
+            0b_01_00_1_1_0_1,
+            0b_1000_0000,
+        ];
+        let mut reader = BitReader::new(&data);
+        let side_info = side_info(info, [false; 4]);
+        let default = Mp3Scalefactors::default();
+        let scalefactors = Mp3Scalefactors::read(&mut reader, &header, &side_info, 0, 0, &default);
+        assert_eq!(reader.position(), 6 * 3 + 5 * 2 + 5);
+        assert_eq!(scalefactors.lsf_slen, [3, 2, 1, 0]);
+        assert_eq!(
+            scalefactors.long,
+            [1, 2, 3, 4, 5, 6, 2, 3, 2, 1, 0, 1, 1, 0, 1, 1, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_read_lsf_intensity_short() {
+        // V2 layer 3 joint stereo with intensity stereo
+        let header = Mp3FrameHeader::new(0xFFF38850);
+        let info = Mp3GranuleInfo {
+            // sfc = 244 + 5: slen = [1, 2, 0, 0] with 15 and 12 scalefactors
+            scalefac_compress: (244 + 5) << 1,
+            window_switching: true,
+            block_type: 2,
+            ..Default::default()
+        };
+        let data = [
+            0b_1111_1111,
+            0b_1111_1110,
+            0b_1010_1010,
+            0b_1010_1010,
+            0b_1010_1010,
+        ];
+        let mut reader = BitReader::new(&data);
+        let side_info = Mp3SideInfo {
+            main_data_begin: 0,
+            private_bits: 0,
+            scfsi: [[false; 4]; 2],
+            granules: vec![vec![info, info]],
+        };
+        let default = Mp3Scalefactors::default();
+        let scalefactors = Mp3Scalefactors::read(&mut reader, &header, &side_info, 0, 1, &default);
+        assert_eq!(reader.position(), 15 + 12 * 2);
+        assert_eq!(scalefactors.lsf_slen, [1, 2, 0, 0]);
+        assert_eq!(scalefactors.lsf_counts, [15, 12, 9, 0]);
+        assert_eq!(scalefactors.short[0..5], [[1; 3]; 5]);
+        assert_eq!(scalefactors.short[5..9], [[1; 3]; 4]);
+        assert_eq!(scalefactors.short[9], [0; 3]);
+    }
+}
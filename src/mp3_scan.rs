@@ -0,0 +1,359 @@
+// References
+// http://www.datavoyage.com/mpgscript/mpeghdr.htm
+// https://id3.org/id3v2.4.0-structure (appended tags)
+// https://wiki.hydrogenaud.io/index.php?title=APE_Tags_Header
+// https://id3.org/Lyrics3v2
+
+use super::apev2::ApeTag;
+use super::id3v1::{Id3v1Tag, ID3V1_LENGTH};
+use super::id3v2::Id3v2Tag;
+use super::lyrics3::lyrics3_length_at_end;
+use super::mp3_header::Mp3FrameHeader;
+
+/// Content of a region of an MP3 file
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mp3RegionKind {
+    Id3v2,
+    Ape,
+    Id3v1,
+    Lyrics3,
+    Frame,
+    /// Frame cut off by the end of the audio
+    TruncatedFrame,
+    /// Data which is neither a frame nor a tag
+    Junk,
+}
+
+/// A region of an MP3 file
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3Region {
+    pub kind: Mp3RegionKind,
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// How an ID3v2 tag is placed in the file
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Id3v2Placement {
+    /// At the start of the file, or following other tags at the start
+    Start,
+    /// At or after the offset given by the SEEK frame of the previous tag
+    Seek,
+    /// After the audio with a footer, only followed by other tags
+    Appended,
+    /// Anywhere else
+    Embedded,
+}
+
+/// Location of an ID3v2 tag found by `find_id3v2_tags`
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Id3v2Location {
+    pub offset: usize,
+    /// Length including header and footer
+    pub length: usize,
+    /// Major version: 2, 3 or 4
+    pub version: u8,
+    pub placement: Id3v2Placement,
+}
+
+/// Length of the frame starting at the data, None if there is no frame header
+fn frame_length_at(data: &[u8]) -> Option<usize> {
+    let header = Mp3FrameHeader::new(u32::from_be_bytes(data.get(0..4)?.try_into().ok()?));
+    match header.has_sync() {
+        true => header.frame_length().ok(),
+        false => None,
+    }
+}
+
+/// Kind and length of the tag starting at the data, None if there is no tag
+fn tag_at(data: &[u8]) -> Option<(Mp3RegionKind, usize)> {
+    if let Some(length) = Id3v2Tag::length_at_start(data) {
+        return Some((Mp3RegionKind::Id3v2, length));
+    }
+    if let Some(length) = ApeTag::length_at_start(data) {
+        return Some((Mp3RegionKind::Ape, length));
+    }
+    match data.len() >= ID3V1_LENGTH && data.starts_with(b"TAG") {
+        true => Some((Mp3RegionKind::Id3v1, ID3V1_LENGTH)),
+        false => None,
+    }
+}
+
+/// Whether the frame of the given length at the start of the data is followed by a frame,
+/// a tag or the end of the data
+fn is_confirmed_frame(data: &[u8], length: usize) -> bool {
+    length == data.len()
+        || frame_length_at(&data[length..]).is_some()
+        || tag_at(&data[length..]).is_some()
+}
+
+/// Length of the junk at the start of the data, up to the first tag or the first frame
+/// which is followed by a frame, a tag or the end of the data
+pub fn junk_length(data: &[u8]) -> usize {
+    (0..data.len())
+        .find(|&offset| {
+            let rest = &data[offset..];
+            tag_at(rest).is_some()
+                || frame_length_at(rest)
+                    .is_some_and(|length| length <= rest.len() && is_confirmed_frame(rest, length))
+        })
+        .unwrap_or(data.len())
+}
+
+/// Splits file data into tags, frames and junk, in file order.
+/// Tags at the end are located from the end, so an APE tag without header, Lyrics3 tags
+/// and ID3v2 tags with footer following a truncated frame are found.
+/// A frame at the start or following junk is only accepted if it is followed by a frame, a tag
+/// or the end of the data, which skips false sync words in junk.
+pub fn scan(data: &[u8]) -> Vec<Mp3Region> {
+    let mut end = data.len();
+    let mut tail = Vec::new();
+    if end >= ID3V1_LENGTH && Id3v1Tag::parse(&data[end - ID3V1_LENGTH..]).is_some() {
+        end -= ID3V1_LENGTH;
+        tail.push(Mp3Region {
+            kind: Mp3RegionKind::Id3v1,
+            offset: end,
+            length: ID3V1_LENGTH,
+        });
+    }
+    // APE, Lyrics3 and appended ID3v2 tags are found in any order
+    loop {
+        let rest = &data[..end];
+        let found = [
+            (Mp3RegionKind::Ape, ApeTag::length_at_end(rest)),
+            (Mp3RegionKind::Id3v2, Id3v2Tag::length_at_end(rest)),
+            (Mp3RegionKind::Lyrics3, lyrics3_length_at_end(rest)),
+        ]
+        .into_iter()
+        .find(|&(_, length)| length > 0);
+        let Some((kind, length)) = found else {
+            break;
+        };
+        end -= length;
+        tail.insert(
+            0,
+            Mp3Region {
+                kind,
+                offset: end,
+                length,
+            },
+        );
+    }
+
+    let mut regions: Vec<Mp3Region> = Vec::new();
+    let mut offset = 0;
+    while offset < end {
+        let rest = &data[offset..end];
+        let anchored = matches!(regions.last(), Some(region) if region.kind != Mp3RegionKind::Junk);
+        let mut region = None;
+        if let Some((kind, length)) = tag_at(rest) {
+            region = Some((kind, length.min(rest.len())));
+        } else if let Some(length) = frame_length_at(rest) {
+            if length > rest.len() {
+                if anchored {
+                    region = Some((Mp3RegionKind::TruncatedFrame, rest.len()));
+                }
+            } else if anchored || is_confirmed_frame(rest, length) {
+                region = Some((Mp3RegionKind::Frame, length));
+            }
+        }
+        match (region, regions.last_mut()) {
+            (Some((kind, length)), _) => {
+                regions.push(Mp3Region {
+                    kind,
+                    offset,
+                    length,
+                });
+                offset += length;
+            }
+            (None, Some(last)) if last.kind == Mp3RegionKind::Junk => {
+                last.length += 1;
+                offset += 1;
+            }
+            (None, _) => {
+                regions.push(Mp3Region {
+                    kind: Mp3RegionKind::Junk,
+                    offset,
+                    length: 1,
+                });
+                offset += 1;
+            }
+        }
+    }
+    regions.extend(tail);
+    regions
+}
+
+/// Finds all ID3v2 tags of the file, in file order
+pub fn find_id3v2_tags(data: &[u8]) -> Vec<Id3v2Location> {
+    let regions = scan(data);
+    let mut locations = Vec::new();
+    // minimum offset of the next tag given by a SEEK frame
+    let mut seek_target = None;
+    for (i, region) in regions.iter().enumerate() {
+        if region.kind != Mp3RegionKind::Id3v2 {
+            continue;
+        }
+        let bytes = &data[region.offset..region.offset + region.length];
+        let at_start = regions[..i]
+            .iter()
+            .all(|other| other.kind == Mp3RegionKind::Id3v2);
+        let after_audio = regions[i + 1..].iter().all(|other| {
+            [
+                Mp3RegionKind::Id3v2,
+                Mp3RegionKind::Ape,
+                Mp3RegionKind::Lyrics3,
+                Mp3RegionKind::Id3v1,
+            ]
+            .contains(&other.kind)
+        });
+        let placement = if at_start {
+            Id3v2Placement::Start
+        } else if seek_target.is_some_and(|target| region.offset >= target) {
+            Id3v2Placement::Seek
+        } else if after_audio && bytes[3] == 4 && bytes[5] & 0x10 != 0 {
+            Id3v2Placement::Appended
+        } else {
+            Id3v2Placement::Embedded
+        };
+        seek_target = Id3v2Tag::parse(bytes)
+            .and_then(|tag| tag.seek_offset())
+            .map(|offset| region.offset + region.length + offset);
+        locations.push(Id3v2Location {
+            offset: region.offset,
+            length: region.length,
+            version: bytes[3],
+            placement,
+        });
+    }
+    locations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::id3v2::Id3v2Frame;
+    use super::super::mp3_header::{Mp3ChannelMode, Mp3Layer, Mp3Version};
+    use super::super::mp3_stream::Mp3Stream;
+    use super::super::mp3_synth::Mp3Synth;
+    use super::*;
+
+    fn synth(frames: usize) -> Mp3Synth {
+        let header = Mp3FrameHeader::from_parameters(
+            Mp3Version::V1,
+            Mp3Layer::Layer3,
+            128,
+            44100,
+            Mp3ChannelMode::JointStereo,
+        )
+        .unwrap();
+        Mp3Synth::new(header, frames)
+    }
+
+    fn kinds(regions: &[Mp3Region]) -> Vec<Mp3RegionKind> {
+        regions.iter().map(|region| region.kind).collect()
+    }
+
+    #[test]
+    fn test_scan_tags_and_frames() {
+        let mut synth = synth(3);
+        synth.id3v2 = Some(Id3v2Tag::default());
+        let mut ape = ApeTag::default();
+        ape.set("Title", "Scan");
+        synth.ape = Some(ape);
+        synth.id3v1 = Some(Id3v1Tag::default());
+        let mut data = synth.to_bytes();
+        let lyrics3 = b"LYRICSBEGINLyricsLYRICSEND";
+        data.splice(data.len() - 128..data.len() - 128, *lyrics3);
+        let regions = scan(&data);
+        use Mp3RegionKind::*;
+        assert_eq!(
+            kinds(&regions),
+            [Id3v2, Frame, Frame, Frame, Ape, Lyrics3, Id3v1]
+        );
+        assert_eq!(regions[1].offset, regions[0].length);
+        assert_eq!(regions[1].length, 417);
+        let last = regions.last().unwrap();
+        assert_eq!(last.offset + last.length, data.len());
+    }
+
+    #[test]
+    fn test_scan_junk_and_truncation() {
+        let mut synth = synth(4);
+        synth.junk = vec![(0, vec![0xFF, 0xFB, 0x90]), (2, vec![0x12; 10])];
+        synth.truncate = 100;
+        let regions = scan(&synth.to_bytes());
+        use Mp3RegionKind::*;
+        assert_eq!(
+            kinds(&regions),
+            [Junk, Frame, Frame, Junk, Frame, TruncatedFrame]
+        );
+        assert_eq!(regions[0].length, 3);
+        assert_eq!(regions[3].length, 10);
+        assert_eq!(regions[5].length, 317);
+    }
+
+    #[test]
+    fn test_find_id3v2_tags() {
+        let tag = |title: &str, seek: Option<u32>| {
+            let mut tag = Id3v2Tag::default();
+            tag.set_text("TIT2", title);
+            if let Some(seek) = seek {
+                tag.frames.push(Id3v2Frame {
+                    id: "SEEK".to_string(),
+                    flags: 0,
+                    data: seek.to_be_bytes().to_vec(),
+                });
+            }
+            tag
+        };
+        let mut synth = synth(6);
+        synth.id3v2 = Some(tag("First", None));
+        synth.junk = vec![
+            (0, tag("Second", Some(417)).to_bytes()),
+            (1, tag("Seek", None).to_bytes()),
+            (4, tag("Embedded", None).to_bytes()),
+            (6, tag("Appended", None).to_bytes_with_footer()),
+        ];
+        synth.truncate = 100;
+        synth.id3v1 = Some(Id3v1Tag::default());
+        let data = synth.to_bytes();
+        let regions = scan(&data);
+        use Mp3RegionKind::*;
+        assert_eq!(
+            kinds(&regions),
+            [
+                Id3v2,
+                Id3v2,
+                Frame,
+                Id3v2,
+                Frame,
+                Frame,
+                Frame,
+                Id3v2,
+                Frame,
+                TruncatedFrame,
+                Id3v2,
+                Id3v1
+            ]
+        );
+        let locations = find_id3v2_tags(&data);
+        let placements: Vec<Id3v2Placement> = locations
+            .iter()
+            .map(|location| location.placement)
+            .collect();
+        use Id3v2Placement::*;
+        assert_eq!(placements, [Start, Start, Seek, Embedded, Appended]);
+        assert_eq!(locations[3].offset, regions[7].offset);
+        assert_eq!(locations[4].version, 4);
+
+        // the stream skips the tags within and after the audio without scanning
+        let stream = Mp3Stream::parse(&data).unwrap();
+        assert_eq!(stream.id3v2.len(), regions[2].offset);
+        assert_eq!(stream.frames.len(), 5);
+        assert_eq!(stream.id3v1.len(), ID3V1_LENGTH);
+    }
+}
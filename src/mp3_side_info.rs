@@ -0,0 +1,305 @@
+// References
+// ISO/IEC 11172-3 2.4.1.7 and ISO/IEC 13818-3 2.4.1.7
+// http://www.mp3-tech.org/programmer/docs/mp3_theory.pdf
+
+use super::bit_reader::BitReader;
+use super::bit_writer::BitWriter;
+use super::mp3_header::{Mp3FrameHeader, Mp3Version};
+use super::MPeakError;
+
+/// Side information of one channel in one granule
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3GranuleInfo {
+    pub part2_3_length: u16,
+    pub big_values: u16,
+    pub global_gain: u8,
+    /// 4 bits for MPEG 1, 9 bits for MPEG 2 and 2.5
+    pub scalefac_compress: u16,
+    pub window_switching: bool,
+    pub block_type: u8,
+    pub mixed_block: bool,
+    pub table_select: [u8; 3],
+    pub subblock_gain: [u8; 3],
+    pub region0_count: u8,
+    pub region1_count: u8,
+    /// Read from the stream for MPEG 1, derived from scalefac_compress for MPEG 2 and 2.5
+    pub preflag: bool,
+    pub scalefac_scale: bool,
+    pub count1table_select: bool,
+}
+
+impl Mp3GranuleInfo {
+    pub fn is_short_block(&self) -> bool {
+        self.window_switching && self.block_type == 2
+    }
+}
+
+/// Layer III side information of a frame
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3SideInfo {
+    pub main_data_begin: u16,
+    pub private_bits: u8,
+    /// Scalefactor selection information per channel and band group, MPEG 1 only
+    pub scfsi: [[bool; 4]; 2],
+    /// Granule information indexed by granule and channel
+    pub granules: Vec<Vec<Mp3GranuleInfo>>,
+}
+
+impl Mp3SideInfo {
+    /// Parses the side information following the frame header (and crc).
+    pub fn parse(header: &Mp3FrameHeader, data: &[u8]) -> Result<Mp3SideInfo, MPeakError> {
+        if data.len() < header.side_info_length() {
+            return Err(MPeakError::InvalidSideInfo);
+        }
+
+        let is_mpeg1 = header.version() == Mp3Version::V1;
+        let channels = header.channels();
+        let mut reader = BitReader::new(data);
+        let mut side_info = Mp3SideInfo {
+            main_data_begin: 0,
+            private_bits: 0,
+            scfsi: [[false; 4]; 2],
+            granules: vec![vec![Mp3GranuleInfo::default(); channels]; header.granules()],
+        };
+
+        if is_mpeg1 {
+            side_info.main_data_begin = reader.read_bits(9) as u16;
+            side_info.private_bits = reader.read_bits(if channels == 1 { 5 } else { 3 }) as u8;
+            for scfsi in side_info.scfsi.iter_mut().take(channels) {
+                for band in scfsi.iter_mut() {
+                    *band = reader.read_bit();
+                }
+            }
+        } else {
+            side_info.main_data_begin = reader.read_bits(8) as u16;
+            side_info.private_bits = reader.read_bits(channels) as u8;
+        }
+
+        // the right channel of intensity stereo frames uses a different scalefac_compress layout
+        let intensity_stereo = header.is_intensity_stereo();
+        for granule in side_info.granules.iter_mut() {
+            for (ch, info) in granule.iter_mut().enumerate() {
+                info.part2_3_length = reader.read_bits(12) as u16;
+                info.big_values = reader.read_bits(9) as u16;
+                if info.big_values > 288 {
+                    return Err(MPeakError::InvalidSideInfo);
+                }
+                info.global_gain = reader.read_bits(8) as u8;
+                info.scalefac_compress = reader.read_bits(if is_mpeg1 { 4 } else { 9 }) as u16;
+                info.window_switching = reader.read_bit();
+                if info.window_switching {
+                    info.block_type = reader.read_bits(2) as u8;
+                    if info.block_type == 0 {
+                        return Err(MPeakError::InvalidSideInfo);
+                    }
+                    info.mixed_block = reader.read_bit();
+                    info.table_select[0] = reader.read_bits(5) as u8;
+                    info.table_select[1] = reader.read_bits(5) as u8;
+                    for gain in info.subblock_gain.iter_mut() {
+                        *gain = reader.read_bits(3) as u8;
+                    }
+                    // implicit region boundaries, see ISO/IEC 11172-3 2.4.2.7
+                    info.region0_count = if info.is_short_block() && !info.mixed_block {
+                        8
+                    } else {
+                        7
+                    };
+                    info.region1_count = 36;
+                } else {
+                    for table in info.table_select.iter_mut() {
+                        *table = reader.read_bits(5) as u8;
+                    }
+                    info.region0_count = reader.read_bits(4) as u8;
+                    info.region1_count = reader.read_bits(3) as u8;
+                }
+                info.preflag = if is_mpeg1 {
+                    reader.read_bit()
+                } else {
+                    !(intensity_stereo && ch == 1) && info.scalefac_compress >= 500
+                };
+                info.scalefac_scale = reader.read_bit();
+                info.count1table_select = reader.read_bit();
+            }
+        }
+
+        Ok(side_info)
+    }
+
+    /// Serializes the side information, the inverse of `parse`.
+    /// Implicit fields (region counts of window switching granules, preflag of
+    /// MPEG 2 and 2.5) are not written.
+    pub fn to_bytes(&self, header: &Mp3FrameHeader) -> Vec<u8> {
+        let is_mpeg1 = header.version() == Mp3Version::V1;
+        let channels = header.channels();
+        let mut writer = BitWriter::new();
+
+        if is_mpeg1 {
+            writer.write_bits(self.main_data_begin as u32, 9);
+            writer.write_bits(self.private_bits as u32, if channels == 1 { 5 } else { 3 });
+            for scfsi in self.scfsi.iter().take(channels) {
+                for &band in scfsi.iter() {
+                    writer.write_bit(band);
+                }
+            }
+        } else {
+            writer.write_bits(self.main_data_begin as u32, 8);
+            writer.write_bits(self.private_bits as u32, channels);
+        }
+
+        for info in self.granules.iter().flatten() {
+            writer.write_bits(info.part2_3_length as u32, 12);
+            writer.write_bits(info.big_values as u32, 9);
+            writer.write_bits(info.global_gain as u32, 8);
+            writer.write_bits(info.scalefac_compress as u32, if is_mpeg1 { 4 } else { 9 });
+            writer.write_bit(info.window_switching);
+            if info.window_switching {
+                writer.write_bits(info.block_type as u32, 2);
+                writer.write_bit(info.mixed_block);
+                writer.write_bits(info.table_select[0] as u32, 5);
+                writer.write_bits(info.table_select[1] as u32, 5);
+                for &gain in info.subblock_gain.iter() {
+                    writer.write_bits(gain as u32, 3);
+                }
+            } else {
+                for &table in info.table_select.iter() {
+                    writer.write_bits(table as u32, 5);
+                }
+                writer.write_bits(info.region0_count as u32, 4);
+                writer.write_bits(info.region1_count as u32, 3);
+            }
+            if is_mpeg1 {
+                writer.write_bit(info.preflag);
+            }
+            writer.write_bit(info.scalefac_scale);
+            writer.write_bit(info.count1table_select);
+        }
+
+        let mut data = writer.into_bytes();
+        data.resize(header.side_info_length(), 0);
+        data
+    }
+
+    /// Total number of main data bits used by all granules and channels
+    pub fn main_data_bits(&self) -> usize {
+        self.granules
+            .iter()
+            .flatten()
+            .map(|info| info.part2_3_length as usize)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mpeg1_mono() {
+        // V1 layer 3 128kbit/s 44100Hz mono
+        let header = Mp3FrameHeader::new(0xFFFB90C0);
+        let data = [
+            0b_0000_0001, // main_data_begin
+            0b_1000_0010, // main_data_begin, private bits, scfsi
+            0b_1100_0000, // scfsi, part2_3_length
+            0b_0001_0000, // part2_3_length, big_values
+            0b_0000_0101, // big_values, global_gain
+            0b_0101_1100, // global_gain, scalefac_compress
+            0b_1000_0000, // scalefac_compress, window_switching, table_select
+            0b_0000_0111, // table_select
+            0b_1110_0000, // table_select, region0_count, region1_count
+            0b_1010_1000, // region1_count, preflag, scalefac_scale, count1table_select
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ];
+        let side_info = Mp3SideInfo::parse(&header, &data).unwrap();
+        assert_eq!(side_info.main_data_begin, 3);
+        assert_eq!(side_info.private_bits, 0);
+        assert_eq!(side_info.scfsi[0], [true, false, true, true]);
+        assert_eq!(side_info.granules.len(), 2);
+        assert_eq!(side_info.granules[0].len(), 1);
+
+        let info = &side_info.granules[0][0];
+        assert_eq!(info.part2_3_length, 4);
+        assert_eq!(info.big_values, 2);
+        assert_eq!(info.global_gain, 174);
+        assert_eq!(info.scalefac_compress, 4);
+        assert!(!info.window_switching);
+        assert_eq!(info.table_select, [0, 1, 31]);
+        assert_eq!(info.region0_count, 0);
+        assert_eq!(info.region1_count, 2);
+        assert!(info.preflag);
+        assert!(!info.scalefac_scale);
+        assert!(info.count1table_select);
+    }
+
+    #[test]
+    fn test_parse_window_switching() {
+        // V2 layer 3 64kbit/s 16000Hz mono
+        let header = Mp3FrameHeader::new(0xFFF388C0);
+        let data = [
+            0b_0000_0010, // main_data_begin
+            0b_1000_0000, // private bits, part2_3_length
+            0b_0000_0000, // part2_3_length, big_values
+            0b_0000_0000, // big_values, global_gain
+            0b_0000_0011, // global_gain, scalefac_compress
+            0b_1111_0001, // scalefac_compress, window_switching, block_type
+            0b_1001_0000, // block_type, mixed_block, table_select
+            0b_0010_0110, // table_select, subblock_gain
+            0b_1010_1110, // subblock_gain, scalefac_scale, count1table_select
+        ];
+        let side_info = Mp3SideInfo::parse(&header, &data).unwrap();
+        assert_eq!(side_info.main_data_begin, 2);
+        assert_eq!(side_info.private_bits, 1);
+        assert_eq!(side_info.granules.len(), 1);
+
+        let info = &side_info.granules[0][0];
+        assert_eq!(info.scalefac_compress, 504);
+        assert!(info.window_switching);
+        assert_eq!(info.block_type, 2);
+        assert!(info.is_short_block());
+        assert!(!info.mixed_block);
+        assert_eq!(info.table_select, [16, 4, 0]);
+        assert_eq!(info.subblock_gain, [6, 5, 3]);
+        assert_eq!(info.region0_count, 8);
+        assert!(info.preflag);
+        assert!(info.scalefac_scale);
+        assert!(!info.count1table_select);
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        // V1 layer 3 128kbit/s 44100Hz mono
+        let header = Mp3FrameHeader::new(0xFFFB90C0);
+        let mut data = [0; 17];
+        data[0..10].copy_from_slice(&[1, 130, 192, 16, 5, 92, 128, 7, 224, 168]);
+        let side_info = Mp3SideInfo::parse(&header, &data).unwrap();
+        assert_eq!(side_info.to_bytes(&header), data);
+
+        // V2 layer 3 64kbit/s 16000Hz mono with window switching
+        let header = Mp3FrameHeader::new(0xFFF388C0);
+        let data = [2, 128, 0, 0, 3, 241, 144, 38, 174];
+        let mut side_info = Mp3SideInfo::parse(&header, &data).unwrap();
+        assert_eq!(side_info.to_bytes(&header), data);
+
+        side_info.main_data_begin = 0;
+        side_info.granules[0][0].global_gain = 255;
+        let parsed = Mp3SideInfo::parse(&header, &side_info.to_bytes(&header)).unwrap();
+        assert_eq!(parsed, side_info);
+    }
+
+    #[test]
+    fn test_parse_too_short() {
+        let header = Mp3FrameHeader::new(0xFFFB9000);
+        assert_eq!(
+            Mp3SideInfo::parse(&header, &[0; 17]),
+            Err(MPeakError::InvalidSideInfo)
+        );
+    }
+}
@@ -0,0 +1,258 @@
+// References
+// https://id3.org/id3v2.4.0-frames (TIT2, TPE1, TALB, TRCK)
+
+use super::cue_sheet::CueSheet;
+use super::id3v2::Id3v2Tag;
+use super::mp3_edit::{cut, Mp3Cut, Mp3ReservoirHandling};
+use super::mp3_stream::Mp3Stream;
+use super::waveform::Waveform;
+
+/// Metadata written into the ID3v2 tag of a track
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3TrackInfo {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub album: Option<String>,
+    /// Track number and number of tracks, by default the position among the split tracks
+    pub track: Option<(u32, u32)>,
+}
+
+/// Splits the stream before each of the given frame indices without re-encoding.
+/// Each track keeps the ID3v2 tag of the stream with the track number (and the
+/// metadata from `infos` of the point the track starts at, if given) set.
+/// Of repeated points the first is used. The ID3v1 tag is dropped.
+pub fn split(
+    stream: &Mp3Stream,
+    points: &[usize],
+    infos: &[Mp3TrackInfo],
+    handling: Mp3ReservoirHandling,
+) -> Vec<Mp3Cut> {
+    let mut bounds: Vec<(usize, Option<&Mp3TrackInfo>)> = points
+        .iter()
+        .enumerate()
+        .map(|(i, &point)| (point.min(stream.frames.len()), infos.get(i)))
+        .collect();
+    bounds.push((0, None));
+    bounds.push((stream.frames.len(), None));
+    // stable, so the given points precede the added ones
+    bounds.sort_by_key(|&(point, _)| point);
+    bounds.dedup_by_key(|&mut (point, _)| point);
+
+    let total = bounds.len() - 1;
+    let mut tracks = Vec::new();
+    for (i, range) in bounds.windows(2).enumerate() {
+        let mut track = cut(stream, range[0].0, range[1].0, handling);
+        let info = range[0].1.cloned().unwrap_or_default();
+        let number = info.track.unwrap_or((i as u32 + 1, total as u32));
+        track.stream.id3v2 = track_tag(&stream.id3v2, number, &info).to_bytes();
+        track.stream.id3v1 = Vec::new();
+        tracks.push(track);
+    }
+    tracks
+}
+
+/// Splits the stream at the start of each track of the CUE sheet and tags the tracks
+/// with their number, title and performer. The album performer is used for tracks without
+/// one. Audio before the first track becomes a track numbered 0.
+pub fn split_cue(
+    stream: &Mp3Stream,
+    sheet: &CueSheet,
+    handling: Mp3ReservoirHandling,
+) -> Vec<Mp3Cut> {
+    let mut points: Vec<usize> = sheet
+        .tracks
+        .iter()
+        .map(|track| stream.frame_at(track.start))
+        .collect();
+    let total = sheet.tracks.len() as u32;
+    let mut infos: Vec<Mp3TrackInfo> = sheet
+        .tracks
+        .iter()
+        .map(|track| Mp3TrackInfo {
+            title: track.title.clone(),
+            performer: track.performer.clone().or(sheet.performer.clone()),
+            album: sheet.title.clone(),
+            track: Some((track.number, total)),
+        })
+        .collect();
+    // audio before the first track becomes a track without metadata
+    if points.first().is_some_and(|&point| point > 0) {
+        points.insert(0, 0);
+        infos.insert(
+            0,
+            Mp3TrackInfo {
+                track: Some((0, total)),
+                ..Default::default()
+            },
+        );
+    }
+    split(stream, &points, &infos, handling)
+}
+
+/// Tag of a track based on the tag of the whole stream.
+/// An ID3v2.2 tag is replaced, its frame identifiers differ.
+fn track_tag(id3v2: &[u8], (number, total): (u32, u32), info: &Mp3TrackInfo) -> Id3v2Tag {
+    let mut tag = Id3v2Tag::parse(id3v2)
+        .filter(|tag| tag.version > 2)
+        .unwrap_or_default();
+    tag.set_text("TRCK", &format!("{}/{}", number, total));
+    for (id, value) in [
+        ("TIT2", &info.title),
+        ("TPE1", &info.performer),
+        ("TALB", &info.album),
+    ] {
+        if let Some(value) = value {
+            tag.set_text(id, value);
+        }
+    }
+    tag
+}
+
+/// Times in seconds in the middle of each silence of at least `min_duration` seconds,
+/// where the peak amplitude of all channels stays below `threshold`.
+/// Silences at the start and end are not included.
+pub fn silence_points(waveform: &Waveform, threshold: f32, min_duration: f64) -> Vec<f64> {
+    let channels = waveform.channels.max(1);
+    let pixel_duration = waveform.samples_per_pixel as f64 / waveform.sample_rate.max(1) as f64;
+    let silent: Vec<bool> = waveform
+        .peaks
+        .chunks(channels)
+        .map(|pixel| {
+            pixel
+                .iter()
+                .all(|peak| peak.max < threshold && -peak.min < threshold)
+        })
+        .collect();
+
+    let mut points = Vec::new();
+    let mut start = None;
+    for (i, &is_silent) in silent.iter().enumerate() {
+        match (is_silent, start) {
+            (true, None) => start = Some(i),
+            (false, Some(first)) => {
+                let duration = (i - first) as f64 * pixel_duration;
+                if first > 0 && duration >= min_duration {
+                    points.push((first + i) as f64 / 2.0 * pixel_duration);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cue_sheet::CueTrack;
+    use super::super::mp3_frame::Mp3Frame;
+    use super::super::mp3_header::Mp3FrameHeader;
+    use super::super::waveform::Peak;
+    use super::*;
+
+    fn stream(frames: usize) -> Mp3Stream {
+        let mut data = vec![0; 417];
+        data[0..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        let frame = Mp3Frame::new(Mp3FrameHeader::new(0xFFFB9000), data, 0);
+        let mut tag = Id3v2Tag::default();
+        tag.set_text("TIT2", "Mix");
+        Mp3Stream {
+            id3v2: tag.to_bytes(),
+            xing: None,
+            frames: vec![frame; frames],
+            ape: Vec::new(),
+            id3v1: b"TAG".to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        let tracks = split(&stream(10), &[4, 0, 7, 20], &[], Mp3ReservoirHandling::Zero);
+        let lengths: Vec<usize> = tracks.iter().map(|t| t.stream.frames.len()).collect();
+        // later tracks start with a frame covering the decoder delay
+        assert_eq!(lengths, vec![4, 4, 4]);
+        let tag = Id3v2Tag::parse(&tracks[1].stream.id3v2).unwrap();
+        assert_eq!(tag.text("TRCK"), Some("2/3".to_string()));
+        assert_eq!(tag.text("TIT2"), Some("Mix".to_string()));
+        assert!(tracks[1].stream.id3v1.is_empty());
+
+        // the metadata follows its point through sorting and deduplication
+        let info = |title: &str| Mp3TrackInfo {
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+        let infos = [info("Seven"), info("Four"), info("Repeated"), info("Start")];
+        let tracks = split(
+            &stream(10),
+            &[7, 4, 4, 0],
+            &infos,
+            Mp3ReservoirHandling::Zero,
+        );
+        let titles: Vec<String> = tracks
+            .iter()
+            .map(|track| Id3v2Tag::parse(&track.stream.id3v2).unwrap())
+            .map(|tag| tag.text("TIT2").unwrap())
+            .collect();
+        assert_eq!(titles, ["Start", "Four", "Seven"]);
+    }
+
+    #[test]
+    fn test_split_cue() {
+        let sheet = CueSheet {
+            title: Some("Album".to_string()),
+            performer: Some("DJ".to_string()),
+            tracks: vec![
+                CueTrack {
+                    number: 1,
+                    title: Some("First".to_string()),
+                    performer: None,
+                    start: 0.05,
+                },
+                CueTrack {
+                    number: 2,
+                    title: Some("Second".to_string()),
+                    performer: Some("Artist".to_string()),
+                    start: 0.15,
+                },
+            ],
+        };
+        // frames of 26.1ms
+        let tracks = split_cue(&stream(10), &sheet, Mp3ReservoirHandling::Zero);
+        let lengths: Vec<usize> = tracks.iter().map(|t| t.stream.frames.len()).collect();
+        assert_eq!(lengths, vec![1, 5, 6]);
+        let tag = Id3v2Tag::parse(&tracks[1].stream.id3v2).unwrap();
+        assert_eq!(tag.text("TIT2"), Some("First".to_string()));
+        assert_eq!(tag.text("TRCK"), Some("1/2".to_string()));
+        assert_eq!(tag.text("TPE1"), Some("DJ".to_string()));
+        assert_eq!(tag.text("TALB"), Some("Album".to_string()));
+        let tag = Id3v2Tag::parse(&tracks[2].stream.id3v2).unwrap();
+        assert_eq!(tag.text("TPE1"), Some("Artist".to_string()));
+        assert_eq!(tag.text("TRCK"), Some("2/2".to_string()));
+        let tag = Id3v2Tag::parse(&tracks[0].stream.id3v2).unwrap();
+        assert_eq!(tag.text("TRCK"), Some("0/2".to_string()));
+    }
+
+    #[test]
+    fn test_silence_points() {
+        let loud = Peak {
+            min: -0.5,
+            max: 0.5,
+            rms: 0.3,
+        };
+        let quiet = Peak {
+            min: -0.001,
+            max: 0.001,
+            rms: 0.0,
+        };
+        let pattern = [quiet, loud, quiet, quiet, quiet, loud, quiet, loud, quiet];
+        let waveform = Waveform {
+            sample_rate: 100,
+            channels: 1,
+            samples_per_pixel: 100,
+            peaks: pattern.to_vec(),
+        };
+        assert_eq!(silence_points(&waveform, 0.01, 2.0), vec![3.5]);
+        assert_eq!(silence_points(&waveform, 0.01, 1.0), vec![3.5, 6.5]);
+    }
+}
@@ -0,0 +1,212 @@
+// References
+// ISO/IEC 11172-3 2.4.3.4.9 and ISO/IEC 13818-3 2.4.3.2 (intensity stereo)
+// http://www.mp3-tech.org/programmer/docs/mp3_theory.pdf
+
+use super::mp3_bands::Mp3Band;
+use super::mp3_header::{Mp3FrameHeader, Mp3Version};
+use super::mp3_scalefactors::Mp3Scalefactors;
+use super::mp3_side_info::Mp3GranuleInfo;
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+/// Applies mid/side and intensity stereo processing to the requantized spectrum of a granule.
+/// `right_info` and `right_scalefactors` belong to the right channel, which carries the
+/// intensity positions. `bands` is the layout of the granule before reordering.
+pub fn process(
+    header: &Mp3FrameHeader,
+    bands: &[Mp3Band],
+    right_info: &Mp3GranuleInfo,
+    right_scalefactors: &Mp3Scalefactors,
+    samples: &mut [[f32; 576]; 2],
+) {
+    let mid_side = header.is_mid_side_stereo();
+    let intensity = header.is_intensity_stereo();
+    if !mid_side && !intensity {
+        return;
+    }
+
+    // intensity stereo starts above the last band with nonzero values in the right channel,
+    // determined per window for short blocks
+    let mut intensity_start = [0; 3];
+    if intensity {
+        for (i, band) in bands.iter().enumerate() {
+            if samples[1][band.start..band.end].iter().any(|&x| x != 0.0) {
+                intensity_start[band.window.unwrap_or(0)] = i + 1;
+            }
+        }
+        if bands.iter().all(|band| band.window.is_none()) || right_info.mixed_block {
+            let start = *intensity_start.iter().max().unwrap();
+            intensity_start = [start; 3];
+        }
+    }
+
+    let positions = intensity_positions(header, bands, right_info, right_scalefactors);
+    for (i, band) in bands.iter().enumerate() {
+        let window = band.window.unwrap_or(0);
+        let position = if intensity && i >= intensity_start[window] {
+            positions[i]
+        } else {
+            None
+        };
+        let (left, right) = samples.split_at_mut(1);
+        let left = &mut left[0][band.start..band.end];
+        let right = &mut right[0][band.start..band.end];
+        if let Some((kl, kr)) = position {
+            for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+                let value = *l;
+                *l = value * kl;
+                *r = value * kr;
+            }
+        } else if mid_side {
+            for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+                let (mid, side) = (*l, *r);
+                *l = (mid + side) * FRAC_1_SQRT_2;
+                *r = (mid - side) * FRAC_1_SQRT_2;
+            }
+        }
+    }
+}
+
+/// Left and right intensity stereo factors of each band, None for illegal positions.
+/// The last long band and the last short band of each window carry no scalefactor and
+/// reuse the position of the band below.
+fn intensity_positions(
+    header: &Mp3FrameHeader,
+    bands: &[Mp3Band],
+    info: &Mp3GranuleInfo,
+    scalefactors: &Mp3Scalefactors,
+) -> Vec<Option<(f32, f32)>> {
+    let is_mpeg1 = header.version() == Mp3Version::V1;
+    let long_bands = bands.iter().filter(|band| band.window.is_none()).count();
+    let first_short = bands.get(long_bands).map_or(0, |band| band.index);
+
+    let mut positions = Vec::with_capacity(bands.len());
+    for band in bands.iter() {
+        // index of the scalefactor in bitstream order
+        let (index, scalefactor) = match band.window {
+            None => {
+                let band = usize::min(band.index, 20);
+                (band, scalefactors.long[band])
+            }
+            Some(window) => {
+                let band = usize::min(band.index, 11);
+                let index = long_bands + (band - first_short) * 3 + window;
+                (index, scalefactors.short[band][window])
+            }
+        };
+
+        let position = if is_mpeg1 {
+            (scalefactor < 7).then(|| {
+                let ratio = (scalefactor as f32 * PI / 12.0).tan();
+                (ratio / (1.0 + ratio), 1.0 / (1.0 + ratio))
+            })
+        } else {
+            let slen = scalefactors
+                .lsf_counts
+                .iter()
+                .zip(scalefactors.lsf_slen.iter())
+                .scan(0, |end, (&count, &slen)| {
+                    *end += count as usize;
+                    Some((*end, slen))
+                })
+                .find(|&(end, _)| index < end)
+                .map_or(0, |(_, slen)| slen);
+            let illegal = slen > 0 && scalefactor as usize == (1 << slen) - 1;
+            let scale = if info.scalefac_compress & 1 == 0 {
+                2f32.powf(-0.25)
+            } else {
+                FRAC_1_SQRT_2
+            };
+            let factor = scale.powi((scalefactor as i32 + 1) / 2);
+            (!illegal).then_some(if scalefactor & 1 == 1 {
+                (factor, 1.0)
+            } else {
+                (1.0, factor)
+            })
+        };
+        positions.push(position);
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mp3_bands::Mp3Bands;
+    use super::*;
+
+    #[test]
+    fn test_mid_side() {
+        // V1 layer 3 joint stereo with mid/side stereo
+        let header = Mp3FrameHeader::new(0xFFFB9060);
+        let info = Mp3GranuleInfo::default();
+        let bands = Mp3Bands::new(&header).granule_bands(&info);
+        let mut samples = [[0.0; 576]; 2];
+        samples[0][0] = 3.0 * FRAC_1_SQRT_2;
+        samples[1][0] = FRAC_1_SQRT_2;
+        process(
+            &header,
+            &bands,
+            &info,
+            &Mp3Scalefactors::default(),
+            &mut samples,
+        );
+        assert!((samples[0][0] - 2.0).abs() < 1e-6);
+        assert!((samples[1][0] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_intensity_mpeg1() {
+        // V1 layer 3 joint stereo with intensity stereo
+        let header = Mp3FrameHeader::new(0xFFFB9050);
+        let info = Mp3GranuleInfo::default();
+        let bands = Mp3Bands::new(&header).granule_bands(&info);
+        let mut scalefactors = Mp3Scalefactors::default();
+        scalefactors.long[2] = 3;
+        scalefactors.long[3] = 7;
+        let mut samples = [[1.0; 576]; 2];
+        samples[1][8..].fill(0.0);
+        process(&header, &bands, &info, &scalefactors, &mut samples);
+        // band 1 holds nonzero right channel values
+        assert_eq!(samples[1][4], 1.0);
+        // position 3 pans to the center
+        assert!((samples[0][8] - 0.5).abs() < 1e-6);
+        assert!((samples[1][8] - 0.5).abs() < 1e-6);
+        // position 7 is illegal
+        assert_eq!(samples[0][12], 1.0);
+        assert_eq!(samples[1][12], 0.0);
+        // position 0 pans to the right
+        assert!(samples[0][16].abs() < 1e-6);
+        assert!((samples[1][16] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_intensity_lsf() {
+        // V2 layer 3 joint stereo with intensity stereo
+        let header = Mp3FrameHeader::new(0xFFF39050);
+        let info = Mp3GranuleInfo {
+            scalefac_compress: 1,
+            ..Default::default()
+        };
+        let bands = Mp3Bands::new(&header).granule_bands(&info);
+        let mut scalefactors = Mp3Scalefactors {
+            lsf_slen: [2, 2, 0, 0],
+            lsf_counts: [7, 7, 7, 0],
+            ..Default::default()
+        };
+        scalefactors.long[0] = 1;
+        scalefactors.long[1] = 2;
+        scalefactors.long[2] = 3;
+        let mut samples = [[1.0; 576]; 2];
+        samples[1].fill(0.0);
+        process(&header, &bands, &info, &scalefactors, &mut samples);
+        assert!((samples[0][0] - FRAC_1_SQRT_2).abs() < 1e-6);
+        assert_eq!(samples[1][0], 1.0);
+        assert_eq!(samples[0][6], 1.0);
+        assert!((samples[1][6] - FRAC_1_SQRT_2).abs() < 1e-6);
+        // position 3 is illegal for 2 bit scalefactors
+        assert_eq!(samples[0][12], 1.0);
+        assert_eq!(samples[1][12], 0.0);
+        // position 0 keeps the left channel
+        assert_eq!(samples[0][18], 1.0);
+        assert_eq!(samples[1][18], 1.0);
+    }
+}
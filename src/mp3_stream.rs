@@ -0,0 +1,168 @@
+// References
+// https://id3.org/ID3v1
+// https://wiki.hydrogenaud.io/index.php?title=APEv2_specification
+// http://gabriel.mp3-tech.org/mp3infotag.html
+
+use super::apev2::ApeTag;
+use super::id3v1::ID3V1_LENGTH;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_xing::Mp3XingTag;
+use super::{get_frames, get_id3_data, get_id3_offset, MPeakError};
+
+/// An MP3 file split into its tags and audio frames
+#[derive(Clone)]
+pub struct Mp3Stream {
+    /// ID3v2 tag preceding the audio, empty if there is none.
+    /// Consecutive tags are kept together, `Id3v2Tag::parse` reads the first.
+    pub id3v2: Vec<u8>,
+    /// Xing/Info tag of the stream. Its frame is not part of `frames`
+    /// and is rebuilt by `to_bytes`.
+    pub xing: Option<Mp3XingTag>,
+    /// Audio frames
+    pub frames: Vec<Mp3Frame>,
+    /// APE tag following the audio, empty if there is none
+    pub ape: Vec<u8>,
+    /// ID3v1 tag following the audio, empty if there is none
+    pub id3v1: Vec<u8>,
+}
+
+impl Mp3Stream {
+    /// Splits file data into tags and frames.
+    /// Incomplete frames, ID3v2 tags within or after the audio and data which is no frame
+    /// are dropped. An ID3v2 tag at the start reaching beyond the data is an error.
+    pub fn parse(data: &[u8]) -> Result<Mp3Stream, MPeakError> {
+        if get_id3_offset(data) as usize > data.len() {
+            return Err(MPeakError::InvalidId3v2Tag);
+        }
+        let id3v2 = get_id3_data(data);
+        let has_id3v1 = data.len() >= id3v2.len() + ID3V1_LENGTH
+            && data[data.len() - ID3V1_LENGTH..].starts_with(b"TAG");
+        let (audio, id3v1) = match has_id3v1 {
+            true => data.split_at(data.len() - ID3V1_LENGTH),
+            false => (data, &[][..]),
+        };
+
+        let (audio, ape) = audio.split_at(audio.len() - ApeTag::length_at_end(audio));
+
+        let mut frames = get_frames(audio)?;
+        frames.retain(|frame| frame.header.frame_length() == Ok(frame.body.data.len()));
+        let xing = frames.first().and_then(Mp3XingTag::parse);
+        if xing.is_some() {
+            frames.remove(0);
+        }
+        Ok(Mp3Stream {
+            id3v2,
+            xing,
+            frames,
+            ape: ape.to_vec(),
+            id3v1: id3v1.to_vec(),
+        })
+    }
+
+    /// Serializes the stream. A new tag frame is built from the frames if the stream
+    /// has a Xing/Info tag, keeping its LAME extension.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.id3v2.clone();
+        if let Some(xing) = &self.xing {
+            if let Some(frame) = Mp3XingTag::build_frame(&self.frames, xing.lame.clone()) {
+                data.extend(&frame.body.data);
+            }
+        }
+        for frame in &self.frames {
+            data.extend(&frame.body.data);
+        }
+        data.extend(&self.ape);
+        data.extend(&self.id3v1);
+        data
+    }
+
+    /// Index of the frame playing at the given time in seconds
+    pub fn frame_at(&self, seconds: f64) -> usize {
+        let mut time = 0.0;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let sample_rate = frame.header.sample_rate().max(1) as f64;
+            time += frame.header.samples_per_frame() as f64 / sample_rate;
+            if time > seconds {
+                return i;
+            }
+        }
+        self.frames.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// V1 layer 3 128kbit/s 44100Hz stereo frame
+    fn frame() -> Vec<u8> {
+        let mut data = vec![0; 417];
+        data[0..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        data
+    }
+
+    fn file() -> Vec<u8> {
+        let mut xing = frame();
+        xing[36..44].copy_from_slice(b"Info\x00\x00\x00\x00");
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x02\x00\x00".to_vec();
+        data.extend(xing);
+        for _ in 0..3 {
+            data.extend(frame());
+        }
+        // truncated frame
+        data.extend(&frame()[..100]);
+        let mut ape = ApeTag::default();
+        ape.set("MP3GAIN_MINMAX", "000,000");
+        data.extend(ape.to_bytes());
+        let mut id3v1 = b"TAG".to_vec();
+        id3v1.resize(128, 0);
+        data.extend(id3v1);
+        data
+    }
+
+    #[test]
+    fn test_parse() {
+        let stream = Mp3Stream::parse(&file()).unwrap();
+        assert_eq!(stream.id3v2.len(), 12);
+        assert!(stream.xing.is_some());
+        assert_eq!(stream.frames.len(), 3);
+        assert!(ApeTag::parse(&stream.ape).is_some());
+        assert_eq!(stream.id3v1.len(), 128);
+    }
+
+    #[test]
+    fn test_parse_junk() {
+        let mut data = Vec::new();
+        for i in 0..4 {
+            if i == 2 {
+                data.extend([0x12; 20]);
+            }
+            data.extend(frame());
+        }
+        assert_eq!(Mp3Stream::parse(&data).unwrap().frames.len(), 4);
+        // ID3v2 tag size beyond the data
+        assert!(matches!(
+            Mp3Stream::parse(b"ID3\x03\x00\x00\x00\x00\x10\x00abc"),
+            Err(MPeakError::InvalidId3v2Tag)
+        ));
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let stream = Mp3Stream::parse(&file()).unwrap();
+        let data = stream.to_bytes();
+        assert_eq!(data.len(), 12 + 4 * 417 + stream.ape.len() + 128);
+        let parsed = Mp3Stream::parse(&data).unwrap();
+        assert_eq!(parsed.xing.unwrap().frames, Some(3));
+        assert_eq!(parsed.frames.len(), 3);
+    }
+
+    #[test]
+    fn test_frame_at() {
+        let stream = Mp3Stream::parse(&file()).unwrap();
+        assert_eq!(stream.frame_at(0.0), 0);
+        // 1152 samples at 44100Hz last 26.1ms
+        assert_eq!(stream.frame_at(0.027), 1);
+        assert_eq!(stream.frame_at(10.0), 3);
+    }
+}
@@ -0,0 +1,244 @@
+// References
+// http://www.datavoyage.com/mpgscript/mpeghdr.htm
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::apev2::ApeTag;
+use super::id3v1::Id3v1Tag;
+use super::id3v2::Id3v2Tag;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::Mp3FrameHeader;
+use super::mp3_stream::Mp3Stream;
+use super::mp3_xing::{Mp3LameTag, Mp3XingTag};
+
+/// Content of the synthetic frames after the header and crc
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mp3SynthPayload {
+    /// All zero, decodes to silence in every layer
+    Silent,
+    /// Random bytes from the given seed, the frames are not necessarily decodable
+    Random(u64),
+}
+
+/// Generator of synthetic MP3 streams for tests
+#[derive(Clone)]
+pub struct Mp3Synth {
+    /// Header of every frame, the crc is computed if it is protected
+    pub header: Mp3FrameHeader,
+    pub frames: usize,
+    pub payload: Mp3SynthPayload,
+    pub id3v2: Option<Id3v2Tag>,
+    /// Xing/Info tag frame with the optional LAME extension
+    pub xing: Option<Option<Mp3LameTag>>,
+    pub ape: Option<ApeTag>,
+    pub id3v1: Option<Id3v1Tag>,
+    /// Bytes inserted before the audio frame with the given index
+    pub junk: Vec<(usize, Vec<u8>)>,
+    /// Number of bytes cut from the end of the last frame
+    pub truncate: usize,
+}
+
+impl Mp3Synth {
+    /// Silent stream of `frames` frames without tags
+    pub fn new(header: Mp3FrameHeader, frames: usize) -> Mp3Synth {
+        Mp3Synth {
+            header,
+            frames,
+            payload: Mp3SynthPayload::Silent,
+            id3v2: None,
+            xing: None,
+            ape: None,
+            id3v1: None,
+            junk: Vec::new(),
+            truncate: 0,
+        }
+    }
+
+    /// Complete audio frames, free format headers yield empty frames
+    pub fn audio_frames(&self) -> Vec<Mp3Frame> {
+        let length = self.header.frame_length().unwrap_or(0).max(4);
+        let mut rng = match self.payload {
+            Mp3SynthPayload::Silent => None,
+            Mp3SynthPayload::Random(seed) => Some(StdRng::seed_from_u64(seed)),
+        };
+        (0..self.frames)
+            .map(|position| {
+                let mut data = vec![0; length];
+                data[0..4].copy_from_slice(&self.header.to_bytes());
+                let mut frame = Mp3Frame::new(self.header, data, position as u32);
+                if let Some(rng) = rng.as_mut() {
+                    let offset = frame.payload_offset().min(length);
+                    rng.fill(&mut frame.body.data[offset..]);
+                }
+                frame.update_crc();
+                frame
+            })
+            .collect()
+    }
+
+    /// The stream without junk and truncation
+    pub fn stream(&self) -> Mp3Stream {
+        Mp3Stream {
+            id3v2: self.id3v2.as_ref().map_or(Vec::new(), Id3v2Tag::to_bytes),
+            xing: self.xing.clone().map(|lame| Mp3XingTag {
+                lame,
+                ..Default::default()
+            }),
+            frames: self.audio_frames(),
+            ape: self.ape.as_ref().map_or(Vec::new(), ApeTag::to_bytes),
+            id3v1: self.id3v1.as_ref().map_or(Vec::new(), Id3v1Tag::to_bytes),
+        }
+    }
+
+    /// The file data with junk and truncation applied
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let stream = self.stream();
+        let mut data = stream.id3v2.clone();
+        if let Some(xing) = &stream.xing {
+            if let Some(frame) = Mp3XingTag::build_frame(&stream.frames, xing.lame.clone()) {
+                data.extend(&frame.body.data);
+            }
+        }
+        let count = stream.frames.len();
+        for (i, frame) in stream.frames.iter().enumerate() {
+            self.extend_junk(&mut data, i);
+            match i + 1 == count {
+                true => {
+                    let end = frame.body.data.len().saturating_sub(self.truncate);
+                    data.extend(&frame.body.data[..end]);
+                }
+                false => data.extend(&frame.body.data),
+            }
+        }
+        self.extend_junk(&mut data, count);
+        data.extend(&stream.ape);
+        data.extend(&stream.id3v1);
+        data
+    }
+
+    fn extend_junk(&self, data: &mut Vec<u8>, index: usize) {
+        for (_, junk) in self.junk.iter().filter(|(i, _)| *i == index) {
+            data.extend(junk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::get_frames;
+    use super::super::mp3_header::{Mp3ChannelMode, Mp3Layer, Mp3Protection, Mp3Version};
+    use super::*;
+
+    fn header() -> Mp3FrameHeader {
+        Mp3FrameHeader::from_parameters(
+            Mp3Version::V1,
+            Mp3Layer::Layer3,
+            128,
+            44100,
+            Mp3ChannelMode::JointStereo,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_synth_frames() {
+        let mut header = header();
+        header.set_protection(Mp3Protection::ProtectedByCrc);
+        let mut synth = Mp3Synth::new(header, 5);
+        synth.payload = Mp3SynthPayload::Random(1);
+        let data = synth.to_bytes();
+        assert_eq!(data.len(), 5 * 417);
+        let frames = get_frames(&data).unwrap();
+        assert_eq!(frames.len(), 5);
+        let mut copy = frames[2].clone();
+        copy.update_crc();
+        assert_eq!(copy.body.data, frames[2].body.data);
+        assert_ne!(frames[1].body.data, frames[2].body.data);
+
+        // silent Layer II at 96 kbit/s per channel and 48 kHz: the crc covers the last two
+        // header bytes and 2 x (11 x 4 + 12 x 3 + 4 x 2) zero bits of bit allocation
+        let mut header = Mp3FrameHeader::from_parameters(
+            Mp3Version::V1,
+            Mp3Layer::Layer2,
+            192,
+            48000,
+            Mp3ChannelMode::Stereo,
+        )
+        .unwrap();
+        header.set_protection(Mp3Protection::ProtectedByCrc);
+        let bytes = header.to_bytes();
+        let mut crc: u16 = 0xFFFF;
+        for i in 0..16 + 176 {
+            let bit = match i < 16 {
+                true => (bytes[2 + i / 8] >> (7 - i % 8) & 1) as u16,
+                false => 0,
+            };
+            crc = match (crc >> 15) ^ bit {
+                0 => crc << 1,
+                _ => crc << 1 ^ 0x8005,
+            };
+        }
+        let frames = Mp3Synth::new(header, 2).audio_frames();
+        assert_eq!(frames[1].body.data[4..6], crc.to_be_bytes());
+        assert_eq!(frames[1].crc_matches(), Some(true));
+    }
+
+    #[test]
+    fn test_synth_formats() {
+        for (version, layer, bitrate, sample_rate, length) in [
+            (Mp3Version::V1, Mp3Layer::Layer1, 384, 48000, 384),
+            (Mp3Version::V1, Mp3Layer::Layer2, 192, 32000, 864),
+            (Mp3Version::V2, Mp3Layer::Layer3, 64, 24000, 192),
+            (Mp3Version::V25, Mp3Layer::Layer3, 8, 8000, 72),
+        ] {
+            let header = Mp3FrameHeader::from_parameters(
+                version,
+                layer,
+                bitrate,
+                sample_rate,
+                Mp3ChannelMode::SingleChannel,
+            )
+            .unwrap();
+            let stream = Mp3Stream::parse(&Mp3Synth::new(header, 3).to_bytes()).unwrap();
+            assert_eq!(stream.frames.len(), 3);
+            assert_eq!(stream.frames[0].body.data.len(), length);
+        }
+    }
+
+    #[test]
+    fn test_synth_tags() {
+        let mut synth = Mp3Synth::new(header(), 4);
+        let mut id3v2 = Id3v2Tag::default();
+        id3v2.set_text("TIT2", "Synth");
+        synth.id3v2 = Some(id3v2.clone());
+        let mut ape = ApeTag::default();
+        ape.set("Title", "Synth");
+        synth.ape = Some(ape.clone());
+        synth.id3v1 = Some(Id3v1Tag {
+            title: "Synth".to_string(),
+            ..Default::default()
+        });
+        synth.xing = Some(Some(Mp3LameTag::default()));
+
+        let stream = Mp3Stream::parse(&synth.to_bytes()).unwrap();
+        assert_eq!(Id3v2Tag::parse(&stream.id3v2), Some(id3v2));
+        assert_eq!(ApeTag::parse(&stream.ape), Some(ape));
+        assert_eq!(Id3v1Tag::parse(&stream.id3v1).unwrap().title, "Synth");
+        assert_eq!(stream.xing.unwrap().frames, Some(4));
+        assert_eq!(stream.frames.len(), 4);
+    }
+
+    #[test]
+    fn test_synth_junk_and_truncation() {
+        let mut synth = Mp3Synth::new(header(), 4);
+        synth.junk = vec![(2, vec![0x12; 10]), (4, vec![0x34; 3])];
+        synth.truncate = 17;
+        let data = synth.to_bytes();
+        assert_eq!(data.len(), 4 * 417 + 13 - 17);
+        assert_eq!(data[2 * 417..2 * 417 + 10], [0x12; 10]);
+        assert_eq!(data[data.len() - 3..], [0x34; 3]);
+        assert_eq!(synth.stream().frames.len(), 4);
+    }
+}
@@ -0,0 +1,221 @@
+// References
+// ISO/IEC 11172-3 2.4.3.2 and Annex A, Figure A.2 (synthesis subband filter)
+// ISO/IEC 11172-3 Annex B, Table B.3 (synthesis window)
+
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+/// Coefficients D[i] of the synthesis window, scaled by 65536
+#[rustfmt::skip]
+const SYNTHESIS_WINDOW: [i32; 512] = [
+    0, -1, -1, -1, -1, -1, -1, -2, -2, -2, -2, -3, -3, -4, -4, -5,
+    -5, -6, -7, -7, -8, -9, -10, -11, -13, -14, -16, -17, -19, -21, -24, -26,
+    -29, -31, -35, -38, -41, -45, -49, -53, -58, -63, -68, -73, -79, -85, -91, -97,
+    -104, -111, -117, -125, -132, -139, -147, -154, -161, -169, -176, -183, -190, -196, -202, -208,
+    213, 218, 222, 225, 227, 228, 228, 227, 224, 221, 215, 208, 200, 189, 177, 163,
+    146, 127, 106, 83, 57, 29, -2, -36, -72, -111, -153, -197, -244, -294, -347, -401,
+    -459, -519, -581, -645, -711, -779, -848, -919, -991, -1064, -1137, -1210, -1283, -1356, -1428, -1498,
+    -1567, -1634, -1698, -1759, -1817, -1870, -1919, -1962, -2001, -2032, -2057, -2075, -2085, -2087, -2080, -2063,
+    2037, 2000, 1952, 1893, 1822, 1739, 1644, 1535, 1414, 1280, 1131, 970, 794, 605, 402, 185,
+    -45, -288, -545, -814, -1095, -1388, -1692, -2006, -2330, -2663, -3004, -3351, -3705, -4063, -4425, -4788,
+    -5153, -5517, -5879, -6237, -6589, -6935, -7271, -7597, -7910, -8209, -8491, -8755, -8998, -9219, -9416, -9585,
+    -9727, -9838, -9916, -9959, -9966, -9935, -9863, -9750, -9592, -9389, -9139, -8840, -8492, -8092, -7640, -7134,
+    6574, 5959, 5288, 4561, 3776, 2935, 2037, 1082, 70, -998, -2122, -3300, -4533, -5818, -7154, -8540,
+    -9975, -11455, -12980, -14548, -16155, -17799, -19478, -21189, -22929, -24694, -26482, -28289, -30112, -31947, -33791, -35640,
+    -37489, -39336, -41176, -43006, -44821, -46617, -48390, -50137, -51853, -53534, -55178, -56778, -58333, -59838, -61289, -62684,
+    -64019, -65290, -66494, -67629, -68692, -69679, -70590, -71420, -72169, -72835, -73415, -73908, -74313, -74630, -74856, -74992,
+    75038, 74992, 74856, 74630, 74313, 73908, 73415, 72835, 72169, 71420, 70590, 69679, 68692, 67629, 66494, 65290,
+    64019, 62684, 61289, 59838, 58333, 56778, 55178, 53534, 51853, 50137, 48390, 46617, 44821, 43006, 41176, 39336,
+    37489, 35640, 33791, 31947, 30112, 28289, 26482, 24694, 22929, 21189, 19478, 17799, 16155, 14548, 12980, 11455,
+    9975, 8540, 7154, 5818, 4533, 3300, 2122, 998, -70, -1082, -2037, -2935, -3776, -4561, -5288, -5959,
+    6574, 7134, 7640, 8092, 8492, 8840, 9139, 9389, 9592, 9750, 9863, 9935, 9966, 9959, 9916, 9838,
+    9727, 9585, 9416, 9219, 8998, 8755, 8491, 8209, 7910, 7597, 7271, 6935, 6589, 6237, 5879, 5517,
+    5153, 4788, 4425, 4063, 3705, 3351, 3004, 2663, 2330, 2006, 1692, 1388, 1095, 814, 545, 288,
+    45, -185, -402, -605, -794, -970, -1131, -1280, -1414, -1535, -1644, -1739, -1822, -1893, -1952, -2000,
+    2037, 2063, 2080, 2087, 2085, 2075, 2057, 2032, 2001, 1962, 1919, 1870, 1817, 1759, 1698, 1634,
+    1567, 1498, 1428, 1356, 1283, 1210, 1137, 1064, 991, 919, 848, 779, 711, 645, 581, 519,
+    459, 401, 347, 294, 244, 197, 153, 111, 72, 36, 2, -29, -57, -83, -106, -127,
+    -146, -163, -177, -189, -200, -208, -215, -221, -224, -227, -228, -228, -227, -225, -222, -218,
+    213, 208, 202, 196, 190, 183, 176, 169, 161, 154, 147, 139, 132, 125, 117, 111,
+    104, 97, 91, 85, 79, 73, 68, 63, 58, 53, 49, 45, 41, 38, 35, 31,
+    29, 26, 24, 21, 19, 17, 16, 14, 13, 11, 10, 9, 8, 7, 7, 6,
+    5, 5, 4, 4, 3, 3, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1,
+];
+
+/// Matrixing cosines N[i][k] = cos((16 + i) * (2k + 1) * pi / 64) indexed by i * 32 + k
+fn matrix() -> &'static Vec<f32> {
+    static MATRIX: OnceLock<Vec<f32>> = OnceLock::new();
+    MATRIX.get_or_init(|| {
+        let mut matrix = Vec::with_capacity(64 * 32);
+        for i in 0..64 {
+            for k in 0..32 {
+                matrix.push(((16 + i) as f64 * (2 * k + 1) as f64 * PI / 64.0).cos() as f32);
+            }
+        }
+        matrix
+    })
+}
+
+/// Polyphase synthesis filterbank of one channel, shared by all layers.
+/// Turns 32 subband samples into 32 PCM samples.
+pub struct Mp3Synthesis {
+    v: [f32; 1024],
+}
+
+impl Mp3Synthesis {
+    pub fn new() -> Mp3Synthesis {
+        Mp3Synthesis { v: [0.0; 1024] }
+    }
+
+    /// Synthesizes 32 PCM samples from one time slot of subband samples
+    pub fn synthesize(&mut self, subbands: &[f32; 32]) -> [f32; 32] {
+        let matrix = matrix();
+        self.v.copy_within(0..960, 64);
+        for (i, v) in self.v[..64].iter_mut().enumerate() {
+            let cosines = &matrix[i * 32..i * 32 + 32];
+            *v = subbands
+                .iter()
+                .zip(cosines.iter())
+                .map(|(s, n)| s * n)
+                .sum();
+        }
+
+        let mut pcm = [0.0; 32];
+        for (j, sample) in pcm.iter_mut().enumerate() {
+            let mut sum = 0.0;
+            for i in 0..8 {
+                // U[64i + j] = V[128i + j], U[64i + 32 + j] = V[128i + 96 + j]
+                sum += self.v[128 * i + j] * SYNTHESIS_WINDOW[64 * i + j] as f32;
+                sum += self.v[128 * i + 96 + j] * SYNTHESIS_WINDOW[64 * i + 32 + j] as f32;
+            }
+            *sample = sum / 65536.0;
+        }
+        pcm
+    }
+}
+
+/// Synthesizes one time slot of all channels, writing 32 interleaved samples per channel to `output`
+pub fn synthesize_channels(
+    synthesis: &mut [Mp3Synthesis; 2],
+    subbands: &[[f32; 32]; 2],
+    channels: usize,
+    output: &mut [f32],
+) {
+    for ch in 0..channels {
+        let pcm = synthesis[ch].synthesize(&subbands[ch]);
+        for (j, &sample) in pcm.iter().enumerate() {
+            output[j * channels + ch] = sample;
+        }
+    }
+}
+
+impl Default for Mp3Synthesis {
+    fn default() -> Self {
+        Mp3Synthesis::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Synthesis following Figure A.2 step by step: shift V, matrixing, build U,
+    /// window with D and sum the 16 values of each sample
+    struct Reference {
+        v: Vec<f64>,
+    }
+
+    impl Reference {
+        fn synthesize(&mut self, subbands: &[f32; 32]) -> Vec<f64> {
+            self.v.rotate_right(64);
+            for i in 0..64 {
+                self.v[i] = (0..32)
+                    .map(|k| ((16 + i) as f64 * (2 * k + 1) as f64 * PI / 64.0).cos())
+                    .zip(subbands.iter())
+                    .map(|(n, &s)| n * s as f64)
+                    .sum();
+            }
+            let mut u = vec![0.0; 512];
+            for i in 0..8 {
+                for j in 0..32 {
+                    u[i * 64 + j] = self.v[i * 128 + j];
+                    u[i * 64 + 32 + j] = self.v[i * 128 + 96 + j];
+                }
+            }
+            let w: Vec<f64> = u
+                .iter()
+                .zip(SYNTHESIS_WINDOW.iter())
+                .map(|(u, &d)| u * d as f64 / 65536.0)
+                .collect();
+            (0..32)
+                .map(|j| (0..16).map(|i| w[j + 32 * i]).sum())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_synthesize_silence() {
+        let mut synthesis = Mp3Synthesis::new();
+        for _ in 0..20 {
+            assert_eq!(synthesis.synthesize(&[0.0; 32]), [0.0; 32]);
+        }
+    }
+
+    #[test]
+    fn test_synthesize_constant() {
+        // a constant in the lowest subband is passed with unity gain
+        // once the 512 samples of the filter are filled
+        let mut synthesis = Mp3Synthesis::new();
+        let mut subbands = [0.0; 32];
+        subbands[0] = 0.5;
+        for slot in 0..32 {
+            let pcm = synthesis.synthesize(&subbands);
+            if slot >= 16 {
+                assert!(pcm.iter().all(|x| (x - 0.5).abs() < 1e-4), "{:?}", pcm);
+            }
+        }
+    }
+
+    #[test]
+    fn test_synthesize_matches_standard() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut synthesis = Mp3Synthesis::new();
+        let mut reference = Reference { v: vec![0.0; 1024] };
+        for _ in 0..40 {
+            let subbands: [f32; 32] = std::array::from_fn(|_| rng.gen_range(-1.0..1.0));
+            let pcm = synthesis.synthesize(&subbands);
+            let expected = reference.synthesize(&subbands);
+            for (x, y) in pcm.iter().zip(expected.iter()) {
+                assert!((*x as f64 - y).abs() < 1e-5, "{} {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_synthesize_channels() {
+        let mut synthesis: [Mp3Synthesis; 2] = Default::default();
+        let mut left = Mp3Synthesis::new();
+        let mut right = Mp3Synthesis::new();
+        let mut subbands = [[0.0; 32]; 2];
+        subbands[0][2] = 0.25;
+        subbands[1][7] = -0.5;
+        let mut output = [0.0; 64];
+        for _ in 0..20 {
+            synthesize_channels(&mut synthesis, &subbands, 2, &mut output);
+            let expected = [
+                left.synthesize(&subbands[0]),
+                right.synthesize(&subbands[1]),
+            ];
+            for j in 0..32 {
+                assert_eq!(output[2 * j], expected[0][j]);
+                assert_eq!(output[2 * j + 1], expected[1][j]);
+            }
+        }
+
+        let mut mono = [0.0; 32];
+        synthesize_channels(&mut synthesis, &subbands, 1, &mut mono);
+        assert_eq!(mono, left.synthesize(&subbands[0]));
+    }
+}
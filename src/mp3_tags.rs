@@ -0,0 +1,219 @@
+// References
+// https://id3.org/id3v2.4.0-structure
+// https://id3.org/ID3v1
+// https://id3.org/Lyrics3v2
+
+use super::id3v1::Id3v1Tag;
+use super::id3v2::Id3v2Tag;
+use super::mp3_scan::{scan, Mp3RegionKind};
+
+/// Replaces the first ID3v2 tag at the start of the file or inserts the tag if there is none.
+/// An empty tag removes it. All other data is copied unchanged.
+pub fn replace_id3v2(data: &[u8], tag: &[u8]) -> Vec<u8> {
+    let length = Id3v2Tag::length_at_start(data)
+        .filter(|&length| length <= data.len())
+        .unwrap_or(0);
+    [tag, &data[length..]].concat()
+}
+
+/// Offset and length of the last APE tag among the tags at the end of the file.
+/// Without one, the empty region before the Lyrics3 and ID3v1 tags at the end.
+fn ape_region(data: &[u8]) -> (usize, usize) {
+    use Mp3RegionKind::*;
+    let regions = scan(data);
+    let tail = regions
+        .iter()
+        .rposition(|region| matches!(region.kind, Frame | TruncatedFrame | Junk))
+        .map_or(0, |last| last + 1);
+    if let Some(ape) = regions[tail..].iter().rfind(|region| region.kind == Ape) {
+        return (ape.offset, ape.length);
+    }
+    let end = regions
+        .iter()
+        .rposition(|region| !matches!(region.kind, Lyrics3 | Id3v1))
+        .map_or(0, |last| regions[last].offset + regions[last].length);
+    (end, 0)
+}
+
+/// APE tag at the end of the file as replaced by `replace_ape`, empty if there is none
+pub fn find_ape(data: &[u8]) -> &[u8] {
+    let (offset, length) = ape_region(data);
+    &data[offset..offset + length]
+}
+
+/// Replaces the APE tag at the end of the file or inserts the tag before the Lyrics3
+/// and ID3v1 tags at the end. An empty tag removes it. All other data is copied unchanged.
+pub fn replace_ape(data: &[u8], tag: &[u8]) -> Vec<u8> {
+    let (offset, length) = ape_region(data);
+    [&data[..offset], tag, &data[offset + length..]].concat()
+}
+
+/// Removes all tags of the given kinds, including misplaced and repeated ones.
+/// Frames and junk are copied unchanged.
+pub fn strip_tags(data: &[u8], kinds: &[Mp3RegionKind]) -> Vec<u8> {
+    scan(data)
+        .iter()
+        .filter(|region| !kinds.contains(&region.kind))
+        .flat_map(|region| &data[region.offset..region.offset + region.length])
+        .copied()
+        .collect()
+}
+
+/// Replaces all ID3v2 tags by a single tag at the start of the file in the given version.
+/// The frames of all ID3v2 tags and missing fields of the ID3v1 tag are merged,
+/// strings are re-encoded if an encoding is given and empty and repeated frames removed.
+/// Other tags, frames and junk are copied unchanged.
+pub fn normalise_tags(data: &[u8], version: u8, encoding: Option<u8>) -> Vec<u8> {
+    let regions = scan(data);
+    let bytes = |offset: usize, length: usize| &data[offset..offset + length];
+    let mut tag: Option<Id3v2Tag> = None;
+    for region in regions.iter().filter(|r| r.kind == Mp3RegionKind::Id3v2) {
+        if let Some(other) = Id3v2Tag::parse(bytes(region.offset, region.length)) {
+            match tag.as_mut() {
+                Some(tag) => tag.merge(&other),
+                None => tag = Some(other),
+            }
+        }
+    }
+    let id3v1 = regions
+        .iter()
+        .filter(|r| r.kind == Mp3RegionKind::Id3v1)
+        .find_map(|r| Id3v1Tag::parse(bytes(r.offset, r.length)));
+    if tag.is_none() && id3v1.is_some() {
+        tag = Some(Id3v2Tag::default());
+    }
+
+    let mut result = Vec::new();
+    if let Some(mut tag) = tag {
+        tag.convert(version);
+        if let Some(id3v1) = &id3v1 {
+            tag.merge_id3v1(id3v1);
+        }
+        if let Some(encoding) = encoding {
+            tag.set_encoding(encoding);
+        }
+        tag.clean();
+        result.extend(tag.to_bytes());
+    }
+    for region in regions.iter().filter(|r| r.kind != Mp3RegionKind::Id3v2) {
+        result.extend(bytes(region.offset, region.length));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::apev2::ApeTag;
+    use super::super::id3v2::Id3v2Frame;
+    use super::super::mp3_header::{Mp3ChannelMode, Mp3FrameHeader, Mp3Layer, Mp3Version};
+    use super::super::mp3_synth::Mp3Synth;
+    use super::*;
+
+    fn synth() -> Mp3Synth {
+        let header = Mp3FrameHeader::from_parameters(
+            Mp3Version::V1,
+            Mp3Layer::Layer3,
+            128,
+            48000,
+            Mp3ChannelMode::JointStereo,
+        )
+        .unwrap();
+        Mp3Synth::new(header, 3)
+    }
+
+    fn kinds(data: &[u8]) -> Vec<Mp3RegionKind> {
+        scan(data).iter().map(|region| region.kind).collect()
+    }
+
+    #[test]
+    fn test_strip_tags() {
+        let mut synth = synth();
+        synth.id3v2 = Some(Id3v2Tag::default());
+        synth.junk = vec![(1, Id3v2Tag::default().to_bytes())];
+        synth.id3v1 = Some(Id3v1Tag::default());
+        let mut data = synth.to_bytes();
+        data.splice(
+            data.len() - 128..data.len() - 128,
+            *b"LYRICSBEGINTextLYRICSEND",
+        );
+        use Mp3RegionKind::*;
+        assert_eq!(
+            kinds(&strip_tags(&data, &[Id3v2, Lyrics3])),
+            [Frame, Frame, Frame, Id3v1]
+        );
+        assert_eq!(
+            kinds(&strip_tags(&data, &[Id3v1])),
+            [Id3v2, Frame, Id3v2, Frame, Frame, Lyrics3]
+        );
+    }
+
+    #[test]
+    fn test_replace_tags() {
+        let mut synth = synth();
+        synth.id3v2 = Some(Id3v2Tag::default());
+        synth.junk = vec![
+            (2, vec![0x12; 30]),
+            (3, Id3v2Tag::default().to_bytes_with_footer()),
+        ];
+        synth.truncate = 100;
+        synth.id3v1 = Some(Id3v1Tag::default());
+        let mut data = synth.to_bytes();
+        let lyrics3 = b"LYRICSBEGINTextLYRICSEND";
+        data.splice(data.len() - 128..data.len() - 128, *lyrics3);
+        let start = Id3v2Tag::length_at_start(&data).unwrap();
+
+        let mut tag = Id3v2Tag::default();
+        tag.set_text("TIT2", "Title");
+        let replaced = replace_id3v2(&data, &tag.to_bytes());
+        assert_eq!(Id3v2Tag::parse(&replaced), Some(tag.clone()));
+        assert_eq!(replaced[tag.to_bytes().len()..], data[start..]);
+        assert_eq!(replace_id3v2(&data[start..], &tag.to_bytes()), replaced);
+
+        let mut ape = ApeTag::default();
+        ape.set("Title", "Title");
+        let end = data.len() - 128 - lyrics3.len();
+        let inserted = replace_ape(&data, &ape.to_bytes());
+        assert_eq!(inserted[..end], data[..end]);
+        assert_eq!(inserted[end + ape.to_bytes().len()..], data[end..]);
+        assert_eq!(find_ape(&inserted), ape.to_bytes());
+        assert!(find_ape(&data).is_empty());
+        ape.set("Artist", "Artist");
+        let updated = replace_ape(&inserted, &ape.to_bytes());
+        assert_eq!(find_ape(&updated), ape.to_bytes());
+        assert_eq!(replace_ape(&updated, &[]), data);
+    }
+
+    #[test]
+    fn test_normalise_tags() {
+        let mut first = Id3v2Tag::default();
+        first.set_text("TIT2", "Title");
+        first.set_text("TDRC", "2001-02-03");
+        let mut second = Id3v2Tag {
+            version: 3,
+            frames: Vec::new(),
+        };
+        second.set_text("TIT2", "Other title");
+        second.set_text("TPE1", "Artist");
+        second.frames.push(Id3v2Frame::text("TCOM", "", 3));
+        let mut synth = synth();
+        synth.id3v2 = Some(first);
+        synth.junk = vec![(3, second.to_bytes())];
+        synth.id3v1 = Some(Id3v1Tag {
+            artist: "Other artist".to_string(),
+            album: "Album".to_string(),
+            ..Default::default()
+        });
+
+        let data = normalise_tags(&synth.to_bytes(), 3, Some(0));
+        use Mp3RegionKind::*;
+        assert_eq!(kinds(&data), [Id3v2, Frame, Frame, Frame, Id3v1]);
+        let tag = Id3v2Tag::parse(&data).unwrap();
+        assert_eq!(tag.version, 3);
+        let ids: Vec<&str> = tag.frames.iter().map(|frame| frame.id.as_str()).collect();
+        assert_eq!(ids, ["TIT2", "TPE1", "TYER", "TDAT", "TALB"]);
+        assert_eq!(tag.text("TIT2"), Some("Title".to_string()));
+        assert_eq!(tag.text("TPE1"), Some("Artist".to_string()));
+        assert_eq!(tag.text("TDAT"), Some("0302".to_string()));
+        assert!(tag.frames.iter().all(|frame| frame.data[0] == 0));
+    }
+}
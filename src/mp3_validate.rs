@@ -0,0 +1,768 @@
+// References
+// http://www.datavoyage.com/mpgscript/mpeghdr.htm
+// ISO/IEC 11172-3 2.4.2.3 (Layer II bitrate and mode combinations)
+// http://gabriel.mp3-tech.org/mp3infotag.html
+// https://id3.org/id3v2.4.0-structure (appended tags)
+// https://id3.org/id3v2-chapters-1.0
+
+use super::id3v2::Id3v2Tag;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::{Mp3Emphasis, Mp3FrameHeader, Mp3Layer, Mp3Version};
+use super::mp3_scan::{scan, Mp3Region, Mp3RegionKind};
+use super::mp3_xing::Mp3XingTag;
+
+/// Maximum main_data_begin of MPEG 1 Layer III frames
+const MAX_RESERVOIR: usize = 511;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mp3Severity {
+    /// Allowed, but unusual
+    Info,
+    /// Tolerated by most players
+    Warning,
+    /// Violates the standard, players may fail or produce audible errors
+    Error,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mp3IssueKind {
+    NoFrames,
+    Junk,
+    TruncatedFrame,
+    ReservedValue,
+    ForbiddenLayer2Mode,
+    FormatChange,
+    XingMismatch,
+    PaddingMismatch,
+    BadCrc,
+    MisplacedTag,
+    DuplicateTag,
+    ReservoirUnderflow,
+    /// Chapter or table of contents inconsistent with the audio or the tag
+    BadChapter,
+}
+
+/// A conformance issue found by `validate`
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3Issue {
+    pub severity: Mp3Severity,
+    pub kind: Mp3IssueKind,
+    /// Byte offset of the first affected region
+    pub offset: usize,
+    /// Index of the first affected frame, counting the Xing/Info frame
+    pub frame: Option<usize>,
+    pub message: String,
+}
+
+/// Names of the reserved fields of a header with sync word
+fn reserved_fields(header: &Mp3FrameHeader) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    if header.version() == Mp3Version::Reserved {
+        fields.push("version");
+    }
+    if header.layer() == Mp3Layer::Reserved {
+        fields.push("layer");
+    }
+    if header.bitrate_index() == 15 {
+        fields.push("bitrate");
+    }
+    if header.sampling_rate_index() == 3 {
+        fields.push("sample rate");
+    }
+    if header.emphasis() == Mp3Emphasis::Reserved {
+        fields.push("emphasis");
+    }
+    fields
+}
+
+/// MPEG 1 Layer II only allows low bitrates for mono and high bitrates for stereo
+fn is_forbidden_layer2_mode(header: &Mp3FrameHeader) -> bool {
+    if header.version() != Mp3Version::V1 || header.layer() != Mp3Layer::Layer2 {
+        return false;
+    }
+    match header.channels() {
+        1 => [224, 256, 320, 384].contains(&header.bitrate()),
+        _ => [32, 48, 56, 80].contains(&header.bitrate()),
+    }
+}
+
+/// Version, layer and sample rate, which must not change within a stream
+fn format(header: &Mp3FrameHeader) -> (Mp3Version, Mp3Layer, u16) {
+    (header.version(), header.layer(), header.sample_rate())
+}
+
+/// Collects issues affecting several frames into one issue
+struct FrameIssue {
+    severity: Mp3Severity,
+    kind: Mp3IssueKind,
+    description: &'static str,
+    first: Option<(usize, usize)>,
+    count: usize,
+}
+
+impl FrameIssue {
+    fn new(severity: Mp3Severity, kind: Mp3IssueKind, description: &'static str) -> FrameIssue {
+        FrameIssue {
+            severity,
+            kind,
+            description,
+            first: None,
+            count: 0,
+        }
+    }
+
+    fn add(&mut self, offset: usize, frame: usize) {
+        self.first.get_or_insert((offset, frame));
+        self.count += 1;
+    }
+
+    fn issue(&self) -> Option<Mp3Issue> {
+        let (offset, frame) = self.first?;
+        Some(Mp3Issue {
+            severity: self.severity,
+            kind: self.kind,
+            offset,
+            frame: Some(frame),
+            message: format!("{} frame(s) {}", self.count, self.description),
+        })
+    }
+}
+
+/// Checks the conformance of an MP3 file. The issues are sorted by offset.
+pub fn validate(data: &[u8]) -> Vec<Mp3Issue> {
+    let regions = scan(data);
+    let mut issues = Vec::new();
+    let frames: Vec<(&Mp3Region, Mp3Frame)> = regions
+        .iter()
+        .filter(|region| region.kind == Mp3RegionKind::Frame)
+        .enumerate()
+        .map(|(position, region)| {
+            let bytes = &data[region.offset..region.offset + region.length];
+            let header = Mp3FrameHeader::new(u32::from_be_bytes(bytes[0..4].try_into().unwrap()));
+            (
+                region,
+                Mp3Frame::new(header, bytes.to_vec(), position as u32),
+            )
+        })
+        .collect();
+    let first_frame = frames.first().map(|(region, _)| region.offset);
+    let last_frame = frames.last().map(|(region, _)| region.offset);
+
+    for (i, region) in regions.iter().enumerate() {
+        let issue = |severity, kind, message: String| Mp3Issue {
+            severity,
+            kind,
+            offset: region.offset,
+            frame: None,
+            message,
+        };
+        let before_frames = first_frame.is_none_or(|offset| region.offset < offset);
+        let after_frames = last_frame.is_none_or(|offset| region.offset > offset);
+        let same_tags = regions[..i]
+            .iter()
+            .filter(|other| other.kind == region.kind)
+            .count();
+        match region.kind {
+            Mp3RegionKind::Frame => {}
+            Mp3RegionKind::Junk => {
+                let header = data
+                    .get(region.offset..region.offset + 4)
+                    .map(|bytes| Mp3FrameHeader::new(u32::from_be_bytes(bytes.try_into().unwrap())))
+                    .filter(Mp3FrameHeader::has_sync);
+                let reserved = header.map_or(Vec::new(), |header| reserved_fields(&header));
+                issues.push(match reserved.is_empty() {
+                    true => issue(
+                        Mp3Severity::Warning,
+                        Mp3IssueKind::Junk,
+                        format!("{} bytes of junk", region.length),
+                    ),
+                    false => issue(
+                        Mp3Severity::Error,
+                        Mp3IssueKind::ReservedValue,
+                        format!(
+                            "{} bytes starting with a header with reserved {}",
+                            region.length,
+                            reserved.join(", ")
+                        ),
+                    ),
+                });
+            }
+            Mp3RegionKind::TruncatedFrame => {
+                let header = Mp3FrameHeader::new(u32::from_be_bytes(
+                    data[region.offset..region.offset + 4].try_into().unwrap(),
+                ));
+                issues.push(issue(
+                    Mp3Severity::Error,
+                    Mp3IssueKind::TruncatedFrame,
+                    format!(
+                        "frame truncated to {} of {} bytes",
+                        region.length,
+                        header.frame_length().unwrap_or(0)
+                    ),
+                ));
+            }
+            Mp3RegionKind::Id3v2 if same_tags > 0 => issues.push(issue(
+                Mp3Severity::Warning,
+                Mp3IssueKind::DuplicateTag,
+                "additional ID3v2 tag".to_string(),
+            )),
+            Mp3RegionKind::Id3v2 if region.offset == 0 => {}
+            Mp3RegionKind::Id3v2 if after_frames && data[region.offset + 5] & 0x10 != 0 => issues
+                .push(issue(
+                    Mp3Severity::Info,
+                    Mp3IssueKind::MisplacedTag,
+                    "ID3v2 tag appended after the audio".to_string(),
+                )),
+            Mp3RegionKind::Id3v2 => issues.push(issue(
+                Mp3Severity::Warning,
+                Mp3IssueKind::MisplacedTag,
+                "ID3v2 tag not at the start of the file".to_string(),
+            )),
+            Mp3RegionKind::Ape | Mp3RegionKind::Lyrics3 | Mp3RegionKind::Id3v1 if same_tags > 0 => {
+                issues.push(issue(
+                    Mp3Severity::Warning,
+                    Mp3IssueKind::DuplicateTag,
+                    format!("additional {:?} tag", region.kind),
+                ))
+            }
+            Mp3RegionKind::Ape | Mp3RegionKind::Lyrics3
+                if before_frames
+                    || !regions[i + 1..].iter().all(|other| {
+                        [
+                            Mp3RegionKind::Ape,
+                            Mp3RegionKind::Lyrics3,
+                            Mp3RegionKind::Id3v1,
+                        ]
+                        .contains(&other.kind)
+                    }) =>
+            {
+                issues.push(issue(
+                    Mp3Severity::Warning,
+                    Mp3IssueKind::MisplacedTag,
+                    format!("{:?} tag not at the end of the audio", region.kind),
+                ))
+            }
+            Mp3RegionKind::Id3v1 if i + 1 != regions.len() => issues.push(issue(
+                Mp3Severity::Warning,
+                Mp3IssueKind::MisplacedTag,
+                "ID3v1 tag not at the end of the file".to_string(),
+            )),
+            Mp3RegionKind::Ape | Mp3RegionKind::Lyrics3 | Mp3RegionKind::Id3v1 => {}
+        }
+    }
+
+    if frames.is_empty() {
+        issues.push(Mp3Issue {
+            severity: Mp3Severity::Error,
+            kind: Mp3IssueKind::NoFrames,
+            offset: 0,
+            frame: None,
+            message: "no audio frames".to_string(),
+        });
+        issues.sort_by_key(|issue| issue.offset);
+        return issues;
+    }
+
+    let xing = Mp3XingTag::parse(&frames[0].1);
+    let audio = match xing {
+        Some(_) => &frames[1..],
+        None => &frames[..],
+    };
+    let skipped = frames.len() - audio.len();
+
+    let mut reserved = FrameIssue::new(
+        Mp3Severity::Error,
+        Mp3IssueKind::ReservedValue,
+        "with reserved header values",
+    );
+    let mut forbidden = FrameIssue::new(
+        Mp3Severity::Error,
+        Mp3IssueKind::ForbiddenLayer2Mode,
+        "with a bitrate not allowed for their Layer II channel mode",
+    );
+    let mut format_change = FrameIssue::new(
+        Mp3Severity::Error,
+        Mp3IssueKind::FormatChange,
+        "changing version, layer or sample rate",
+    );
+    let mut channel_change = FrameIssue::new(
+        Mp3Severity::Warning,
+        Mp3IssueKind::FormatChange,
+        "changing the number of channels",
+    );
+    let mut bad_crc = FrameIssue::new(Mp3Severity::Error, Mp3IssueKind::BadCrc, "with a bad crc");
+    let mut underflow = FrameIssue::new(
+        Mp3Severity::Warning,
+        Mp3IssueKind::ReservoirUnderflow,
+        "referencing main data before the stream",
+    );
+
+    let reference = audio.first().unwrap_or(&frames[0]).1.header;
+    let mut reservoir = 0;
+    let mut previous_end = None;
+    for (i, (region, frame)) in frames.iter().enumerate() {
+        let header = &frame.header;
+        if !reserved_fields(header).is_empty() {
+            reserved.add(region.offset, i);
+        }
+        if is_forbidden_layer2_mode(header) {
+            forbidden.add(region.offset, i);
+        }
+        if i >= skipped && format(header) != format(&reference) {
+            format_change.add(region.offset, i);
+        } else if i >= skipped && header.channels() != reference.channels() {
+            channel_change.add(region.offset, i);
+        }
+        if frame.crc_matches() == Some(false) {
+            bad_crc.add(region.offset, i);
+        }
+
+        // the reservoir only spans directly adjacent frames
+        if previous_end != Some(region.offset) {
+            reservoir = 0;
+        }
+        previous_end = Some(region.offset + region.length);
+        if header.layer() == Mp3Layer::Layer3 {
+            if let Ok(side_info) = frame.side_info() {
+                if side_info.main_data_begin as usize > reservoir {
+                    underflow.add(region.offset, i);
+                }
+            }
+            reservoir = usize::min(reservoir + frame.main_data().len(), MAX_RESERVOIR);
+        }
+    }
+    issues.extend(
+        [
+            reserved,
+            forbidden,
+            format_change,
+            channel_change,
+            bad_crc,
+            underflow,
+        ]
+        .iter()
+        .filter_map(FrameIssue::issue),
+    );
+
+    if let Some(xing) = &xing {
+        let mut mismatch = |message: String| {
+            issues.push(Mp3Issue {
+                severity: Mp3Severity::Warning,
+                kind: Mp3IssueKind::XingMismatch,
+                offset: frames[0].0.offset,
+                frame: Some(0),
+                message,
+            })
+        };
+        if let Some(count) = xing.frames.filter(|&count| count as usize != audio.len()) {
+            mismatch(format!(
+                "Xing tag counts {} frames, the stream has {}",
+                count,
+                audio.len()
+            ));
+        }
+        let bytes: usize = frames.iter().map(|(region, _)| region.length).sum();
+        if let Some(count) = xing.bytes.filter(|&count| count as usize != bytes) {
+            mismatch(format!(
+                "Xing tag counts {} bytes, the stream has {}",
+                count, bytes
+            ));
+        }
+    }
+
+    if let Some(issue) = padding_issue(audio) {
+        issues.push(issue);
+    }
+
+    let duration: f64 = audio
+        .iter()
+        .map(|(_, frame)| {
+            frame.header.samples_per_frame() as f64 / frame.header.sample_rate().max(1) as f64
+        })
+        .sum();
+    for region in regions
+        .iter()
+        .filter(|region| region.kind == Mp3RegionKind::Id3v2)
+    {
+        if let Some(tag) = Id3v2Tag::parse(&data[region.offset..]) {
+            issues.extend(chapter_issues(
+                &tag,
+                region.offset,
+                duration,
+                &reference,
+                data.len(),
+            ));
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.offset);
+    issues
+}
+
+/// Checks that the padding of a constant bitrate stream keeps the average frame length
+/// within one slot of the exact length
+fn padding_issue(audio: &[(&Mp3Region, Mp3Frame)]) -> Option<Mp3Issue> {
+    let (_, first) = audio.first()?;
+    let rate = |header: &Mp3FrameHeader| (format(header), header.bitrate_index());
+    if audio
+        .iter()
+        .any(|(_, frame)| rate(&frame.header) != rate(&first.header))
+    {
+        return None;
+    }
+    let header = &first.header;
+    let slot = match header.layer() {
+        Mp3Layer::Layer1 => 4.0,
+        _ => 1.0,
+    };
+    let exact = header.samples_per_frame() as f64 / 8.0 * header.bitrate() as f64 * 1000.0
+        / header.sample_rate() as f64;
+    let mut total = 0;
+    for (i, (region, frame)) in audio.iter().enumerate() {
+        total += region.length;
+        if (total as f64 - exact * (i + 1) as f64).abs() > slot + 1e-6 {
+            return Some(Mp3Issue {
+                severity: Mp3Severity::Warning,
+                kind: Mp3IssueKind::PaddingMismatch,
+                offset: region.offset,
+                frame: Some(frame.position as usize),
+                message: format!(
+                    "padding drifts from the constant bitrate of {} kbit/s",
+                    header.bitrate()
+                ),
+            });
+        }
+    }
+    None
+}
+
+/// Checks the chapters and tables of contents of a tag against the audio duration in seconds
+/// and the file length. Chapters may end up to one frame after the audio.
+fn chapter_issues(
+    tag: &Id3v2Tag,
+    offset: usize,
+    duration: f64,
+    reference: &Mp3FrameHeader,
+    file_length: usize,
+) -> Vec<Mp3Issue> {
+    let mut issues = Vec::new();
+    let mut issue = |severity, message: String| {
+        issues.push(Mp3Issue {
+            severity,
+            kind: Mp3IssueKind::BadChapter,
+            offset,
+            frame: None,
+            message,
+        })
+    };
+    let duration_ms = duration * 1000.0;
+    let tolerance_ms =
+        reference.samples_per_frame() as f64 * 1000.0 / reference.sample_rate().max(1) as f64;
+    let chapters = tag.chapters();
+    let tocs = tag.tables_of_contents();
+    let mut ids: Vec<&str> = Vec::new();
+    for chapter in &chapters {
+        let id = &chapter.element_id;
+        if chapter.end_time < chapter.start_time {
+            issue(
+                Mp3Severity::Error,
+                format!("chapter {} ends before it starts", id),
+            );
+        }
+        if chapter.start_time as f64 >= duration_ms {
+            issue(
+                Mp3Severity::Error,
+                format!(
+                    "chapter {} starts at {} ms, after the audio ends at {:.0} ms",
+                    id, chapter.start_time, duration_ms
+                ),
+            );
+        } else if chapter.end_time as f64 > duration_ms + tolerance_ms {
+            issue(
+                Mp3Severity::Warning,
+                format!(
+                    "chapter {} ends at {} ms, after the audio ends at {:.0} ms",
+                    id, chapter.end_time, duration_ms
+                ),
+            );
+        }
+        if [chapter.start_offset, chapter.end_offset]
+            .iter()
+            .flatten()
+            .any(|&offset| offset as usize > file_length)
+        {
+            issue(
+                Mp3Severity::Warning,
+                format!("chapter {} has a byte offset after the end of the file", id),
+            );
+        }
+        ids.push(id);
+    }
+    for toc in &tocs {
+        ids.push(&toc.element_id);
+        for child in &toc.children {
+            if !chapters.iter().any(|chapter| &chapter.element_id == child)
+                && !tocs.iter().any(|other| &other.element_id == child)
+            {
+                issue(
+                    Mp3Severity::Warning,
+                    format!(
+                        "table of contents {} lists missing element {}",
+                        toc.element_id, child
+                    ),
+                );
+            }
+        }
+    }
+    for (i, id) in ids.iter().enumerate() {
+        if ids[..i].contains(id) {
+            issue(
+                Mp3Severity::Warning,
+                format!("element identifier {} is not unique", id),
+            );
+        }
+    }
+    if tocs.iter().filter(|toc| toc.top_level).count() > 1 {
+        issue(
+            Mp3Severity::Warning,
+            "more than one top level table of contents".to_string(),
+        );
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::apev2::ApeTag;
+    use super::super::id3v1::Id3v1Tag;
+    use super::super::id3v2_chapter::{Id3v2Chapter, Id3v2TableOfContents};
+    use super::super::mp3_header::{Mp3ChannelMode, Mp3Protection};
+    use super::super::mp3_synth::{Mp3Synth, Mp3SynthPayload};
+    use super::super::mp3_xing::Mp3LameTag;
+    use super::*;
+
+    fn header() -> Mp3FrameHeader {
+        Mp3FrameHeader::from_parameters(
+            Mp3Version::V1,
+            Mp3Layer::Layer3,
+            128,
+            48000,
+            Mp3ChannelMode::JointStereo,
+        )
+        .unwrap()
+    }
+
+    fn kinds(issues: &[Mp3Issue]) -> Vec<(Mp3Severity, Mp3IssueKind)> {
+        issues
+            .iter()
+            .map(|issue| (issue.severity, issue.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_stream() {
+        let mut synth = Mp3Synth::new(header(), 10);
+        synth.id3v2 = Some(Id3v2Tag::default());
+        synth.xing = Some(Some(Mp3LameTag::default()));
+        let mut ape = ApeTag::default();
+        ape.set("Title", "Valid");
+        synth.ape = Some(ape);
+        synth.id3v1 = Some(Id3v1Tag::default());
+        assert_eq!(validate(&synth.to_bytes()), []);
+        assert_eq!(
+            kinds(&validate(&[0; 100])),
+            [
+                (Mp3Severity::Warning, Mp3IssueKind::Junk),
+                (Mp3Severity::Error, Mp3IssueKind::NoFrames),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_junk_truncation_and_crc() {
+        let mut header = header();
+        header.set_protection(Mp3Protection::ProtectedByCrc);
+        let mut synth = Mp3Synth::new(header, 6);
+        synth.junk = vec![(3, vec![0x12; 10])];
+        synth.truncate = 20;
+        let mut data = synth.to_bytes();
+        data[2 * 384 + 10] ^= 1;
+        let issues = validate(&data);
+        assert_eq!(
+            kinds(&issues),
+            [
+                (Mp3Severity::Error, Mp3IssueKind::BadCrc),
+                (Mp3Severity::Warning, Mp3IssueKind::Junk),
+                (Mp3Severity::Error, Mp3IssueKind::TruncatedFrame),
+            ]
+        );
+        assert_eq!(issues[0].frame, Some(2));
+        assert_eq!(issues[1].offset, 3 * 384);
+        assert_eq!(issues[2].message, "frame truncated to 364 of 384 bytes");
+    }
+
+    #[test]
+    fn test_protected_layer1_and_layer2() {
+        for (layer, bitrate, channel_mode) in [
+            (Mp3Layer::Layer1, 384, Mp3ChannelMode::JointStereo),
+            (Mp3Layer::Layer2, 192, Mp3ChannelMode::Stereo),
+            (Mp3Layer::Layer2, 64, Mp3ChannelMode::SingleChannel),
+        ] {
+            let mut header = Mp3FrameHeader::from_parameters(
+                Mp3Version::V1,
+                layer,
+                bitrate,
+                48000,
+                channel_mode,
+            )
+            .unwrap();
+            header.set_protection(Mp3Protection::ProtectedByCrc);
+            let mut synth = Mp3Synth::new(header, 8);
+            synth.payload = Mp3SynthPayload::Random(bitrate as u64);
+            let mut data = synth.to_bytes();
+            assert_eq!(validate(&data), []);
+            // the first bits of bit allocation are always covered
+            let length = header.frame_length().unwrap();
+            data[3 * length + 6] ^= 0x80;
+            let issues = validate(&data);
+            assert_eq!(kinds(&issues), [(Mp3Severity::Error, Mp3IssueKind::BadCrc)]);
+            assert_eq!(issues[0].frame, Some(3));
+        }
+    }
+
+    #[test]
+    fn test_misplaced_tags() {
+        let mut synth = Mp3Synth::new(header(), 4);
+        synth.junk = vec![
+            (2, Id3v1Tag::default().to_bytes()),
+            (4, Id3v2Tag::default().to_bytes()),
+        ];
+        synth.id3v1 = Some(Id3v1Tag::default());
+        let issues = validate(&synth.to_bytes());
+        assert_eq!(
+            kinds(&issues),
+            [
+                (Mp3Severity::Warning, Mp3IssueKind::MisplacedTag),
+                (Mp3Severity::Warning, Mp3IssueKind::MisplacedTag),
+                (Mp3Severity::Warning, Mp3IssueKind::DuplicateTag),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chapters() {
+        // 10 frames of 24 ms
+        let mut tag = Id3v2Tag::default();
+        tag.set_chapters(
+            &[
+                Id3v2Chapter::new("intro", 0, 100),
+                Id3v2Chapter::new("main", 100, 250),
+            ],
+            None,
+        );
+        let mut synth = Mp3Synth::new(header(), 10);
+        synth.id3v2 = Some(tag.clone());
+        assert_eq!(validate(&synth.to_bytes()), []);
+
+        let mut late = Id3v2Chapter::new("late", 300, 200);
+        late.start_offset = Some(1 << 20);
+        let toc = Id3v2TableOfContents {
+            element_id: "extra".to_string(),
+            top_level: true,
+            ordered: false,
+            children: vec!["missing".to_string()],
+            frames: Vec::new(),
+        };
+        tag.frames.push(late.to_frame(4));
+        tag.frames.push(toc.to_frame(4));
+        tag.frames
+            .push(Id3v2Chapter::new("main", 0, 300).to_frame(4));
+        synth.id3v2 = Some(tag);
+        let messages: Vec<String> = validate(&synth.to_bytes())
+            .into_iter()
+            .map(|issue| issue.message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "chapter late ends before it starts",
+                "chapter late starts at 300 ms, after the audio ends at 240 ms",
+                "chapter late has a byte offset after the end of the file",
+                "chapter main ends at 300 ms, after the audio ends at 240 ms",
+                "table of contents extra lists missing element missing",
+                "element identifier main is not unique",
+                "more than one top level table of contents",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_xing_and_format() {
+        let mut synth = Mp3Synth::new(header(), 5);
+        synth.xing = Some(None);
+        let mut data = synth.to_bytes();
+        data.truncate(data.len() - 2 * 384);
+        let mut other = header();
+        other.set_sampling_rate_index(0).unwrap();
+        data.extend(&Mp3Synth::new(other, 1).to_bytes());
+        let issues = validate(&data);
+        assert_eq!(
+            kinds(&issues),
+            [
+                (Mp3Severity::Warning, Mp3IssueKind::XingMismatch),
+                (Mp3Severity::Warning, Mp3IssueKind::XingMismatch),
+                (Mp3Severity::Error, Mp3IssueKind::FormatChange),
+            ]
+        );
+        assert_eq!(
+            issues[0].message,
+            "Xing tag counts 5 frames, the stream has 4"
+        );
+    }
+
+    #[test]
+    fn test_layer2_mode_and_padding() {
+        let header = Mp3FrameHeader::from_parameters(
+            Mp3Version::V1,
+            Mp3Layer::Layer2,
+            56,
+            44100,
+            Mp3ChannelMode::Stereo,
+        )
+        .unwrap();
+        let issues = validate(&Mp3Synth::new(header, 20).to_bytes());
+        assert_eq!(
+            kinds(&issues),
+            [
+                (Mp3Severity::Error, Mp3IssueKind::ForbiddenLayer2Mode),
+                (Mp3Severity::Warning, Mp3IssueKind::PaddingMismatch),
+            ]
+        );
+        assert_eq!(
+            issues[0].message,
+            "20 frame(s) with a bitrate not allowed for their Layer II channel mode"
+        );
+    }
+
+    #[test]
+    fn test_reservoir_underflow() {
+        let synth = Mp3Synth::new(header(), 3);
+        let mut frames = synth.audio_frames();
+        let mut side_info = frames[0].side_info().unwrap();
+        side_info.main_data_begin = 10;
+        frames[0].set_side_info(&side_info);
+        let data: Vec<u8> = frames
+            .iter()
+            .flat_map(|frame| frame.body.data.clone())
+            .collect();
+        let issues = validate(&data);
+        assert_eq!(
+            kinds(&issues),
+            [(Mp3Severity::Warning, Mp3IssueKind::ReservoirUnderflow)]
+        );
+        assert_eq!(issues[0].frame, Some(0));
+    }
+}
@@ -0,0 +1,403 @@
+// References
+// https://www.codeproject.com/Articles/8295/MPEG-Audio-Frame-Header#XINGHeader
+// http://gabriel.mp3-tech.org/mp3infotag.html (LAME tag)
+
+use super::mp3_crc::lame_crc16;
+use super::mp3_frame::Mp3Frame;
+use super::mp3_header::{Mp3Layer, Mp3Protection};
+
+const FLAG_FRAMES: u32 = 0x1;
+const FLAG_BYTES: u32 = 0x2;
+const FLAG_TOC: u32 = 0x4;
+const FLAG_QUALITY: u32 = 0x8;
+
+/// Length of the LAME extension following the Xing/Info fields
+const LAME_TAG_LENGTH: usize = 36;
+
+/// Xing/Info tag stored in place of the audio data of the first frame
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3XingTag {
+    /// "Xing" marks a variable bitrate stream, "Info" a constant bitrate stream
+    pub is_vbr: bool,
+    /// Number of audio frames, excluding the tag frame
+    pub frames: Option<u32>,
+    /// Number of bytes of the stream
+    pub bytes: Option<u32>,
+    /// Seek table: byte position in 1/256 of the stream for each percent of the duration
+    #[cfg_attr(feature = "serde", serde(with = "super::serde_array::option"))]
+    pub toc: Option<[u8; 100]>,
+    pub quality: Option<u32>,
+    pub lame: Option<Mp3LameTag>,
+}
+
+/// LAME extension of the Xing/Info tag
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mp3LameTag {
+    /// Encoder version string, e.g. "LAME3.100"
+    pub encoder: String,
+    /// Tag revision (upper 4 bits) and VBR method (lower 4 bits)
+    pub revision_method: u8,
+    /// Lowpass frequency in 100 Hz
+    pub lowpass: u8,
+    /// Peak amplitude, radio and audiophile replay gain
+    pub replay_gain: [u8; 8],
+    /// Encoding flags (upper 4 bits) and ATH type (lower 4 bits)
+    pub encoding_flags: u8,
+    /// Average, minimal or constant bitrate in kbit/s
+    pub bitrate: u8,
+    /// Samples per channel added by the encoder before the audio
+    pub encoder_delay: u16,
+    /// Samples per channel added by the encoder after the audio
+    pub encoder_padding: u16,
+    /// Noise shaping, stereo mode, unwise settings and source sample rate
+    pub misc: u8,
+    pub mp3_gain: u8,
+    /// Surround info and preset
+    pub preset: u16,
+    /// Length of the stream in bytes, including the tag frame
+    pub music_length: u32,
+    /// CRC of the audio frames following the tag frame
+    pub music_crc: u16,
+}
+
+impl Mp3XingTag {
+    /// Parses the Xing/Info tag of a Layer III frame, if it contains one
+    pub fn parse(frame: &Mp3Frame) -> Option<Mp3XingTag> {
+        if frame.header.layer() != Mp3Layer::Layer3 {
+            return None;
+        }
+        let data = &frame.body.data;
+        let mut offset = frame.payload_offset() + frame.header.side_info_length();
+        let is_vbr = match data.get(offset..offset + 4)? {
+            b"Xing" => true,
+            b"Info" => false,
+            _ => return None,
+        };
+        let flags = read_u32(data, offset + 4)?;
+        offset += 8;
+
+        let mut read_field = |flag: u32| {
+            if flags & flag == 0 {
+                return Some(None);
+            }
+            let value = read_u32(data, offset)?;
+            offset += 4;
+            Some(Some(value))
+        };
+        let frames = read_field(FLAG_FRAMES)?;
+        let bytes = read_field(FLAG_BYTES)?;
+        let toc = if flags & FLAG_TOC != 0 {
+            let toc = data.get(offset..offset + 100)?.try_into().ok()?;
+            offset += 100;
+            Some(toc)
+        } else {
+            None
+        };
+        let quality = if flags & FLAG_QUALITY != 0 {
+            let quality = read_u32(data, offset)?;
+            offset += 4;
+            Some(quality)
+        } else {
+            None
+        };
+
+        Some(Mp3XingTag {
+            is_vbr,
+            frames,
+            bytes,
+            toc,
+            quality,
+            lame: data
+                .get(offset..offset + LAME_TAG_LENGTH)
+                .and_then(Mp3LameTag::parse),
+        })
+    }
+
+    /// Builds the tag frame for a stream of `frames`, with frame and byte counts,
+    /// a seek table and the LAME extension with updated length and CRC.
+    /// The tag frame uses the format of the first frame and the smallest bitrate
+    /// fitting the tag, starting at the bitrate of the first frame for constant bitrate streams.
+    pub fn build_frame(frames: &[Mp3Frame], lame: Option<Mp3LameTag>) -> Option<Mp3Frame> {
+        let first = frames.first()?;
+        if first.header.layer() != Mp3Layer::Layer3 || first.body.data.len() < 4 {
+            return None;
+        }
+        let is_vbr = frames
+            .iter()
+            .any(|frame| frame.header.bitrate_index() != first.header.bitrate_index());
+        let tag_length = 8 + 4 + 4 + 100 + lame.as_ref().map_or(0, |_| LAME_TAG_LENGTH);
+        let required = 4 + first.header.side_info_length() + tag_length;
+
+        // without crc and padding
+        let mut header = first.header;
+        header.set_protection(Mp3Protection::NotProtected);
+        header.set_padding_bit(false);
+        let start = if is_vbr {
+            1
+        } else {
+            first.header.bitrate_index()
+        };
+        let (header, frame_length) = (start..15).find_map(|index| {
+            let mut header = header;
+            header.set_bitrate_index(index).ok()?;
+            let length = header.frame_length().ok()?;
+            (length >= required).then_some((header, length))
+        })?;
+
+        let lengths: Vec<usize> = frames.iter().map(|frame| frame.body.data.len()).collect();
+        let bytes = frame_length + lengths.iter().sum::<usize>();
+        let mut toc = [0; 100];
+        let mut offset = frame_length;
+        let mut frame = 0;
+        for (percent, entry) in toc.iter_mut().enumerate() {
+            while frame < percent * frames.len() / 100 {
+                offset += lengths[frame];
+                frame += 1;
+            }
+            *entry = usize::min(offset * 256 / bytes, 255) as u8;
+        }
+        let lame = lame.map(|lame| Mp3LameTag {
+            music_length: bytes as u32,
+            music_crc: lame_crc16(
+                &frames
+                    .iter()
+                    .flat_map(|frame| frame.body.data.iter().copied())
+                    .collect::<Vec<u8>>(),
+            ),
+            ..lame
+        });
+        let tag = Mp3XingTag {
+            is_vbr,
+            frames: Some(frames.len() as u32),
+            bytes: Some(bytes as u32),
+            toc: Some(toc),
+            quality: None,
+            lame,
+        };
+
+        let mut data = vec![0; frame_length];
+        data[0..4].copy_from_slice(&header.to_bytes());
+        let start = 4 + header.side_info_length();
+        let tag_bytes = tag.to_bytes();
+        data[start..start + tag_bytes.len()].copy_from_slice(&tag_bytes);
+        if tag.lame.is_some() {
+            // the tag CRC covers all bytes of the frame up to the CRC itself
+            let end = start + tag_bytes.len() - 2;
+            let crc = lame_crc16(&data[..end]);
+            data[end..end + 2].copy_from_slice(&crc.to_be_bytes());
+        }
+        Some(Mp3Frame::new(header, data, 0))
+    }
+
+    /// Serializes the tag, a LAME extension is followed by an empty tag CRC
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = if self.is_vbr {
+            b"Xing".to_vec()
+        } else {
+            b"Info".to_vec()
+        };
+        let flags = self.frames.map_or(0, |_| FLAG_FRAMES)
+            | self.bytes.map_or(0, |_| FLAG_BYTES)
+            | self.toc.map_or(0, |_| FLAG_TOC)
+            | self.quality.map_or(0, |_| FLAG_QUALITY);
+        data.extend(flags.to_be_bytes());
+        for value in [self.frames, self.bytes].into_iter().flatten() {
+            data.extend(value.to_be_bytes());
+        }
+        if let Some(toc) = &self.toc {
+            data.extend(toc);
+        }
+        if let Some(quality) = self.quality {
+            data.extend(quality.to_be_bytes());
+        }
+        if let Some(lame) = &self.lame {
+            data.extend(lame.to_bytes());
+            data.extend([0, 0]);
+        }
+        data
+    }
+}
+
+impl Mp3LameTag {
+    fn parse(data: &[u8]) -> Option<Mp3LameTag> {
+        // the tag starts with a printable encoder name, e.g. "LAME" or "Lavc"
+        let encoder = &data[0..9];
+        if !encoder[0..4].iter().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        let encoder: String = encoder
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as char)
+            .collect();
+        let delay_padding = u32::from_be_bytes([0, data[21], data[22], data[23]]);
+        Some(Mp3LameTag {
+            encoder: encoder.trim_end().to_string(),
+            revision_method: data[9],
+            lowpass: data[10],
+            replay_gain: data[11..19].try_into().ok()?,
+            encoding_flags: data[19],
+            bitrate: data[20],
+            encoder_delay: (delay_padding >> 12) as u16,
+            encoder_padding: (delay_padding & 0xFFF) as u16,
+            misc: data[24],
+            mp3_gain: data[25],
+            preset: u16::from_be_bytes([data[26], data[27]]),
+            music_length: read_u32(data, 28)?,
+            music_crc: u16::from_be_bytes([data[32], data[33]]),
+        })
+    }
+
+    /// Serializes the tag without the trailing tag CRC, which covers the whole tag frame
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0; LAME_TAG_LENGTH - 2];
+        for (byte, c) in data[0..9].iter_mut().zip(self.encoder.bytes()) {
+            *byte = c;
+        }
+        data[9] = self.revision_method;
+        data[10] = self.lowpass;
+        data[11..19].copy_from_slice(&self.replay_gain);
+        data[19] = self.encoding_flags;
+        data[20] = self.bitrate;
+        let delay_padding =
+            (self.encoder_delay.min(0xFFF) as u32) << 12 | self.encoder_padding.min(0xFFF) as u32;
+        data[21..24].copy_from_slice(&delay_padding.to_be_bytes()[1..]);
+        data[24] = self.misc;
+        data[25] = self.mp3_gain;
+        data[26..28].copy_from_slice(&self.preset.to_be_bytes());
+        data[28..32].copy_from_slice(&self.music_length.to_be_bytes());
+        data[32..34].copy_from_slice(&self.music_crc.to_be_bytes());
+        data
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mp3_header::Mp3FrameHeader;
+    use super::*;
+
+    /// V1 layer 3 128kbit/s 44100Hz stereo frame with the given tag after the side info
+    fn tag_frame(tag: &[u8]) -> Mp3Frame {
+        let mut data = vec![0; 417];
+        data[0..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        data[36..36 + tag.len()].copy_from_slice(tag);
+        Mp3Frame::new(Mp3FrameHeader::new(0xFFFB9000), data, 0)
+    }
+
+    #[test]
+    fn test_parse_xing() {
+        let mut tag = b"Xing".to_vec();
+        tag.extend([0, 0, 0, 0x0F]);
+        tag.extend(390u32.to_be_bytes());
+        tag.extend(1234u32.to_be_bytes());
+        tag.extend(0..100);
+        tag.extend(57u32.to_be_bytes());
+        tag.extend(b"LAME3.100");
+        tag.extend([0; 12]);
+        // delay 576, padding 984
+        tag.extend([0x24, 0x03, 0xD8]);
+        tag.extend([0; 4]);
+        tag.extend(5678u32.to_be_bytes());
+        tag.extend([0; 4]);
+        let xing = Mp3XingTag::parse(&tag_frame(&tag)).unwrap();
+        assert!(xing.is_vbr);
+        assert_eq!(xing.frames, Some(390));
+        assert_eq!(xing.bytes, Some(1234));
+        assert_eq!(xing.toc.unwrap()[99], 99);
+        assert_eq!(xing.quality, Some(57));
+        assert_eq!(
+            xing.lame,
+            Some(Mp3LameTag {
+                encoder: "LAME3.100".to_string(),
+                revision_method: 0,
+                lowpass: 0,
+                replay_gain: [0; 8],
+                encoding_flags: 0,
+                bitrate: 0,
+                encoder_delay: 576,
+                encoder_padding: 984,
+                misc: 0,
+                mp3_gain: 0,
+                preset: 0,
+                music_length: 5678,
+                music_crc: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_info() {
+        let mut tag = b"Info".to_vec();
+        tag.extend([0, 0, 0, 0x01]);
+        tag.extend(10u32.to_be_bytes());
+        let xing = Mp3XingTag::parse(&tag_frame(&tag)).unwrap();
+        assert!(!xing.is_vbr);
+        assert_eq!(xing.frames, Some(10));
+        assert_eq!(xing.bytes, None);
+        assert_eq!(xing.toc, None);
+        assert_eq!(xing.lame, None);
+    }
+
+    #[test]
+    fn test_build_frame() {
+        let frames: Vec<Mp3Frame> = (0..10).map(|_| tag_frame(&[])).collect();
+        let lame = Mp3LameTag {
+            encoder: "LAME3.100".to_string(),
+            encoder_delay: 576,
+            encoder_padding: 1000,
+            ..Default::default()
+        };
+        let frame = Mp3XingTag::build_frame(&frames, Some(lame.clone())).unwrap();
+        assert_eq!(frame.body.data.len(), 417);
+        assert_eq!(frame.body.data[0..4], [0xFF, 0xFB, 0x90, 0x00]);
+        let xing = Mp3XingTag::parse(&frame).unwrap();
+        assert!(!xing.is_vbr);
+        assert_eq!(xing.frames, Some(10));
+        assert_eq!(xing.bytes, Some(11 * 417));
+        let toc = xing.toc.unwrap();
+        assert_eq!(toc[0], (417 * 256 / (11 * 417)) as u8);
+        assert_eq!(toc[50], (6 * 417 * 256 / (11 * 417)) as u8);
+        let parsed = xing.lame.unwrap();
+        assert_eq!(parsed.encoder_delay, 576);
+        assert_eq!(parsed.music_length, 11 * 417);
+        assert_eq!(
+            parsed.music_crc,
+            lame_crc16(&vec![frames[0].body.data.clone(); 10].concat())
+        );
+        // the tag CRC covers the frame up to the CRC
+        let crc = u16::from_be_bytes([frame.body.data[186], frame.body.data[187]]);
+        assert_eq!(crc, lame_crc16(&frame.body.data[..186]));
+    }
+
+    #[test]
+    fn test_parse_no_tag() {
+        assert_eq!(Mp3XingTag::parse(&tag_frame(&[1, 2, 3, 4])), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let frames: Vec<Mp3Frame> = (0..10).map(|_| tag_frame(&[])).collect();
+        let lame = Mp3LameTag {
+            encoder: "LAME3.100".to_string(),
+            ..Default::default()
+        };
+        let frame = Mp3XingTag::build_frame(&frames, Some(lame)).unwrap();
+        let xing = Mp3XingTag::parse(&frame).unwrap();
+        let json = serde_json::to_string(&xing).unwrap();
+        assert_eq!(serde_json::from_str::<Mp3XingTag>(&json).unwrap(), xing);
+        let json = serde_json::to_string(&Mp3XingTag::default()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Mp3XingTag>(&json).unwrap(),
+            Mp3XingTag::default()
+        );
+    }
+}
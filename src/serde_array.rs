@@ -0,0 +1,50 @@
+// Serde helpers for arrays longer than 32 elements, stored as sequences.
+// Use with `#[serde(with = "serde_array")]` or `#[serde(with = "serde_array::option")]`.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<S: Serializer, T: Serialize, const N: usize>(
+    array: &[T; N],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(array)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>, const N: usize>(
+    deserializer: D,
+) -> Result<[T; N], D::Error> {
+    let values = Vec::<T>::deserialize(deserializer)?;
+    let length = values.len();
+    values
+        .try_into()
+        .map_err(|_| D::Error::invalid_length(length, &format!("{} elements", N).as_str()))
+}
+
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer, T: Serialize, const N: usize>(
+        array: &Option<[T; N]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match array {
+            Some(array) => serializer.serialize_some(&array[..]),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<Option<[T; N]>, D::Error> {
+        let values = Option::<Vec<T>>::deserialize(deserializer)?;
+        values
+            .map(|values| {
+                let length = values.len();
+                values.try_into().map_err(|_| {
+                    D::Error::invalid_length(length, &format!("{} elements", N).as_str())
+                })
+            })
+            .transpose()
+    }
+}
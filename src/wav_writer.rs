@@ -0,0 +1,182 @@
+// References
+// http://soundfile.sapp.org/doc/WaveFormat/
+// https://learn.microsoft.com/en-us/windows/win32/api/mmreg/ns-mmreg-waveformatextensible
+
+use super::mp3_decoder::Mp3Audio;
+
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Tail of the KSDATAFORMAT_SUBTYPE GUIDs, preceded by the 16 bit format tag
+const SUBFORMAT_GUID: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// Sample format of a WAV file
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WavFormat {
+    Pcm16,
+    Float32,
+}
+
+impl WavFormat {
+    fn format_tag(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => WAVE_FORMAT_PCM,
+            WavFormat::Float32 => WAVE_FORMAT_IEEE_FLOAT,
+        }
+    }
+
+    fn bits_per_sample(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 16,
+            WavFormat::Float32 => 32,
+        }
+    }
+}
+
+/// Encodes the audio as a RIFF/WAVE file.
+/// More than two channels are written as WAVE_FORMAT_EXTENSIBLE
+/// with the default speaker assignment.
+pub fn to_wav(audio: &Mp3Audio, format: WavFormat) -> Vec<u8> {
+    let channels = audio.channels as u16;
+    let block_align = channels * format.bits_per_sample() / 8;
+    let extensible = channels > 2;
+
+    let mut fmt = Vec::new();
+    fmt.extend(
+        match extensible {
+            true => WAVE_FORMAT_EXTENSIBLE,
+            false => format.format_tag(),
+        }
+        .to_le_bytes(),
+    );
+    fmt.extend(channels.to_le_bytes());
+    fmt.extend(audio.sample_rate.to_le_bytes());
+    fmt.extend((audio.sample_rate * block_align as u32).to_le_bytes());
+    fmt.extend(block_align.to_le_bytes());
+    fmt.extend(format.bits_per_sample().to_le_bytes());
+    if extensible {
+        let channel_mask = (1u32 << channels.min(18)) - 1;
+        fmt.extend(22u16.to_le_bytes());
+        fmt.extend(format.bits_per_sample().to_le_bytes());
+        fmt.extend(channel_mask.to_le_bytes());
+        fmt.extend(format.format_tag().to_le_bytes());
+        fmt.extend(SUBFORMAT_GUID);
+    } else if format != WavFormat::Pcm16 {
+        // non PCM formats carry an empty extension
+        fmt.extend(0u16.to_le_bytes());
+    }
+
+    let mut data = Vec::with_capacity(audio.samples.len() * block_align as usize);
+    match format {
+        WavFormat::Pcm16 => {
+            for sample in audio.to_i16() {
+                data.extend(sample.to_le_bytes());
+            }
+        }
+        WavFormat::Float32 => {
+            for sample in &audio.samples {
+                data.extend(sample.to_le_bytes());
+            }
+        }
+    }
+
+    let mut chunks = Vec::new();
+    write_chunk(&mut chunks, b"fmt ", &fmt);
+    if format != WavFormat::Pcm16 {
+        let frames = (audio.samples.len() / audio.channels.max(1)) as u32;
+        write_chunk(&mut chunks, b"fact", &frames.to_le_bytes());
+    }
+    write_chunk(&mut chunks, b"data", &data);
+
+    let mut wav = Vec::with_capacity(12 + chunks.len());
+    wav.extend(b"RIFF");
+    wav.extend((4 + chunks.len() as u32).to_le_bytes());
+    wav.extend(b"WAVE");
+    wav.extend(chunks);
+    wav
+}
+
+fn write_chunk(output: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    output.extend(id);
+    output.extend((data.len() as u32).to_le_bytes());
+    output.extend(data);
+    // chunks are word aligned
+    if data.len() % 2 == 1 {
+        output.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_wav_pcm16() {
+        let audio = Mp3Audio {
+            sample_rate: 44100,
+            channels: 2,
+            samples: vec![0.0, 0.5, -1.0, 1.0],
+        };
+        let wav = to_wav(&audio, WavFormat::Pcm16);
+        assert_eq!(wav.len(), 44 + 8);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(wav[4..8], 44u32.to_le_bytes());
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(wav[16..20], 16u32.to_le_bytes());
+        // format, channels, sample rate, byte rate, block align, bits
+        assert_eq!(wav[20..24], [1, 0, 2, 0]);
+        assert_eq!(wav[24..28], 44100u32.to_le_bytes());
+        assert_eq!(wav[28..32], 176400u32.to_le_bytes());
+        assert_eq!(wav[32..36], [4, 0, 16, 0]);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(wav[40..44], 8u32.to_le_bytes());
+        assert_eq!(
+            wav[44..52],
+            [0x00, 0x00, 0x00, 0x40, 0x00, 0x80, 0xFF, 0x7F]
+        );
+    }
+
+    #[test]
+    fn test_to_wav_float32() {
+        let audio = Mp3Audio {
+            sample_rate: 48000,
+            channels: 1,
+            samples: vec![0.25, -0.5, 2.0],
+        };
+        let wav = to_wav(&audio, WavFormat::Float32);
+        assert_eq!(wav[16..20], 18u32.to_le_bytes());
+        assert_eq!(wav[20..24], [3, 0, 1, 0]);
+        assert_eq!(wav[32..38], [4, 0, 32, 0, 0, 0]);
+        assert_eq!(&wav[38..42], b"fact");
+        assert_eq!(wav[46..50], 3u32.to_le_bytes());
+        assert_eq!(&wav[50..54], b"data");
+        assert_eq!(wav[54..58], 12u32.to_le_bytes());
+        // float samples are not clipped
+        assert_eq!(wav[66..70], 2.0f32.to_le_bytes());
+        assert_eq!(wav.len(), 70);
+    }
+
+    #[test]
+    fn test_to_wav_extensible() {
+        let audio = Mp3Audio {
+            sample_rate: 44100,
+            channels: 6,
+            samples: vec![0.0; 12],
+        };
+        let wav = to_wav(&audio, WavFormat::Pcm16);
+        assert_eq!(wav[16..20], 40u32.to_le_bytes());
+        assert_eq!(wav[20..24], [0xFE, 0xFF, 6, 0]);
+        assert_eq!(wav[32..36], [12, 0, 16, 0]);
+        // extension size, valid bits, channel mask
+        assert_eq!(wav[36..40], [22, 0, 16, 0]);
+        assert_eq!(wav[40..44], 0x3Fu32.to_le_bytes());
+        assert_eq!(wav[44..46], [1, 0]);
+        assert_eq!(wav[46..60], SUBFORMAT_GUID);
+        assert_eq!(&wav[60..64], b"data");
+        assert_eq!(wav.len(), 68 + 24);
+    }
+}
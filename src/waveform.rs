@@ -0,0 +1,244 @@
+// References
+// https://github.com/bbc/audiowaveform/blob/master/doc/DataFormat.md
+
+use super::mp3_decoder::{to_i16, Mp3Audio};
+
+/// Minimum, maximum and RMS of one channel over one pixel, in the range -1.0 to 1.0
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Peak {
+    pub min: f32,
+    pub max: f32,
+    pub rms: f32,
+}
+
+/// Sample resolution of exported peak data
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WaveformBits {
+    Bits8,
+    Bits16,
+}
+
+impl WaveformBits {
+    fn bits(&self) -> u32 {
+        match self {
+            WaveformBits::Bits8 => 8,
+            WaveformBits::Bits16 => 16,
+        }
+    }
+
+    /// Scales a sample to the integer range of the resolution
+    fn quantize(&self, sample: f32) -> i16 {
+        match self {
+            WaveformBits::Bits8 => to_i16(sample) >> 8,
+            WaveformBits::Bits16 => to_i16(sample),
+        }
+    }
+}
+
+/// Peak data of audio for waveform rendering
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Waveform {
+    pub sample_rate: u32,
+    pub channels: usize,
+    /// Number of samples per channel summarized by one pixel
+    pub samples_per_pixel: usize,
+    /// Peaks of each pixel, interleaved by channel
+    pub peaks: Vec<Peak>,
+}
+
+impl Waveform {
+    /// Computes the peaks of each channel over `samples_per_pixel` samples.
+    /// The last pixel may cover fewer samples.
+    pub fn from_audio(audio: &Mp3Audio, samples_per_pixel: usize) -> Waveform {
+        let channels = audio.channels.max(1);
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let mut peaks = Vec::new();
+        for pixel in audio.samples.chunks(samples_per_pixel * channels) {
+            for ch in 0..channels {
+                let samples = pixel.iter().skip(ch).step_by(channels);
+                let mut peak = Peak {
+                    min: f32::MAX,
+                    max: f32::MIN,
+                    rms: 0.0,
+                };
+                let mut count = 0;
+                for &sample in samples {
+                    peak.min = peak.min.min(sample);
+                    peak.max = peak.max.max(sample);
+                    peak.rms += sample * sample;
+                    count += 1;
+                }
+                peak.rms = (peak.rms / count as f32).sqrt();
+                peaks.push(peak);
+            }
+        }
+        Waveform {
+            sample_rate: audio.sample_rate,
+            channels: audio.channels,
+            samples_per_pixel,
+            peaks,
+        }
+    }
+
+    /// Number of pixels
+    pub fn len(&self) -> usize {
+        self.peaks.len() / self.channels.max(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peaks.is_empty()
+    }
+
+    /// Merges `factor` consecutive pixels into one
+    pub fn downsample(&self, factor: usize) -> Waveform {
+        let channels = self.channels.max(1);
+        let factor = factor.max(1);
+        let mut peaks = Vec::new();
+        for pixels in self.peaks.chunks(factor * channels) {
+            for ch in 0..channels {
+                let merged: Vec<&Peak> = pixels.iter().skip(ch).step_by(channels).collect();
+                let energy: f32 = merged.iter().map(|peak| peak.rms * peak.rms).sum();
+                peaks.push(Peak {
+                    min: merged.iter().fold(f32::MAX, |min, peak| min.min(peak.min)),
+                    max: merged.iter().fold(f32::MIN, |max, peak| max.max(peak.max)),
+                    rms: (energy / merged.len() as f32).sqrt(),
+                });
+            }
+        }
+        Waveform {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            samples_per_pixel: self.samples_per_pixel * factor,
+            peaks,
+        }
+    }
+
+    /// Exports the min/max data in the audiowaveform JSON format (version 2)
+    pub fn to_json(&self, bits: WaveformBits) -> String {
+        let data: Vec<String> = self
+            .peaks
+            .iter()
+            .flat_map(|peak| [bits.quantize(peak.min), bits.quantize(peak.max)])
+            .map(|value| value.to_string())
+            .collect();
+        format!(
+            "{{\"version\":2,\"channels\":{},\"sample_rate\":{},\"samples_per_pixel\":{},\"bits\":{},\"length\":{},\"data\":[{}]}}",
+            self.channels,
+            self.sample_rate,
+            self.samples_per_pixel,
+            bits.bits(),
+            self.len(),
+            data.join(",")
+        )
+    }
+
+    /// Exports the min/max data in the audiowaveform binary .dat format (version 2)
+    pub fn to_dat(&self, bits: WaveformBits) -> Vec<u8> {
+        let mut dat = Vec::new();
+        dat.extend(2u32.to_le_bytes());
+        // flags: bit 0 set for 8 bit data
+        let flags: u32 = match bits {
+            WaveformBits::Bits8 => 1,
+            WaveformBits::Bits16 => 0,
+        };
+        dat.extend(flags.to_le_bytes());
+        dat.extend(self.sample_rate.to_le_bytes());
+        dat.extend((self.samples_per_pixel as u32).to_le_bytes());
+        dat.extend((self.len() as u32).to_le_bytes());
+        dat.extend((self.channels as u32).to_le_bytes());
+        for peak in &self.peaks {
+            for value in [bits.quantize(peak.min), bits.quantize(peak.max)] {
+                match bits {
+                    WaveformBits::Bits8 => dat.push(value as i8 as u8),
+                    WaveformBits::Bits16 => dat.extend(value.to_le_bytes()),
+                }
+            }
+        }
+        dat
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waveform() -> Waveform {
+        let audio = Mp3Audio {
+            sample_rate: 44100,
+            channels: 2,
+            samples: vec![0.5, -0.25, -0.5, 0.25, 1.0, 0.0],
+        };
+        Waveform::from_audio(&audio, 2)
+    }
+
+    #[test]
+    fn test_from_audio() {
+        let waveform = waveform();
+        assert_eq!(waveform.len(), 2);
+        assert_eq!(
+            waveform.peaks,
+            vec![
+                Peak {
+                    min: -0.5,
+                    max: 0.5,
+                    rms: 0.5,
+                },
+                Peak {
+                    min: -0.25,
+                    max: 0.25,
+                    rms: 0.25,
+                },
+                Peak {
+                    min: 1.0,
+                    max: 1.0,
+                    rms: 1.0,
+                },
+                Peak {
+                    min: 0.0,
+                    max: 0.0,
+                    rms: 0.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_downsample() {
+        let waveform = waveform().downsample(2);
+        assert_eq!(waveform.samples_per_pixel, 4);
+        assert_eq!(waveform.len(), 1);
+        assert_eq!(waveform.peaks[0].min, -0.5);
+        assert_eq!(waveform.peaks[0].max, 1.0);
+        assert!((waveform.peaks[0].rms - 0.625f32.sqrt()).abs() < 1e-6);
+        assert_eq!(waveform.peaks[1].min, -0.25);
+        assert_eq!(waveform.peaks[1].max, 0.25);
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(
+            waveform().to_json(WaveformBits::Bits8),
+            "{\"version\":2,\"channels\":2,\"sample_rate\":44100,\"samples_per_pixel\":2,\
+             \"bits\":8,\"length\":2,\"data\":[-64,64,-32,32,127,127,0,0]}"
+        );
+    }
+
+    #[test]
+    fn test_to_dat() {
+        let dat = waveform().to_dat(WaveformBits::Bits16);
+        assert_eq!(dat[0..8], [2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(dat[8..12], 44100u32.to_le_bytes());
+        assert_eq!(dat[12..16], 2u32.to_le_bytes());
+        assert_eq!(dat[16..20], 2u32.to_le_bytes());
+        assert_eq!(dat[20..24], 2u32.to_le_bytes());
+        assert_eq!(dat[24..28], [0x00, 0xC0, 0x00, 0x40]);
+        assert_eq!(dat.len(), 24 + 16);
+
+        let dat = waveform().to_dat(WaveformBits::Bits8);
+        assert_eq!(dat[4..8], [1, 0, 0, 0]);
+        assert_eq!(dat[24..], [0xC0, 0x40, 0xE0, 0x20, 0x7F, 0x7F, 0, 0]);
+    }
+}
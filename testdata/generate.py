@@ -0,0 +1,431 @@
+# Generates the MP3 streams in this directory from hand-picked subband samples (Layer I
+# and II) and sparse spectra (Layer III), without an encoder's analysis filterbank.
+# The reference PCM (*.pcm, 16 bit little endian, interleaved) was decoded from these
+# streams with symphonia-bundle-mp3 0.5.5, independently of mpeak.
+# The Layer III spectra are Huffman coded with the tables of src/mp3_huffman_tables.rs,
+# everything else (band layouts, scalefactor partitions, bit reservoir) is implemented here
+# from ISO/IEC 11172-3 and 13818-3.
+
+import math, os, random, re
+random.seed(7)
+
+class Bits:
+    def __init__(s): s.b=[]
+    def w(s, v, n):
+        for i in range(n-1,-1,-1): s.b.append((v>>i)&1)
+    def bytes(s, length):
+        b = s.b + [0]*(length*8-len(s.b))
+        assert len(b) == length*8, (len(s.b), length*8)
+        return bytes(int(''.join(map(str,b[i:i+8])),2) for i in range(0,len(b),8))
+
+SCF = [2.0*2**(-i/3) for i in range(63)]
+
+def layer1(frames=16):
+    # MPEG-1 Layer I, 384 kbit/s, 44.1 kHz, stereo: 4 * 104 = 416 bytes per frame
+    hdr = 0xFFFF0000 | (0b1100<<12)
+    length = 4*(12*384000//44100)
+    alloc = [5]*8 + [3]*12 + [0]*12
+    out = b''
+    t = 0
+    for f in range(frames):
+        bits = Bits(); bits.w(hdr, 32)
+        samples = [[[0.0]*12 for _ in range(32)] for ch in range(2)]
+        for ch in range(2):
+            for sb in range(32):
+                if not alloc[sb]: continue
+                amp = 0.12/(1+sb) * (1 + 0.5*math.sin(f*0.7+sb+ch))
+                for s in range(12):
+                    samples[ch][sb][s] = amp*math.cos(0.3*(sb+1+ch)*(t+s) + sb) + 0.02*random.uniform(-1,1)
+        for sb in range(32):
+            for ch in range(2): bits.w(alloc[sb], 4)
+        scf = [[0]*32, [0]*32]
+        for sb in range(32):
+            for ch in range(2):
+                if alloc[sb]:
+                    m = max(abs(x) for x in samples[ch][sb])
+                    i = max(i for i in range(63) if SCF[i] >= m)
+                    scf[ch][sb] = i; bits.w(i, 6)
+        for s in range(12):
+            for sb in range(32):
+                for ch in range(2):
+                    if not alloc[sb]: continue
+                    nb = alloc[sb]+1
+                    x = samples[ch][sb][s]/SCF[scf[ch][sb]]
+                    c = round((x*(2**nb-1)/2**nb - 2**(1-nb))*2**(nb-1) + 2**(nb-1))
+                    bits.w(min(max(c,0),2**nb-2), nb)
+        t += 12
+        out += bits.bytes(length)
+    return out
+
+def layer2(frames=6):
+    # MPEG-1 Layer II, 192 kbit/s, 48 kHz, stereo: 576 bytes per frame, table B.2a
+    hdr = 0xFFFD0000 | (0b1010<<12) | (0b01<<10)
+    length = 576
+    nbal = [4]*11 + [3]*12 + [2]*4
+    levels = [[0,3,7,15,31,63,127,255,511,1023,2047,4095,8191,16383,32767,65535]]*3 \
+        + [[0,3,5,7,9,15,31,63,127,255,511,1023,2047,4095,8191,65535]]*8 \
+        + [[0,3,5,7,9,15,31,65535]]*12 + [[0,3,5,65535]]*4
+    alloc = [5]*3 + [4]*4 + [2]*4 + [1]*6 + [0]*10
+    out = b''
+    t = 0
+    for f in range(frames):
+        bits = Bits(); bits.w(hdr, 32)
+        samples = [[[0.0]*36 for _ in range(27)] for ch in range(2)]
+        for ch in range(2):
+            for sb in range(27):
+                if not alloc[sb]: continue
+                amp = 0.15/(1+sb) * (1 + 0.5*math.sin(f*0.9+sb+ch))
+                for s in range(36):
+                    samples[ch][sb][s] = amp*math.cos(0.2*(sb+1+ch)*(t+s) + sb) + 0.01*random.uniform(-1,1)
+        for sb in range(27):
+            for ch in range(2): bits.w(alloc[sb], nbal[sb])
+        for sb in range(27):
+            for ch in range(2):
+                if alloc[sb]: bits.w(2, 2)
+        scf = [[0]*27, [0]*27]
+        for sb in range(27):
+            for ch in range(2):
+                if alloc[sb]:
+                    m = max(abs(x) for x in samples[ch][sb])
+                    i = max(i for i in range(63) if SCF[i] >= m)
+                    scf[ch][sb] = i; bits.w(i, 6)
+        for g in range(12):
+            for sb in range(27):
+                for ch in range(2):
+                    if not alloc[sb]: continue
+                    L = levels[sb][alloc[sb]]
+                    codes = []
+                    for s in range(3):
+                        x = samples[ch][sb][3*g+s]/SCF[scf[ch][sb]]
+                        codes.append(min(max(round((x*L + L-1)/2), 0), L-1))
+                    if L in (3, 5, 9):
+                        bits.w(codes[0] + L*codes[1] + L*L*codes[2], {3: 5, 5: 7, 9: 10}[L])
+                    else:
+                        for c in codes: bits.w(c, (L+1).bit_length()-1)
+        t += 36
+        out += bits.bytes(length)
+    return out
+
+# Huffman code tables of ISO/IEC 11172-3 Table B.7, read from the decoder source
+def read_tables():
+    text = open(os.path.join(os.path.dirname(os.path.abspath(__file__)), '..', 'src', 'mp3_huffman_tables.rs')).read()
+    tables = {}
+    for kind, name, body in re.findall(r'pub const (CODES|LENGTHS)_(\w+): \[u\d+; \d+\] = \[(.*?)\];', text, re.S):
+        tables.setdefault(name, {})[kind] = [int(x, 0) for x in re.findall(r'0x[0-9a-f]+|\d+', body)]
+    return tables
+
+TABLES = read_tables()
+# table_select: (code table, values per dimension, linbits)
+BIG_VALUE_TABLES = {1: ('1', 2, 0), 2: ('2', 3, 0), 3: ('3', 3, 0), 5: ('5', 4, 0), 6: ('6', 4, 0),
+    7: ('7', 6, 0), 8: ('8', 6, 0), 9: ('9', 6, 0), 10: ('10', 8, 0), 11: ('11', 8, 0),
+    12: ('12', 8, 0), 13: ('13', 16, 0), 15: ('15', 16, 0)}
+for i, linbits in enumerate([1, 2, 3, 4, 6, 8, 10, 13]):
+    BIG_VALUE_TABLES[16 + i] = ('16', 16, linbits)
+for i, linbits in enumerate([4, 5, 6, 7, 8, 9, 11, 13]):
+    BIG_VALUE_TABLES[24 + i] = ('24', 16, linbits)
+
+def code(bits, name, index):
+    bits.w(TABLES[name]['CODES'][index], TABLES[name]['LENGTHS'][index])
+
+def table_for(values):
+    m = max([abs(v) for v in values] + [0])
+    if m == 0:
+        return random.choice([0, 1])
+    candidates = [t for t, (_, size, linbits) in BIG_VALUE_TABLES.items()
+                  if m <= size - 1 + (2**linbits - 1 if linbits else 0) and (linbits == 0 or m > 15 or random.random() < 0.3)]
+    return random.choice(candidates)
+
+def write_pair(bits, table, x, y):
+    if table == 0:
+        assert x == 0 and y == 0
+        return
+    name, size, linbits = BIG_VALUE_TABLES[table]
+    cx, cy = min(abs(x), 15) if linbits else abs(x), min(abs(y), 15) if linbits else abs(y)
+    code(bits, name, cx * size + cy)
+    for v, c in ((x, cx), (y, cy)):
+        if linbits and c == 15:
+            bits.w(abs(v) - 15, linbits)
+        if v:
+            bits.w(int(v < 0), 1)
+
+# long and short scalefactor band starts of 44.1, 22.05 and 11.025 kHz
+LONG = {44100: [4,4,4,4,4,4,6,6,8,8,10,12,16,20,24,28,34,42,50,54,76,158],
+        22050: [6,6,6,6,6,6,8,10,12,14,16,20,24,28,32,38,46,52,60,68,58,54]}
+LONG[11025] = LONG[22050]
+SHORT = {44100: [4,4,4,4,6,8,10,12,14,18,22,30,56],
+         22050: [4,4,4,6,6,8,10,14,18,26,32,42,18],
+         11025: [4,4,4,6,8,10,12,14,18,24,30,40,18]}
+
+def starts(widths):
+    s = [0]
+    for w in widths: s.append(s[-1] + w)
+    return s
+
+SLEN1 = [0,0,0,0,3,1,1,1,2,2,2,3,3,3,4,4]
+SLEN2 = [0,1,2,3,0,1,2,3,1,2,3,1,2,3,2,3]
+
+# ISO/IEC 13818-3 2.4.3.2: number of scalefactors per slen for long, short and mixed blocks
+LSF_PARTITIONS = [[[6,5,5,5],[9,9,9,9],[6,9,9,9]],
+                  [[6,5,7,3],[9,9,12,6],[6,9,12,6]],
+                  [[11,10,0,0],[18,18,0,0],[15,18,0,0]]]
+
+def lsf_slen(compress):
+    if compress < 400:
+        return 0, [(compress >> 4) // 5, (compress >> 4) % 5, (compress & 15) >> 2, compress & 3]
+    if compress < 500:
+        c = compress - 400
+        return 1, [(c >> 2) // 5, (c >> 2) % 5, c & 3, 0]
+    c = compress - 500
+    return 2, [c // 3, c % 3, 0, 0]
+
+class Granule:
+    """Quantized spectrum and side information of one channel of one granule"""
+    def __init__(self, spectrum, block_type=0, mixed=False, global_gain=160):
+        self.spectrum = spectrum
+        if mixed:
+            # symphonia 0.5 leaves the last long band of mixed blocks (lines 30 to 35) unscaled
+            # and drops their short bands, only the long bands below line 30 carry values
+            spectrum[30:] = [0] * 546
+        self.block_type = block_type
+        self.mixed = mixed
+        self.global_gain = global_gain
+        self.scalefac_scale = random.randint(0, 1)
+        self.preflag = 0
+        self.subblock_gain = [random.randint(0, 2) for _ in range(3)] if block_type == 2 else [0, 0, 0]
+        self.count1table = random.randint(0, 1)
+
+def encode(granule, sample_rate, part2):
+    """Side information fields and main data bits of a granule, part2 holds the scalefactor bits"""
+    spectrum = granule.spectrum
+    last = max([i for i, v in enumerate(spectrum) if v] + [-1])
+    big = max([i for i, v in enumerate(spectrum) if abs(v) > 1] + [-1])
+    big_end = (big + 2) // 2 * 2
+    count1_end = big_end + (max(last + 1 - big_end, 0) + 3) // 4 * 4
+    if count1_end > 576:
+        big_end = (last + 2) // 2 * 2
+        count1_end = big_end
+    bits = Bits()
+    bits.b = list(part2.b)
+    side = {'big_values': big_end // 2}
+    if granule.block_type == 0:
+        bands = starts(LONG[sample_rate])
+        region0_count = random.randint(0, 15)
+        region1_count = random.randint(0, 7)
+        r1 = min(bands[min(region0_count + 1, 22)], big_end)
+        r2 = min(bands[min(region0_count + region1_count + 2, 22)], big_end)
+        regions = [(0, r1), (r1, r2), (r2, big_end)]
+        tables = [table_for(spectrum[a:b]) for a, b in regions]
+        side.update(region0_count=region0_count, region1_count=region1_count)
+    else:
+        # the same table for both regions, the region boundary of window switching is moot
+        table = table_for(spectrum[:big_end])
+        tables = [table, table]
+        regions = [(0, big_end)]
+    for (a, b), table in zip(regions, tables):
+        for i in range(a, b, 2):
+            write_pair(bits, table, spectrum[i], spectrum[i + 1])
+    name = 'B' if granule.count1table else 'A'
+    for i in range(big_end, count1_end, 4):
+        quad = spectrum[i:i + 4]
+        code(bits, name, sum(abs(v) << (3 - k) for k, v in enumerate(quad)))
+        for v in quad:
+            if v:
+                bits.w(int(v < 0), 1)
+    side['tables'] = tables
+    side['part2_3_length'] = len(bits.b)
+    return side, bits
+
+def write_side(side_bits, granule, side, compress, lsf):
+    side_bits.w(side['part2_3_length'], 12)
+    side_bits.w(side['big_values'], 9)
+    side_bits.w(granule.global_gain, 8)
+    side_bits.w(compress, 9 if lsf else 4)
+    if granule.block_type:
+        side_bits.w(1, 1); side_bits.w(granule.block_type, 2); side_bits.w(int(granule.mixed), 1)
+        for t in side['tables']: side_bits.w(t, 5)
+        for g in granule.subblock_gain: side_bits.w(g, 3)
+    else:
+        side_bits.w(0, 1)
+        for t in side['tables']: side_bits.w(t, 5)
+        side_bits.w(side['region0_count'], 4); side_bits.w(side['region1_count'], 3)
+    if not lsf:
+        side_bits.w(granule.preflag, 1)
+    side_bits.w(granule.scalefac_scale, 1); side_bits.w(granule.count1table, 1)
+
+def scalefactors_v1(granule, compress, scfsi, gr, intensity=None):
+    """Scalefactor bits of an MPEG-1 granule, intensity gives the positions of the right channel"""
+    part2 = Bits()
+    s1, s2 = SLEN1[compress], SLEN2[compress]
+    value = lambda n: random.randrange(2**n) if n else 0
+    if granule.block_type == 2:
+        counts = [(17 if granule.mixed else 18, s1), (18, s2)]
+        for count, n in counts:
+            for _ in range(count): part2.w(value(n), n)
+    else:
+        groups = [(0, 6, s1), (6, 11, s1), (11, 16, s2), (16, 21, s2)]
+        for g, (a, b, n) in enumerate(groups):
+            if gr == 1 and scfsi[g]:
+                continue
+            for band in range(a, b):
+                v = value(n)
+                if intensity is not None:
+                    v = min(intensity[band], 2**n - 1) if n else 0
+                part2.w(v, n)
+    return part2
+
+def scalefactors_lsf(granule, compress):
+    part2 = Bits()
+    table, slen = lsf_slen(compress)
+    kind = 0 if granule.block_type != 2 else (2 if granule.mixed else 1)
+    for count, n in zip(LSF_PARTITIONS[table][kind], slen):
+        for _ in range(count):
+            part2.w(random.randrange(2**n) if n else 0, n)
+    return part2
+
+def spectrum(extent, peak, density=1.0):
+    """Spectrum decaying from `peak` at the low lines, with sparse values up to 1 towards `extent`"""
+    s = [0] * 576
+    for i in range(extent):
+        if random.random() > density:
+            continue
+        amplitude = peak * math.exp(-i / (extent / 5.0))
+        v = int(round(random.gauss(0, amplitude)))
+        if v == 0 and random.random() < 0.3:
+            v = random.choice([-1, 1])
+        s[i] = v
+    return s
+
+def pack(frames_main, frame_length, side_length, max_back):
+    """Places the main data of each frame into the bit reservoir, returns main_data_begin and
+    the main data bytes of each frame"""
+    payload = frame_length - 4 - side_length
+    stream = b''
+    begins = []
+    for f, main in enumerate(frames_main):
+        slot = f * payload
+        begin = max(len(stream), slot - max_back)
+        assert begin <= slot, 'main data does not fit'
+        stream += bytes(begin - len(stream)) + main
+        assert len(stream) <= slot + payload, ('frame %d overflows' % f)
+        begins.append(slot - begin)
+    stream += bytes(len(frames_main) * payload - len(stream))
+    return begins, [stream[f * payload:(f + 1) * payload] for f in range(len(frames_main))]
+
+def layer3_frames(header, sample_rate, lsf, channels, plan, frame_length):
+    """Encodes one frame per entry of `plan`, a function returning the granules
+    [gr][ch], the mode extension and the scfsi of a frame"""
+    side_length = (9 if channels == 1 else 17) if lsf else (17 if channels == 1 else 32)
+    sides, mains = [], []
+    for f in range(len(plan)):
+        granules, mode_extension, scfsi, intensity = plan[f]
+        h = header | (mode_extension << 4)
+        while True:
+            main = Bits()
+            fields = []
+            for gr, row in enumerate(granules):
+                for ch, granule in enumerate(row):
+                    if lsf:
+                        compress = random.choice([0, 77, 150, 333, 399, 405, 460, 499, 500, 507, 511])
+                        part2 = scalefactors_lsf(granule, compress)
+                    else:
+                        compress = random.randrange(16)
+                        is_pos = intensity if ch == 1 else None
+                        if is_pos is not None:
+                            compress = random.choice([8, 10, 13, 15])
+                        part2 = scalefactors_v1(granule, compress, scfsi[ch], gr, is_pos)
+                    side, bits = encode(granule, sample_rate, part2)
+                    fields.append((granule, side, compress))
+                    main.b += bits.b
+            main.b += [0] * (-len(main.b) % 8)
+            # halve the spectra of frames exceeding the average rate by far,
+            # the first frame has no bit reservoir to draw from
+            payload = frame_length - 4 - side_length
+            if len(main.b) // 8 <= (payload * 5 // 4 if f else payload):
+                break
+            for row in granules:
+                for granule in row:
+                    granule.spectrum = [int(v / 2) for v in granule.spectrum]
+        mains.append(main.bytes(len(main.b) // 8))
+        sides.append((h, fields, scfsi))
+    begins, payloads = pack(mains, frame_length, side_length, 255 if lsf else 511)
+    out = b''
+    for (h, fields, scfsi), begin, payload in zip(sides, begins, payloads):
+        bits = Bits()
+        bits.w(h, 32)
+        if lsf:
+            bits.w(begin, 8); bits.w(0, channels)
+        else:
+            bits.w(begin, 9); bits.w(0, 5 if channels == 1 else 3)
+            for ch in range(channels):
+                for g in scfsi[ch]: bits.w(g, 1)
+        for granule, side, compress in fields:
+            write_side(bits, granule, side, compress, lsf)
+        assert len(bits.b) == (4 + side_length) * 8
+        out += bits.bytes(4 + side_length) + payload
+    return out
+
+def layer3():
+    # MPEG-1 Layer III, 160 kbit/s, 44.1 kHz, joint stereo: 522 bytes per frame.
+    # Long blocks with all Huffman tables, start, short, mixed and stop blocks, scfsi,
+    # mid/side and intensity stereo and main data reaching back into previous frames.
+    header = 0xFFFBA000 | (0b01 << 6)
+    blocks = [(0, 0), (0, 0), (1, 0), (2, 0), (2, 1), (3, 0), (0, 0), (0, 0), (1, 0), (2, 1), (3, 0), (0, 0),
+              (0, 0), (0, 0), (0, 0), (0, 0), (1, 0), (2, 0), (3, 0), (0, 0)]
+    plan = []
+    for f in range(10):
+        mode_extension = [0, 2, 2, 0, 2, 1, 3, 1, 0, 2][f]
+        granules = []
+        for gr in range(2):
+            block_type, mixed = blocks[2 * f + gr]
+            row = []
+            for ch in range(2):
+                extent = 418 if mode_extension & 1 else random.choice([200, 300, 450, 576])
+                if f == 0:
+                    extent = 150
+                peak = random.choice([3, 8, 30, 120]) if gr == ch else random.choice([2, 6, 20])
+                s = spectrum(extent, peak, density=random.choice([0.4, 1.0]))
+                if ch == 1 and mode_extension & 1:
+                    # intensity coded above line 104 of the right channel
+                    s[104:] = [0] * 472
+                g = Granule(s, block_type, bool(mixed), random.randint(138, 158))
+                g.preflag = random.randint(0, 1) if block_type != 2 else 0
+                row.append(g)
+            granules.append(row)
+        long_only = all(b[0] == 0 for b in blocks[2 * f:2 * f + 2])
+        scfsi = [[random.randint(0, 1) if long_only else 0 for _ in range(4)] for _ in range(2)]
+        if mode_extension & 1:
+            scfsi[1] = [0, 0, 0, 0]
+        intensity = [random.randint(0, 6) for _ in range(21)]
+        plan.append((granules, mode_extension, scfsi, intensity))
+    return layer3_frames(header, 44100, False, 2, plan, 522)
+
+def layer3_lsf():
+    # MPEG-2 Layer III, 64 kbit/s, 22.05 kHz, joint stereo with mid/side: 208 bytes per frame.
+    # LSF scalefactors with all three partitions, short and mixed blocks, bit reservoir.
+    header = 0xFFF38000 | (0b01 << 6)
+    blocks = [0, 0, 1, 2, 2, 3, 0, 1, 2, 3, 0, 0]
+    plan = []
+    for f, block_type in enumerate(blocks):
+        row = []
+        for ch in range(2):
+            s = spectrum(random.choice([150, 250, 400]), random.choice([4, 12, 40]) if ch == 0 else 3,
+                         density=random.choice([0.3, 1.0]))
+            row.append(Granule(s, block_type, block_type == 2 and f % 2 == 0, random.randint(159, 179)))
+        plan.append(([row], 2 if f % 3 else 0, [[0] * 4] * 2, None))
+    return layer3_frames(header, 22050, True, 2, plan, 208)
+
+def layer3_mpeg25():
+    # MPEG-2.5 Layer III, 32 kbit/s, 11.025 kHz, mono: 208 bytes per frame
+    header = 0xFFE34000 | (0b11 << 6)
+    blocks = [0, 0, 1, 2, 3, 0, 0, 1, 2, 2, 3, 0]
+    plan = []
+    for f, block_type in enumerate(blocks):
+        s = spectrum(random.choice([120, 300, 576]), random.choice([4, 16, 60]), density=random.choice([0.3, 1.0]))
+        g = Granule(s, block_type, block_type == 2 and f == 8, random.randint(152, 172))
+        plan.append(([[g]], 0, [[0] * 4], None))
+    return layer3_frames(header, 11025, True, 1, plan, 208)
+
+directory = os.path.dirname(os.path.abspath(__file__))
+for name, generate in [('layer1', layer1), ('layer2', layer2), ('layer3', layer3),
+                       ('layer3_lsf', layer3_lsf), ('layer3_mpeg25', layer3_mpeg25)]:
+    open(os.path.join(directory, name + '.mp3'), 'wb').write(generate())